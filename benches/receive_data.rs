@@ -1,11 +1,15 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use client::data::snapshot::QuoteSnapshot;
+use client::data::trade::TradeTick;
+use client::data::event::MarketEvent;
 use client::order::{
-    core::{OrderCore, LimitOrder},
+    core::{OrderCore, LimitOrder, OrderAttributes},
     request::OrderRequest,
-    enums::{OrderStatus, OrderSide},
+    enums::OrderSide,
 };
 use client::InstId;
+use criterion::measurement::WallTime;
+use criterion::BenchmarkGroup;
 use crossbeam_channel::{bounded, unbounded};
 use std::time::Duration;
 
@@ -89,21 +93,105 @@ fn benchmark_quote_snapshot(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_clone_and_channels<T: Clone + Send + 'static>(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    sample: &T,
+) {
+    // Clone only (비교를 위한 기준점)
+    group.bench_function(format!("clone_only_{}", name), |b| {
+        let sample = sample.clone();
+
+        b.iter(|| {
+            black_box(sample.clone());
+        });
+    });
+
+    // Clone and send
+    group.bench_function(format!("clone_and_send_{}", name), |b| {
+        let (sender, receiver) = bounded(1);
+        let sample = sample.clone();
+
+        b.iter_with_setup(
+            || {
+                while receiver.try_recv().is_ok() {}
+                sender.try_send(sample.clone()).unwrap();
+            },
+            |_| {
+                let _received = black_box(receiver.recv().unwrap());
+            }
+        );
+    });
+
+    // Bounded channel recv
+    group.bench_function(format!("bounded_channel_recv_{}", name), |b| {
+        let (sender, receiver) = bounded(1);
+        let sample = sample.clone();
+
+        b.iter_with_setup(|| {
+            while receiver.try_recv().is_ok() {}
+            sender.send(sample.clone()).unwrap();
+        }, |_| {
+            let _received = black_box(receiver.recv().unwrap());
+        });
+    });
+
+    // Unbounded channel recv
+    group.bench_function(format!("unbounded_channel_recv_{}", name), |b| {
+        let (sender, receiver) = unbounded();
+        let sample = sample.clone();
+
+        b.iter_with_setup(|| {
+            while receiver.try_recv().is_ok() {}
+            sender.send(sample.clone()).unwrap();
+        }, |_| {
+            let _received = black_box(receiver.recv().unwrap());
+        });
+    });
+
+    // Try recv
+    group.bench_function(format!("try_recv_{}", name), |b| {
+        let (sender, receiver) = bounded(1);
+        let sample = sample.clone();
+
+        b.iter_with_setup(
+            || {
+                sender.send(sample.clone()).unwrap();
+            },
+            |_| {
+                let _received = black_box(receiver.try_recv().unwrap());
+            }
+        );
+    });
+}
+
+fn benchmark_trade_tick(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trade_tick_channel");
+    bench_clone_and_channels(&mut group, "trade_tick", &TradeTick::sample());
+    group.finish();
+}
+
+fn benchmark_market_event(c: &mut Criterion) {
+    let mut group = c.benchmark_group("market_event_channel");
+    bench_clone_and_channels(&mut group, "quote_level_4", &MarketEvent::Quote(QuoteSnapshot::sample(4)));
+    bench_clone_and_channels(&mut group, "trade", &MarketEvent::Trade(TradeTick::sample()));
+    group.finish();
+}
+
 fn create_sample_order_request() -> OrderRequest {
     let limit_order = LimitOrder {
         price: 1000,
         quantity: 100,
         order_side: OrderSide::Bid,
         order_id: 12345,
+        attributes: OrderAttributes::default(),
     };
 
-    OrderRequest {
-        order_core: OrderCore::LimitOrder(limit_order),
-        instid: InstId::default(),
-        systemtime: 1234567890,
-        status: OrderStatus::PendingNew,
-        filled: None,
-    }
+    OrderRequest::new(
+        InstId::default(),
+        OrderCore::LimitOrder(limit_order),
+        1234567890,
+    )
 }
 
 fn benchmark_order_request(c: &mut Criterion) {
@@ -198,6 +286,8 @@ criterion_group! {
         .sample_size(100);
     targets = 
     benchmark_order_request,
-    benchmark_quote_snapshot
+    benchmark_quote_snapshot,
+    benchmark_trade_tick,
+    benchmark_market_event
 }
 criterion_main!(benches);
\ No newline at end of file
@@ -7,12 +7,7 @@ use futures_util::{StreamExt, FutureExt};
 async fn setup_tcp_server() {
     tokio::spawn(async move {
         let listener = TcpListener::bind("127.0.0.1:5001").await.unwrap();
-        loop {
-            match listener.accept().await {
-                Ok(_) => {},
-                Err(_) => break,
-            }
-        }
+        while listener.accept().await.is_ok() {}
     });
     tokio::time::sleep(Duration::from_millis(100)).await;
 }
@@ -76,10 +71,7 @@ fn bench_sockets_check(c: &mut Criterion) {
     group.bench_function("async_ws_check", |b| {
         b.iter(|| {
             runtime.block_on(async {
-                match ws_stream.next().now_or_never() {
-                    Some(_) => {},
-                    None => {},
-                }
+                let _ = ws_stream.next().now_or_never();
             })
         });
     });
@@ -101,10 +93,7 @@ fn bench_sockets_check(c: &mut Criterion) {
             }
 
             runtime.block_on(async {
-                match ws_stream.next().now_or_never() {
-                    Some(_) => {},
-                    None => {},
-                }
+                let _ = ws_stream.next().now_or_never();
             })
         });
     });
@@ -0,0 +1,114 @@
+//! A scripted FIX 4.4 acceptor to test gateways offline
+//!
+//! fix_acceptor --instruments instruments.json [--port 9878] [--sender BROKER] [--target CLIENT]
+//!     [--venue KRX] [--script script.json] [--record conversation.jsonl] [--store dir] [--sessions 1]
+use client::fix::acceptor::{AcceptorScript, FixAcceptor};
+use client::fix::error::FixError;
+use client::fix::mapping::FixMapping;
+use client::fix::session::SessionConfig;
+use client::fix::store::{FileStore, MessageStore};
+use client::instrument::registry::InstrumentRegistry;
+use std::net::TcpListener;
+
+const USAGE: &str = "usage: fix_acceptor --instruments <json|csv> [--port 9878] [--sender BROKER] [--target CLIENT] \
+[--venue <default venue>] [--script <json>] [--record <jsonl>] [--store <dir>] [--sessions <n>]";
+
+struct Args {
+    port: u16,
+    sender: String,
+    target: String,
+    instruments: String,
+    venue: Option<String>,
+    script: Option<String>,
+    record: Option<String>,
+    store: Option<String>,
+    sessions: Option<usize>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        port: 9878,
+        sender: "BROKER".to_string(),
+        target: "CLIENT".to_string(),
+        instruments: String::new(),
+        venue: None,
+        script: None,
+        record: None,
+        store: None,
+        sessions: None,
+    };
+    let mut it = std::env::args().skip(1);
+    while let Some(flag) = it.next() {
+        let value = it.next().ok_or_else(|| format!("missing value of {}", flag))?;
+        match flag.as_str() {
+            "--port" => args.port = value.parse().map_err(|_| format!("invalid port: {}", value))?,
+            "--sender" => args.sender = value,
+            "--target" => args.target = value,
+            "--instruments" => args.instruments = value,
+            "--venue" => args.venue = Some(value),
+            "--script" => args.script = Some(value),
+            "--record" => args.record = Some(value),
+            "--store" => args.store = Some(value),
+            "--sessions" => args.sessions = Some(value.parse().map_err(|_| format!("invalid sessions: {}", value))?),
+            _ => return Err(format!("unknown option: {}", flag)),
+        }
+    }
+    if args.instruments.is_empty() {
+        return Err("--instruments is required".to_string());
+    }
+    Ok(args)
+}
+
+/// Serves the sessions one at a time, appending each conversation to the record file
+fn serve<S: MessageStore>(mut acceptor: FixAcceptor<S>, args: &Args) -> Result<(), FixError> {
+    let listener = TcpListener::bind(("0.0.0.0", args.port))?;
+    println!("fix acceptor {} listening on {}", acceptor.session().config().session_id(), args.port);
+    let mut served = 0;
+    while args.sessions.is_none_or(|sessions| served < sessions) {
+        let (stream, peer) = listener.accept()?;
+        println!("session from {}", peer);
+        if let Err(e) = acceptor.run(stream) {
+            eprintln!("session from {} failed: {}", peer, e);
+        }
+        if let Some(record) = &args.record {
+            acceptor.save_conversation(record)?;
+            acceptor.clear_conversation();
+        }
+        served += 1;
+    }
+    Ok(())
+}
+
+fn run(args: Args) -> Result<(), FixError> {
+    let registry = InstrumentRegistry::load(&args.instruments)?;
+    let mut mapping = FixMapping::new(registry);
+    if let Some(venue) = &args.venue {
+        mapping = mapping.with_default_venue(venue);
+    }
+    let script = match &args.script {
+        Some(path) => AcceptorScript::load_json(path)?,
+        None => AcceptorScript::default(),
+    };
+    let config = SessionConfig::new(&args.sender, &args.target);
+    match &args.store {
+        Some(dir) => {
+            let store = FileStore::open(dir, &config.session_id())?;
+            serve(FixAcceptor::with_store(config, mapping, script, store), &args)
+        }
+        None => serve(FixAcceptor::new(config, mapping, script), &args),
+    }
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(args) {
+        eprintln!("fix acceptor failed: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::{BookPrice, BookQuantity, InstId, TimeStamp, UnixNano};
+use crate::data::session::SessionSchedule;
+use crate::data::snapshot::QuoteSnapshot;
+use crate::data::trade::TradeTick;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+/// When a bar is closed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum BarSpec {
+    /// fixed time buckets in nanoseconds, aligned to the session start (or to the unix epoch without a schedule)
+    Time(UnixNano),
+    /// number of price events (trades or mids depending on `PriceSource`)
+    Tick(u64),
+    /// traded quantity
+    Volume(BookQuantity),
+    /// traded price * quantity in book units
+    Dollar(u128),
+}
+
+/// Which events drive open/high/low/close.
+/// Volume, turnover and VWAP always come from trades.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+pub enum PriceSource {
+    #[default]
+    Trade,
+    Mid,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Bar {
+    pub id: InstId,
+    /// For time bars, \[start_time, end_time) is the bucket.
+    /// For the others, they are the times of the first and the last event in the bar.
+    pub start_time: TimeStamp,
+    pub end_time: TimeStamp,
+    //
+    pub open: BookPrice,
+    pub high: BookPrice,
+    pub low: BookPrice,
+    pub close: BookPrice,
+    //
+    pub volume: BookQuantity,
+    pub turnover: u128,
+    pub trade_count: u64,
+    pub tick_count: u64,
+}
+
+impl Bar {
+    /// The prices are set by the first price event
+    #[inline]
+    fn new(id: InstId, start_time: TimeStamp, end_time: TimeStamp) -> Self {
+        Self {
+            id,
+            start_time,
+            end_time,
+            open: 0,
+            high: 0,
+            low: 0,
+            close: 0,
+            volume: 0,
+            turnover: 0,
+            trade_count: 0,
+            tick_count: 0,
+        }
+    }
+
+    #[inline]
+    fn update_price(&mut self, price: BookPrice) {
+        if self.tick_count == 0 {
+            self.open = price;
+            self.high = price;
+            self.low = price;
+        }
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.tick_count += 1;
+    }
+
+    /// Volume weighted average price in book units. None if nothing traded in the bar
+    #[inline]
+    pub fn vwap(&self) -> Option<f64> {
+        if self.volume == 0 {
+            None
+        } else {
+            Some(self.turnover as f64 / self.volume as f64)
+        }
+    }
+}
+
+struct BarState {
+    bar: Bar,
+    // start of the session the bar belongs to
+    session_start: Option<TimeStamp>,
+}
+
+/// Builds bars per `InstId` from `TradeTick`s and `QuoteSnapshot` mids.
+///
+/// Only the events of the `PriceSource` set open/high/low/close. With `PriceSource::Mid`, a trade before the first mid
+/// opens the bar for its volume, and the prices are set by the first mid (`tick_count` is 0 until then).
+/// With `PriceSource::Trade`, quotes never open a bar. An event that crosses a volume or dollar threshold closes the bar as a whole,
+/// i.e., trades are not split between bars.
+/// With a `SessionSchedule`, events outside the sessions are ignored and no bar spans two sessions.
+pub struct BarAggregator {
+    spec: BarSpec,
+    price_source: PriceSource,
+    schedule: Option<SessionSchedule>,
+    states: FxHashMap<InstId, BarState>,
+}
+
+impl BarAggregator {
+    pub fn new(spec: BarSpec, price_source: PriceSource) -> Self {
+        Self {
+            spec,
+            price_source,
+            schedule: None,
+            states: FxHashMap::default(),
+        }
+    }
+
+    pub fn with_schedule(mut self, schedule: SessionSchedule) -> Self {
+        self.schedule = Some(schedule);
+        self
+    }
+
+    #[inline]
+    pub fn spec(&self) -> BarSpec {
+        self.spec
+    }
+
+    /// The bar being built for `id`
+    #[inline]
+    pub fn current(&self, id: &InstId) -> Option<&Bar> {
+        self.states.get(id).map(|state| &state.bar)
+    }
+
+    /// Returns the bars closed by this trade. At most two bars are closed at once:
+    /// the one of the former bucket or session, and the one filled by the trade itself.
+    pub fn on_trade(&mut self, trade: &TradeTick) -> Vec<Bar> {
+        let moves_price = self.price_source == PriceSource::Trade;
+        self.on_event(trade.id, trade.datatime, trade.price, Some(trade.quantity), moves_price)
+    }
+
+    /// Returns the bars closed by this quote. Quotes without a mid are ignored
+    pub fn on_quote(&mut self, quote: &QuoteSnapshot) -> Vec<Bar> {
+        let Some(mid) = quote.mid() else {
+            return Vec::new();
+        };
+        let moves_price = self.price_source == PriceSource::Mid;
+        self.on_event(quote.id, quote.datatime, mid, None, moves_price)
+    }
+
+    /// Closes the time bars (and bars of ended sessions) that end at or before `now`.
+    /// This is needed since a time bar is otherwise closed only by the next event.
+    pub fn flush(&mut self, now: TimeStamp) -> Vec<Bar> {
+        let spec = self.spec;
+        let schedule = self.schedule.as_ref();
+        let expired: Vec<InstId> = self
+            .states
+            .iter()
+            .filter(|(_, state)| {
+                let bar_ended = matches!(spec, BarSpec::Time(_)) && state.bar.end_time <= now;
+                let session_ended = match (schedule, state.session_start) {
+                    (Some(schedule), Some(session_start)) => {
+                        schedule.session_of(now).map(|(start, _)| start) != Some(session_start)
+                    }
+                    _ => false,
+                };
+                bar_ended || session_ended
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|id| self.states.remove(&id).map(|state| state.bar))
+            .collect()
+    }
+
+    fn on_event(
+        &mut self,
+        id: InstId,
+        time: TimeStamp,
+        price: BookPrice,
+        quantity: Option<BookQuantity>,
+        moves_price: bool,
+    ) -> Vec<Bar> {
+        let session = match self.schedule.as_ref() {
+            Some(schedule) => match schedule.session_of(time) {
+                Some(session) => Some(session),
+                None => return Vec::new(),
+            },
+            None => None,
+        };
+        let session_start = session.map(|(start, _)| start);
+
+        let mut closed = Vec::new();
+        if let Some(state) = self.states.get(&id) {
+            let new_session = state.session_start != session_start;
+            let new_bucket = matches!(self.spec, BarSpec::Time(_)) && time >= state.bar.end_time;
+            if new_session || new_bucket {
+                closed.extend(self.states.remove(&id).map(|state| state.bar));
+            }
+        }
+
+        if !moves_price && quantity.is_none() && !self.states.contains_key(&id) {
+            return closed;
+        }
+
+        let spec = self.spec;
+        let state = self.states.entry(id).or_insert_with(|| {
+            let (start_time, end_time) = match spec {
+                BarSpec::Time(interval) => {
+                    let interval = interval.max(1);
+                    let anchor = session_start.unwrap_or(0);
+                    let start = anchor + (time - anchor) / interval * interval;
+                    let end = match session {
+                        Some((_, session_end)) => (start + interval).min(session_end),
+                        None => start + interval,
+                    };
+                    (start, end)
+                }
+                _ => (time, time),
+            };
+            BarState {
+                bar: Bar::new(id, start_time, end_time),
+                session_start,
+            }
+        });
+
+        let bar = &mut state.bar;
+        if moves_price {
+            bar.update_price(price);
+        }
+        if let Some(quantity) = quantity {
+            bar.volume += quantity;
+            bar.turnover += price.max(0) as u128 * quantity as u128;
+            bar.trade_count += 1;
+        }
+        if !matches!(spec, BarSpec::Time(_)) {
+            bar.end_time = time;
+        }
+
+        let is_full = match spec {
+            BarSpec::Time(_) => false,
+            BarSpec::Tick(count) => bar.tick_count >= count,
+            BarSpec::Volume(volume) => bar.volume >= volume,
+            BarSpec::Dollar(turnover) => bar.turnover >= turnover,
+        };
+
+        if is_full {
+            closed.extend(self.states.remove(&id).map(|state| state.bar));
+        }
+        closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::level::LevelSnapshot;
+    use crate::data::session::{SessionWindow, NANOS_PER_SECOND};
+    use crate::order::enums::OrderSide;
+
+    fn trade(time: TimeStamp, price: BookPrice, quantity: BookQuantity) -> TradeTick {
+        let id = InstId::from_str("KR7005930003", "KRX");
+        TradeTick::new(id, 0, price, quantity, OrderSide::Bid, time, time)
+    }
+
+    #[test]
+    fn test_time_bar_in_session() {
+        let minute = 60 * NANOS_PER_SECOND;
+        // session starts at 00:00:30 UTC, so buckets are aligned to 30 seconds past the minute
+        let schedule = SessionSchedule::new(0, vec![SessionWindow::new(minute / 2, 10 * minute)]);
+        let mut aggregator = BarAggregator::new(BarSpec::Time(minute), PriceSource::Trade).with_schedule(schedule);
+
+        assert!(aggregator.on_trade(&trade(minute / 4, 100, 1)).is_empty()); // before the session
+        assert!(aggregator.on_trade(&trade(minute, 100, 1)).is_empty());
+        assert!(aggregator.on_trade(&trade(minute + 1, 105, 3)).is_empty());
+        assert!(aggregator.on_trade(&trade(minute + 2, 98, 1)).is_empty());
+
+        let bar = aggregator.on_trade(&trade(2 * minute, 101, 1))[0];
+        assert_eq!((bar.start_time, bar.end_time), (minute / 2, minute + minute / 2));
+        assert_eq!((bar.open, bar.high, bar.low, bar.close), (100, 105, 98, 98));
+        assert_eq!(bar.volume, 5);
+        assert_eq!(bar.vwap(), Some((100.0 + 315.0 + 98.0) / 5.0));
+
+        let flushed = aggregator.flush(3 * minute);
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].open, 101);
+        assert!(aggregator.current(&flushed[0].id).is_none());
+    }
+
+    #[test]
+    fn test_volume_and_dollar_bars() {
+        let mut volume = BarAggregator::new(BarSpec::Volume(10), PriceSource::Trade);
+        let mut dollar = BarAggregator::new(BarSpec::Dollar(1_000), PriceSource::Trade);
+        let trades = [trade(1, 100, 4), trade(2, 101, 4), trade(3, 102, 4), trade(4, 103, 4)];
+
+        let volume_bars: Vec<Bar> = trades.iter().flat_map(|t| volume.on_trade(t)).collect();
+        let dollar_bars: Vec<Bar> = trades.iter().flat_map(|t| dollar.on_trade(t)).collect();
+
+        assert_eq!(volume_bars.len(), 1);
+        assert_eq!(volume_bars[0].volume, 12);
+        assert_eq!((volume_bars[0].start_time, volume_bars[0].end_time), (1, 3));
+        assert_eq!(dollar_bars.len(), 1);
+        assert_eq!(dollar_bars[0].turnover, 400 + 404 + 408);
+        assert_eq!(volume.current(&trades[3].id).unwrap().open, 103);
+    }
+
+    #[test]
+    fn test_tick_bar_from_mids() {
+        let mut aggregator = BarAggregator::new(BarSpec::Tick(2), PriceSource::Mid);
+        let mut quote = QuoteSnapshot::sample(1);
+        quote.id = InstId::from_str("KR7005930003", "KRX");
+        let level = |book_price| LevelSnapshot { book_price, book_quantity: 1, ..LevelSnapshot::default() };
+
+        quote.ask_quote_data[0] = level(102);
+        quote.bid_quote_data[0] = level(100);
+        assert!(aggregator.on_quote(&quote).is_empty());
+        // a trade adds volume but does not count as a tick
+        assert!(aggregator.on_trade(&trade(2, 110, 5)).is_empty());
+        quote.ask_quote_data[0] = level(104);
+        let bar = aggregator.on_quote(&quote)[0];
+        assert_eq!((bar.open, bar.high, bar.close), (101, 102, 102));
+        assert_eq!(bar.volume, 5);
+    }
+
+    #[test]
+    fn test_trade_bar_ignores_earlier_quote() {
+        let mut aggregator = BarAggregator::new(BarSpec::Tick(2), PriceSource::Trade);
+        let mut quote = QuoteSnapshot::sample(1);
+        quote.id = InstId::from_str("KR7005930003", "KRX");
+        quote.ask_quote_data[0] = LevelSnapshot { book_price: 90, book_quantity: 1, ..LevelSnapshot::default() };
+        quote.bid_quote_data[0] = LevelSnapshot { book_price: 80, book_quantity: 1, ..LevelSnapshot::default() };
+
+        assert!(aggregator.on_quote(&quote).is_empty());
+        assert!(aggregator.current(&quote.id).is_none());
+        assert!(aggregator.on_trade(&trade(2, 100, 1)).is_empty());
+        assert!(aggregator.on_quote(&quote).is_empty());
+        let bar = aggregator.on_trade(&trade(3, 101, 1))[0];
+        assert_eq!((bar.open, bar.high, bar.low, bar.close), (100, 101, 100, 101));
+    }
+}
@@ -0,0 +1,343 @@
+use crate::{BookPrice, BookQuantity, InstId, OrderCount, TimeStamp, TradeId};
+use crate::data::level::LevelSnapshot;
+use crate::data::snapshot::QuoteSnapshot;
+use crate::data::trade::TradeTick;
+use crate::order::enums::OrderSide;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The reference number of an order on the venue's book (not my `OrderId`)
+pub type OrderRef = u64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookError {
+    InstIdMismatch { expected: InstId, received: InstId },
+    DuplicateOrder(OrderRef),
+    UnknownOrder(OrderRef),
+}
+
+impl std::fmt::Display for BookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BookError::InstIdMismatch { expected, received } => {
+                write!(f, "InstId mismatch: expected {}, received {}", expected, received)
+            }
+            BookError::DuplicateOrder(order_ref) => write!(f, "order {} is already on the book", order_ref),
+            BookError::UnknownOrder(order_ref) => write!(f, "order {} is not on the book", order_ref),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+/// A change of an order by order book
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum BookAction {
+    Add {
+        order_ref: OrderRef,
+        side: OrderSide,
+        price: BookPrice,
+        quantity: BookQuantity,
+    },
+    /// `price` is None when the order is executed at its own price
+    Execute {
+        order_ref: OrderRef,
+        quantity: BookQuantity,
+        trade_id: TradeId,
+        price: Option<BookPrice>,
+    },
+    /// a partial cancel of `quantity`
+    Cancel { order_ref: OrderRef, quantity: BookQuantity },
+    Delete { order_ref: OrderRef },
+    /// the order loses its priority and is known by `new_order_ref` afterwards
+    Replace {
+        order_ref: OrderRef,
+        new_order_ref: OrderRef,
+        price: BookPrice,
+        quantity: BookQuantity,
+    },
+    Clear,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct BookEvent {
+    pub id: InstId,
+    //
+    pub datatime: TimeStamp,
+    pub systemtime: TimeStamp,
+    //
+    pub action: BookAction,
+}
+
+impl BookEvent {
+    #[inline]
+    pub fn new(id: InstId, action: BookAction, datatime: TimeStamp, systemtime: TimeStamp) -> Self {
+        Self {
+            id,
+            datatime,
+            systemtime,
+            action,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BookOrder {
+    pub side: OrderSide,
+    pub price: BookPrice,
+    pub quantity: BookQuantity,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct PriceLevel {
+    quantity: BookQuantity,
+    order_count: OrderCount,
+}
+
+/// An order by order book of an `InstId`, aggregated into price levels
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    id: InstId,
+    orders: FxHashMap<OrderRef, BookOrder>,
+    asks: BTreeMap<BookPrice, PriceLevel>,
+    bids: BTreeMap<BookPrice, PriceLevel>,
+    datatime: TimeStamp,
+    systemtime: TimeStamp,
+}
+
+impl OrderBook {
+    pub fn new(id: InstId) -> Self {
+        Self {
+            id,
+            orders: FxHashMap::default(),
+            asks: BTreeMap::new(),
+            bids: BTreeMap::new(),
+            datatime: 0,
+            systemtime: 0,
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> InstId {
+        self.id
+    }
+
+    #[inline]
+    pub fn get(&self, order_ref: OrderRef) -> Option<&BookOrder> {
+        self.orders.get(&order_ref)
+    }
+
+    #[inline]
+    pub fn order_count(&self) -> usize {
+        self.orders.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// (price, quantity) of the best level
+    #[inline]
+    pub fn best_ask(&self) -> Option<(BookPrice, BookQuantity)> {
+        self.asks.first_key_value().map(|(price, level)| (*price, level.quantity))
+    }
+
+    #[inline]
+    pub fn best_bid(&self) -> Option<(BookPrice, BookQuantity)> {
+        self.bids.last_key_value().map(|(price, level)| (*price, level.quantity))
+    }
+
+    /// Levels of the side from the best price
+    pub fn levels(&self, side: OrderSide) -> Box<dyn Iterator<Item = LevelSnapshot> + '_> {
+        let to_snapshot = |(price, level): (&BookPrice, &PriceLevel)| LevelSnapshot {
+            order_count: Some(level.order_count),
+            book_price: *price,
+            book_quantity: level.quantity,
+            book_yield: None,
+            lp_quantity: None,
+        };
+        match side {
+            OrderSide::Ask => Box::new(self.asks.iter().map(to_snapshot)),
+            OrderSide::Bid => Box::new(self.bids.iter().rev().map(to_snapshot)),
+        }
+    }
+
+    /// The best `depth` levels of each side
+    pub fn snapshot(&self, depth: usize) -> QuoteSnapshot {
+        let ask_quote_data: Vec<LevelSnapshot> = self.levels(OrderSide::Ask).take(depth).collect();
+        let bid_quote_data: Vec<LevelSnapshot> = self.levels(OrderSide::Bid).take(depth).collect();
+        QuoteSnapshot {
+            id: self.id,
+            datatime: self.datatime,
+            systemtime: self.systemtime,
+            quote_level_cut: ask_quote_data.len().max(bid_quote_data.len()),
+            ask_quote_data,
+            bid_quote_data,
+            all_lp_holdings: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.orders.clear();
+        self.asks.clear();
+        self.bids.clear();
+    }
+
+    fn level_mut(&mut self, side: OrderSide, price: BookPrice) -> &mut PriceLevel {
+        match side {
+            OrderSide::Ask => self.asks.entry(price).or_default(),
+            OrderSide::Bid => self.bids.entry(price).or_default(),
+        }
+    }
+
+    /// Takes `quantity` (and the order itself if `removed`) out of its level
+    fn reduce_level(&mut self, order: &BookOrder, quantity: BookQuantity, removed: bool) {
+        let levels = match order.side {
+            OrderSide::Ask => &mut self.asks,
+            OrderSide::Bid => &mut self.bids,
+        };
+        if let Some(level) = levels.get_mut(&order.price) {
+            level.quantity = level.quantity.saturating_sub(quantity);
+            if removed {
+                level.order_count = level.order_count.saturating_sub(1);
+            }
+            if level.order_count == 0 {
+                levels.remove(&order.price);
+            }
+        }
+    }
+
+    pub fn add(&mut self, order_ref: OrderRef, order: BookOrder) -> Result<(), BookError> {
+        if self.orders.contains_key(&order_ref) {
+            return Err(BookError::DuplicateOrder(order_ref));
+        }
+        let level = self.level_mut(order.side, order.price);
+        level.quantity += order.quantity;
+        level.order_count += 1;
+        self.orders.insert(order_ref, order);
+        Ok(())
+    }
+
+    /// Takes up to `quantity` from the order, removing it once nothing is left.
+    /// Returns the order as it was with the quantity actually taken
+    pub fn reduce(&mut self, order_ref: OrderRef, quantity: BookQuantity) -> Result<BookOrder, BookError> {
+        let order = self.orders.get_mut(&order_ref).ok_or(BookError::UnknownOrder(order_ref))?;
+        let taken = quantity.min(order.quantity);
+        order.quantity -= taken;
+        let removed = order.quantity == 0;
+        let reduced = BookOrder { quantity: taken, ..*order };
+        if removed {
+            self.orders.remove(&order_ref);
+        }
+        self.reduce_level(&reduced, taken, removed);
+        Ok(reduced)
+    }
+
+    pub fn delete(&mut self, order_ref: OrderRef) -> Result<BookOrder, BookError> {
+        let quantity = self.orders.get(&order_ref).ok_or(BookError::UnknownOrder(order_ref))?.quantity;
+        self.reduce(order_ref, quantity)
+    }
+
+    /// Applies the event. An execution returns the trade, where the aggressor is the opposite of the resting order
+    pub fn apply(&mut self, event: &BookEvent) -> Result<Option<TradeTick>, BookError> {
+        if event.id != self.id {
+            return Err(BookError::InstIdMismatch { expected: self.id, received: event.id });
+        }
+        let mut trade = None;
+        match event.action {
+            BookAction::Add { order_ref, side, price, quantity } => {
+                self.add(order_ref, BookOrder { side, price, quantity })?
+            }
+            BookAction::Execute { order_ref, quantity, trade_id, price } => {
+                let executed = self.reduce(order_ref, quantity)?;
+                let aggressor = match executed.side {
+                    OrderSide::Ask => OrderSide::Bid,
+                    OrderSide::Bid => OrderSide::Ask,
+                };
+                trade = Some(TradeTick::new(
+                    self.id,
+                    trade_id,
+                    price.unwrap_or(executed.price),
+                    executed.quantity,
+                    aggressor,
+                    event.datatime,
+                    event.systemtime,
+                ));
+            }
+            BookAction::Cancel { order_ref, quantity } => {
+                self.reduce(order_ref, quantity)?;
+            }
+            BookAction::Delete { order_ref } => {
+                self.delete(order_ref)?;
+            }
+            BookAction::Replace { order_ref, new_order_ref, price, quantity } => {
+                if self.orders.contains_key(&new_order_ref) {
+                    return Err(BookError::DuplicateOrder(new_order_ref));
+                }
+                let side = self.delete(order_ref)?.side;
+                self.add(new_order_ref, BookOrder { side, price, quantity })?;
+            }
+            BookAction::Clear => self.clear(),
+        }
+        self.datatime = event.datatime;
+        self.systemtime = event.systemtime;
+        Ok(trade)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(action: BookAction) -> BookEvent {
+        BookEvent::new(InstId::from_str("AAPL", "NASDAQ"), action, 1, 2)
+    }
+
+    fn add(order_ref: OrderRef, side: OrderSide, price: BookPrice, quantity: BookQuantity) -> BookEvent {
+        event(BookAction::Add { order_ref, side, price, quantity })
+    }
+
+    #[test]
+    fn test_levels_and_snapshot() {
+        let mut book = OrderBook::new(InstId::from_str("AAPL", "NASDAQ"));
+        book.apply(&add(1, OrderSide::Bid, 100, 10)).unwrap();
+        book.apply(&add(2, OrderSide::Bid, 100, 5)).unwrap();
+        book.apply(&add(3, OrderSide::Bid, 99, 7)).unwrap();
+        book.apply(&add(4, OrderSide::Ask, 101, 3)).unwrap();
+        book.apply(&add(5, OrderSide::Ask, 102, 4)).unwrap();
+        assert_eq!(book.apply(&add(5, OrderSide::Ask, 103, 1)), Err(BookError::DuplicateOrder(5)));
+
+        assert_eq!(book.best_bid(), Some((100, 15)));
+        assert_eq!(book.best_ask(), Some((101, 3)));
+        let snapshot = book.snapshot(1);
+        assert_eq!(snapshot.quote_level_cut, 1);
+        assert_eq!(snapshot.bid_quote_data[0].order_count, Some(2));
+        assert_eq!(snapshot.mid(), Some(100));
+        let bids: Vec<(BookPrice, BookQuantity)> =
+            book.levels(OrderSide::Bid).map(|level| (level.book_price, level.book_quantity)).collect();
+        assert_eq!(bids, vec![(100, 15), (99, 7)]);
+    }
+
+    #[test]
+    fn test_executions_cancels_and_replaces() {
+        let mut book = OrderBook::new(InstId::from_str("AAPL", "NASDAQ"));
+        book.apply(&add(1, OrderSide::Ask, 101, 10)).unwrap();
+        book.apply(&add(2, OrderSide::Ask, 101, 5)).unwrap();
+
+        let execute = event(BookAction::Execute { order_ref: 1, quantity: 4, trade_id: 9, price: None });
+        let trade = book.apply(&execute).unwrap().unwrap();
+        assert_eq!((trade.price, trade.quantity, trade.aggressor, trade.trade_id), (101, 4, OrderSide::Bid, 9));
+        assert_eq!(book.best_ask(), Some((101, 11)));
+
+        book.apply(&event(BookAction::Cancel { order_ref: 1, quantity: 6 })).unwrap();
+        assert!(book.get(1).is_none());
+        book.apply(&event(BookAction::Replace { order_ref: 2, new_order_ref: 3, price: 102, quantity: 8 })).unwrap();
+        assert_eq!(book.best_ask(), Some((102, 8)));
+        assert_eq!(book.get(3), Some(&BookOrder { side: OrderSide::Ask, price: 102, quantity: 8 }));
+        assert_eq!(book.apply(&event(BookAction::Delete { order_ref: 2 })), Err(BookError::UnknownOrder(2)));
+        book.apply(&event(BookAction::Delete { order_ref: 3 })).unwrap();
+        assert!(book.is_empty() && book.best_ask().is_none());
+    }
+}
@@ -0,0 +1,685 @@
+use crate::{BookQuantity, InstId, TimeStamp};
+use crate::data::level::LevelSnapshot;
+use crate::data::snapshot::QuoteSnapshot;
+use crate::instrument::error::InstrumentError;
+use crate::instrument::registry::InstrumentRegistry;
+use crate::order::enums::OrderSide;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeltaError {
+    InstIdMismatch { expected: InstId, received: InstId },
+    LevelOutOfRange { side: OrderSide, level: usize, depth: usize },
+    SequenceGap { id: InstId, expected: u64, received: u64 },
+    MissingRefresh(InstId),
+    Truncated,
+    UnknownMessageKind(u8),
+    UnknownSide(u8),
+    InvalidUtf8,
+    /// the value does not fit the width of the field on the wire
+    FieldOverflow { field: &'static str, value: usize },
+    /// the code and venue are not in the registry
+    Instrument(InstrumentError),
+}
+
+impl std::fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DeltaError::InstIdMismatch { expected, received } => {
+                write!(f, "InstId mismatch: expected {}, received {}", expected, received)
+            }
+            DeltaError::LevelOutOfRange { side, level, depth } => {
+                write!(f, "{:?} level {} is out of range (depth {})", side, level, depth)
+            }
+            DeltaError::SequenceGap { id, expected, received } => {
+                write!(f, "sequence gap on {}: expected {}, received {}", id, expected, received)
+            }
+            DeltaError::MissingRefresh(id) => write!(f, "no full refresh received for {}", id),
+            DeltaError::Truncated => write!(f, "delta message is truncated"),
+            DeltaError::UnknownMessageKind(kind) => write!(f, "unknown delta message kind: {}", kind),
+            DeltaError::UnknownSide(side) => write!(f, "unknown side byte: {}", side),
+            DeltaError::InvalidUtf8 => write!(f, "delta message has an invalid UTF-8 string"),
+            DeltaError::FieldOverflow { field, value } => write!(f, "{} of {} does not fit its field", field, value),
+            DeltaError::Instrument(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DeltaError {}
+
+impl From<InstrumentError> for DeltaError {
+    fn from(e: InstrumentError) -> Self {
+        DeltaError::Instrument(e)
+    }
+}
+
+/// A single level that has to be (re)written on one side of the book.
+/// Levels are positional, i.e., `level` is the index in `ask_quote_data` or `bid_quote_data`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct LevelDelta {
+    pub side: OrderSide,
+    pub level: usize,
+    pub snapshot: LevelSnapshot,
+}
+
+/// The difference between two `QuoteSnapshot`s of the same `InstId`.
+/// Removed levels are expressed by the new depth of each side (`ask_depth`, `bid_depth`),
+/// and every level that is new or different is carried in `changes`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SnapshotDelta {
+    pub id: InstId,
+    //
+    pub datatime: TimeStamp,
+    pub systemtime: TimeStamp,
+    //
+    pub ask_depth: usize,
+    pub bid_depth: usize,
+    pub changes: Vec<LevelDelta>,
+    pub quote_level_cut: usize,
+    //
+    pub all_lp_holdings: Option<BookQuantity>,
+}
+
+impl SnapshotDelta {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+fn diff_side(
+    side: OrderSide,
+    prev: &[LevelSnapshot],
+    next: &[LevelSnapshot],
+    changes: &mut Vec<LevelDelta>,
+) {
+    for (level, snapshot) in next.iter().enumerate() {
+        if prev.get(level) != Some(snapshot) {
+            changes.push(LevelDelta { side, level, snapshot: *snapshot });
+        }
+    }
+}
+
+/// Computes the level changes that turn `prev` into `next`.
+pub fn diff(prev: &QuoteSnapshot, next: &QuoteSnapshot) -> Result<SnapshotDelta, DeltaError> {
+    if prev.id != next.id {
+        return Err(DeltaError::InstIdMismatch { expected: prev.id, received: next.id });
+    }
+
+    let mut changes = Vec::new();
+    diff_side(OrderSide::Ask, &prev.ask_quote_data, &next.ask_quote_data, &mut changes);
+    diff_side(OrderSide::Bid, &prev.bid_quote_data, &next.bid_quote_data, &mut changes);
+
+    Ok(SnapshotDelta {
+        id: next.id,
+        datatime: next.datatime,
+        systemtime: next.systemtime,
+        ask_depth: next.ask_quote_data.len(),
+        bid_depth: next.bid_quote_data.len(),
+        changes,
+        quote_level_cut: next.quote_level_cut,
+        all_lp_holdings: next.all_lp_holdings,
+    })
+}
+
+/// Applies `delta` on `snapshot` in place.
+pub fn apply_in_place(snapshot: &mut QuoteSnapshot, delta: &SnapshotDelta) -> Result<(), DeltaError> {
+    if snapshot.id != delta.id {
+        return Err(DeltaError::InstIdMismatch { expected: snapshot.id, received: delta.id });
+    }
+
+    snapshot.ask_quote_data.resize(delta.ask_depth, LevelSnapshot::default());
+    snapshot.bid_quote_data.resize(delta.bid_depth, LevelSnapshot::default());
+
+    for change in delta.changes.iter() {
+        let levels = match change.side {
+            OrderSide::Ask => &mut snapshot.ask_quote_data,
+            OrderSide::Bid => &mut snapshot.bid_quote_data,
+        };
+        let depth = levels.len();
+        match levels.get_mut(change.level) {
+            Some(level) => *level = change.snapshot,
+            None => {
+                return Err(DeltaError::LevelOutOfRange { side: change.side, level: change.level, depth });
+            }
+        }
+    }
+
+    snapshot.datatime = delta.datatime;
+    snapshot.systemtime = delta.systemtime;
+    snapshot.quote_level_cut = delta.quote_level_cut;
+    snapshot.all_lp_holdings = delta.all_lp_holdings;
+    Ok(())
+}
+
+/// Reconstructs the next snapshot from `prev` and `delta`.
+pub fn apply(prev: &QuoteSnapshot, delta: &SnapshotDelta) -> Result<QuoteSnapshot, DeltaError> {
+    let mut next = prev.clone();
+    apply_in_place(&mut next, delta)?;
+    Ok(next)
+}
+
+/// A message of the delta stream. The sequence number is per `InstId`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum DeltaMessage {
+    Refresh { seq: u64, snapshot: QuoteSnapshot },
+    Delta { seq: u64, delta: SnapshotDelta },
+}
+
+const REFRESH_KIND: u8 = 0;
+const DELTA_KIND: u8 = 1;
+
+const HAS_ORDER_COUNT: u8 = 1;
+const HAS_YIELD: u8 = 1 << 1;
+const HAS_LP_QUANTITY: u8 = 1 << 2;
+
+impl DeltaMessage {
+    #[inline]
+    pub fn id(&self) -> InstId {
+        match self {
+            DeltaMessage::Refresh { snapshot, .. } => snapshot.id,
+            DeltaMessage::Delta { delta, .. } => delta.id,
+        }
+    }
+
+    #[inline]
+    pub fn seq(&self) -> u64 {
+        match self {
+            DeltaMessage::Refresh { seq, .. } => *seq,
+            DeltaMessage::Delta { seq, .. } => *seq,
+        }
+    }
+
+    /// Appends the little-endian binary form of the message to `buf`.
+    ///
+    /// layout: kind(u8) seq(u64) code_len(u8) code venue_len(u8) venue datatime(u64) systemtime(u64)
+    /// quote_level_cut(u32) lp_flag(u8) [all_lp_holdings(u64)] followed by
+    /// - Refresh: ask_count(u16) levels, bid_count(u16) levels
+    /// - Delta: ask_depth(u16) bid_depth(u16) change_count(u16) (side(u8) level(u16) level)*
+    ///
+    /// A value that does not fit its field is an error, and `buf` is left as it was.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<(), DeltaError> {
+        let start = buf.len();
+        self.encode_into(buf).inspect_err(|_| buf.truncate(start))
+    }
+
+    fn encode_into(&self, buf: &mut Vec<u8>) -> Result<(), DeltaError> {
+        let (kind, seq, id, datatime, systemtime, quote_level_cut, all_lp_holdings) = match self {
+            DeltaMessage::Refresh { seq, snapshot } => (
+                REFRESH_KIND,
+                *seq,
+                snapshot.id,
+                snapshot.datatime,
+                snapshot.systemtime,
+                snapshot.quote_level_cut,
+                snapshot.all_lp_holdings,
+            ),
+            DeltaMessage::Delta { seq, delta } => (
+                DELTA_KIND,
+                *seq,
+                delta.id,
+                delta.datatime,
+                delta.systemtime,
+                delta.quote_level_cut,
+                delta.all_lp_holdings,
+            ),
+        };
+
+        buf.push(kind);
+        buf.extend_from_slice(&seq.to_le_bytes());
+        put_str(buf, "code", id.code_str())?;
+        put_str(buf, "venue", id.venue_str())?;
+        buf.extend_from_slice(&datatime.to_le_bytes());
+        buf.extend_from_slice(&systemtime.to_le_bytes());
+        buf.extend_from_slice(&narrow::<u32>("quote_level_cut", quote_level_cut)?.to_le_bytes());
+        match all_lp_holdings {
+            Some(holdings) => {
+                buf.push(1);
+                buf.extend_from_slice(&holdings.to_le_bytes());
+            }
+            None => buf.push(0),
+        }
+
+        match self {
+            DeltaMessage::Refresh { snapshot, .. } => {
+                buf.extend_from_slice(&narrow::<u16>("ask_count", snapshot.ask_quote_data.len())?.to_le_bytes());
+                snapshot.ask_quote_data.iter().for_each(|level| put_level(buf, level));
+                buf.extend_from_slice(&narrow::<u16>("bid_count", snapshot.bid_quote_data.len())?.to_le_bytes());
+                snapshot.bid_quote_data.iter().for_each(|level| put_level(buf, level));
+            }
+            DeltaMessage::Delta { delta, .. } => {
+                buf.extend_from_slice(&narrow::<u16>("ask_depth", delta.ask_depth)?.to_le_bytes());
+                buf.extend_from_slice(&narrow::<u16>("bid_depth", delta.bid_depth)?.to_le_bytes());
+                buf.extend_from_slice(&narrow::<u16>("change_count", delta.changes.len())?.to_le_bytes());
+                for change in delta.changes.iter() {
+                    buf.push(match change.side {
+                        OrderSide::Bid => 0,
+                        OrderSide::Ask => 1,
+                    });
+                    buf.extend_from_slice(&narrow::<u16>("level", change.level)?.to_le_bytes());
+                    put_level(buf, &change.snapshot);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes one message from the front of `buf` and returns it with the number of bytes consumed.
+    /// The instrument is resolved through `registry`, so an unknown code is an error.
+    pub fn decode(buf: &[u8], registry: &InstrumentRegistry) -> Result<(Self, usize), DeltaError> {
+        let mut reader = Reader { buf, pos: 0 };
+        let kind = reader.u8()?;
+        let seq = reader.u64()?;
+        let code = reader.str()?;
+        let venue = reader.str()?;
+        let id = registry.try_find(code, venue)?;
+        let datatime = reader.u64()?;
+        let systemtime = reader.u64()?;
+        let quote_level_cut = reader.u32()? as usize;
+        let all_lp_holdings = match reader.u8()? {
+            0 => None,
+            _ => Some(reader.u64()?),
+        };
+
+        let message = match kind {
+            REFRESH_KIND => {
+                let ask_count = reader.u16()? as usize;
+                let ask_quote_data = (0..ask_count).map(|_| reader.level()).collect::<Result<Vec<_>, _>>()?;
+                let bid_count = reader.u16()? as usize;
+                let bid_quote_data = (0..bid_count).map(|_| reader.level()).collect::<Result<Vec<_>, _>>()?;
+                DeltaMessage::Refresh {
+                    seq,
+                    snapshot: QuoteSnapshot {
+                        id,
+                        datatime,
+                        systemtime,
+                        ask_quote_data,
+                        bid_quote_data,
+                        quote_level_cut,
+                        all_lp_holdings,
+                    },
+                }
+            }
+            DELTA_KIND => {
+                let ask_depth = reader.u16()? as usize;
+                let bid_depth = reader.u16()? as usize;
+                let change_count = reader.u16()? as usize;
+                let mut changes = Vec::with_capacity(change_count);
+                for _ in 0..change_count {
+                    let side = match reader.u8()? {
+                        0 => OrderSide::Bid,
+                        1 => OrderSide::Ask,
+                        side => return Err(DeltaError::UnknownSide(side)),
+                    };
+                    let level = reader.u16()? as usize;
+                    let snapshot = reader.level()?;
+                    changes.push(LevelDelta { side, level, snapshot });
+                }
+                DeltaMessage::Delta {
+                    seq,
+                    delta: SnapshotDelta {
+                        id,
+                        datatime,
+                        systemtime,
+                        ask_depth,
+                        bid_depth,
+                        changes,
+                        quote_level_cut,
+                        all_lp_holdings,
+                    },
+                }
+            }
+            kind => return Err(DeltaError::UnknownMessageKind(kind)),
+        };
+
+        Ok((message, reader.pos))
+    }
+}
+
+#[inline]
+fn narrow<T: TryFrom<usize>>(field: &'static str, value: usize) -> Result<T, DeltaError> {
+    T::try_from(value).map_err(|_| DeltaError::FieldOverflow { field, value })
+}
+
+#[inline]
+fn put_str(buf: &mut Vec<u8>, field: &'static str, s: &str) -> Result<(), DeltaError> {
+    buf.push(narrow::<u8>(field, s.len())?);
+    buf.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+#[inline]
+fn put_level(buf: &mut Vec<u8>, level: &LevelSnapshot) {
+    let mut flags = 0;
+    if level.order_count.is_some() {
+        flags |= HAS_ORDER_COUNT;
+    }
+    if level.book_yield.is_some() {
+        flags |= HAS_YIELD;
+    }
+    if level.lp_quantity.is_some() {
+        flags |= HAS_LP_QUANTITY;
+    }
+    buf.push(flags);
+    buf.extend_from_slice(&level.book_price.to_le_bytes());
+    buf.extend_from_slice(&level.book_quantity.to_le_bytes());
+    if let Some(order_count) = level.order_count {
+        buf.extend_from_slice(&order_count.to_le_bytes());
+    }
+    if let Some(book_yield) = level.book_yield {
+        buf.extend_from_slice(&book_yield.to_le_bytes());
+    }
+    if let Some(lp_quantity) = level.lp_quantity {
+        buf.extend_from_slice(&lp_quantity.to_le_bytes());
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    #[inline]
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], DeltaError> {
+        let bytes = self.buf.get(self.pos..self.pos + N).ok_or(DeltaError::Truncated)?;
+        self.pos += N;
+        Ok(bytes.try_into().expect("slice length is checked"))
+    }
+
+    #[inline]
+    fn u8(&mut self) -> Result<u8, DeltaError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    #[inline]
+    fn u16(&mut self) -> Result<u16, DeltaError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    #[inline]
+    fn u32(&mut self) -> Result<u32, DeltaError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    #[inline]
+    fn u64(&mut self) -> Result<u64, DeltaError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    #[inline]
+    fn str(&mut self) -> Result<&'a str, DeltaError> {
+        let len = self.u8()? as usize;
+        let bytes = self.buf.get(self.pos..self.pos + len).ok_or(DeltaError::Truncated)?;
+        self.pos += len;
+        std::str::from_utf8(bytes).map_err(|_| DeltaError::InvalidUtf8)
+    }
+
+    fn level(&mut self) -> Result<LevelSnapshot, DeltaError> {
+        let flags = self.u8()?;
+        let book_price = i64::from_le_bytes(self.take()?);
+        let book_quantity = self.u64()?;
+        let order_count = if flags & HAS_ORDER_COUNT != 0 { Some(self.u32()?) } else { None };
+        let book_yield = if flags & HAS_YIELD != 0 { Some(i32::from_le_bytes(self.take()?)) } else { None };
+        let lp_quantity = if flags & HAS_LP_QUANTITY != 0 { Some(self.u64()?) } else { None };
+        Ok(LevelSnapshot {
+            order_count,
+            book_price,
+            book_quantity,
+            book_yield,
+            lp_quantity,
+        })
+    }
+}
+
+/// Turns a stream of `QuoteSnapshot`s into `DeltaMessage`s.
+/// Every `refresh_interval`-th message of an `InstId` (and the first one) is a full refresh,
+/// so that late joiners and receivers that detected a gap can recover.
+pub struct DeltaEncoder {
+    refresh_interval: u64,
+    states: FxHashMap<InstId, EncoderState>,
+}
+
+struct EncoderState {
+    last: QuoteSnapshot,
+    seq: u64,
+    since_refresh: u64,
+}
+
+impl DeltaEncoder {
+    /// `refresh_interval` of 0 or 1 means every message is a full refresh
+    pub fn new(refresh_interval: u64) -> Self {
+        Self {
+            refresh_interval,
+            states: FxHashMap::default(),
+        }
+    }
+
+    /// Forces the next message of `id` to be a full refresh
+    pub fn request_refresh(&mut self, id: InstId) {
+        if let Some(state) = self.states.get_mut(&id) {
+            state.since_refresh = self.refresh_interval;
+        }
+    }
+
+    pub fn encode(&mut self, snapshot: &QuoteSnapshot) -> DeltaMessage {
+        let refresh_interval = self.refresh_interval;
+        match self.states.get_mut(&snapshot.id) {
+            Some(state) => {
+                state.seq += 1;
+                state.since_refresh += 1;
+                let message = if state.since_refresh >= refresh_interval {
+                    state.since_refresh = 0;
+                    DeltaMessage::Refresh { seq: state.seq, snapshot: snapshot.clone() }
+                } else {
+                    let delta = diff(&state.last, snapshot).expect("states are keyed by InstId");
+                    DeltaMessage::Delta { seq: state.seq, delta }
+                };
+                state.last.clone_from(snapshot);
+                message
+            }
+            None => {
+                self.states.insert(
+                    snapshot.id,
+                    EncoderState { last: snapshot.clone(), seq: 0, since_refresh: 0 },
+                );
+                DeltaMessage::Refresh { seq: 0, snapshot: snapshot.clone() }
+            }
+        }
+    }
+}
+
+/// Rebuilds `QuoteSnapshot`s from `DeltaMessage`s.
+/// After a sequence gap, deltas of that `InstId` are rejected until the next full refresh.
+#[derive(Default)]
+pub struct DeltaDecoder {
+    states: FxHashMap<InstId, DecoderState>,
+}
+
+struct DecoderState {
+    snapshot: QuoteSnapshot,
+    seq: u64,
+    stale: bool,
+}
+
+impl DeltaDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, id: &InstId) -> Option<&QuoteSnapshot> {
+        self.states.get(id).filter(|state| !state.stale).map(|state| &state.snapshot)
+    }
+
+    pub fn decode(&mut self, message: &DeltaMessage) -> Result<&QuoteSnapshot, DeltaError> {
+        match message {
+            DeltaMessage::Refresh { seq, snapshot } => {
+                let state = self.states.entry(snapshot.id).or_insert_with(|| DecoderState {
+                    snapshot: snapshot.clone(),
+                    seq: *seq,
+                    stale: false,
+                });
+                state.snapshot.clone_from(snapshot);
+                state.seq = *seq;
+                state.stale = false;
+                Ok(&state.snapshot)
+            }
+            DeltaMessage::Delta { seq, delta } => {
+                let state = self.states.get_mut(&delta.id).ok_or(DeltaError::MissingRefresh(delta.id))?;
+                if state.stale {
+                    return Err(DeltaError::MissingRefresh(delta.id));
+                }
+                if *seq != state.seq + 1 {
+                    state.stale = true;
+                    return Err(DeltaError::SequenceGap { id: delta.id, expected: state.seq + 1, received: *seq });
+                }
+                if let Err(e) = apply_in_place(&mut state.snapshot, delta) {
+                    state.stale = true;
+                    return Err(e);
+                }
+                state.seq = *seq;
+                Ok(&state.snapshot)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instrument::spec::InstrumentSpec;
+
+    fn registry() -> InstrumentRegistry {
+        let mut registry = InstrumentRegistry::new();
+        registry.insert(InstrumentSpec::new(InstId::from_str("KR7005930003", "KRX"), 1, 0));
+        registry
+    }
+
+    fn level(book_price: i64, book_quantity: u64) -> LevelSnapshot {
+        LevelSnapshot {
+            order_count: Some(1),
+            book_price,
+            book_quantity,
+            book_yield: None,
+            lp_quantity: None,
+        }
+    }
+
+    fn snapshot(asks: &[(i64, u64)], bids: &[(i64, u64)]) -> QuoteSnapshot {
+        let mut snapshot = QuoteSnapshot::sample(0);
+        snapshot.id = InstId::from_str("KR7005930003", "KRX");
+        snapshot.ask_quote_data = asks.iter().map(|&(p, q)| level(p, q)).collect();
+        snapshot.bid_quote_data = bids.iter().map(|&(p, q)| level(p, q)).collect();
+        snapshot.quote_level_cut = asks.len().max(bids.len());
+        snapshot
+    }
+
+    #[test]
+    fn test_diff_and_apply() {
+        let prev = snapshot(&[(101, 10), (102, 20)], &[(100, 5), (99, 7)]);
+        let mut next = snapshot(&[(101, 12), (102, 20), (103, 1)], &[(100, 5)]);
+        next.datatime = 10;
+
+        let delta = diff(&prev, &next).unwrap();
+        assert_eq!(delta.changes.len(), 2);
+        assert_eq!(delta.bid_depth, 1);
+        assert_eq!(apply(&prev, &delta).unwrap(), next);
+
+        let same = diff(&next, &next).unwrap();
+        assert!(same.is_empty());
+    }
+
+    #[test]
+    fn test_diff_different_id() {
+        let prev = snapshot(&[(101, 10)], &[(100, 5)]);
+        let mut next = prev.clone();
+        next.id = InstId::from_str("KR7000660001", "KRX");
+        assert!(matches!(diff(&prev, &next), Err(DeltaError::InstIdMismatch { .. })));
+    }
+
+    #[test]
+    fn test_codec_round_trip() {
+        let prev = snapshot(&[(101, 10), (102, 20)], &[(100, 5), (99, 7)]);
+        let mut next = snapshot(&[(101, 11), (102, 20)], &[(100, 5)]);
+        next.ask_quote_data[0].lp_quantity = Some(3);
+        next.all_lp_holdings = Some(42);
+
+        let mut encoder = DeltaEncoder::new(3);
+        let mut buf = Vec::new();
+        for message in [encoder.encode(&prev), encoder.encode(&next)] {
+            message.encode(&mut buf).unwrap();
+        }
+
+        let registry = registry();
+        let mut decoder = DeltaDecoder::new();
+        let mut pos = 0;
+        let mut decoded = Vec::new();
+        while pos < buf.len() {
+            let (message, size) = DeltaMessage::decode(&buf[pos..], &registry).unwrap();
+            pos += size;
+            decoded.push(decoder.decode(&message).unwrap().clone());
+        }
+        assert_eq!(decoded, vec![prev, next]);
+        assert_eq!(DeltaMessage::decode(&buf[..10], &registry).err(), Some(DeltaError::Truncated));
+
+        // a code that is not in the registry
+        let mut unknown = snapshot(&[(101, 10)], &[(100, 5)]);
+        unknown.id = InstId::from_str("KR7000660001", "KRX");
+        let mut buf = Vec::new();
+        DeltaMessage::Refresh { seq: 0, snapshot: unknown }.encode(&mut buf).unwrap();
+        assert_eq!(
+            DeltaMessage::decode(&buf, &registry).err(),
+            Some(DeltaError::Instrument(InstrumentError::UnknownCode { code: "KR7000660001".to_string(), venue: "KRX".to_string() }))
+        );
+    }
+
+    #[test]
+    fn test_periodic_refresh_and_gap() {
+        let book = snapshot(&[(101, 10)], &[(100, 5)]);
+        let mut encoder = DeltaEncoder::new(3);
+        let messages: Vec<DeltaMessage> = (0..7).map(|_| encoder.encode(&book)).collect();
+        let refreshes: Vec<u64> = messages
+            .iter()
+            .filter(|m| matches!(m, DeltaMessage::Refresh { .. }))
+            .map(|m| m.seq())
+            .collect();
+        assert_eq!(refreshes, vec![0, 3, 6]);
+
+        let mut decoder = DeltaDecoder::new();
+        decoder.decode(&messages[0]).unwrap();
+        assert!(matches!(decoder.decode(&messages[2]), Err(DeltaError::SequenceGap { .. })));
+        assert!(matches!(decoder.decode(&messages[2]), Err(DeltaError::MissingRefresh(_))));
+        assert!(decoder.get(&book.id).is_none());
+        decoder.decode(&messages[3]).unwrap();
+        assert_eq!(decoder.decode(&messages[4]).unwrap(), &book);
+    }
+
+    #[test]
+    fn test_codec_rejects_invalid_fields() {
+        let mut book = snapshot(&[(101, 10)], &[(100, 5)]);
+        book.ask_quote_data.resize(u16::MAX as usize + 1, level(101, 10));
+        let mut buf = vec![7];
+        let message = DeltaMessage::Refresh { seq: 0, snapshot: book.clone() };
+        assert_eq!(
+            message.encode(&mut buf),
+            Err(DeltaError::FieldOverflow { field: "ask_count", value: u16::MAX as usize + 1 })
+        );
+        assert_eq!(buf, vec![7]);
+
+        let mut encoder = DeltaEncoder::new(10);
+        let prev = snapshot(&[(101, 10)], &[(100, 5)]);
+        encoder.encode(&prev);
+        let mut next = prev.clone();
+        next.bid_quote_data[0].book_quantity = 6;
+        let mut buf = Vec::new();
+        encoder.encode(&next).encode(&mut buf).unwrap();
+        let side_at = buf.len() - 1 - 2 - 1 - 8 - 8 - 4;
+        assert_eq!(buf[side_at], 0);
+        buf[side_at] = 2;
+        assert_eq!(DeltaMessage::decode(&buf, &registry()).err(), Some(DeltaError::UnknownSide(2)));
+
+        buf[10] = 0xFF; // the first byte of the code
+        assert_eq!(DeltaMessage::decode(&buf, &registry()).err(), Some(DeltaError::InvalidUtf8));
+    }
+}
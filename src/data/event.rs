@@ -0,0 +1,128 @@
+use crate::{InstId, TimeStamp};
+use crate::data::book::BookEvent;
+use crate::data::snapshot::QuoteSnapshot;
+use crate::data::trade::TradeTick;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+pub enum TradingStatus {
+    #[default]
+    Unknown,
+    PreOpen,
+    OpeningAuction,
+    Continuous,
+    ClosingAuction,
+    Halted,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct MarketStatus {
+    pub id: InstId,
+    //
+    pub datatime: TimeStamp,
+    pub systemtime: TimeStamp,
+    //
+    pub status: TradingStatus,
+}
+
+impl MarketStatus {
+    #[inline]
+    pub fn new(id: InstId, status: TradingStatus, datatime: TimeStamp, systemtime: TimeStamp) -> Self {
+        Self {
+            id,
+            datatime,
+            systemtime,
+            status,
+        }
+    }
+}
+
+/// Everything a market data feed can deliver for an `InstId`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum MarketEvent {
+    Quote(QuoteSnapshot),
+    Trade(TradeTick),
+    Status(MarketStatus),
+    /// an order by order change, see `OrderBook`
+    Book(BookEvent),
+}
+
+impl MarketEvent {
+    #[inline]
+    pub fn id(&self) -> InstId {
+        match self {
+            MarketEvent::Quote(quote) => quote.id,
+            MarketEvent::Trade(trade) => trade.id,
+            MarketEvent::Status(status) => status.id,
+            MarketEvent::Book(book) => book.id,
+        }
+    }
+
+    #[inline]
+    pub fn datatime(&self) -> TimeStamp {
+        match self {
+            MarketEvent::Quote(quote) => quote.datatime,
+            MarketEvent::Trade(trade) => trade.datatime,
+            MarketEvent::Status(status) => status.datatime,
+            MarketEvent::Book(book) => book.datatime,
+        }
+    }
+
+    #[inline]
+    pub fn systemtime(&self) -> TimeStamp {
+        match self {
+            MarketEvent::Quote(quote) => quote.systemtime,
+            MarketEvent::Trade(trade) => trade.systemtime,
+            MarketEvent::Status(status) => status.systemtime,
+            MarketEvent::Book(book) => book.systemtime,
+        }
+    }
+}
+
+impl From<QuoteSnapshot> for MarketEvent {
+    fn from(quote: QuoteSnapshot) -> Self {
+        MarketEvent::Quote(quote)
+    }
+}
+
+impl From<TradeTick> for MarketEvent {
+    fn from(trade: TradeTick) -> Self {
+        MarketEvent::Trade(trade)
+    }
+}
+
+impl From<MarketStatus> for MarketEvent {
+    fn from(status: MarketStatus) -> Self {
+        MarketEvent::Status(status)
+    }
+}
+
+impl From<BookEvent> for MarketEvent {
+    fn from(book: BookEvent) -> Self {
+        MarketEvent::Book(book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::enums::OrderSide;
+
+    #[test]
+    fn test_market_event_serde() {
+        let id = InstId::from_str("KR7005930003", "KRX");
+        let mut quote = QuoteSnapshot::sample(2);
+        quote.id = id;
+        let events = vec![
+            MarketEvent::from(quote),
+            MarketEvent::from(TradeTick::new(id, 7, 70_000, 10, OrderSide::Ask, 1, 2)),
+            MarketEvent::from(MarketStatus::new(id, TradingStatus::Halted, 3, 4)),
+        ];
+
+        let serialized = serde_json::to_string(&events).unwrap();
+        let deserialized: Vec<MarketEvent> = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(events, deserialized);
+        assert!(deserialized.iter().all(|event| event.id() == id));
+    }
+}
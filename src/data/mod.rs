@@ -1,2 +1,8 @@
 pub mod level;
-pub mod snapshot;
\ No newline at end of file
+pub mod snapshot;
+pub mod delta;
+pub mod trade;
+pub mod event;
+pub mod session;
+pub mod bar;
+pub mod book;
\ No newline at end of file
@@ -0,0 +1,107 @@
+use crate::{TimeStamp, UnixNano};
+use serde::{Deserialize, Serialize};
+
+pub const NANOS_PER_SECOND: UnixNano = 1_000_000_000;
+pub const NANOS_PER_DAY: UnixNano = 86_400 * NANOS_PER_SECOND;
+
+/// A trading session in local time. `start` and `end` are nanoseconds since local midnight.
+/// If `end` is not greater than `start`, the session runs overnight into the next day.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct SessionWindow {
+    pub start: UnixNano,
+    pub end: UnixNano,
+}
+
+impl SessionWindow {
+    #[inline]
+    pub fn new(start: UnixNano, end: UnixNano) -> Self {
+        Self { start, end }
+    }
+
+    /// e.g., SessionWindow::from_hms((9, 0, 0), (15, 30, 0))
+    #[inline]
+    pub fn from_hms(start: (u64, u64, u64), end: (u64, u64, u64)) -> Self {
+        let to_nanos = |(h, m, s): (u64, u64, u64)| (h * 3600 + m * 60 + s) * NANOS_PER_SECOND;
+        Self {
+            start: to_nanos(start),
+            end: to_nanos(end),
+        }
+    }
+
+    #[inline]
+    pub fn duration(&self) -> UnixNano {
+        if self.end > self.start {
+            self.end - self.start
+        } else {
+            self.end + NANOS_PER_DAY - self.start
+        }
+    }
+}
+
+/// Daily trading sessions of a venue. Holidays and weekends are not modeled.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct SessionSchedule {
+    /// local time = UTC + utc_offset (in nanoseconds), e.g., +9h for KRX
+    pub utc_offset: i64,
+    pub windows: Vec<SessionWindow>,
+}
+
+impl SessionSchedule {
+    pub fn new(utc_offset: i64, windows: Vec<SessionWindow>) -> Self {
+        Self { utc_offset, windows }
+    }
+
+    #[inline]
+    pub fn is_open(&self, time: TimeStamp) -> bool {
+        self.session_of(time).is_some()
+    }
+
+    /// Returns the absolute \[start, end) of the session containing `time`
+    pub fn session_of(&self, time: TimeStamp) -> Option<(TimeStamp, TimeStamp)> {
+        let day = NANOS_PER_DAY as i64;
+        let local = time as i64 + self.utc_offset;
+        let today = local - local.rem_euclid(day);
+        for window in self.windows.iter() {
+            // an overnight session may have started yesterday
+            for midnight in [today - day, today] {
+                let start = midnight + window.start as i64;
+                let end = start + window.duration() as i64;
+                if start <= local && local < end {
+                    let start = (start - self.utc_offset).max(0) as TimeStamp;
+                    let end = (end - self.utc_offset).max(0) as TimeStamp;
+                    return Some((start, end));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_of() {
+        let hour = 3600 * NANOS_PER_SECOND;
+        // KRX regular session 09:00 ~ 15:30 (UTC+9) and a night session 18:00 ~ 05:00
+        let schedule = SessionSchedule::new(
+            9 * hour as i64,
+            vec![
+                SessionWindow::from_hms((9, 0, 0), (15, 30, 0)),
+                SessionWindow::from_hms((18, 0, 0), (5, 0, 0)),
+            ],
+        );
+        let day = 20 * NANOS_PER_DAY;
+        // 01:00 UTC = 10:00 local
+        let (start, end) = schedule.session_of(day + hour).unwrap();
+        assert_eq!(start, day);
+        assert_eq!(end, day + 6 * hour + hour / 2);
+        // 07:00 UTC = 16:00 local, closed
+        assert!(!schedule.is_open(day + 7 * hour));
+        // 18:00 UTC = 03:00 local next day, night session started at 09:00 UTC
+        let (start, end) = schedule.session_of(day + 18 * hour).unwrap();
+        assert_eq!(start, day + 9 * hour);
+        assert_eq!(end, day + 20 * hour);
+    }
+}
@@ -1,9 +1,9 @@
-use crate::{BookQuantity, TimeStamp};
+use crate::{BookPrice, BookQuantity, TimeStamp};
 use crate::data::level::LevelSnapshot;
 use crate::InstId;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct QuoteSnapshot {
     pub id: InstId,
     //
@@ -35,4 +35,24 @@ impl QuoteSnapshot {
             all_lp_holdings: None,
         }
     }
-}
\ No newline at end of file
+
+    #[inline]
+    pub fn best_ask(&self) -> Option<&LevelSnapshot> {
+        self.ask_quote_data.first().filter(|level| self.quote_level_cut > 0 && level.book_quantity > 0)
+    }
+
+    #[inline]
+    pub fn best_bid(&self) -> Option<&LevelSnapshot> {
+        self.bid_quote_data.first().filter(|level| self.quote_level_cut > 0 && level.book_quantity > 0)
+    }
+
+    /// (best ask + best bid) / 2, rounded down to the book unit.
+    /// None if either side is empty (a level with zero quantity is regarded as empty)
+    #[inline]
+    pub fn mid(&self) -> Option<BookPrice> {
+        match (self.best_ask(), self.best_bid()) {
+            (Some(ask), Some(bid)) => Some(bid.book_price + (ask.book_price - bid.book_price).div_euclid(2)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{BookPrice, BookQuantity, InstId, TimeStamp, TradeId};
+use crate::order::enums::OrderSide;
+use serde::{Deserialize, Serialize};
+
+/// A trade print from the market data feed.
+/// `aggressor` is the side of the incoming (liquidity taking) order, i.e., `Bid` means the buyer lifted the ask.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TradeTick {
+    pub id: InstId,
+    //
+    pub datatime: TimeStamp,
+    pub systemtime: TimeStamp,
+    //
+    pub trade_id: TradeId,
+    pub price: BookPrice,
+    pub quantity: BookQuantity,
+    pub aggressor: OrderSide,
+}
+
+impl TradeTick {
+    #[inline]
+    pub fn new(
+        id: InstId,
+        trade_id: TradeId,
+        price: BookPrice,
+        quantity: BookQuantity,
+        aggressor: OrderSide,
+        datatime: TimeStamp,
+        systemtime: TimeStamp,
+    ) -> Self {
+        Self {
+            id,
+            datatime,
+            systemtime,
+            trade_id,
+            price,
+            quantity,
+            aggressor,
+        }
+    }
+
+    pub fn sample() -> Self {
+        Self {
+            id: InstId::default(),
+            datatime: TimeStamp::default(),
+            systemtime: TimeStamp::default(),
+            trade_id: TradeId::default(),
+            price: BookPrice::default(),
+            quantity: BookQuantity::default(),
+            aggressor: OrderSide::default(),
+        }
+    }
+
+    /// price * quantity in book units
+    #[inline]
+    pub fn notional(&self) -> i128 {
+        self.price as i128 * self.quantity as i128
+    }
+}
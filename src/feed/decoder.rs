@@ -0,0 +1,17 @@
+use crate::TimeStamp;
+use crate::data::event::MarketEvent;
+use crate::feed::error::FeedError;
+
+/// Turns the packets of a market data feed into `MarketEvent`s.
+/// Order by order feeds emit `MarketEvent::Book`, and `FeedHandler` keeps the books
+pub trait FeedDecoder {
+    fn name(&self) -> &'static str;
+
+    /// Decodes one packet, appending its events to `events` in feed order.
+    /// On an error, `events` keeps the events of the messages before the failing one,
+    /// and the decoder expects the failing message next
+    fn decode(&mut self, packet: &[u8], systemtime: TimeStamp, events: &mut Vec<MarketEvent>) -> Result<(), FeedError>;
+
+    /// Forgets the sequencing state, e.g., after the books are recovered from a snapshot
+    fn reset(&mut self) {}
+}
@@ -0,0 +1,60 @@
+use crate::data::book::BookError;
+use crate::instrument::error::InstrumentError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedError {
+    Io(String),
+    /// a message or a packet shorter than its type requires
+    Truncated {
+        msg_type: u8,
+        len: usize,
+    },
+    InvalidField {
+        field: &'static str,
+        value: String,
+    },
+    /// packets were lost. The decoder expects `received` next, so the packet can be decoded again after recovery
+    SequenceGap {
+        expected: u64,
+        received: u64,
+    },
+    Book(BookError),
+    Instrument(InstrumentError),
+}
+
+impl std::fmt::Display for FeedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FeedError::Io(msg) => write!(f, "feed io error: {}", msg),
+            FeedError::Truncated { msg_type, len } => {
+                write!(f, "truncated feed message 0x{:02x} of {} bytes", msg_type, len)
+            }
+            FeedError::InvalidField { field, value } => write!(f, "invalid {}: {}", field, value),
+            FeedError::SequenceGap { expected, received } => {
+                write!(f, "feed sequence gap: expected {}, received {}", expected, received)
+            }
+            FeedError::Book(e) => write!(f, "{}", e),
+            FeedError::Instrument(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FeedError {}
+
+impl From<std::io::Error> for FeedError {
+    fn from(e: std::io::Error) -> Self {
+        FeedError::Io(e.to_string())
+    }
+}
+
+impl From<BookError> for FeedError {
+    fn from(e: BookError) -> Self {
+        FeedError::Book(e)
+    }
+}
+
+impl From<InstrumentError> for FeedError {
+    fn from(e: InstrumentError) -> Self {
+        FeedError::Instrument(e)
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{InstId, TimeStamp};
+use crate::data::book::{BookError, OrderBook};
+use crate::data::event::MarketEvent;
+use crate::feed::decoder::FeedDecoder;
+use crate::feed::error::FeedError;
+use crate::udp_client::UdpClient;
+use flashlog::get_unix_nano;
+use rustc_hash::FxHashMap;
+
+/// Drives a `FeedDecoder` over a `UdpClient` and keeps an `OrderBook` per `InstId`.
+/// Book events are applied to the books, and every book a packet touched is published
+/// as a `QuoteSnapshot` of `depth` levels after the packet. Executions on the books are published as `TradeTick`s,
+/// and the other events of the decoder are passed through.
+///
+/// A change of an order missing from its book, routine after joining a live feed, is skipped and counted.
+/// On a sequence gap the books are dropped and the packet is decoded from scratch, since the packets
+/// missed can not be fed again; the gap is still returned
+pub struct FeedHandler<D> {
+    decoder: D,
+    depth: usize,
+    books: FxHashMap<InstId, OrderBook>,
+    publish_book_events: bool,
+    decoded: Vec<MarketEvent>,
+    events: Vec<MarketEvent>,
+    touched: Vec<InstId>,
+    skipped: u64,
+}
+
+impl<D: FeedDecoder> FeedHandler<D> {
+    pub fn new(decoder: D, depth: usize) -> Self {
+        Self {
+            decoder,
+            depth,
+            books: FxHashMap::default(),
+            publish_book_events: false,
+            decoded: Vec::new(),
+            events: Vec::new(),
+            touched: Vec::new(),
+            skipped: 0,
+        }
+    }
+
+    /// Publishes the book events too, before the snapshots
+    pub fn with_book_events(mut self) -> Self {
+        self.publish_book_events = true;
+        self
+    }
+
+    #[inline]
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    #[inline]
+    pub fn decoder_mut(&mut self) -> &mut D {
+        &mut self.decoder
+    }
+
+    #[inline]
+    pub fn book(&self, id: &InstId) -> Option<&OrderBook> {
+        self.books.get(id)
+    }
+
+    pub fn books(&self) -> impl Iterator<Item = &OrderBook> {
+        self.books.values()
+    }
+
+    /// The events published for the last packet, also when it returned an error
+    #[inline]
+    pub fn events(&self) -> &[MarketEvent] {
+        &self.events
+    }
+
+    /// Book events skipped because their order was not on the book
+    #[inline]
+    pub fn skipped_events(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Drops the books and the sequencing state of the decoder, e.g., after a sequence gap
+    pub fn reset(&mut self) {
+        self.books.clear();
+        self.decoder.reset();
+    }
+
+    /// Decodes `packet` and returns the events to publish.
+    /// On an error, the events decoded before it are still applied and published, see `events`.
+    /// After a `SequenceGap` the books hold the events from this packet on
+    pub fn on_packet(&mut self, packet: &[u8], systemtime: TimeStamp) -> Result<&[MarketEvent], FeedError> {
+        self.events.clear();
+        self.decoded.clear();
+        self.touched.clear();
+        let mut error = self.decoder.decode(packet, systemtime, &mut self.decoded).err();
+        if let Some(gap @ FeedError::SequenceGap { expected, received }) = error {
+            flashlog::flash_warn!("FEED"; "sequence gap, expected {} but received {}: dropping the books", expected, received);
+            self.reset();
+            self.decoded.clear();
+            error = Some(self.decoder.decode(packet, systemtime, &mut self.decoded).err().unwrap_or(gap));
+        }
+
+        for event in self.decoded.drain(..) {
+            let MarketEvent::Book(book_event) = event else {
+                self.events.push(event);
+                continue;
+            };
+            let book = self.books.entry(book_event.id).or_insert_with(|| OrderBook::new(book_event.id));
+            let trade = match book.apply(&book_event) {
+                Ok(trade) => trade,
+                Err(BookError::UnknownOrder(order_ref)) => {
+                    self.skipped += 1;
+                    flashlog::flash_warn!("FEED"; "skipping an event of unknown order {}", order_ref);
+                    continue;
+                }
+                Err(e) => {
+                    error.get_or_insert(e.into());
+                    continue;
+                }
+            };
+            if self.publish_book_events {
+                self.events.push(event);
+            }
+            if let Some(trade) = trade {
+                self.events.push(MarketEvent::Trade(trade));
+            }
+            if !self.touched.contains(&book_event.id) {
+                self.touched.push(book_event.id);
+            }
+        }
+        for id in self.touched.iter() {
+            if let Some(book) = self.books.get(id) {
+                self.events.push(MarketEvent::Quote(book.snapshot(self.depth)));
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(&self.events),
+        }
+    }
+
+    /// Handles the next packet of `client` if any, `buf` must fit the largest packet of the feed
+    pub fn poll(&mut self, client: &UdpClient, buf: &mut [u8]) -> Result<Option<&[MarketEvent]>, FeedError> {
+        match client.recv_one(buf)? {
+            Some(size) => self.on_packet(&buf[..size], get_unix_nano()).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::book::{BookAction, BookEvent};
+    use crate::data::event::{MarketStatus, TradingStatus};
+    use crate::order::enums::OrderSide;
+    use std::net::UdpSocket;
+
+    /// A packet is a list of (order_ref, side, price, quantity) of 4 bytes each, quantity 0 executes the order
+    struct ToyDecoder;
+
+    impl FeedDecoder for ToyDecoder {
+        fn name(&self) -> &'static str {
+            "toy"
+        }
+
+        fn decode(&mut self, packet: &[u8], systemtime: TimeStamp, events: &mut Vec<MarketEvent>) -> Result<(), FeedError> {
+            let id = InstId::from_str("AAPL", "NASDAQ");
+            events.push(MarketEvent::Status(MarketStatus::new(id, TradingStatus::Continuous, 0, systemtime)));
+            for chunk in packet.chunks(4) {
+                let [order_ref, side, price, quantity] = chunk else {
+                    return Err(FeedError::Truncated { msg_type: 0, len: chunk.len() });
+                };
+                let action = match quantity {
+                    0 => BookAction::Execute { order_ref: *order_ref as u64, quantity: u64::MAX, trade_id: 1, price: None },
+                    _ => BookAction::Add {
+                        order_ref: *order_ref as u64,
+                        side: if *side == 0 { OrderSide::Bid } else { OrderSide::Ask },
+                        price: *price as i64,
+                        quantity: *quantity as u64,
+                    },
+                };
+                events.push(MarketEvent::Book(BookEvent::new(id, action, 0, systemtime)));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_books_and_published_events() {
+        let mut handler = FeedHandler::new(ToyDecoder, 5);
+        let events = handler.on_packet(&[1, 0, 100, 10, 2, 1, 101, 5, 3, 1, 102, 7], 1).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], MarketEvent::Status(_)));
+        let MarketEvent::Quote(quote) = &events[1] else { panic!("a quote is expected") };
+        assert_eq!((quote.best_bid().unwrap().book_price, quote.best_ask().unwrap().book_price), (100, 101));
+        assert_eq!(quote.ask_quote_data.len(), 2);
+
+        let events = handler.on_packet(&[2, 1, 0, 0], 2).unwrap();
+        let MarketEvent::Trade(trade) = &events[1] else { panic!("a trade is expected") };
+        assert_eq!((trade.price, trade.quantity, trade.aggressor), (101, 5, OrderSide::Bid));
+        let id = InstId::from_str("AAPL", "NASDAQ");
+        assert_eq!(handler.book(&id).unwrap().best_ask(), Some((102, 7)));
+
+        // an execution of an order missing from the book is skipped, the rest of the packet is applied
+        let events = handler.on_packet(&[9, 9, 0, 0, 4, 0, 98, 3], 3).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(handler.skipped_events(), 1);
+        assert_eq!(handler.book(&id).unwrap().best_bid(), Some((100, 10)));
+        assert!(matches!(handler.on_packet(&[1, 0, 100, 10], 3), Err(FeedError::Book(BookError::DuplicateOrder(1)))));
+
+        // the events before a decoding error are applied and published
+        assert!(matches!(handler.on_packet(&[5, 0, 101, 3, 1, 0], 4), Err(FeedError::Truncated { .. })));
+        assert_eq!(handler.book(&id).unwrap().best_bid(), Some((101, 3)));
+        let MarketEvent::Quote(quote) = &handler.events()[1] else { panic!("a quote is expected") };
+        assert_eq!(quote.best_bid().unwrap().book_price, 101);
+        handler.reset();
+        assert!(handler.book(&id).is_none());
+    }
+
+    #[test]
+    fn test_poll_udp_client() {
+        let client = UdpClient::new("127.0.0.1:0", None).unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = client.local_addr().unwrap();
+        sender.send_to(&[1, 0, 100, 10], address).unwrap();
+        sender.send_to(&[2, 0, 99, 10], address).unwrap();
+
+        let mut handler = FeedHandler::new(ToyDecoder, 5).with_book_events();
+        let mut buf = [0u8; 1500];
+        let mut quotes = Vec::new();
+        let start = std::time::Instant::now();
+        while quotes.len() < 2 && start.elapsed() < std::time::Duration::from_secs(5) {
+            if let Some(events) = handler.poll(&client, &mut buf).unwrap() {
+                assert!(events.iter().any(|event| matches!(event, MarketEvent::Book(_))));
+                quotes.extend(events.iter().filter_map(|event| match event {
+                    MarketEvent::Quote(quote) => Some(quote.bid_quote_data.len()),
+                    _ => None,
+                }));
+            }
+        }
+        assert_eq!(quotes, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_sequence_gap_recovers() {
+        use crate::feed::itch::tests::{add, executed, packet, registry};
+        use crate::feed::itch::ItchDecoder;
+
+        let mut handler = FeedHandler::new(ItchDecoder::new(registry(), "NASDAQ"), 5);
+        let id = InstId::from_str("AAPL", "NASDAQ");
+        handler.on_packet(&packet(1, &[add(7, 1, b'B', 10, "AAPL", 1_000_000)]), 1).unwrap();
+        assert_eq!(handler.book(&id).unwrap().best_bid(), Some((10_000, 10)));
+
+        // packets 2 to 4 are lost: the books start over from the packet after the gap
+        let after_gap = packet(5, &[add(7, 5, b'S', 20, "AAPL", 1_010_000)]);
+        assert_eq!(handler.on_packet(&after_gap, 2), Err(FeedError::SequenceGap { expected: 2, received: 5 }));
+        assert_eq!((handler.book(&id).unwrap().best_bid(), handler.book(&id).unwrap().best_ask()), (None, Some((10_100, 20))));
+        assert!(matches!(handler.events(), [MarketEvent::Quote(_)]));
+
+        // the feed goes on, and a change of an order from before the gap is skipped
+        handler.on_packet(&packet(6, &[executed(7, 1, 5, 1), add(7, 6, b'B', 30, "AAPL", 1_000_000)]), 3).unwrap();
+        assert_eq!(handler.book(&id).unwrap().best_bid(), Some((10_000, 30)));
+        assert_eq!(handler.skipped_events(), 1);
+        assert_eq!(handler.decoder().next_seq(), Some(8));
+    }
+}
@@ -0,0 +1,462 @@
+//! An ITCH 5.0 style order by order feed over MoldUDP64.
+//! A packet is a session (10 bytes), the sequence number of its first message (u64), a message count (u16)
+//! and the messages, each prefixed with its length (u16). Integers are big endian, timestamps are
+//! nanoseconds since midnight in 6 bytes, and prices are u32 with 4 decimals.
+//! Only the messages below are decoded, the others are skipped.
+use crate::{BookPrice, InstId, TimeStamp, UnixNano};
+use crate::data::book::{BookAction, BookEvent};
+use crate::data::event::{MarketEvent, MarketStatus, TradingStatus};
+use crate::data::trade::TradeTick;
+use crate::feed::decoder::FeedDecoder;
+use crate::feed::error::FeedError;
+use crate::instrument::error::InstrumentError;
+use crate::instrument::registry::InstrumentRegistry;
+use crate::order::enums::OrderSide;
+use crate::price;
+use rustc_hash::FxHashMap;
+
+pub const PRICE_SCALE: u8 = 4;
+pub const SESSION_LEN: usize = 10;
+pub const HEADER_LEN: usize = 20;
+pub const STOCK_LEN: usize = 8;
+/// the message count of the last packet of a session
+pub const END_OF_SESSION: u16 = 0xFFFF;
+
+pub mod msg_type {
+    pub const SYSTEM_EVENT: u8 = b'S';
+    pub const STOCK_DIRECTORY: u8 = b'R';
+    pub const TRADING_ACTION: u8 = b'H';
+    pub const ADD_ORDER: u8 = b'A';
+    pub const ADD_ORDER_MPID: u8 = b'F';
+    pub const ORDER_EXECUTED: u8 = b'E';
+    pub const ORDER_EXECUTED_WITH_PRICE: u8 = b'C';
+    pub const ORDER_CANCEL: u8 = b'X';
+    pub const ORDER_DELETE: u8 = b'D';
+    pub const ORDER_REPLACE: u8 = b'U';
+    pub const TRADE: u8 = b'P';
+
+    pub fn len(msg_type: u8) -> Option<usize> {
+        match msg_type {
+            SYSTEM_EVENT => Some(12),
+            STOCK_DIRECTORY => Some(39),
+            TRADING_ACTION => Some(25),
+            ADD_ORDER => Some(36),
+            ADD_ORDER_MPID => Some(40),
+            ORDER_EXECUTED => Some(31),
+            ORDER_EXECUTED_WITH_PRICE => Some(36),
+            ORDER_CANCEL => Some(23),
+            ORDER_DELETE => Some(19),
+            ORDER_REPLACE => Some(35),
+            TRADE => Some(44),
+            _ => None,
+        }
+    }
+}
+
+pub fn trading_status(state: u8) -> TradingStatus {
+    match state {
+        b'H' | b'P' => TradingStatus::Halted,
+        b'Q' => TradingStatus::PreOpen,
+        b'T' => TradingStatus::Continuous,
+        _ => TradingStatus::Unknown,
+    }
+}
+
+fn side_of(code: u8) -> Result<OrderSide, FeedError> {
+    match code {
+        b'B' => Ok(OrderSide::Bid),
+        b'S' => Ok(OrderSide::Ask),
+        _ => Err(FeedError::InvalidField { field: "side", value: (code as char).to_string() }),
+    }
+}
+
+#[inline]
+fn u16_at(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+#[inline]
+fn u32_at(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().expect("4 bytes"))
+}
+
+#[inline]
+fn u64_at(buf: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(buf[offset..offset + 8].try_into().expect("8 bytes"))
+}
+
+#[inline]
+fn u48_at(buf: &[u8], offset: usize) -> u64 {
+    buf[offset..offset + 6].iter().fold(0, |acc, b| acc << 8 | *b as u64)
+}
+
+fn stock_at(message: &[u8], offset: usize) -> &str {
+    std::str::from_utf8(&message[offset..offset + STOCK_LEN])
+        .unwrap_or_default()
+        .trim_end_matches(' ')
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Locate {
+    id: InstId,
+    price_scale: u8,
+}
+
+/// Decodes the stocks of `registry` on `venue`, the messages of the other stocks are skipped.
+/// Stock locate codes are learned from the stock directory and the messages carrying a stock.
+#[derive(Debug, Clone)]
+pub struct ItchDecoder {
+    registry: InstrumentRegistry,
+    venue: String,
+    session_date: UnixNano,
+    next_seq: Option<u64>,
+    locates: FxHashMap<u16, Locate>,
+}
+
+impl ItchDecoder {
+    pub fn new(registry: InstrumentRegistry, venue: &str) -> Self {
+        Self {
+            registry,
+            venue: venue.to_string(),
+            session_date: 0,
+            next_seq: None,
+            locates: FxHashMap::default(),
+        }
+    }
+
+    /// Midnight of the session day, added to the timestamps of the messages
+    pub fn with_session_date(mut self, session_date: UnixNano) -> Self {
+        self.session_date = session_date;
+        self
+    }
+
+    /// The sequence number of the next message, None before the first packet
+    #[inline]
+    pub fn next_seq(&self) -> Option<u64> {
+        self.next_seq
+    }
+
+    fn learn_locate(&mut self, locate: u16, stock: &str) -> Option<Locate> {
+        let id = self.registry.find(stock, &self.venue)?;
+        let entry = Locate { id, price_scale: self.registry.get(&id)?.price_scale };
+        self.locates.insert(locate, entry);
+        Some(entry)
+    }
+
+    fn locate(&mut self, message: &[u8], stock_offset: Option<usize>) -> Option<Locate> {
+        let locate = u16_at(message, 1);
+        match (self.locates.get(&locate), stock_offset) {
+            (Some(entry), _) => Some(*entry),
+            (None, Some(offset)) => self.learn_locate(locate, stock_at(message, offset)),
+            (None, None) => None,
+        }
+    }
+
+    fn on_message(&mut self, message: &[u8], systemtime: TimeStamp, events: &mut Vec<MarketEvent>) -> Result<(), FeedError> {
+        let msg_type = message[0];
+        let Some(len) = msg_type::len(msg_type) else {
+            return Ok(());
+        };
+        if message.len() < len {
+            return Err(FeedError::Truncated { msg_type, len: message.len() });
+        }
+        let stock_offset = match msg_type {
+            msg_type::STOCK_DIRECTORY | msg_type::TRADING_ACTION => Some(11),
+            msg_type::ADD_ORDER | msg_type::ADD_ORDER_MPID | msg_type::TRADE => Some(24),
+            _ => None,
+        };
+        if msg_type == msg_type::SYSTEM_EVENT {
+            return Ok(());
+        }
+        let Some(locate) = self.locate(message, stock_offset) else {
+            return Ok(());
+        };
+        let id = locate.id;
+        let datatime = self.session_date + u48_at(message, 5);
+        let price_at = |offset: usize| -> Result<BookPrice, FeedError> {
+            let wire = u32_at(message, offset) as BookPrice;
+            Ok(price::rescale(wire, PRICE_SCALE, locate.price_scale).map_err(InstrumentError::from)?)
+        };
+        let book = |action: BookAction| MarketEvent::Book(BookEvent::new(id, action, datatime, systemtime));
+
+        let event = match msg_type {
+            msg_type::STOCK_DIRECTORY => return Ok(()),
+            msg_type::TRADING_ACTION => {
+                MarketEvent::Status(MarketStatus::new(id, trading_status(message[19]), datatime, systemtime))
+            }
+            msg_type::ADD_ORDER | msg_type::ADD_ORDER_MPID => book(BookAction::Add {
+                order_ref: u64_at(message, 11),
+                side: side_of(message[19])?,
+                price: price_at(32)?,
+                quantity: u32_at(message, 20) as u64,
+            }),
+            msg_type::ORDER_EXECUTED => book(BookAction::Execute {
+                order_ref: u64_at(message, 11),
+                quantity: u32_at(message, 19) as u64,
+                trade_id: u64_at(message, 23),
+                price: None,
+            }),
+            msg_type::ORDER_EXECUTED_WITH_PRICE => book(BookAction::Execute {
+                order_ref: u64_at(message, 11),
+                quantity: u32_at(message, 19) as u64,
+                trade_id: u64_at(message, 23),
+                price: Some(price_at(32)?),
+            }),
+            msg_type::ORDER_CANCEL => book(BookAction::Cancel {
+                order_ref: u64_at(message, 11),
+                quantity: u32_at(message, 19) as u64,
+            }),
+            msg_type::ORDER_DELETE => book(BookAction::Delete { order_ref: u64_at(message, 11) }),
+            msg_type::ORDER_REPLACE => book(BookAction::Replace {
+                order_ref: u64_at(message, 11),
+                new_order_ref: u64_at(message, 19),
+                price: price_at(31)?,
+                quantity: u32_at(message, 27) as u64,
+            }),
+            // an execution against a hidden order, the side is of the resting order
+            _ => {
+                let aggressor = match side_of(message[19])? {
+                    OrderSide::Bid => OrderSide::Ask,
+                    OrderSide::Ask => OrderSide::Bid,
+                };
+                MarketEvent::Trade(TradeTick::new(
+                    id,
+                    u64_at(message, 36),
+                    price_at(32)?,
+                    u32_at(message, 20) as u64,
+                    aggressor,
+                    datatime,
+                    systemtime,
+                ))
+            }
+        };
+        events.push(event);
+        Ok(())
+    }
+}
+
+impl FeedDecoder for ItchDecoder {
+    fn name(&self) -> &'static str {
+        "ITCH 5.0"
+    }
+
+    /// Packets already seen (e.g., on the other line of an A/B feed) are skipped.
+    /// A gap is returned as `FeedError::SequenceGap` without decoding the packet; the packet decodes once
+    /// fed again, or after a `reset`, as `FeedHandler` does.
+    /// `next_seq` moves past a message only once its events are in `events`
+    fn decode(&mut self, packet: &[u8], systemtime: TimeStamp, events: &mut Vec<MarketEvent>) -> Result<(), FeedError> {
+        if packet.len() < HEADER_LEN {
+            return Err(FeedError::Truncated { msg_type: 0, len: packet.len() });
+        }
+        let seq = u64_at(packet, SESSION_LEN);
+        let count = u16_at(packet, SESSION_LEN + 8);
+        if count == END_OF_SESSION {
+            return Ok(());
+        }
+        let expected = self.next_seq.unwrap_or(seq);
+        if seq > expected {
+            self.next_seq = Some(seq);
+            return Err(FeedError::SequenceGap { expected, received: seq });
+        }
+
+        let mut offset = HEADER_LEN;
+        for message_seq in seq..seq + count as u64 {
+            let len = packet
+                .get(offset..offset + 2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+                .ok_or(FeedError::Truncated { msg_type: 0, len: packet.len() - offset })?;
+            let message = packet
+                .get(offset + 2..offset + 2 + len)
+                .filter(|message| !message.is_empty())
+                .ok_or(FeedError::Truncated { msg_type: 0, len })?;
+            offset += 2 + len;
+            if message_seq < expected {
+                continue;
+            }
+            self.on_message(message, systemtime, events)?;
+            self.next_seq = Some(message_seq + 1);
+        }
+        if self.next_seq.is_none() {
+            self.next_seq = Some(seq);
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.next_seq = None;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::instrument::spec::InstrumentSpec;
+
+    pub(crate) fn registry() -> InstrumentRegistry {
+        let mut registry = InstrumentRegistry::new();
+        registry.insert(InstrumentSpec::new(InstId::from_str("AAPL", "NASDAQ"), 1, 2));
+        registry
+    }
+
+    fn header(msg_type: u8, locate: u16, timestamp: u64) -> Vec<u8> {
+        let mut message = vec![msg_type];
+        message.extend_from_slice(&locate.to_be_bytes());
+        message.extend_from_slice(&0u16.to_be_bytes());
+        message.extend_from_slice(&timestamp.to_be_bytes()[2..]);
+        message
+    }
+
+    fn stock(message: &mut Vec<u8>, stock: &str) {
+        message.extend_from_slice(format!("{:<8}", stock).as_bytes());
+    }
+
+    fn directory(locate: u16, code: &str) -> Vec<u8> {
+        let mut message = header(msg_type::STOCK_DIRECTORY, locate, 1);
+        stock(&mut message, code);
+        message.resize(39, b' ');
+        message
+    }
+
+    pub(crate) fn add(locate: u16, order_ref: u64, side: u8, shares: u32, code: &str, price: u32) -> Vec<u8> {
+        let mut message = header(msg_type::ADD_ORDER, locate, 2);
+        message.extend_from_slice(&order_ref.to_be_bytes());
+        message.push(side);
+        message.extend_from_slice(&shares.to_be_bytes());
+        stock(&mut message, code);
+        message.extend_from_slice(&price.to_be_bytes());
+        message
+    }
+
+    pub(crate) fn executed(locate: u16, order_ref: u64, shares: u32, match_number: u64) -> Vec<u8> {
+        let mut message = header(msg_type::ORDER_EXECUTED, locate, 3);
+        message.extend_from_slice(&order_ref.to_be_bytes());
+        message.extend_from_slice(&shares.to_be_bytes());
+        message.extend_from_slice(&match_number.to_be_bytes());
+        message
+    }
+
+    fn replace(locate: u16, order_ref: u64, new_order_ref: u64, shares: u32, price: u32) -> Vec<u8> {
+        let mut message = header(msg_type::ORDER_REPLACE, locate, 4);
+        message.extend_from_slice(&order_ref.to_be_bytes());
+        message.extend_from_slice(&new_order_ref.to_be_bytes());
+        message.extend_from_slice(&shares.to_be_bytes());
+        message.extend_from_slice(&price.to_be_bytes());
+        message
+    }
+
+    pub(crate) fn packet(seq: u64, messages: &[Vec<u8>]) -> Vec<u8> {
+        let mut packet = b"SESSION001".to_vec();
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&(messages.len() as u16).to_be_bytes());
+        for message in messages {
+            packet.extend_from_slice(&(message.len() as u16).to_be_bytes());
+            packet.extend_from_slice(message);
+        }
+        packet
+    }
+
+    #[test]
+    fn test_decode_messages() {
+        let mut decoder = ItchDecoder::new(registry(), "NASDAQ").with_session_date(1_000_000);
+        let id = InstId::from_str("AAPL", "NASDAQ");
+        let mut delete = header(msg_type::ORDER_DELETE, 7, 5);
+        delete.extend_from_slice(&11u64.to_be_bytes());
+        let mut halt = header(msg_type::TRADING_ACTION, 7, 6);
+        stock(&mut halt, "AAPL");
+        halt.extend_from_slice(b"H RSN1");
+        let mut events = Vec::new();
+        let messages = [
+            directory(7, "AAPL"),
+            directory(8, "MSFT"),
+            add(7, 10, b'B', 100, "AAPL", 1_502_500),
+            add(8, 20, b'S', 100, "MSFT", 3_000_000),
+            executed(7, 10, 40, 99),
+            executed(8, 20, 40, 100),
+            replace(7, 10, 11, 50, 1_503_000),
+            delete,
+            halt,
+            b"Lunknown".to_vec(),
+        ];
+        decoder.decode(&packet(1, &messages), 42, &mut events).unwrap();
+        assert_eq!(decoder.next_seq(), Some(11));
+
+        let book = |action| MarketEvent::Book(BookEvent::new(id, action, 0, 42));
+        let mut expected = vec![
+            book(BookAction::Add { order_ref: 10, side: OrderSide::Bid, price: 15_025, quantity: 100 }),
+            book(BookAction::Execute { order_ref: 10, quantity: 40, trade_id: 99, price: None }),
+            book(BookAction::Replace { order_ref: 10, new_order_ref: 11, price: 15_030, quantity: 50 }),
+            book(BookAction::Delete { order_ref: 11 }),
+            MarketEvent::Status(MarketStatus::new(id, TradingStatus::Halted, 0, 42)),
+        ];
+        let times = [2, 3, 4, 5, 6];
+        for (event, time) in expected.iter_mut().zip(times) {
+            match event {
+                MarketEvent::Book(book) => book.datatime = 1_000_000 + time,
+                MarketEvent::Status(status) => status.datatime = 1_000_000 + time,
+                _ => unreachable!(),
+            }
+        }
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn test_sequencing() {
+        let mut decoder = ItchDecoder::new(registry(), "NASDAQ");
+        let mut events = Vec::new();
+        let first = packet(1, &[add(7, 1, b'B', 10, "AAPL", 1_000_000), add(7, 2, b'S', 10, "AAPL", 1_010_000)]);
+        decoder.decode(&first, 0, &mut events).unwrap();
+        // the same packet from the other line, and a packet overlapping it
+        decoder.decode(&first, 0, &mut events).unwrap();
+        let overlapping = packet(2, &[add(7, 2, b'S', 10, "AAPL", 1_010_000), executed(7, 1, 5, 1)]);
+        decoder.decode(&overlapping, 0, &mut events).unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(decoder.next_seq(), Some(4));
+
+        let heartbeat = packet(4, &[]);
+        decoder.decode(&heartbeat, 0, &mut events).unwrap();
+        let late = packet(6, &[executed(7, 1, 5, 2)]);
+        assert_eq!(
+            decoder.decode(&late, 0, &mut events),
+            Err(FeedError::SequenceGap { expected: 4, received: 6 })
+        );
+        decoder.decode(&late, 0, &mut events).unwrap();
+        assert_eq!((events.len(), decoder.next_seq()), (4, Some(7)));
+
+        let mut truncated = packet(7, &[executed(7, 1, 5, 3)]);
+        truncated.truncate(truncated.len() - 1);
+        assert!(matches!(decoder.decode(&truncated, 0, &mut events), Err(FeedError::Truncated { .. })));
+    }
+
+    #[test]
+    fn test_error_mid_packet() {
+        let mut decoder = ItchDecoder::new(registry(), "NASDAQ");
+        let mut events = Vec::new();
+        let bad_side = add(7, 2, b'Z', 10, "AAPL", 1_010_000);
+        let messages = [add(7, 1, b'B', 10, "AAPL", 1_000_000), bad_side, add(7, 3, b'S', 10, "AAPL", 1_020_000)];
+        assert!(matches!(
+            decoder.decode(&packet(1, &messages), 0, &mut events),
+            Err(FeedError::InvalidField { field: "side", .. })
+        ));
+        // the message before the error is kept, the decoder waits for the failing one
+        assert_eq!(events.len(), 1);
+        assert_eq!(decoder.next_seq(), Some(2));
+
+        events.clear();
+        let mut truncated = packet(4, &[executed(7, 1, 5, 3), executed(7, 1, 5, 4)]);
+        decoder.reset();
+        truncated.truncate(truncated.len() - 1);
+        assert!(matches!(decoder.decode(&truncated, 0, &mut events), Err(FeedError::Truncated { .. })));
+        assert_eq!((events.len(), decoder.next_seq()), (1, Some(5)));
+    }
+
+    #[test]
+    fn test_unknown_stock_is_not_learned() {
+        let mut decoder = ItchDecoder::new(registry(), "NASDAQ");
+        let mut events = Vec::new();
+        let messages = [directory(8, "MSFT"), executed(8, 20, 40, 1), add(8, 21, b'B', 10, "MSFT", 1_000_000)];
+        decoder.decode(&packet(1, &messages), 0, &mut events).unwrap();
+        assert!(events.is_empty());
+        assert!(decoder.locates.is_empty());
+        assert_eq!(decoder.next_seq(), Some(4));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod error;
+pub mod decoder;
+pub mod handler;
+pub mod itch;
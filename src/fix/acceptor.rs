@@ -0,0 +1,695 @@
+use crate::{BookPrice, BookQuantity, ExecId, InstId, OrderCore, OrderId, UnixNano};
+use crate::fix::error::FixError;
+use crate::fix::mapping::FixMapping;
+use crate::fix::message::{msg_type, tags, FixDecoder, FixMessage};
+use crate::fix::session::{FixSession, SessionConfig, SessionRole, SessionState};
+use crate::fix::store::{MemoryStore, MessageStore};
+use crate::order::enums::OrderSide;
+use crate::order::error::OrderError;
+use crate::order::execution::{CancelRejectResponseTo, ExecType, ExecutionReport};
+use crate::order::request::OrderRequest;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+/// A scripted response of the acceptor to an order message
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ScriptAction {
+    /// New, Canceled or Replaced, depending on the request
+    Ack,
+    /// fills `quantity` (None for the open quantity) at `price` (None for the order price)
+    Fill {
+        quantity: Option<BookQuantity>,
+        price: Option<BookPrice>,
+    },
+    /// Rejected for a new order, OrderCancelReject for a cancel or replace
+    Reject(String),
+    /// an unsolicited cancel of the order
+    Cancel,
+    /// skips sequence numbers before the next message
+    Gap(u64),
+    /// drops the connection without a logout
+    Disconnect,
+    Ignore,
+}
+
+/// The responses of the acceptor. The n-th new order (from 1) uses `orders[n]` if present
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AcceptorScript {
+    #[serde(default = "AcceptorScript::ack")]
+    pub new_order: Vec<ScriptAction>,
+    #[serde(default = "AcceptorScript::ack")]
+    pub cancel: Vec<ScriptAction>,
+    #[serde(default = "AcceptorScript::ack")]
+    pub replace: Vec<ScriptAction>,
+    #[serde(default)]
+    pub orders: BTreeMap<u64, Vec<ScriptAction>>,
+}
+
+impl Default for AcceptorScript {
+    fn default() -> Self {
+        Self {
+            new_order: Self::ack(),
+            cancel: Self::ack(),
+            replace: Self::ack(),
+            orders: BTreeMap::new(),
+        }
+    }
+}
+
+impl AcceptorScript {
+    fn ack() -> Vec<ScriptAction> {
+        vec![ScriptAction::Ack]
+    }
+
+    pub fn with_new_order(mut self, actions: Vec<ScriptAction>) -> Self {
+        self.new_order = actions;
+        self
+    }
+
+    pub fn with_cancel(mut self, actions: Vec<ScriptAction>) -> Self {
+        self.cancel = actions;
+        self
+    }
+
+    pub fn with_replace(mut self, actions: Vec<ScriptAction>) -> Self {
+        self.replace = actions;
+        self
+    }
+
+    /// Actions of the `n`-th new order, from 1
+    pub fn with_order(mut self, n: u64, actions: Vec<ScriptAction>) -> Self {
+        self.orders.insert(n, actions);
+        self
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn load_json(path: impl AsRef<Path>) -> Result<Self, FixError> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json_str(&json).map_err(|e| FixError::Io(e.to_string()))
+    }
+}
+
+/// One line of the conversation, messages with '|' as the separator
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind")]
+pub enum ConversationEntry {
+    Inbound { time: UnixNano, message: String },
+    Outbound { time: UnixNano, message: String },
+    Error { time: UnixNano, error: String },
+}
+
+#[derive(Debug, Clone)]
+struct AcceptedOrder {
+    instid: InstId,
+    side: OrderSide,
+    price: Option<BookPrice>,
+    quantity: BookQuantity,
+    cum_quantity: BookQuantity,
+    notional: f64,
+}
+
+impl AcceptedOrder {
+    #[inline]
+    fn leaves_quantity(&self) -> BookQuantity {
+        self.quantity.saturating_sub(self.cum_quantity)
+    }
+}
+
+/// The counterparty of a FIX gateway in tests: accepts sessions, validates the sequence numbers,
+/// answers orders as the script says and records the conversation
+pub struct FixAcceptor<S: MessageStore = MemoryStore> {
+    session: FixSession<S>,
+    mapping: FixMapping,
+    script: AcceptorScript,
+    decoder: FixDecoder,
+    orders: FxHashMap<OrderId, AcceptedOrder>,
+    new_orders: u64,
+    next_exec_id: ExecId,
+    conversation: Vec<ConversationEntry>,
+    disconnected: bool,
+}
+
+impl FixAcceptor<MemoryStore> {
+    pub fn new(config: SessionConfig, mapping: FixMapping, script: AcceptorScript) -> Self {
+        Self::with_store(config, mapping, script, MemoryStore::new())
+    }
+}
+
+impl<S: MessageStore> FixAcceptor<S> {
+    pub fn with_store(config: SessionConfig, mapping: FixMapping, script: AcceptorScript, store: S) -> Self {
+        let decoder = FixDecoder::new(&config.begin_string);
+        Self {
+            session: FixSession::new(config, SessionRole::Acceptor, store),
+            mapping,
+            script,
+            decoder,
+            orders: FxHashMap::default(),
+            new_orders: 0,
+            next_exec_id: 1,
+            conversation: Vec::new(),
+            disconnected: false,
+        }
+    }
+
+    #[inline]
+    pub fn session(&self) -> &FixSession<S> {
+        &self.session
+    }
+
+    #[inline]
+    pub fn conversation(&self) -> &[ConversationEntry] {
+        &self.conversation
+    }
+
+    #[inline]
+    pub fn set_script(&mut self, script: AcceptorScript) {
+        self.script = script;
+    }
+
+    /// The connection is over: logged out, disconnected by the script or after a session error
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.disconnected || self.session.state() == SessionState::Closed
+    }
+
+    #[inline]
+    pub fn clear_conversation(&mut self) {
+        self.conversation.clear();
+    }
+
+    /// Ready for a new connection, keeping the sequence numbers, the orders and the conversation
+    pub fn reconnect(&mut self) {
+        self.session.reconnect();
+        self.decoder = FixDecoder::new(&self.session.config().begin_string);
+        self.disconnected = false;
+    }
+
+    /// Appends the conversation to `path`, one JSON entry per line
+    pub fn save_conversation(&self, path: impl AsRef<Path>) -> Result<(), FixError> {
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        for entry in &self.conversation {
+            let line = serde_json::to_string(entry).map_err(|e| FixError::Io(e.to_string()))?;
+            writeln!(file, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Feeds bytes from the initiator and returns the bytes to write back
+    pub fn on_bytes(&mut self, bytes: &[u8], now: UnixNano) -> Vec<u8> {
+        self.decoder.extend(bytes);
+        loop {
+            if self.is_closed() {
+                break;
+            }
+            match self.decoder.next_message() {
+                Ok(Some(message)) => {
+                    self.conversation.push(ConversationEntry::Inbound {
+                        time: now,
+                        message: message.to_string(),
+                    });
+                    match self.session.on_message(message, now) {
+                        Ok(delivered) => {
+                            for message in delivered {
+                                if let Err(e) = self.on_application(&message, now) {
+                                    self.reject(&message, &e, now);
+                                }
+                            }
+                        }
+                        Err(e) => self.record_error(&e, now),
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => self.record_error(&e, now),
+            }
+        }
+        self.take_outbox(now)
+    }
+
+    pub fn on_timer(&mut self, now: UnixNano) -> Vec<u8> {
+        if let Err(e) = self.session.on_timer(now) {
+            self.record_error(&e, now);
+        }
+        self.take_outbox(now)
+    }
+
+    fn take_outbox(&mut self, now: UnixNano) -> Vec<u8> {
+        let bytes = self.session.take_outbox();
+        let mut decoder = FixDecoder::new(&self.session.config().begin_string);
+        decoder.extend(&bytes);
+        while let Ok(Some(message)) = decoder.next_message() {
+            self.conversation.push(ConversationEntry::Outbound {
+                time: now,
+                message: message.to_string(),
+            });
+        }
+        bytes
+    }
+
+    fn record_error(&mut self, error: &FixError, now: UnixNano) {
+        let logged = error.to_string();
+        flashlog::flash_warn!("FIX"; "acceptor: {}", logged);
+        self.conversation.push(ConversationEntry::Error {
+            time: now,
+            error: error.to_string(),
+        });
+    }
+
+    /// A session level Reject of a message the acceptor can not handle
+    fn reject(&mut self, message: &FixMessage, error: &FixError, now: UnixNano) {
+        self.record_error(error, now);
+        let mut reject = FixMessage::new(msg_type::REJECT).with(tags::TEXT, error.to_string());
+        if let Some(seq) = message.seq_num() {
+            reject.set(tags::REF_SEQ_NUM, seq);
+        }
+        if let Err(e) = self.session.send(reject, now) {
+            self.record_error(&e, now);
+        }
+    }
+
+    fn on_application(&mut self, message: &FixMessage, now: UnixNano) -> Result<(), FixError> {
+        let actions = match message.msg_type() {
+            msg_type::NEW_ORDER_SINGLE => {
+                self.new_orders += 1;
+                self.script.orders.get(&self.new_orders).unwrap_or(&self.script.new_order).clone()
+            }
+            msg_type::ORDER_CANCEL_REQUEST => self.script.cancel.clone(),
+            msg_type::ORDER_CANCEL_REPLACE_REQUEST => self.script.replace.clone(),
+            // Logout, Reject and anything else is only recorded
+            _ => return Ok(()),
+        };
+        let request = self.mapping.order_request(message, now)?;
+        let cl_ord_id = self.mapping.order_id(message.require(tags::CL_ORD_ID)?)?;
+        // the order acted on, under its ClOrdID once a replace is acked
+        let mut target = target_order_id(&request.order_core, cl_ord_id);
+        match &request.order_core {
+            OrderCore::CancelOrder(_) | OrderCore::ModifyOrder(_) if !self.orders.contains_key(&target) => {
+                return self.cancel_reject(&request, cl_ord_id, "unknown order", now);
+            }
+            _ if request.order_core.order_side().is_some() && self.orders.contains_key(&cl_ord_id) => {
+                return self.reject_duplicate(&request, cl_ord_id, now);
+            }
+            _ => {}
+        }
+        if let Some(side) = request.order_core.order_side() {
+            // a new order: kept from the first action on, even if the script never acks it
+            let order = AcceptedOrder {
+                instid: request.instid,
+                side,
+                price: request.order_core.price(),
+                quantity: request.order_core.quantity().unwrap_or(0),
+                cum_quantity: 0,
+                notional: 0.0,
+            };
+            self.orders.insert(cl_ord_id, order);
+        }
+
+        for action in actions {
+            match action {
+                ScriptAction::Ack => match &request.order_core {
+                    // closed by an earlier action of the script
+                    OrderCore::CancelOrder(_) | OrderCore::ModifyOrder(_) if !self.orders.contains_key(&target) => {
+                        self.cancel_reject(&request, cl_ord_id, "unknown order", now)?;
+                    }
+                    OrderCore::CancelOrder(_) => {
+                        self.send_report(cl_ord_id, Some(target), ExecType::Canceled, None, now)?;
+                        self.orders.remove(&target);
+                    }
+                    OrderCore::ModifyOrder(modify) => {
+                        let Some(mut order) = self.orders.remove(&target) else {
+                            return Err(FixError::Order(OrderError::UnknownOrder(target)));
+                        };
+                        order.price = Some(modify.price);
+                        order.quantity = modify.quantity;
+                        self.orders.insert(cl_ord_id, order);
+                        self.send_report(cl_ord_id, Some(target), ExecType::Replaced, None, now)?;
+                        target = cl_ord_id;
+                    }
+                    _ => self.send_report(cl_ord_id, None, ExecType::New, None, now)?,
+                },
+                ScriptAction::Fill { quantity, price } => {
+                    let order_id = target;
+                    let Some(order) = self.orders.get_mut(&order_id) else {
+                        continue;
+                    };
+                    let quantity = quantity.unwrap_or(order.leaves_quantity()).min(order.leaves_quantity());
+                    let Some(price) = price.or(order.price) else {
+                        return Err(FixError::Unsupported("a fill of a market order without a price".to_string()));
+                    };
+                    order.cum_quantity += quantity;
+                    order.notional += price as f64 * quantity as f64;
+                    self.send_report(order_id, None, ExecType::Trade, Some((price, quantity)), now)?;
+                    if self.orders.get(&order_id).is_some_and(|order| order.leaves_quantity() == 0) {
+                        self.orders.remove(&order_id);
+                    }
+                }
+                ScriptAction::Reject(reason) => match &request.order_core {
+                    OrderCore::CancelOrder(_) | OrderCore::ModifyOrder(_) => {
+                        self.cancel_reject(&request, cl_ord_id, &reason, now)?;
+                    }
+                    _ => {
+                        let mut report = self.report(cl_ord_id, None, ExecType::Rejected, None, now)?;
+                        report.reject_reason = Some(reason);
+                        report.leaves_quantity = 0;
+                        self.send(&report, cl_ord_id)?;
+                        self.orders.remove(&cl_ord_id);
+                    }
+                },
+                ScriptAction::Cancel => {
+                    let order_id = target;
+                    if self.orders.contains_key(&order_id) {
+                        self.send_report(order_id, None, ExecType::Canceled, None, now)?;
+                        self.orders.remove(&order_id);
+                    }
+                }
+                ScriptAction::Gap(count) => self.session.skip_sequence(count)?,
+                ScriptAction::Disconnect => {
+                    self.disconnected = true;
+                    break;
+                }
+                ScriptAction::Ignore => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// OrderCancelReject (9) of a cancel or replace
+    fn cancel_reject(&mut self, request: &OrderRequest, cl_ord_id: OrderId, reason: &str, now: UnixNano) -> Result<(), FixError> {
+        let response_to = match request.order_core {
+            OrderCore::ModifyOrder(_) => CancelRejectResponseTo::Replace,
+            _ => CancelRejectResponseTo::Cancel,
+        };
+        let report = ExecutionReport::new(self.exec_id(), cl_ord_id, request.instid, ExecType::CancelRejected)
+            .with_orig_order_id(target_order_id(&request.order_core, cl_ord_id))
+            .with_response_to(response_to)
+            .with_reject_reason(reason)
+            .with_times(now, now);
+        let reject = self.mapping.execution_report_message(&report, OrderSide::default())?;
+        self.session.send(reject, now)?;
+        Ok(())
+    }
+
+    /// Rejects a new order reusing the ClOrdID of an open order, which is left as it is
+    fn reject_duplicate(&mut self, request: &OrderRequest, cl_ord_id: OrderId, now: UnixNano) -> Result<(), FixError> {
+        let order_core = &request.order_core;
+        let report = ExecutionReport::new(self.exec_id(), cl_ord_id, request.instid, ExecType::Rejected)
+            .with_order_terms(order_core.price(), order_core.quantity())
+            .with_reject_reason("duplicate ClOrdID")
+            .with_times(now, now);
+        let reject = self.mapping.execution_report_message(&report, order_core.order_side().unwrap_or_default())?;
+        self.session.send(reject, now)?;
+        Ok(())
+    }
+
+    #[inline]
+    fn exec_id(&mut self) -> ExecId {
+        self.next_exec_id += 1;
+        self.next_exec_id - 1
+    }
+
+    fn report(
+        &mut self,
+        order_id: OrderId,
+        orig_order_id: Option<OrderId>,
+        exec_type: ExecType,
+        fill: Option<(BookPrice, BookQuantity)>,
+        now: UnixNano,
+    ) -> Result<ExecutionReport, FixError> {
+        let exec_id = self.exec_id();
+        let key = if exec_type == ExecType::Canceled { orig_order_id.unwrap_or(order_id) } else { order_id };
+        let order = self.orders.get(&key).ok_or(FixError::Order(OrderError::UnknownOrder(key)))?;
+        let avg_price = if order.cum_quantity > 0 { order.notional / order.cum_quantity as f64 } else { 0.0 };
+        let leaves_quantity = if exec_type == ExecType::Canceled { 0 } else { order.leaves_quantity() };
+        let mut report = ExecutionReport::new(exec_id, order_id, order.instid, exec_type)
+            .with_quantities(order.cum_quantity, leaves_quantity, avg_price)
+            .with_order_terms(order.price, Some(order.quantity))
+            .with_times(now, now);
+        if let Some(orig_order_id) = orig_order_id {
+            report = report.with_orig_order_id(orig_order_id);
+        }
+        if let Some((price, quantity)) = fill {
+            report = report.with_fill(price, quantity);
+        }
+        Ok(report)
+    }
+
+    fn send_report(
+        &mut self,
+        order_id: OrderId,
+        orig_order_id: Option<OrderId>,
+        exec_type: ExecType,
+        fill: Option<(BookPrice, BookQuantity)>,
+        now: UnixNano,
+    ) -> Result<(), FixError> {
+        let report = self.report(order_id, orig_order_id, exec_type, fill, now)?;
+        let key = if exec_type == ExecType::Canceled { orig_order_id.unwrap_or(order_id) } else { order_id };
+        self.send(&report, key)
+    }
+
+    fn send(&mut self, report: &ExecutionReport, key: OrderId) -> Result<(), FixError> {
+        let side = self.orders.get(&key).map(|order| order.side).unwrap_or_default();
+        let message = self.mapping.execution_report_message(report, side)?;
+        self.session.send(message, report.systemtime)?;
+        Ok(())
+    }
+
+    /// Serves one connection until it is closed, with the sequence numbers of the previous one
+    pub fn run(&mut self, mut stream: TcpStream) -> Result<(), FixError> {
+        self.reconnect();
+        stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+        let mut buffer = vec![0u8; 65536];
+        while !self.is_closed() {
+            let now = flashlog::get_unix_nano();
+            let bytes = match stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(size) => self.on_bytes(&buffer[..size], now),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => self.on_timer(now),
+                Err(e) => return Err(e.into()),
+            };
+            if !bytes.is_empty() {
+                stream.write_all(&bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Accepts connections one at a time, `sessions` of them (None for ever)
+    pub fn serve(&mut self, listener: &TcpListener, sessions: Option<usize>) -> Result<(), FixError> {
+        let mut served = 0;
+        while sessions.is_none_or(|sessions| served < sessions) {
+            let (stream, peer) = listener.accept()?;
+            let logged = peer.to_string();
+            flashlog::flash_info!("FIX"; "acceptor: connection from {}", logged);
+            if let Err(e) = self.run(stream) {
+                self.record_error(&e, flashlog::get_unix_nano());
+            }
+            served += 1;
+        }
+        Ok(())
+    }
+}
+
+/// The order a message acts on: the original order of a cancel or replace
+#[inline]
+fn target_order_id(order_core: &OrderCore, cl_ord_id: OrderId) -> OrderId {
+    match order_core {
+        OrderCore::CancelOrder(cancel) => cancel.order_id,
+        OrderCore::ModifyOrder(modify) => modify.order_id,
+        _ => cl_ord_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instrument::registry::InstrumentRegistry;
+    use crate::instrument::spec::InstrumentSpec;
+    use crate::order::core::{CancelOrder, LimitOrder, ModifyOrder};
+
+    fn instid() -> InstId {
+        InstId::from_str("005930", "KRX")
+    }
+
+    fn mapping() -> FixMapping {
+        let mut registry = InstrumentRegistry::new();
+        registry.insert(InstrumentSpec::new(instid(), 1, 0));
+        FixMapping::new(registry)
+    }
+
+    fn initiator() -> FixSession<MemoryStore> {
+        FixSession::new(SessionConfig::new("CLIENT", "BROKER"), SessionRole::Initiator, MemoryStore::new())
+    }
+
+    fn exchange(initiator: &mut FixSession<MemoryStore>, acceptor: &mut FixAcceptor, now: UnixNano) -> Vec<FixMessage> {
+        let bytes = acceptor.on_bytes(&initiator.take_outbox(), now);
+        initiator.on_bytes(&bytes, now).unwrap()
+    }
+
+    fn new_order(order_id: OrderId) -> FixMessage {
+        let order = LimitOrder::new(100, 10, OrderSide::Bid, order_id);
+        mapping().new_order_single(&OrderRequest::new(instid(), OrderCore::LimitOrder(order), 0)).unwrap()
+    }
+
+    #[test]
+    fn test_scripted_fills_and_gap() {
+        let script = AcceptorScript::default()
+            .with_order(
+                1,
+                vec![
+                    ScriptAction::Ack,
+                    ScriptAction::Fill { quantity: Some(4), price: None },
+                    ScriptAction::Gap(2),
+                    ScriptAction::Fill { quantity: None, price: Some(99) },
+                ],
+            )
+            .with_order(2, vec![ScriptAction::Reject("no".to_string())]);
+        let mut acceptor = FixAcceptor::new(SessionConfig::new("BROKER", "CLIENT"), mapping(), script);
+        let mut initiator = self::initiator();
+        initiator.logon(0).unwrap();
+        exchange(&mut initiator, &mut acceptor, 0);
+        assert!(initiator.is_active());
+
+        initiator.send(new_order(1), 1).unwrap();
+        // the last fill is held by the initiator until the gap is filled
+        let delivered = exchange(&mut initiator, &mut acceptor, 1);
+        assert_eq!(delivered.len(), 2);
+        let delivered = exchange(&mut initiator, &mut acceptor, 2);
+        assert_eq!(delivered.len(), 1);
+        let report = mapping().execution_report(&delivered[0], 2, |_| Some(instid())).unwrap();
+        assert_eq!((report.last_price, report.last_quantity, report.cum_quantity), (99, 6, 10));
+
+        initiator.send(new_order(2), 3).unwrap();
+        let delivered = exchange(&mut initiator, &mut acceptor, 3);
+        assert_eq!(delivered[0].get(tags::EXEC_TYPE), Some("8"));
+        assert_eq!(delivered[0].get(tags::TEXT), Some("no"));
+
+        let kinds: Vec<&str> = acceptor
+            .conversation()
+            .iter()
+            .map(|entry| match entry {
+                ConversationEntry::Inbound { .. } => "in",
+                ConversationEntry::Outbound { .. } => "out",
+                ConversationEntry::Error { .. } => "error",
+            })
+            .collect();
+        // logon, order 1 with three reports, resend request and its answer, order 2 and its reject
+        assert_eq!(kinds, vec!["in", "out", "in", "out", "out", "out", "in", "out", "out", "in", "out"]);
+    }
+
+    fn logged_on(script: AcceptorScript) -> (FixSession<MemoryStore>, FixAcceptor) {
+        let mut acceptor = FixAcceptor::new(SessionConfig::new("BROKER", "CLIENT"), mapping(), script);
+        let mut initiator = self::initiator();
+        initiator.logon(0).unwrap();
+        exchange(&mut initiator, &mut acceptor, 0);
+        (initiator, acceptor)
+    }
+
+    fn original(order_id: OrderId) -> OrderRequest {
+        OrderRequest::new(instid(), OrderCore::LimitOrder(LimitOrder::new(100, 10, OrderSide::Bid, order_id)), 0)
+    }
+
+    #[test]
+    fn test_cancel_reject_of_unknown_or_closed_order() {
+        let (mut initiator, mut acceptor) = logged_on(AcceptorScript::default());
+
+        initiator.send(mapping().order_cancel_request(&original(9), 10, 1).unwrap(), 1).unwrap();
+        let delivered = exchange(&mut initiator, &mut acceptor, 1);
+        assert_eq!(delivered[0].msg_type(), msg_type::ORDER_CANCEL_REJECT);
+        assert_eq!(delivered[0].get(tags::CXL_REJ_RESPONSE_TO), Some("1"));
+        assert_eq!(delivered[0].get(tags::ORIG_CL_ORD_ID), Some("9"));
+
+        // canceled, then replaced
+        initiator.send(new_order(1), 2).unwrap();
+        exchange(&mut initiator, &mut acceptor, 2);
+        initiator.send(mapping().order_cancel_request(&original(1), 2, 3).unwrap(), 3).unwrap();
+        exchange(&mut initiator, &mut acceptor, 3);
+        let modify = ModifyOrder::new(1, 101, 10).with_new_order_id(3);
+        initiator.send(mapping().order_cancel_replace_request(&original(1), &modify, 4).unwrap(), 4).unwrap();
+        let delivered = exchange(&mut initiator, &mut acceptor, 4);
+        assert_eq!(delivered[0].msg_type(), msg_type::ORDER_CANCEL_REJECT);
+        assert_eq!(delivered[0].get(tags::CXL_REJ_RESPONSE_TO), Some("2"));
+        let report = mapping().execution_report(&delivered[0], 4, |_| Some(instid())).unwrap();
+        assert_eq!((report.exec_type, report.order_id), (ExecType::CancelRejected, 1));
+        assert!(!acceptor.conversation().iter().any(|entry| matches!(entry, ConversationEntry::Error { .. })));
+    }
+
+    #[test]
+    fn test_replace_then_fill() {
+        let script = AcceptorScript::default()
+            .with_replace(vec![ScriptAction::Ack, ScriptAction::Fill { quantity: Some(4), price: None }, ScriptAction::Cancel]);
+        let (mut initiator, mut acceptor) = logged_on(script);
+        initiator.send(new_order(1), 1).unwrap();
+        exchange(&mut initiator, &mut acceptor, 1);
+
+        let modify = ModifyOrder::new(1, 101, 10).with_new_order_id(2);
+        initiator.send(mapping().order_cancel_replace_request(&original(1), &modify, 2).unwrap(), 2).unwrap();
+        let delivered = exchange(&mut initiator, &mut acceptor, 2);
+        let reports: Vec<ExecutionReport> =
+            delivered.iter().map(|message| mapping().execution_report(message, 2, |_| None).unwrap()).collect();
+        let summary: Vec<(ExecType, OrderId, BookPrice)> =
+            reports.iter().map(|report| (report.exec_type, report.order_id, report.last_price)).collect();
+        // the fill and the cancel act on the order under its new ClOrdID, at its new price
+        assert_eq!(summary, vec![(ExecType::Replaced, 2, 0), (ExecType::Trade, 2, 101), (ExecType::Canceled, 2, 0)]);
+        assert_eq!((reports[1].cum_quantity, reports[1].leaves_quantity), (4, 6));
+    }
+
+    #[test]
+    fn test_duplicate_cl_ord_id() {
+        let (mut initiator, mut acceptor) = logged_on(AcceptorScript::default());
+        initiator.send(new_order(1), 1).unwrap();
+        exchange(&mut initiator, &mut acceptor, 1);
+
+        let duplicate = LimitOrder::new(200, 5, OrderSide::Ask, 1);
+        let duplicate = mapping().new_order_single(&OrderRequest::new(instid(), OrderCore::LimitOrder(duplicate), 0)).unwrap();
+        initiator.send(duplicate, 2).unwrap();
+        let delivered = exchange(&mut initiator, &mut acceptor, 2);
+        assert_eq!(delivered[0].msg_type(), msg_type::EXECUTION_REPORT);
+        assert_eq!(delivered[0].get(tags::ORD_STATUS), Some("8"));
+        assert_eq!(delivered[0].get(tags::TEXT), Some("duplicate ClOrdID"));
+
+        // the open order is left as it was
+        initiator.send(mapping().order_cancel_request(&original(1), 2, 3).unwrap(), 3).unwrap();
+        let delivered = exchange(&mut initiator, &mut acceptor, 3);
+        let report = mapping().execution_report(&delivered[0], 3, |_| Some(instid())).unwrap();
+        assert_eq!((report.exec_type, report.order_quantity, report.order_price), (ExecType::Canceled, Some(10), Some(100)));
+        assert_eq!(delivered[0].get(tags::SIDE), Some("1"));
+    }
+
+    #[test]
+    fn test_cancel_and_disconnect() {
+        let script = AcceptorScript::default().with_order(2, vec![ScriptAction::Disconnect]);
+        let mut acceptor = FixAcceptor::new(SessionConfig::new("BROKER", "CLIENT"), mapping(), script);
+        let mut initiator = self::initiator();
+        initiator.logon(0).unwrap();
+        exchange(&mut initiator, &mut acceptor, 0);
+
+        initiator.send(new_order(1), 1).unwrap();
+        exchange(&mut initiator, &mut acceptor, 1);
+        let cancel = OrderRequest::new(instid(), OrderCore::LimitOrder(LimitOrder::new(100, 10, OrderSide::Bid, 1)), 0);
+        initiator.send(mapping().order_cancel_request(&cancel, 5, 2).unwrap(), 2).unwrap();
+        let delivered = exchange(&mut initiator, &mut acceptor, 2);
+        let report = mapping().execution_report(&delivered[0], 2, |_| Some(instid())).unwrap();
+        assert_eq!((report.exec_type, report.order_id, report.orig_order_id), (ExecType::Canceled, 5, Some(1)));
+        let request = mapping().order_request(&mapping().order_cancel_request(&cancel, 5, 2).unwrap(), 2).unwrap();
+        assert_eq!(request.order_core, OrderCore::CancelOrder(CancelOrder::new(1)));
+
+        initiator.send(new_order(2), 3).unwrap();
+        assert!(exchange(&mut initiator, &mut acceptor, 3).is_empty());
+        assert!(acceptor.is_closed());
+
+        // a lower sequence number on the next connection is caught
+        acceptor.reconnect();
+        let mut stale = self::initiator();
+        stale.logon(4).unwrap();
+        acceptor.on_bytes(&stale.take_outbox(), 4);
+        assert!(matches!(acceptor.conversation().last(), Some(ConversationEntry::Error { .. })));
+        assert!(acceptor.is_closed());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::instrument::error::InstrumentError;
+use crate::order::error::OrderError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FixError {
+    Io(String),
+    /// the message does not start with 8=BeginString|9=BodyLength|
+    BadHeader,
+    BadBeginString(String),
+    BadBodyLength,
+    BadChecksum {
+        expected: u8,
+        computed: u8,
+    },
+    /// a field without '=' or with a non numeric tag
+    BadField(String),
+    MissingField(u32),
+    InvalidField {
+        tag: u32,
+        value: String,
+    },
+    /// a message type or an order the mapping can not express
+    Unsupported(String),
+    /// the counterparty sent a MsgSeqNum lower than expected without PossDupFlag
+    SequenceTooLow {
+        expected: u64,
+        received: u64,
+    },
+    CompIdMismatch {
+        expected: String,
+        received: String,
+    },
+    NotLoggedOn,
+    /// no message from the counterparty within two heartbeat intervals
+    HeartbeatTimeout,
+    /// the session was logged out
+    Disconnected(String),
+    /// a stored record is damaged and more data follows it, so it is not a record cut short by a crash
+    CorruptStore {
+        offset: u64,
+    },
+    Order(OrderError),
+    Instrument(InstrumentError),
+}
+
+impl std::fmt::Display for FixError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FixError::Io(msg) => write!(f, "fix io error: {}", msg),
+            FixError::BadHeader => write!(f, "fix message without BeginString and BodyLength"),
+            FixError::BadBeginString(begin_string) => write!(f, "unexpected BeginString: {}", begin_string),
+            FixError::BadBodyLength => write!(f, "invalid BodyLength"),
+            FixError::BadChecksum { expected, computed } => {
+                write!(f, "fix checksum mismatch: expected {:03}, computed {:03}", expected, computed)
+            }
+            FixError::BadField(field) => write!(f, "malformed fix field: {}", field),
+            FixError::MissingField(tag) => write!(f, "missing fix field {}", tag),
+            FixError::InvalidField { tag, value } => write!(f, "invalid value of fix field {}: {}", tag, value),
+            FixError::Unsupported(what) => write!(f, "unsupported by the fix mapping: {}", what),
+            FixError::SequenceTooLow { expected, received } => {
+                write!(f, "MsgSeqNum too low: expected {}, received {}", expected, received)
+            }
+            FixError::CompIdMismatch { expected, received } => {
+                write!(f, "CompID mismatch: expected {}, received {}", expected, received)
+            }
+            FixError::NotLoggedOn => write!(f, "fix session is not logged on"),
+            FixError::HeartbeatTimeout => write!(f, "fix heartbeat timeout"),
+            FixError::Disconnected(reason) => write!(f, "fix session disconnected: {}", reason),
+            FixError::CorruptStore { offset } => write!(f, "corrupt fix message store record at {}", offset),
+            FixError::Order(e) => write!(f, "{}", e),
+            FixError::Instrument(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FixError {}
+
+impl From<std::io::Error> for FixError {
+    fn from(e: std::io::Error) -> Self {
+        FixError::Io(e.to_string())
+    }
+}
+
+impl From<OrderError> for FixError {
+    fn from(e: OrderError) -> Self {
+        FixError::Order(e)
+    }
+}
+
+impl From<InstrumentError> for FixError {
+    fn from(e: InstrumentError) -> Self {
+        FixError::Instrument(e)
+    }
+}
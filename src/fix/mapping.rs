@@ -0,0 +1,627 @@
+use crate::{BookPrice, BookQuantity, ExecId, InstId, OrderCore, OrderId, TimeStamp};
+use crate::fix::error::FixError;
+use crate::fix::message::{format_utc_timestamp, msg_type, parse_utc_timestamp, tags, FixMessage};
+use crate::instrument::error::InstrumentError;
+use crate::instrument::registry::InstrumentRegistry;
+use crate::instrument::spec::InstrumentSpec;
+use crate::order::core::{
+    CancelOrder, IfTouchedOrder, LimitOrder, MarketOrder, ModifyOrder, OrderAttributes, StopLimitOrder,
+    StopMarketOrder, TrailingStopOrder,
+};
+use crate::order::enums::{OrderSide, OrderType, TimeInForce};
+use crate::order::error::OrderError;
+use crate::order::execution::{CancelRejectResponseTo, ExecType, ExecutionReport};
+use crate::order::id_gen::ClOrdIdEncoding;
+use crate::order::request::OrderRequest;
+use crate::price;
+use std::hash::{Hash, Hasher};
+
+/// ExecInst (18) values
+const PARTICIPATE_DONT_INITIATE: char = '6';
+const DO_NOT_INCREASE: char = 'E';
+
+/// Translates orders and execution reports between `OrderRequest`/`ExecutionReport` and FIX 4.4.
+///
+/// Symbol (55) and SecurityExchange (207) carry the code and venue of the `InstId`,
+/// prices are written with the price scale of the instrument in the registry,
+/// and ClOrdID (11) is the `OrderId` in the given encoding
+#[derive(Debug, Clone)]
+pub struct FixMapping {
+    registry: InstrumentRegistry,
+    encoding: ClOrdIdEncoding,
+    /// the venue of instruments sent without SecurityExchange
+    default_venue: Option<String>,
+}
+
+impl FixMapping {
+    pub fn new(registry: InstrumentRegistry) -> Self {
+        Self {
+            registry,
+            encoding: ClOrdIdEncoding::Decimal,
+            default_venue: None,
+        }
+    }
+
+    pub fn with_encoding(mut self, encoding: ClOrdIdEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn with_default_venue(mut self, venue: &str) -> Self {
+        self.default_venue = Some(venue.to_string());
+        self
+    }
+
+    #[inline]
+    pub fn registry(&self) -> &InstrumentRegistry {
+        &self.registry
+    }
+
+    #[inline]
+    pub fn cl_ord_id(&self, order_id: OrderId) -> String {
+        self.encoding.encode(order_id)
+    }
+
+    #[inline]
+    pub fn order_id(&self, cl_ord_id: &str) -> Result<OrderId, FixError> {
+        Ok(self.encoding.decode(cl_ord_id)?)
+    }
+
+    fn spec(&self, instid: &InstId) -> Result<&InstrumentSpec, FixError> {
+        Ok(self.registry.try_get(instid)?)
+    }
+
+    /// The registered instrument of Symbol (55) and SecurityExchange (207)
+    pub fn instid(&self, message: &FixMessage) -> Result<InstId, FixError> {
+        let symbol = message.require(tags::SYMBOL)?;
+        let venue = match (message.get(tags::SECURITY_EXCHANGE), &self.default_venue) {
+            (Some(venue), _) => venue,
+            (None, Some(venue)) => venue.as_str(),
+            (None, None) => return Err(FixError::MissingField(tags::SECURITY_EXCHANGE)),
+        };
+        Ok(self.registry.try_find(symbol, venue)?)
+    }
+
+    fn set_instrument(message: &mut FixMessage, instid: &InstId) {
+        message.set(tags::SYMBOL, instid.code_str());
+        message.set(tags::SECURITY_EXCHANGE, instid.venue_str());
+    }
+
+    fn parse_price(spec: &InstrumentSpec, message: &FixMessage, tag: u32) -> Result<Option<BookPrice>, FixError> {
+        match message.get(tag) {
+            Some(value) => Ok(Some(spec.price_from_str(value).map_err(InstrumentError::from)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn require_price(spec: &InstrumentSpec, message: &FixMessage, tag: u32) -> Result<BookPrice, FixError> {
+        Self::parse_price(spec, message, tag)?.ok_or(FixError::MissingField(tag))
+    }
+
+    /// NewOrderSingle (D) of a new order
+    pub fn new_order_single(&self, request: &OrderRequest) -> Result<FixMessage, FixError> {
+        let order_core = &request.order_core;
+        let order_type = OrderType::from(order_core);
+        let (Some(ord_type), Some(order_id), Some(side), Some(quantity), Some(attributes)) = (
+            order_type.fix_code(),
+            order_core.order_id(),
+            order_core.order_side(),
+            order_core.quantity(),
+            order_core.attributes(),
+        ) else {
+            return Err(FixError::Unsupported(format!("{} as NewOrderSingle", order_core.order_type())));
+        };
+        let spec = self.spec(&request.instid)?;
+
+        let mut message = FixMessage::new(msg_type::NEW_ORDER_SINGLE).with(tags::CL_ORD_ID, self.cl_ord_id(order_id));
+        Self::set_instrument(&mut message, &request.instid);
+        message.set(tags::SIDE, side.fix_code());
+        message.set(tags::TRANSACT_TIME, format_utc_timestamp(request.systemtime));
+        message.set(tags::ORDER_QTY, quantity);
+        message.set(tags::ORD_TYPE, ord_type);
+        match order_core {
+            OrderCore::TrailingStopOrder(order) => {
+                if order.limit_offset.is_some() {
+                    return Err(FixError::Unsupported("trailing stop with a limit offset".to_string()));
+                }
+                message.set(tags::PEG_OFFSET_VALUE, spec.price_to_string(order.trail));
+            }
+            _ => {
+                if let Some(price) = order_core.price().or(match order_core {
+                    OrderCore::StopLimitOrder(order) => Some(order.price),
+                    OrderCore::IfTouchedOrder(order) => order.price,
+                    _ => None,
+                }) {
+                    message.set(tags::PRICE, spec.price_to_string(price));
+                }
+                if let Some(trigger_price) = order_core.trigger_price() {
+                    message.set(tags::STOP_PX, spec.price_to_string(trigger_price));
+                }
+            }
+        }
+        Self::set_attributes(&mut message, attributes);
+        Ok(message)
+    }
+
+    fn set_attributes(message: &mut FixMessage, attributes: &OrderAttributes) {
+        let time_in_force = match attributes.time_in_force {
+            TimeInForce::Day => '0',
+            TimeInForce::Gtc => '1',
+            TimeInForce::Ioc => '3',
+            TimeInForce::Fok => '4',
+            TimeInForce::Gtd(expire_time) => {
+                message.set(tags::EXPIRE_TIME, format_utc_timestamp(expire_time));
+                '6'
+            }
+        };
+        message.set(tags::TIME_IN_FORCE, time_in_force);
+        let exec_inst: String = [
+            attributes.post_only.then_some(PARTICIPATE_DONT_INITIATE),
+            attributes.reduce_only.then_some(DO_NOT_INCREASE),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|c| format!("{} ", c))
+        .collect();
+        if !exec_inst.is_empty() {
+            message.set(tags::EXEC_INST, exec_inst.trim_end());
+        }
+        if let Some(display_quantity) = attributes.display_quantity {
+            message.set(tags::MAX_FLOOR, display_quantity);
+        }
+        if let Some(min_quantity) = attributes.min_quantity {
+            message.set(tags::MIN_QTY, min_quantity);
+        }
+        if let Some(account) = &attributes.account {
+            message.set(tags::ACCOUNT, account);
+        }
+        if let Some(tag) = &attributes.tag {
+            message.set(tags::TEXT, tag);
+        }
+    }
+
+    fn parse_attributes(message: &FixMessage) -> Result<OrderAttributes, FixError> {
+        let time_in_force = match message.get_char(tags::TIME_IN_FORCE)? {
+            None | Some('0') => TimeInForce::Day,
+            Some('1') => TimeInForce::Gtc,
+            Some('3') => TimeInForce::Ioc,
+            Some('4') => TimeInForce::Fok,
+            Some('6') => {
+                let expire_time = message.require(tags::EXPIRE_TIME)?;
+                TimeInForce::Gtd(parse_utc_timestamp(expire_time).ok_or_else(|| FixError::InvalidField {
+                    tag: tags::EXPIRE_TIME,
+                    value: expire_time.to_string(),
+                })?)
+            }
+            Some(code) => {
+                return Err(FixError::InvalidField {
+                    tag: tags::TIME_IN_FORCE,
+                    value: code.to_string(),
+                })
+            }
+        };
+        let exec_inst = message.get(tags::EXEC_INST).unwrap_or_default();
+        let has_exec_inst = |code: char| exec_inst.split(' ').any(|value| value.chars().eq([code]));
+        Ok(OrderAttributes {
+            time_in_force,
+            post_only: has_exec_inst(PARTICIPATE_DONT_INITIATE),
+            reduce_only: has_exec_inst(DO_NOT_INCREASE),
+            display_quantity: message.parse(tags::MAX_FLOOR)?,
+            min_quantity: message.parse(tags::MIN_QTY)?,
+            account: message.get(tags::ACCOUNT).map(str::to_string),
+            tag: message.get(tags::TEXT).map(str::to_string),
+        })
+    }
+
+    /// OrderCancelRequest (F) of `original`, identified by `cl_ord_id`
+    pub fn order_cancel_request(
+        &self,
+        original: &OrderRequest,
+        cl_ord_id: OrderId,
+        time: TimeStamp,
+    ) -> Result<FixMessage, FixError> {
+        let (Some(order_id), Some(side)) = (original.get_id(), original.order_core.order_side()) else {
+            return Err(OrderError::MissingOrderId(original.order_core.order_type()).into());
+        };
+        let mut message = FixMessage::new(msg_type::ORDER_CANCEL_REQUEST)
+            .with(tags::ORIG_CL_ORD_ID, self.cl_ord_id(order_id))
+            .with(tags::CL_ORD_ID, self.cl_ord_id(cl_ord_id));
+        Self::set_instrument(&mut message, &original.instid);
+        message.set(tags::SIDE, side.fix_code());
+        message.set(tags::TRANSACT_TIME, format_utc_timestamp(time));
+        if let Some(quantity) = original.order_core.quantity() {
+            message.set(tags::ORDER_QTY, quantity);
+        }
+        Ok(message)
+    }
+
+    /// OrderCancelReplaceRequest (G) of `original`. The new ClOrdID is `modify.new_order_id`
+    pub fn order_cancel_replace_request(
+        &self,
+        original: &OrderRequest,
+        modify: &ModifyOrder,
+        time: TimeStamp,
+    ) -> Result<FixMessage, FixError> {
+        let Some(new_order_id) = modify.new_order_id else {
+            return Err(OrderError::MissingOrderId(OrderType::Modify).into());
+        };
+        let order_core = &original.order_core;
+        let (Some(side), Some(ord_type), Some(attributes)) =
+            (order_core.order_side(), OrderType::from(order_core).fix_code(), order_core.attributes())
+        else {
+            return Err(OrderError::NotReplaceable(order_core.order_type()).into());
+        };
+        let spec = self.spec(&original.instid)?;
+        let mut message = FixMessage::new(msg_type::ORDER_CANCEL_REPLACE_REQUEST)
+            .with(tags::ORIG_CL_ORD_ID, self.cl_ord_id(modify.order_id))
+            .with(tags::CL_ORD_ID, self.cl_ord_id(new_order_id));
+        Self::set_instrument(&mut message, &original.instid);
+        message.set(tags::SIDE, side.fix_code());
+        message.set(tags::TRANSACT_TIME, format_utc_timestamp(time));
+        message.set(tags::ORDER_QTY, modify.quantity);
+        message.set(tags::ORD_TYPE, ord_type);
+        message.set(tags::PRICE, spec.price_to_string(modify.price));
+        Self::set_attributes(&mut message, attributes);
+        Ok(message)
+    }
+
+    /// The order of a NewOrderSingle (D), OrderCancelRequest (F) or OrderCancelReplaceRequest (G),
+    /// e.g., on the acceptor side
+    pub fn order_request(&self, message: &FixMessage, systemtime: TimeStamp) -> Result<OrderRequest, FixError> {
+        let instid = self.instid(message)?;
+        let cl_ord_id = self.order_id(message.require(tags::CL_ORD_ID)?)?;
+        let order_core = match message.msg_type() {
+            msg_type::NEW_ORDER_SINGLE => self.new_order_core(&instid, message, cl_ord_id)?,
+            msg_type::ORDER_CANCEL_REQUEST => {
+                let orig_order_id = self.order_id(message.require(tags::ORIG_CL_ORD_ID)?)?;
+                OrderCore::CancelOrder(CancelOrder::new(orig_order_id))
+            }
+            msg_type::ORDER_CANCEL_REPLACE_REQUEST => {
+                let spec = self.spec(&instid)?;
+                let orig_order_id = self.order_id(message.require(tags::ORIG_CL_ORD_ID)?)?;
+                let price = Self::require_price(spec, message, tags::PRICE)?;
+                let quantity = parse_quantity(message, tags::ORDER_QTY)?.ok_or(FixError::MissingField(tags::ORDER_QTY))?;
+                OrderCore::ModifyOrder(ModifyOrder::new(orig_order_id, price, quantity).with_new_order_id(cl_ord_id))
+            }
+            other => return Err(FixError::Unsupported(format!("MsgType {} as an order", other))),
+        };
+        Ok(OrderRequest::new(instid, order_core, systemtime))
+    }
+
+    fn new_order_core(&self, instid: &InstId, message: &FixMessage, order_id: OrderId) -> Result<OrderCore, FixError> {
+        let spec = self.spec(instid)?;
+        let side_code = message.get_char(tags::SIDE)?.ok_or(FixError::MissingField(tags::SIDE))?;
+        let side = OrderSide::try_from(side_code)?;
+        let quantity = parse_quantity(message, tags::ORDER_QTY)?.ok_or(FixError::MissingField(tags::ORDER_QTY))?;
+        let ord_type = message.get_char(tags::ORD_TYPE)?.ok_or(FixError::MissingField(tags::ORD_TYPE))?;
+        let attributes = Self::parse_attributes(message)?;
+        let order_core = match OrderType::try_from(ord_type)? {
+            OrderType::Market => OrderCore::MarketOrder(MarketOrder::new(quantity, side, order_id).with_attributes(attributes)),
+            OrderType::Limit => {
+                let price = Self::require_price(spec, message, tags::PRICE)?;
+                OrderCore::LimitOrder(LimitOrder::new(price, quantity, side, order_id).with_attributes(attributes))
+            }
+            OrderType::StopMarket => match Self::parse_price(spec, message, tags::PEG_OFFSET_VALUE)? {
+                Some(trail) => OrderCore::TrailingStopOrder(
+                    TrailingStopOrder::new(trail, quantity, side, order_id).with_attributes(attributes),
+                ),
+                None => {
+                    let stop_price = Self::require_price(spec, message, tags::STOP_PX)?;
+                    OrderCore::StopMarketOrder(
+                        StopMarketOrder::new(stop_price, quantity, side, order_id).with_attributes(attributes),
+                    )
+                }
+            },
+            OrderType::StopLimit => {
+                let stop_price = Self::require_price(spec, message, tags::STOP_PX)?;
+                let price = Self::require_price(spec, message, tags::PRICE)?;
+                OrderCore::StopLimitOrder(
+                    StopLimitOrder::new(stop_price, price, quantity, side, order_id).with_attributes(attributes),
+                )
+            }
+            OrderType::IfTouched => {
+                let trigger_price = Self::require_price(spec, message, tags::STOP_PX)?;
+                let mut order = IfTouchedOrder::new(trigger_price, quantity, side, order_id).with_attributes(attributes);
+                order.price = Self::parse_price(spec, message, tags::PRICE)?;
+                OrderCore::IfTouchedOrder(order)
+            }
+            other => return Err(FixError::Unsupported(format!("{} as NewOrderSingle", other))),
+        };
+        Ok(order_core)
+    }
+
+    /// The `ExecutionReport` of an ExecutionReport (8) or an OrderCancelReject (9).
+    /// An OrderCancelReject has no Symbol (55), so its instrument is the one `order_instid` gives for the order
+    pub fn execution_report(
+        &self,
+        message: &FixMessage,
+        systemtime: TimeStamp,
+        order_instid: impl FnOnce(OrderId) -> Option<InstId>,
+    ) -> Result<ExecutionReport, FixError> {
+        let datatime = message
+            .get(tags::TRANSACT_TIME)
+            .or(message.get(tags::SENDING_TIME))
+            .and_then(parse_utc_timestamp)
+            .unwrap_or(systemtime);
+        let orig_order_id = match message.get(tags::ORIG_CL_ORD_ID) {
+            Some(orig_cl_ord_id) => Some(self.order_id(orig_cl_ord_id)?),
+            None => None,
+        };
+
+        if message.msg_type() == msg_type::ORDER_CANCEL_REJECT {
+            // the order keeps its OrigClOrdID when the cancel or replace is rejected
+            let cl_ord_id = self.order_id(message.require(tags::CL_ORD_ID)?)?;
+            let order_id = orig_order_id.unwrap_or(cl_ord_id);
+            let instid = match message.get(tags::SYMBOL) {
+                Some(_) => self.instid(message)?,
+                None => order_instid(order_id).ok_or(FixError::MissingField(tags::SYMBOL))?,
+            };
+            // an OrderCancelReject has no ExecID
+            let mut report = ExecutionReport::new(0, order_id, instid, ExecType::CancelRejected).with_times(datatime, systemtime);
+            report.response_to = match message.get_char(tags::CXL_REJ_RESPONSE_TO)? {
+                Some('1') => Some(CancelRejectResponseTo::Cancel),
+                Some('2') => Some(CancelRejectResponseTo::Replace),
+                None => None,
+                Some(code) => {
+                    return Err(FixError::InvalidField {
+                        tag: tags::CXL_REJ_RESPONSE_TO,
+                        value: code.to_string(),
+                    })
+                }
+            };
+            report.reject_reason = message.get(tags::TEXT).map(str::to_string);
+            return Ok(report);
+        }
+        if message.msg_type() != msg_type::EXECUTION_REPORT {
+            return Err(FixError::Unsupported(format!("MsgType {} as an execution report", message.msg_type())));
+        }
+        let instid = self.instid(message)?;
+
+        let spec = self.spec(&instid)?;
+        let exec_type = message.get_char(tags::EXEC_TYPE)?.ok_or(FixError::MissingField(tags::EXEC_TYPE))?;
+        let exec_type = exec_type_from_fix(exec_type)?;
+        let order_id = self.order_id(message.require(tags::CL_ORD_ID)?)?;
+        let mut report = ExecutionReport::new(exec_id_of(message.require(tags::EXEC_ID)?), order_id, instid, exec_type)
+            .with_quantities(
+                parse_quantity(message, tags::CUM_QTY)?.unwrap_or(0),
+                parse_quantity(message, tags::LEAVES_QTY)?.unwrap_or(0),
+                message.parse(tags::AVG_PX)?.unwrap_or(0.0),
+            )
+            .with_order_terms(Self::parse_price(spec, message, tags::PRICE)?, parse_quantity(message, tags::ORDER_QTY)?)
+            .with_times(datatime, systemtime);
+        report.orig_order_id = orig_order_id;
+        if exec_type == ExecType::Trade {
+            report = report.with_fill(
+                Self::require_price(spec, message, tags::LAST_PX)?,
+                parse_quantity(message, tags::LAST_QTY)?.ok_or(FixError::MissingField(tags::LAST_QTY))?,
+            );
+        }
+        report.reject_reason = message.get(tags::TEXT).map(str::to_string);
+        Ok(report)
+    }
+
+    /// An ExecutionReport (8) of `report` on an order of `side`, e.g., sent by an acceptor.
+    /// A `CancelRejected` report is sent as an OrderCancelReject (9)
+    pub fn execution_report_message(&self, report: &ExecutionReport, side: OrderSide) -> Result<FixMessage, FixError> {
+        let spec = self.spec(&report.instid)?;
+        let ord_status = report.ord_status();
+        if report.exec_type == ExecType::CancelRejected {
+            let orig_order_id = report.orig_order_id.unwrap_or(report.order_id);
+            let response_to = match report.response_to {
+                Some(CancelRejectResponseTo::Replace) => '2',
+                Some(CancelRejectResponseTo::Cancel) | None => '1',
+            };
+            let mut message = FixMessage::new(msg_type::ORDER_CANCEL_REJECT)
+                .with(tags::ORDER_ID, orig_order_id)
+                .with(tags::CL_ORD_ID, self.cl_ord_id(report.order_id))
+                .with(tags::ORIG_CL_ORD_ID, self.cl_ord_id(orig_order_id))
+                .with(tags::ORD_STATUS, ord_status.fix_code())
+                .with(tags::CXL_REJ_RESPONSE_TO, response_to)
+                .with(tags::TRANSACT_TIME, format_utc_timestamp(report.datatime));
+            if let Some(reason) = &report.reject_reason {
+                message.set(tags::TEXT, reason);
+            }
+            return Ok(message);
+        }
+        let mut message = FixMessage::new(msg_type::EXECUTION_REPORT)
+            .with(tags::ORDER_ID, report.order_id)
+            .with(tags::CL_ORD_ID, self.cl_ord_id(report.order_id));
+        if let Some(orig_order_id) = report.orig_order_id {
+            message.set(tags::ORIG_CL_ORD_ID, self.cl_ord_id(orig_order_id));
+        }
+        message.set(tags::EXEC_ID, report.exec_id);
+        message.set(tags::EXEC_TYPE, exec_type_fix_code(report.exec_type));
+        message.set(tags::ORD_STATUS, ord_status.fix_code());
+        Self::set_instrument(&mut message, &report.instid);
+        message.set(tags::SIDE, side.fix_code());
+        if let Some(order_quantity) = report.order_quantity {
+            message.set(tags::ORDER_QTY, order_quantity);
+        }
+        if let Some(order_price) = report.order_price {
+            message.set(tags::PRICE, spec.price_to_string(order_price));
+        }
+        if report.exec_type == ExecType::Trade {
+            message.set(tags::LAST_PX, spec.price_to_string(report.last_price));
+            message.set(tags::LAST_QTY, report.last_quantity);
+        }
+        message.set(tags::LEAVES_QTY, report.leaves_quantity);
+        message.set(tags::CUM_QTY, report.cum_quantity);
+        message.set(tags::AVG_PX, report.avg_price);
+        message.set(tags::TRANSACT_TIME, format_utc_timestamp(report.datatime));
+        if let Some(reason) = &report.reject_reason {
+            message.set(tags::TEXT, reason);
+        }
+        Ok(message)
+    }
+}
+
+/// ExecType (150) of FIX 4.4. DoneForDay is read as Expired
+fn exec_type_from_fix(code: char) -> Result<ExecType, FixError> {
+    match code {
+        '0' => Ok(ExecType::New),
+        'F' => Ok(ExecType::Trade),
+        '4' => Ok(ExecType::Canceled),
+        '5' => Ok(ExecType::Replaced),
+        '6' => Ok(ExecType::PendingCancel),
+        'E' => Ok(ExecType::PendingReplace),
+        '8' => Ok(ExecType::Rejected),
+        'C' | '3' => Ok(ExecType::Expired),
+        _ => Err(FixError::InvalidField {
+            tag: tags::EXEC_TYPE,
+            value: code.to_string(),
+        }),
+    }
+}
+
+fn exec_type_fix_code(exec_type: ExecType) -> char {
+    match exec_type {
+        ExecType::New => '0',
+        ExecType::Trade => 'F',
+        ExecType::Canceled => '4',
+        ExecType::Replaced => '5',
+        ExecType::PendingCancel => '6',
+        ExecType::PendingReplace => 'E',
+        ExecType::Rejected => '8',
+        ExecType::Expired => 'C',
+        // sent as an OrderCancelReject
+        ExecType::CancelRejected => '8',
+    }
+}
+
+/// Numeric ExecIDs are kept as they are, others are hashed
+pub fn exec_id_of(exec_id: &str) -> ExecId {
+    exec_id.parse().unwrap_or_else(|_| {
+        let mut hasher = rustc_hash::FxHasher::default();
+        exec_id.hash(&mut hasher);
+        hasher.finish()
+    })
+}
+
+/// Quantities are integers, "100" or "100.00"
+fn parse_quantity(message: &FixMessage, tag: u32) -> Result<Option<BookQuantity>, FixError> {
+    match message.get(tag) {
+        Some(value) => price::from_decimal_str(value, 0)
+            .ok()
+            .and_then(|quantity| BookQuantity::try_from(quantity).ok())
+            .map(Some)
+            .ok_or_else(|| FixError::InvalidField {
+                tag,
+                value: value.to_string(),
+            }),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::enums::OrderStatus;
+
+    fn mapping() -> FixMapping {
+        let mut registry = InstrumentRegistry::new();
+        registry.insert(InstrumentSpec::new(InstId::from_str("005930", "KRX"), 10, 2));
+        FixMapping::new(registry).with_encoding(ClOrdIdEncoding::Base36)
+    }
+
+    fn instid() -> InstId {
+        InstId::from_str("005930", "KRX")
+    }
+
+    #[test]
+    fn test_order_round_trips() {
+        let mapping = mapping();
+        let attributes = OrderAttributes::default()
+            .with_time_in_force(TimeInForce::Gtd(1_709_214_307_000_000_000))
+            .with_post_only()
+            .with_display_quantity(10)
+            .with_account("ACC1");
+        let orders = [
+            OrderCore::LimitOrder(LimitOrder::new(12_350, 100, OrderSide::Bid, 42).with_attributes(attributes.clone())),
+            OrderCore::MarketOrder(MarketOrder::new(5, OrderSide::Ask, 43)),
+            OrderCore::StopLimitOrder(StopLimitOrder::new(12_000, 11_990, 7, OrderSide::Ask, 44)),
+            OrderCore::TrailingStopOrder(TrailingStopOrder::new(50, 7, OrderSide::Ask, 45)),
+            OrderCore::IfTouchedOrder(IfTouchedOrder::new(11_000, 7, OrderSide::Bid, 46)),
+        ];
+        for order_core in orders {
+            let request = OrderRequest::new(instid(), order_core, 1_000_000_000);
+            let message = mapping.new_order_single(&request).unwrap();
+            let parsed = mapping.order_request(&message, 1_000_000_000).unwrap();
+            assert_eq!(parsed.order_core, request.order_core);
+            assert_eq!(parsed.instid, instid());
+        }
+
+        let original = OrderRequest::new(
+            instid(),
+            OrderCore::LimitOrder(LimitOrder::new(12_350, 100, OrderSide::Bid, 42)),
+            0,
+        );
+        let text = mapping.new_order_single(&original).unwrap().to_string();
+        assert!(text.contains("|11=16|") && text.contains("|44=123.50|") && text.contains("|40=2|"), "{}", text);
+
+        let cancel = mapping.order_cancel_request(&original, 50, 0).unwrap();
+        let parsed = mapping.order_request(&cancel, 0).unwrap();
+        assert_eq!(parsed.order_core, OrderCore::CancelOrder(CancelOrder::new(42)));
+
+        let modify = ModifyOrder::new(42, 12_360, 80).with_new_order_id(51);
+        let replace = mapping.order_cancel_replace_request(&original, &modify, 0).unwrap();
+        let parsed = mapping.order_request(&replace, 0).unwrap();
+        assert_eq!(parsed.order_core, OrderCore::ModifyOrder(modify));
+        assert!(mapping.order_cancel_replace_request(&original, &ModifyOrder::new(42, 1, 1), 0).is_err());
+
+        let mut unknown = cancel.clone();
+        unknown.set(tags::SYMBOL, "000660");
+        assert_eq!(
+            mapping.order_request(&unknown, 0),
+            Err(FixError::Instrument(InstrumentError::UnknownCode {
+                code: "000660".to_string(),
+                venue: "KRX".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_execution_report_round_trips() {
+        let mapping = mapping();
+        let report = ExecutionReport::new(7, 42, instid(), ExecType::Trade)
+            .with_fill(12_350, 30)
+            .with_quantities(30, 70, 123.5)
+            .with_order_terms(Some(12_350), Some(100))
+            .with_times(1_709_214_307_123_000_000, 5);
+        let message = mapping.execution_report_message(&report, OrderSide::Bid).unwrap();
+        assert_eq!(message.get(tags::ORD_STATUS), Some("1"));
+        assert_eq!(mapping.execution_report(&message, 5, |_| None).unwrap(), report);
+
+        let cancel_rejected = ExecutionReport::new(8, 50, instid(), ExecType::CancelRejected)
+            .with_orig_order_id(42)
+            .with_reject_reason("too late")
+            .with_times(1_709_214_307_123_000_000, 5);
+        let message = mapping.execution_report_message(&cancel_rejected, OrderSide::Bid).unwrap();
+        assert_eq!(message.msg_type(), msg_type::ORDER_CANCEL_REJECT);
+        assert_eq!(message.get(tags::ORDER_ID), Some("42"));
+        assert_eq!(message.get(tags::CXL_REJ_RESPONSE_TO), Some("1"));
+        // no Symbol (55): the instrument is of the order
+        assert_eq!(message.get(tags::SYMBOL), None);
+        assert_eq!(mapping.execution_report(&message, 5, |_| None), Err(FixError::MissingField(tags::SYMBOL)));
+        let parsed = mapping.execution_report(&message, 5, |order_id| (order_id == 42).then(instid)).unwrap();
+        assert_eq!(parsed.instid, instid());
+        assert_eq!((parsed.order_id, parsed.exec_type), (42, ExecType::CancelRejected));
+        assert_eq!((parsed.exec_id, parsed.response_to), (0, Some(CancelRejectResponseTo::Cancel)));
+        assert_eq!(parsed.ord_status(), OrderStatus::Accepted);
+
+        let replace_rejected = cancel_rejected.clone().with_response_to(CancelRejectResponseTo::Replace);
+        let message = mapping.execution_report_message(&replace_rejected, OrderSide::Bid).unwrap();
+        assert_eq!(message.get(tags::CXL_REJ_RESPONSE_TO), Some("2"));
+        let parsed = mapping.execution_report(&message, 5, |_| Some(instid())).unwrap();
+        assert_eq!(parsed.response_to, Some(CancelRejectResponseTo::Replace));
+
+        // as a broker sends it, with a Symbol (55) the order is not looked up
+        let mut message = mapping.execution_report_message(&cancel_rejected, OrderSide::Bid).unwrap();
+        message.set(tags::SYMBOL, "005930");
+        message.set(tags::SECURITY_EXCHANGE, "KRX");
+        assert_eq!(mapping.execution_report(&message, 5, |_| None).unwrap().instid, instid());
+
+        assert_eq!(exec_id_of("123"), 123);
+        assert_eq!(exec_id_of("EX-1"), exec_id_of("EX-1"));
+        assert_ne!(exec_id_of("EX-1"), exec_id_of("EX-2"));
+    }
+}
@@ -0,0 +1,475 @@
+use crate::UnixNano;
+use crate::fix::error::FixError;
+use std::str::FromStr;
+
+pub const SOH: u8 = 0x01;
+pub const FIX_4_4: &str = "FIX.4.4";
+/// Largest BodyLength accepted, so a corrupted header cannot make the decoder buffer forever
+pub const MAX_BODY_LENGTH: usize = 1 << 20;
+
+pub mod tags {
+    pub const ACCOUNT: u32 = 1;
+    pub const AVG_PX: u32 = 6;
+    pub const BEGIN_SEQ_NO: u32 = 7;
+    pub const BEGIN_STRING: u32 = 8;
+    pub const BODY_LENGTH: u32 = 9;
+    pub const CHECK_SUM: u32 = 10;
+    pub const CL_ORD_ID: u32 = 11;
+    pub const CUM_QTY: u32 = 14;
+    pub const END_SEQ_NO: u32 = 16;
+    pub const EXEC_ID: u32 = 17;
+    pub const EXEC_INST: u32 = 18;
+    pub const LAST_PX: u32 = 31;
+    pub const LAST_QTY: u32 = 32;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const NEW_SEQ_NO: u32 = 36;
+    pub const ORDER_ID: u32 = 37;
+    pub const ORDER_QTY: u32 = 38;
+    pub const ORD_STATUS: u32 = 39;
+    pub const ORD_TYPE: u32 = 40;
+    pub const ORIG_CL_ORD_ID: u32 = 41;
+    pub const POSS_DUP_FLAG: u32 = 43;
+    pub const PRICE: u32 = 44;
+    pub const REF_SEQ_NUM: u32 = 45;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const SIDE: u32 = 54;
+    pub const SYMBOL: u32 = 55;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const TIME_IN_FORCE: u32 = 59;
+    pub const TRANSACT_TIME: u32 = 60;
+    pub const POSS_RESEND: u32 = 97;
+    pub const ENCRYPT_METHOD: u32 = 98;
+    pub const STOP_PX: u32 = 99;
+    pub const CXL_REJ_REASON: u32 = 102;
+    pub const ORD_REJ_REASON: u32 = 103;
+    pub const HEART_BT_INT: u32 = 108;
+    pub const MIN_QTY: u32 = 110;
+    pub const MAX_FLOOR: u32 = 111;
+    pub const TEST_REQ_ID: u32 = 112;
+    pub const ORIG_SENDING_TIME: u32 = 122;
+    pub const GAP_FILL_FLAG: u32 = 123;
+    pub const EXPIRE_TIME: u32 = 126;
+    pub const RESET_SEQ_NUM_FLAG: u32 = 141;
+    pub const EXEC_TYPE: u32 = 150;
+    pub const LEAVES_QTY: u32 = 151;
+    pub const SECURITY_EXCHANGE: u32 = 207;
+    pub const PEG_OFFSET_VALUE: u32 = 211;
+    pub const CXL_REJ_RESPONSE_TO: u32 = 434;
+}
+
+pub mod msg_type {
+    pub const HEARTBEAT: &str = "0";
+    pub const TEST_REQUEST: &str = "1";
+    pub const RESEND_REQUEST: &str = "2";
+    pub const REJECT: &str = "3";
+    pub const SEQUENCE_RESET: &str = "4";
+    pub const LOGOUT: &str = "5";
+    pub const EXECUTION_REPORT: &str = "8";
+    pub const ORDER_CANCEL_REJECT: &str = "9";
+    pub const LOGON: &str = "A";
+    pub const NEW_ORDER_SINGLE: &str = "D";
+    pub const ORDER_CANCEL_REQUEST: &str = "F";
+    pub const ORDER_CANCEL_REPLACE_REQUEST: &str = "G";
+
+    /// Session level messages, never resent (a gap fill covers them)
+    #[inline]
+    pub fn is_admin(msg_type: &str) -> bool {
+        matches!(msg_type, HEARTBEAT | TEST_REQUEST | RESEND_REQUEST | REJECT | SEQUENCE_RESET | LOGOUT | LOGON)
+    }
+}
+
+/// The standard header fields, written in this order right after MsgType
+const HEADER_TAGS: [u32; 7] = [
+    tags::SENDER_COMP_ID,
+    tags::TARGET_COMP_ID,
+    tags::MSG_SEQ_NUM,
+    tags::POSS_DUP_FLAG,
+    tags::POSS_RESEND,
+    tags::SENDING_TIME,
+    tags::ORIG_SENDING_TIME,
+];
+
+/// A FIX message without BeginString, BodyLength and CheckSum, which are written by `encode`
+/// and checked by `decode`. Fields keep their order, so repeating groups can be pushed as they are
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FixMessage {
+    fields: Vec<(u32, String)>,
+}
+
+impl FixMessage {
+    pub fn new(msg_type: &str) -> Self {
+        Self {
+            fields: vec![(tags::MSG_TYPE, msg_type.to_string())],
+        }
+    }
+
+    #[inline]
+    pub fn msg_type(&self) -> &str {
+        self.get(tags::MSG_TYPE).unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn is_admin(&self) -> bool {
+        msg_type::is_admin(self.msg_type())
+    }
+
+    #[inline]
+    pub fn fields(&self) -> &[(u32, String)] {
+        &self.fields
+    }
+
+    /// The first value of `tag`
+    #[inline]
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields.iter().find(|(t, _)| *t == tag).map(|(_, value)| value.as_str())
+    }
+
+    #[inline]
+    pub fn require(&self, tag: u32) -> Result<&str, FixError> {
+        self.get(tag).ok_or(FixError::MissingField(tag))
+    }
+
+    pub fn parse<T: FromStr>(&self, tag: u32) -> Result<Option<T>, FixError> {
+        match self.get(tag) {
+            Some(value) => value.parse().map(Some).map_err(|_| FixError::InvalidField {
+                tag,
+                value: value.to_string(),
+            }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn require_parsed<T: FromStr>(&self, tag: u32) -> Result<T, FixError> {
+        self.parse(tag)?.ok_or(FixError::MissingField(tag))
+    }
+
+    #[inline]
+    pub fn get_char(&self, tag: u32) -> Result<Option<char>, FixError> {
+        self.parse(tag)
+    }
+
+    /// Y/N fields, N when missing
+    #[inline]
+    pub fn get_flag(&self, tag: u32) -> bool {
+        self.get(tag) == Some("Y")
+    }
+
+    #[inline]
+    pub fn seq_num(&self) -> Option<u64> {
+        self.get(tags::MSG_SEQ_NUM).and_then(|value| value.parse().ok())
+    }
+
+    #[inline]
+    pub fn is_poss_dup(&self) -> bool {
+        self.get_flag(tags::POSS_DUP_FLAG)
+    }
+
+    /// Replaces the first value of `tag`, or appends it
+    pub fn set(&mut self, tag: u32, value: impl ToString) {
+        let value = value.to_string();
+        match self.fields.iter_mut().find(|(t, _)| *t == tag) {
+            Some(field) => field.1 = value,
+            None => self.fields.push((tag, value)),
+        }
+    }
+
+    #[inline]
+    pub fn with(mut self, tag: u32, value: impl ToString) -> Self {
+        self.set(tag, value);
+        self
+    }
+
+    /// Appends `tag` even if it is already set, e.g., in a repeating group
+    #[inline]
+    pub fn push(&mut self, tag: u32, value: impl ToString) {
+        self.fields.push((tag, value.to_string()));
+    }
+
+    pub fn remove(&mut self, tag: u32) -> Option<String> {
+        let index = self.fields.iter().position(|(t, _)| *t == tag)?;
+        Some(self.fields.remove(index).1)
+    }
+
+    pub fn encode(&self, begin_string: &str) -> Vec<u8> {
+        let mut body = Vec::with_capacity(128);
+        let mut write_field = |tag: u32, value: &str| {
+            body.extend_from_slice(tag.to_string().as_bytes());
+            body.push(b'=');
+            body.extend_from_slice(value.as_bytes());
+            body.push(SOH);
+        };
+        write_field(tags::MSG_TYPE, self.msg_type());
+        for tag in HEADER_TAGS {
+            if let Some(value) = self.get(tag) {
+                write_field(tag, value);
+            }
+        }
+        for (tag, value) in &self.fields {
+            if *tag != tags::MSG_TYPE && !HEADER_TAGS.contains(tag) {
+                write_field(*tag, value);
+            }
+        }
+
+        let mut out = Vec::with_capacity(body.len() + 32);
+        out.extend_from_slice(format!("8={}\x019={}\x01", begin_string, body.len()).as_bytes());
+        out.extend_from_slice(&body);
+        let checksum = checksum(&out);
+        out.extend_from_slice(format!("10={:03}\x01", checksum).as_bytes());
+        out
+    }
+
+    /// Decodes the first message in `buf`, returning it with its length in bytes,
+    /// or None if the message is not complete yet
+    pub fn decode(buf: &[u8], begin_string: &str) -> Result<Option<(FixMessage, usize)>, FixError> {
+        if !buf.starts_with(b"8=") {
+            return if b"8=".starts_with(buf) { Ok(None) } else { Err(FixError::BadHeader) };
+        }
+        let Some(begin_end) = buf.iter().position(|b| *b == SOH) else {
+            return Ok(None);
+        };
+        if &buf[2..begin_end] != begin_string.as_bytes() {
+            return Err(FixError::BadBeginString(String::from_utf8_lossy(&buf[2..begin_end]).into_owned()));
+        }
+        let rest = &buf[begin_end + 1..];
+        if !rest.starts_with(b"9=") {
+            return if b"9=".starts_with(rest) { Ok(None) } else { Err(FixError::BadHeader) };
+        }
+        let length_start = begin_end + 3;
+        let Some(length_end) = buf[length_start..].iter().position(|b| *b == SOH).map(|i| i + length_start) else {
+            return Ok(None);
+        };
+        let body_length: usize = std::str::from_utf8(&buf[length_start..length_end])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|length| *length <= MAX_BODY_LENGTH)
+            .ok_or(FixError::BadBodyLength)?;
+        let body_start = length_end + 1;
+        let body_end = body_start.checked_add(body_length).ok_or(FixError::BadBodyLength)?;
+        // 10=nnn|
+        let total = body_end.checked_add(7).ok_or(FixError::BadBodyLength)?;
+        if buf.len() < total {
+            return Ok(None);
+        }
+        let trailer = &buf[body_end..total];
+        if !trailer.starts_with(b"10=") || trailer[6] != SOH {
+            return Err(FixError::BadBodyLength);
+        }
+        let expected: u8 = std::str::from_utf8(&trailer[3..6])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| FixError::InvalidField {
+                tag: tags::CHECK_SUM,
+                value: String::from_utf8_lossy(&trailer[3..6]).into_owned(),
+            })?;
+        let computed = checksum(&buf[..body_end]);
+        if expected != computed {
+            return Err(FixError::BadChecksum { expected, computed });
+        }
+
+        let mut fields = Vec::new();
+        for field in buf[body_start..body_end].split(|b| *b == SOH).filter(|field| !field.is_empty()) {
+            let field = std::str::from_utf8(field).map_err(|_| FixError::BadField(String::from_utf8_lossy(field).into_owned()))?;
+            let (tag, value) = field.split_once('=').ok_or_else(|| FixError::BadField(field.to_string()))?;
+            let tag: u32 = tag.parse().map_err(|_| FixError::BadField(field.to_string()))?;
+            fields.push((tag, value.to_string()));
+        }
+        match fields.first() {
+            Some((tags::MSG_TYPE, _)) => Ok(Some((FixMessage { fields }, total))),
+            _ => Err(FixError::MissingField(tags::MSG_TYPE)),
+        }
+    }
+}
+
+/// Fields separated by '|' instead of SOH, for logs
+impl std::fmt::Display for FixMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (tag, value) in &self.fields {
+            write!(f, "{}={}|", tag, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+pub fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+/// Splits a byte stream into FIX messages
+#[derive(Debug, Clone, Default)]
+pub struct FixDecoder {
+    buffer: Vec<u8>,
+    begin_string: String,
+}
+
+impl FixDecoder {
+    pub fn new(begin_string: &str) -> Self {
+        Self {
+            buffer: Vec::with_capacity(4096),
+            begin_string: begin_string.to_string(),
+        }
+    }
+
+    #[inline]
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// The next complete message. A corrupted message is dropped up to the next BeginString
+    pub fn next_message(&mut self) -> Result<Option<FixMessage>, FixError> {
+        match FixMessage::decode(&self.buffer, &self.begin_string) {
+            Ok(Some((message, len))) => {
+                self.buffer.drain(..len);
+                Ok(Some(message))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                let next = self.buffer[1..].windows(2).position(|w| w == b"8=").map(|i| i + 1);
+                self.buffer.drain(..next.unwrap_or(self.buffer.len()));
+                Err(e)
+            }
+        }
+    }
+
+    #[inline]
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+/// UTCTimestamp with milliseconds: YYYYMMDD-HH:MM:SS.sss
+pub fn format_utc_timestamp(time: UnixNano) -> String {
+    let secs = time / 1_000_000_000;
+    let millis = (time / 1_000_000) % 1000;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs_of_day = secs % 86_400;
+    format!(
+        "{:04}{:02}{:02}-{:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        (secs_of_day / 60) % 60,
+        secs_of_day % 60,
+        millis
+    )
+}
+
+/// Parses a UTCTimestamp with or without fractional seconds (up to nanoseconds)
+pub fn parse_utc_timestamp(s: &str) -> Option<UnixNano> {
+    let bytes = s.as_bytes();
+    if bytes.len() < 17 || bytes[8] != b'-' || bytes[11] != b':' || bytes[14] != b':' {
+        return None;
+    }
+    let num = |range: std::ops::Range<usize>| s.get(range)?.parse::<u64>().ok();
+    let (year, month, day) = (num(0..4)?, num(4..6)?, num(6..8)?);
+    let (hour, minute, second) = (num(9..11)?, num(12..14)?, num(15..17)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let nanos = match s.get(17..) {
+        Some("") => 0,
+        Some(fraction) => {
+            let digits = fraction.strip_prefix('.')?;
+            if digits.is_empty() || digits.len() > 9 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            digits.parse::<u64>().ok()? * 10u64.pow(9 - digits.len() as u32)
+        }
+        None => return None,
+    };
+    let days = u64::try_from(days_from_civil(year as i64, month as u32, day as u32)).ok()?;
+    Some((days * 86_400 + hour * 3600 + minute * 60 + second) * 1_000_000_000 + nanos)
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let message = FixMessage::new(msg_type::NEW_ORDER_SINGLE)
+            .with(tags::CL_ORD_ID, 7)
+            .with(tags::MSG_SEQ_NUM, 3)
+            .with(tags::SENDER_COMP_ID, "CLIENT")
+            .with(tags::TARGET_COMP_ID, "BROKER")
+            .with(tags::SYMBOL, "005930");
+        let bytes = message.encode(FIX_4_4);
+        let text = String::from_utf8(bytes.clone()).unwrap().replace('\x01', "|");
+        assert!(text.starts_with("8=FIX.4.4|9=45|35=D|49=CLIENT|56=BROKER|34=3|11=7|55=005930|10="));
+
+        let (decoded, len) = FixMessage::decode(&bytes, FIX_4_4).unwrap().unwrap();
+        assert_eq!(len, bytes.len());
+        assert_eq!(decoded.msg_type(), "D");
+        assert_eq!(decoded.seq_num(), Some(3));
+        assert_eq!(decoded.require_parsed::<u64>(tags::CL_ORD_ID), Ok(7));
+        assert_eq!(decoded.get(tags::SYMBOL), Some("005930"));
+
+        // partial and corrupted input
+        assert_eq!(FixMessage::decode(&bytes[..bytes.len() - 1], FIX_4_4), Ok(None));
+        let mut corrupted = bytes.clone();
+        corrupted[20] ^= 1;
+        assert!(matches!(FixMessage::decode(&corrupted, FIX_4_4), Err(FixError::BadChecksum { .. })));
+
+        let mut decoder = FixDecoder::new(FIX_4_4);
+        decoder.extend(&corrupted);
+        decoder.extend(&bytes[..10]);
+        assert!(decoder.next_message().is_err());
+        assert_eq!(decoder.next_message(), Ok(None));
+        decoder.extend(&bytes[10..]);
+        assert_eq!(decoder.next_message(), Ok(Some(decoded)));
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn test_body_length_bounds() {
+        let huge = b"8=FIX.4.4\x019=18446744073709551615\x0135=0\x01";
+        assert_eq!(FixMessage::decode(huge, FIX_4_4), Err(FixError::BadBodyLength));
+        let over_cap = format!("8=FIX.4.4\x019={}\x0135=0\x01", MAX_BODY_LENGTH + 1);
+        assert_eq!(FixMessage::decode(over_cap.as_bytes(), FIX_4_4), Err(FixError::BadBodyLength));
+
+        let mut decoder = FixDecoder::new(FIX_4_4);
+        decoder.extend(huge);
+        assert_eq!(decoder.next_message(), Err(FixError::BadBodyLength));
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn test_utc_timestamp() {
+        // 2024-02-29 13:45:07.123
+        let time = 1_709_214_307_123_000_000;
+        assert_eq!(format_utc_timestamp(time), "20240229-13:45:07.123");
+        assert_eq!(parse_utc_timestamp("20240229-13:45:07.123"), Some(time));
+        assert_eq!(parse_utc_timestamp("20240229-13:45:07"), Some(time - 123_000_000));
+        assert_eq!(parse_utc_timestamp("20240229-13:45:07.123456789"), Some(time + 456_789));
+        assert_eq!(parse_utc_timestamp("20240229 13:45:07"), None);
+        assert_eq!(format_utc_timestamp(0), "19700101-00:00:00.000");
+    }
+}
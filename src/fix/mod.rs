@@ -0,0 +1,6 @@
+pub mod error;
+pub mod message;
+pub mod store;
+pub mod session;
+pub mod mapping;
+pub mod acceptor;
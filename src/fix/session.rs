@@ -0,0 +1,863 @@
+use crate::UnixNano;
+use crate::fix::error::FixError;
+use crate::fix::message::{format_utc_timestamp, msg_type, tags, FixDecoder, FixMessage, FIX_4_4};
+use crate::fix::store::MessageStore;
+use crate::tcp_client::TcpClient;
+use std::collections::BTreeMap;
+use std::net::ToSocketAddrs;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+/// Largest HeartBtInt accepted from a counterparty, in seconds
+pub const MAX_HEARTBEAT_INTERVAL: u64 = 3600;
+/// How long a logon or a logout waits for its answer when HeartBtInt is 0
+const LOGON_TIMEOUT: UnixNano = 10 * NANOS_PER_SEC;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionRole {
+    Initiator,
+    Acceptor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionState {
+    Disconnected,
+    LogonSent,
+    Active,
+    LogoutSent,
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionConfig {
+    pub begin_string: String,
+    pub sender_comp_id: String,
+    pub target_comp_id: String,
+    /// HeartBtInt in seconds, 0 for no heartbeats
+    pub heartbeat_interval: u64,
+    /// logon with ResetSeqNumFlag, starting both sequences from 1
+    pub reset_on_logon: bool,
+}
+
+impl SessionConfig {
+    pub fn new(sender_comp_id: &str, target_comp_id: &str) -> Self {
+        Self {
+            begin_string: FIX_4_4.to_string(),
+            sender_comp_id: sender_comp_id.to_string(),
+            target_comp_id: target_comp_id.to_string(),
+            heartbeat_interval: 30,
+            reset_on_logon: false,
+        }
+    }
+
+    pub fn with_heartbeat_interval(mut self, seconds: u64) -> Self {
+        self.heartbeat_interval = seconds;
+        self
+    }
+
+    pub fn with_reset_on_logon(mut self) -> Self {
+        self.reset_on_logon = true;
+        self
+    }
+
+    /// The name of the session in a `FileStore`
+    pub fn session_id(&self) -> String {
+        format!("{}-{}", self.sender_comp_id, self.target_comp_id)
+    }
+}
+
+/// The FIX session layer, independent of the transport.
+///
+/// Incoming bytes go to `on_bytes`, which returns the application messages (and Reject, Logout) in sequence,
+/// and the bytes to write are collected in an outbox taken with `take_outbox`.
+/// `on_timer` sends heartbeats and test requests, and detects a silent counterparty.
+///
+/// Messages received ahead of a gap are kept until the gap is filled by the resend of the counterparty.
+/// Resend requests are answered from the store, with a gap fill for the session messages
+#[derive(Debug)]
+pub struct FixSession<S: MessageStore> {
+    config: SessionConfig,
+    role: SessionRole,
+    state: SessionState,
+    store: S,
+    decoder: FixDecoder,
+    outbox: Vec<u8>,
+    /// received ahead of a gap, by MsgSeqNum
+    queued: BTreeMap<u64, FixMessage>,
+    resend_requested: bool,
+    /// TestReqID in flight and when it was sent
+    test_request: Option<(String, UnixNano)>,
+    last_sent: UnixNano,
+    last_received: UnixNano,
+}
+
+impl<S: MessageStore> FixSession<S> {
+    pub fn new(config: SessionConfig, role: SessionRole, store: S) -> Self {
+        let decoder = FixDecoder::new(&config.begin_string);
+        Self {
+            config,
+            role,
+            state: SessionState::Disconnected,
+            store,
+            decoder,
+            outbox: Vec::new(),
+            queued: BTreeMap::new(),
+            resend_requested: false,
+            test_request: None,
+            last_sent: 0,
+            last_received: 0,
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    #[inline]
+    pub fn role(&self) -> SessionRole {
+        self.role
+    }
+
+    #[inline]
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.state == SessionState::Active
+    }
+
+    #[inline]
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    #[inline]
+    pub fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    /// Bytes to write to the counterparty
+    #[inline]
+    pub fn take_outbox(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    #[inline]
+    pub fn has_outbox(&self) -> bool {
+        !self.outbox.is_empty()
+    }
+
+    /// Back to Disconnected for a new connection, keeping the sequence numbers of the store
+    pub fn reconnect(&mut self) {
+        self.state = SessionState::Disconnected;
+        self.decoder = FixDecoder::new(&self.config.begin_string);
+        self.outbox.clear();
+        self.queued.clear();
+        self.resend_requested = false;
+        self.test_request = None;
+    }
+
+    /// Skips `count` outgoing sequence numbers, so that the counterparty sees a gap
+    /// and asks for a resend (answered with a gap fill), e.g., in tests
+    pub fn skip_sequence(&mut self, count: u64) -> Result<(), FixError> {
+        let next = self.store.next_sender_seq() + count;
+        self.store.set_next_sender_seq(next)
+    }
+
+    /// Sends the Logon of an initiator
+    pub fn logon(&mut self, now: UnixNano) -> Result<(), FixError> {
+        if self.config.reset_on_logon {
+            self.store.reset()?;
+        }
+        let mut logon = FixMessage::new(msg_type::LOGON)
+            .with(tags::ENCRYPT_METHOD, 0)
+            .with(tags::HEART_BT_INT, self.config.heartbeat_interval);
+        if self.config.reset_on_logon {
+            logon.set(tags::RESET_SEQ_NUM_FLAG, "Y");
+        }
+        self.send_message(logon, now)?;
+        self.state = SessionState::LogonSent;
+        self.last_received = now;
+        Ok(())
+    }
+
+    pub fn logout(&mut self, text: Option<&str>, now: UnixNano) -> Result<(), FixError> {
+        let mut logout = FixMessage::new(msg_type::LOGOUT);
+        if let Some(text) = text {
+            logout.set(tags::TEXT, text);
+        }
+        self.send_message(logout, now)?;
+        self.state = SessionState::LogoutSent;
+        Ok(())
+    }
+
+    /// Sends an application message, returning its MsgSeqNum
+    pub fn send(&mut self, message: FixMessage, now: UnixNano) -> Result<u64, FixError> {
+        if !self.is_active() {
+            return Err(FixError::NotLoggedOn);
+        }
+        self.send_message(message, now)
+    }
+
+    fn send_message(&mut self, mut message: FixMessage, now: UnixNano) -> Result<u64, FixError> {
+        let seq = self.store.next_sender_seq();
+        self.stamp(&mut message, seq, now);
+        let bytes = message.encode(&self.config.begin_string);
+        // session messages are not resent, so they are not kept
+        if !message.is_admin() {
+            self.store.store(seq, &bytes)?;
+        }
+        self.store.set_next_sender_seq(seq + 1)?;
+        let logged = message.to_string();
+        flashlog::flash_debug!("FIX"; "sent {}", logged);
+        self.outbox.extend_from_slice(&bytes);
+        self.last_sent = now;
+        Ok(seq)
+    }
+
+    fn stamp(&self, message: &mut FixMessage, seq: u64, now: UnixNano) {
+        message.set(tags::SENDER_COMP_ID, &self.config.sender_comp_id);
+        message.set(tags::TARGET_COMP_ID, &self.config.target_comp_id);
+        message.set(tags::MSG_SEQ_NUM, seq);
+        message.set(tags::SENDING_TIME, format_utc_timestamp(now));
+    }
+
+    /// Feeds bytes from the counterparty. Garbled messages are dropped, as the spec requires
+    pub fn on_bytes(&mut self, bytes: &[u8], now: UnixNano) -> Result<Vec<FixMessage>, FixError> {
+        self.decoder.extend(bytes);
+        let mut delivered = Vec::new();
+        loop {
+            match self.decoder.next_message() {
+                Ok(Some(message)) => delivered.extend(self.on_message(message, now)?),
+                Ok(None) => return Ok(delivered),
+                Err(e) => {
+                    let logged = e.to_string();
+                    flashlog::flash_warn!("FIX"; "dropping a garbled message: {}", logged);
+                }
+            }
+        }
+    }
+
+    pub fn on_message(&mut self, message: FixMessage, now: UnixNano) -> Result<Vec<FixMessage>, FixError> {
+        let logged = message.to_string();
+        flashlog::flash_debug!("FIX"; "received {}", logged);
+        self.last_received = now;
+        if let Err(e) = self.check_comp_ids(&message) {
+            return Err(self.disconnect(e, now));
+        }
+
+        if message.msg_type() == msg_type::LOGON {
+            // checked before the logon is answered, unless the sequences are reset
+            let expected = self.store.next_target_seq();
+            let seq: u64 = message.require_parsed(tags::MSG_SEQ_NUM)?;
+            if seq < expected && !message.get_flag(tags::RESET_SEQ_NUM_FLAG) {
+                return Err(self.disconnect(FixError::SequenceTooLow { expected, received: seq }, now));
+            }
+            self.on_logon(&message, now)?;
+        } else if matches!(self.state, SessionState::Disconnected | SessionState::LogonSent) {
+            return Err(self.disconnect(FixError::NotLoggedOn, now));
+        }
+
+        // SequenceReset-Reset ignores MsgSeqNum
+        if message.msg_type() == msg_type::SEQUENCE_RESET && !message.get_flag(tags::GAP_FILL_FLAG) {
+            let new_seq: u64 = message.require_parsed(tags::NEW_SEQ_NO)?;
+            if new_seq < self.store.next_target_seq() {
+                flashlog::flash_warn!("FIX"; "ignoring a sequence reset back to {}", new_seq);
+            } else {
+                self.store.set_next_target_seq(new_seq)?;
+                self.queued.retain(|seq, _| *seq >= new_seq);
+            }
+            return self.drain_queue(now);
+        }
+
+        let seq: u64 = message.require_parsed(tags::MSG_SEQ_NUM)?;
+        let expected = self.store.next_target_seq();
+        if seq > expected {
+            if message.msg_type() == msg_type::RESEND_REQUEST {
+                // answered at once, the counterparty may be waiting for it to fill our gap
+                self.on_resend_request(&message, now)?;
+                self.queued.insert(seq, FixMessage::new(msg_type::HEARTBEAT).with(tags::MSG_SEQ_NUM, seq));
+            } else {
+                self.queued.insert(seq, message);
+            }
+            if !self.resend_requested {
+                let resend_request = FixMessage::new(msg_type::RESEND_REQUEST)
+                    .with(tags::BEGIN_SEQ_NO, expected)
+                    .with(tags::END_SEQ_NO, 0);
+                self.send_message(resend_request, now)?;
+                self.resend_requested = true;
+            }
+            return Ok(Vec::new());
+        }
+        if seq < expected {
+            if message.is_poss_dup() {
+                return Ok(Vec::new());
+            }
+            let e = FixError::SequenceTooLow { expected, received: seq };
+            return Err(self.disconnect(e, now));
+        }
+
+        let mut delivered = Vec::new();
+        self.process(message, now, &mut delivered)?;
+        delivered.extend(self.drain_queue(now)?);
+        Ok(delivered)
+    }
+
+    fn check_comp_ids(&self, message: &FixMessage) -> Result<(), FixError> {
+        for (tag, expected) in [
+            (tags::SENDER_COMP_ID, &self.config.target_comp_id),
+            (tags::TARGET_COMP_ID, &self.config.sender_comp_id),
+        ] {
+            let received = message.require(tag)?;
+            if received != expected {
+                return Err(FixError::CompIdMismatch {
+                    expected: expected.clone(),
+                    received: received.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn on_logon(&mut self, message: &FixMessage, now: UnixNano) -> Result<(), FixError> {
+        let reset = message.get_flag(tags::RESET_SEQ_NUM_FLAG);
+        match (self.role, self.state) {
+            (SessionRole::Acceptor, SessionState::Disconnected) => {
+                if reset {
+                    self.store.reset()?;
+                }
+                match message.parse::<u64>(tags::HEART_BT_INT) {
+                    Ok(Some(heartbeat_interval)) if heartbeat_interval <= MAX_HEARTBEAT_INTERVAL => {
+                        self.config.heartbeat_interval = heartbeat_interval;
+                    }
+                    Ok(None) => {}
+                    Ok(Some(heartbeat_interval)) => {
+                        let e = FixError::InvalidField {
+                            tag: tags::HEART_BT_INT,
+                            value: heartbeat_interval.to_string(),
+                        };
+                        return Err(self.disconnect(e, now));
+                    }
+                    Err(e) => return Err(self.disconnect(e, now)),
+                }
+                let mut logon = FixMessage::new(msg_type::LOGON)
+                    .with(tags::ENCRYPT_METHOD, 0)
+                    .with(tags::HEART_BT_INT, self.config.heartbeat_interval);
+                if reset {
+                    logon.set(tags::RESET_SEQ_NUM_FLAG, "Y");
+                }
+                self.send_message(logon, now)?;
+                self.state = SessionState::Active;
+            }
+            (SessionRole::Initiator, SessionState::LogonSent) => {
+                if reset {
+                    self.store.set_next_target_seq(1)?;
+                }
+                self.state = SessionState::Active;
+            }
+            // a logon resent in a gap fill, or a duplicate
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Processes `message`, which has the expected MsgSeqNum
+    fn process(&mut self, message: FixMessage, now: UnixNano, delivered: &mut Vec<FixMessage>) -> Result<(), FixError> {
+        let seq = message.seq_num().unwrap_or_else(|| self.store.next_target_seq());
+        let mut next_seq = seq + 1;
+        match message.msg_type() {
+            msg_type::HEARTBEAT => {
+                if let (Some(id), Some((pending, _))) = (message.get(tags::TEST_REQ_ID), &self.test_request) {
+                    if id == pending {
+                        self.test_request = None;
+                    }
+                }
+            }
+            msg_type::TEST_REQUEST => {
+                let id = message.require(tags::TEST_REQ_ID)?.to_string();
+                self.send_message(FixMessage::new(msg_type::HEARTBEAT).with(tags::TEST_REQ_ID, id), now)?;
+            }
+            msg_type::RESEND_REQUEST => self.on_resend_request(&message, now)?,
+            msg_type::SEQUENCE_RESET => {
+                let new_seq: u64 = message.require_parsed(tags::NEW_SEQ_NO)?;
+                next_seq = next_seq.max(new_seq);
+            }
+            msg_type::LOGON => {}
+            msg_type::LOGOUT => {
+                if self.state != SessionState::LogoutSent {
+                    self.store.set_next_target_seq(next_seq)?;
+                    self.send_message(FixMessage::new(msg_type::LOGOUT), now)?;
+                }
+                self.state = SessionState::Closed;
+                delivered.push(message);
+            }
+            _ => delivered.push(message),
+        }
+        self.store.set_next_target_seq(next_seq)?;
+        Ok(())
+    }
+
+    fn drain_queue(&mut self, now: UnixNano) -> Result<Vec<FixMessage>, FixError> {
+        let mut delivered = Vec::new();
+        loop {
+            let expected = self.store.next_target_seq();
+            self.queued.retain(|seq, _| *seq >= expected);
+            match self.queued.remove(&expected) {
+                Some(message) => self.process(message, now, &mut delivered)?,
+                None => break,
+            }
+        }
+        if self.queued.is_empty() {
+            self.resend_requested = false;
+        }
+        Ok(delivered)
+    }
+
+    fn on_resend_request(&mut self, message: &FixMessage, now: UnixNano) -> Result<(), FixError> {
+        let begin: u64 = message.require_parsed(tags::BEGIN_SEQ_NO)?;
+        let end: u64 = message.require_parsed(tags::END_SEQ_NO)?;
+        let last = self.store.next_sender_seq() - 1;
+        let end = if end == 0 || end > last { last } else { end };
+        flashlog::flash_info!("FIX"; "resending {} to {}", begin, end);
+
+        let stored = self.store.get(begin, end)?;
+        let mut gap_start = None;
+        let mut seq = begin;
+        for (stored_seq, bytes) in stored {
+            if stored_seq > seq {
+                gap_start.get_or_insert(seq);
+            }
+            if let Some(start) = gap_start.take() {
+                self.send_gap_fill(start, stored_seq, now);
+            }
+            let Some((mut resent, _)) = FixMessage::decode(&bytes, &self.config.begin_string)? else {
+                return Err(FixError::BadBodyLength);
+            };
+            if let Some(sending_time) = resent.get(tags::SENDING_TIME).map(str::to_string) {
+                resent.set(tags::ORIG_SENDING_TIME, sending_time);
+            }
+            resent.set(tags::POSS_DUP_FLAG, "Y");
+            resent.set(tags::SENDING_TIME, format_utc_timestamp(now));
+            self.outbox.extend_from_slice(&resent.encode(&self.config.begin_string));
+            seq = stored_seq + 1;
+        }
+        if seq <= end {
+            self.send_gap_fill(seq, end + 1, now);
+        }
+        self.last_sent = now;
+        Ok(())
+    }
+
+    /// SequenceReset-GapFill sent with MsgSeqNum `from`, moving the counterparty to `to`
+    fn send_gap_fill(&mut self, from: u64, to: u64, now: UnixNano) {
+        let mut gap_fill = FixMessage::new(msg_type::SEQUENCE_RESET)
+            .with(tags::POSS_DUP_FLAG, "Y")
+            .with(tags::GAP_FILL_FLAG, "Y")
+            .with(tags::NEW_SEQ_NO, to);
+        self.stamp(&mut gap_fill, from, now);
+        self.outbox.extend_from_slice(&gap_fill.encode(&self.config.begin_string));
+    }
+
+    /// Heartbeats, test requests and the timeout of a silent counterparty or of a logout.
+    /// With a HeartBtInt of 0, no heartbeat nor test request is sent
+    pub fn on_timer(&mut self, now: UnixNano) -> Result<(), FixError> {
+        self.store.sync()?;
+        let interval = self.config.heartbeat_interval.saturating_mul(NANOS_PER_SEC);
+        match self.state {
+            SessionState::Active if interval == 0 => {}
+            SessionState::Active => {
+                if let Some((_, sent_at)) = &self.test_request {
+                    if now.saturating_sub(*sent_at) >= interval {
+                        return Err(self.disconnect(FixError::HeartbeatTimeout, now));
+                    }
+                } else if now.saturating_sub(self.last_received) >= interval.saturating_add(interval / 5) {
+                    let id = now.to_string();
+                    self.send_message(FixMessage::new(msg_type::TEST_REQUEST).with(tags::TEST_REQ_ID, &id), now)?;
+                    self.test_request = Some((id, now));
+                }
+                if now.saturating_sub(self.last_sent) >= interval {
+                    self.send_message(FixMessage::new(msg_type::HEARTBEAT), now)?;
+                }
+            }
+            SessionState::LogonSent | SessionState::LogoutSent => {
+                let timeout = if interval == 0 { LOGON_TIMEOUT } else { interval };
+                if now.saturating_sub(self.last_sent) >= timeout {
+                    self.state = SessionState::Closed;
+                    return Err(FixError::HeartbeatTimeout);
+                }
+            }
+            SessionState::Disconnected | SessionState::Closed => {}
+        }
+        Ok(())
+    }
+
+    /// Logs out because of `error`, which is returned
+    fn disconnect(&mut self, error: FixError, now: UnixNano) -> FixError {
+        let text = error.to_string();
+        let logout = FixMessage::new(msg_type::LOGOUT).with(tags::TEXT, &text);
+        flashlog::flash_error!("FIX"; "disconnecting: {}", text);
+        if matches!(self.state, SessionState::Active | SessionState::LogonSent) {
+            if let Err(e) = self.send_message(logout, now) {
+                let logged = e.to_string();
+                flashlog::flash_error!("FIX"; "failed to send a logout: {}", logged);
+            }
+        }
+        self.state = SessionState::Closed;
+        error
+    }
+}
+
+/// A FIX initiator over `TcpClient`
+pub struct FixClient<S: MessageStore> {
+    tcp: TcpClient,
+    session: FixSession<S>,
+    buffer: Vec<u8>,
+}
+
+impl<S: MessageStore> FixClient<S> {
+    /// `recv` waits at most this long in `poll`
+    pub const IDLE_TIMEOUT: UnixNano = 1_000_000;
+
+    pub fn connect<A: ToSocketAddrs>(address: A, config: SessionConfig, store: S) -> Result<Self, FixError> {
+        let tcp = TcpClient::new(address, Some(Self::IDLE_TIMEOUT))?;
+        Ok(Self::new(tcp, FixSession::new(config, SessionRole::Initiator, store)))
+    }
+
+    pub fn new(tcp: TcpClient, session: FixSession<S>) -> Self {
+        Self {
+            tcp,
+            session,
+            buffer: vec![0; 65536],
+        }
+    }
+
+    #[inline]
+    pub fn session(&self) -> &FixSession<S> {
+        &self.session
+    }
+
+    #[inline]
+    pub fn session_mut(&mut self) -> &mut FixSession<S> {
+        &mut self.session
+    }
+
+    pub fn logon(&mut self, now: UnixNano) -> Result<(), FixError> {
+        self.session.logon(now)?;
+        self.flush()
+    }
+
+    pub fn logout(&mut self, text: Option<&str>, now: UnixNano) -> Result<(), FixError> {
+        self.session.logout(text, now)?;
+        self.flush()
+    }
+
+    pub fn send(&mut self, message: FixMessage, now: UnixNano) -> Result<u64, FixError> {
+        let seq = self.session.send(message, now)?;
+        self.flush()?;
+        Ok(seq)
+    }
+
+    /// Reads what is available, runs the timers and writes the replies.
+    /// Returns the application messages received in sequence
+    pub fn poll(&mut self, now: UnixNano) -> Result<Vec<FixMessage>, FixError> {
+        let delivered = match self.tcp.recv(&mut self.buffer) {
+            Ok(Some(size)) => self.session.on_bytes(&self.buffer[..size], now),
+            Ok(None) => Ok(Vec::new()),
+            Err(e) => {
+                self.session.state = SessionState::Closed;
+                Err(e.into())
+            }
+        };
+        let result = delivered.and_then(|messages| self.session.on_timer(now).map(|_| messages));
+        // a logout sent on an error is still written
+        let flushed = self.flush();
+        let messages = result?;
+        flushed?;
+        Ok(messages)
+    }
+
+    fn flush(&mut self) -> Result<(), FixError> {
+        if self.session.has_outbox() {
+            let bytes = self.session.take_outbox();
+            if let Err(e) = self.tcp.send_all(&bytes) {
+                // a partly written message leaves the stream unusable
+                self.session.state = SessionState::Closed;
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::store::MemoryStore;
+
+    const SEC: UnixNano = NANOS_PER_SEC;
+
+    fn pair() -> (FixSession<MemoryStore>, FixSession<MemoryStore>) {
+        let initiator = FixSession::new(
+            SessionConfig::new("CLIENT", "BROKER").with_heartbeat_interval(10),
+            SessionRole::Initiator,
+            MemoryStore::new(),
+        );
+        let acceptor = FixSession::new(SessionConfig::new("BROKER", "CLIENT"), SessionRole::Acceptor, MemoryStore::new());
+        (initiator, acceptor)
+    }
+
+    fn exchange(from: &mut FixSession<MemoryStore>, to: &mut FixSession<MemoryStore>, now: UnixNano) -> Vec<FixMessage> {
+        let bytes = from.take_outbox();
+        to.on_bytes(&bytes, now).unwrap()
+    }
+
+    #[test]
+    fn test_logon_heartbeat_and_logout() {
+        let (mut initiator, mut acceptor) = pair();
+        initiator.logon(0).unwrap();
+        assert_eq!(initiator.state(), SessionState::LogonSent);
+        exchange(&mut initiator, &mut acceptor, 0);
+        assert!(acceptor.is_active());
+        // the acceptor adopts the heartbeat interval of the initiator
+        assert_eq!(acceptor.config().heartbeat_interval, 10);
+        exchange(&mut acceptor, &mut initiator, 0);
+        assert!(initiator.is_active());
+
+        // silence: heartbeat, then a test request answered by the acceptor
+        initiator.on_timer(10 * SEC).unwrap();
+        initiator.on_timer(12 * SEC).unwrap();
+        let sent = initiator.take_outbox();
+        let text = String::from_utf8_lossy(&sent).replace('\x01', "|");
+        assert!(text.contains("35=0|") && text.contains("35=1|"), "{}", text);
+        acceptor.on_bytes(&sent, 12 * SEC).unwrap();
+        exchange(&mut acceptor, &mut initiator, 12 * SEC);
+        assert!(initiator.on_timer(20 * SEC).is_ok());
+
+        let order = FixMessage::new(msg_type::NEW_ORDER_SINGLE).with(tags::CL_ORD_ID, 1);
+        assert_eq!(initiator.send(order, 20 * SEC).unwrap(), 4);
+        let delivered = exchange(&mut initiator, &mut acceptor, 20 * SEC);
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0].get(tags::CL_ORD_ID), Some("1"));
+
+        initiator.logout(None, 21 * SEC).unwrap();
+        exchange(&mut initiator, &mut acceptor, 21 * SEC);
+        assert_eq!(acceptor.state(), SessionState::Closed);
+        exchange(&mut acceptor, &mut initiator, 21 * SEC);
+        assert_eq!(initiator.state(), SessionState::Closed);
+    }
+
+    #[test]
+    fn test_gap_fill_and_resend() {
+        let (mut initiator, mut acceptor) = pair();
+        initiator.logon(0).unwrap();
+        exchange(&mut initiator, &mut acceptor, 0);
+        exchange(&mut acceptor, &mut initiator, 0);
+
+        // the acceptor sends 2 (app), 3 (heartbeat) and 4 (app), and 2 and 3 are lost
+        let report = |id: u64| FixMessage::new(msg_type::EXECUTION_REPORT).with(tags::EXEC_ID, id);
+        acceptor.send(report(1), SEC).unwrap();
+        acceptor.send_message(FixMessage::new(msg_type::HEARTBEAT), 2 * SEC).unwrap();
+        acceptor.take_outbox();
+        acceptor.send(report(2), 41 * SEC).unwrap();
+        assert!(exchange(&mut acceptor, &mut initiator, 41 * SEC).is_empty());
+
+        // the initiator asks for 2.., the acceptor resends 2 and gap fills 3
+        let resend_request = initiator.take_outbox();
+        assert!(String::from_utf8_lossy(&resend_request).contains("\x0135=2\x01"));
+        acceptor.on_bytes(&resend_request, 42 * SEC).unwrap();
+        let delivered = exchange(&mut acceptor, &mut initiator, 42 * SEC);
+        let exec_ids: Vec<_> = delivered.iter().map(|m| m.get(tags::EXEC_ID).unwrap()).collect();
+        assert_eq!(exec_ids, vec!["1", "2"]);
+        assert!(delivered[0].is_poss_dup());
+        assert_eq!(initiator.store().next_target_seq(), 5);
+
+        // a duplicate is ignored, a lower MsgSeqNum without PossDupFlag disconnects
+        let mut stale = report(3);
+        acceptor.stamp(&mut stale, 2, 43 * SEC);
+        assert!(matches!(
+            initiator.on_message(stale, 43 * SEC),
+            Err(FixError::SequenceTooLow { expected: 5, received: 2 })
+        ));
+        assert_eq!(initiator.state(), SessionState::Closed);
+    }
+
+    fn logged_on() -> (FixSession<MemoryStore>, FixSession<MemoryStore>) {
+        let (mut initiator, mut acceptor) = pair();
+        initiator.logon(0).unwrap();
+        exchange(&mut initiator, &mut acceptor, 0);
+        exchange(&mut acceptor, &mut initiator, 0);
+        (initiator, acceptor)
+    }
+
+    fn decode_all(bytes: &[u8]) -> Vec<FixMessage> {
+        let mut decoder = FixDecoder::new(FIX_4_4);
+        decoder.extend(bytes);
+        std::iter::from_fn(|| decoder.next_message().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_heartbeat_interval_zero() {
+        let mut initiator = FixSession::new(
+            SessionConfig::new("CLIENT", "BROKER").with_heartbeat_interval(0),
+            SessionRole::Initiator,
+            MemoryStore::new(),
+        );
+        let mut acceptor = FixSession::new(SessionConfig::new("BROKER", "CLIENT"), SessionRole::Acceptor, MemoryStore::new());
+        initiator.logon(0).unwrap();
+        exchange(&mut initiator, &mut acceptor, 0);
+        exchange(&mut acceptor, &mut initiator, 0);
+        assert_eq!(acceptor.config().heartbeat_interval, 0);
+
+        // no heartbeat, no test request and no timeout
+        for now in [SEC, 100 * SEC, 100_000 * SEC] {
+            initiator.on_timer(now).unwrap();
+            acceptor.on_timer(now).unwrap();
+        }
+        assert!(initiator.is_active() && acceptor.is_active());
+        assert!(!initiator.has_outbox() && !acceptor.has_outbox());
+
+        // an unanswered logout still times out
+        initiator.logout(None, 100_000 * SEC).unwrap();
+        initiator.on_timer(100_000 * SEC + LOGON_TIMEOUT - 1).unwrap();
+        assert_eq!(initiator.on_timer(100_000 * SEC + LOGON_TIMEOUT), Err(FixError::HeartbeatTimeout));
+    }
+
+    #[test]
+    fn test_heartbeat_interval_too_large() {
+        let huge = 100_000_000_000;
+        let mut initiator = FixSession::new(
+            SessionConfig::new("CLIENT", "BROKER").with_heartbeat_interval(huge),
+            SessionRole::Initiator,
+            MemoryStore::new(),
+        );
+        let mut acceptor = FixSession::new(SessionConfig::new("BROKER", "CLIENT"), SessionRole::Acceptor, MemoryStore::new());
+        initiator.logon(0).unwrap();
+        // the own interval saturates instead of overflowing
+        initiator.on_timer(1_000_000 * SEC).unwrap();
+
+        let bytes = initiator.take_outbox();
+        assert_eq!(
+            acceptor.on_bytes(&bytes, 0),
+            Err(FixError::InvalidField {
+                tag: tags::HEART_BT_INT,
+                value: huge.to_string()
+            })
+        );
+        assert_eq!(acceptor.state(), SessionState::Closed);
+        assert_eq!(acceptor.config().heartbeat_interval, 30);
+    }
+
+    #[test]
+    fn test_sequence_reset() {
+        let (mut initiator, mut acceptor) = logged_on();
+        let reset = |new_seq: u64| FixMessage::new(msg_type::SEQUENCE_RESET).with(tags::NEW_SEQ_NO, new_seq);
+
+        // SequenceReset-Reset moves the expected MsgSeqNum, whatever its own MsgSeqNum
+        acceptor.send_message(reset(10), SEC).unwrap();
+        assert!(exchange(&mut acceptor, &mut initiator, SEC).is_empty());
+        assert_eq!(initiator.store().next_target_seq(), 10);
+
+        acceptor.skip_sequence(7).unwrap();
+        acceptor.send(FixMessage::new(msg_type::EXECUTION_REPORT).with(tags::EXEC_ID, 1), SEC).unwrap();
+        assert_eq!(exchange(&mut acceptor, &mut initiator, SEC).len(), 1);
+        assert_eq!(initiator.store().next_target_seq(), 11);
+
+        // a reset backwards is ignored
+        acceptor.send_message(reset(5), SEC).unwrap();
+        assert!(exchange(&mut acceptor, &mut initiator, SEC).is_empty());
+        assert_eq!(initiator.store().next_target_seq(), 11);
+        assert!(initiator.is_active());
+    }
+
+    #[test]
+    fn test_resend_spans_stored_and_admin_messages() {
+        let (mut initiator, mut acceptor) = logged_on();
+        let report = |id: u64| FixMessage::new(msg_type::EXECUTION_REPORT).with(tags::EXEC_ID, id);
+
+        // 2, 4 and 6 are heartbeats, 3, 5 and 7 are reports, and all but 7 are lost
+        for id in 1..=3 {
+            acceptor.send_message(FixMessage::new(msg_type::HEARTBEAT), SEC).unwrap();
+            if id < 3 {
+                acceptor.send(report(id), SEC).unwrap();
+            }
+        }
+        acceptor.take_outbox();
+        acceptor.send(report(3), SEC).unwrap();
+        assert!(exchange(&mut acceptor, &mut initiator, SEC).is_empty());
+
+        let resend_request = initiator.take_outbox();
+        acceptor.on_bytes(&resend_request, 2 * SEC).unwrap();
+        let resent = acceptor.take_outbox();
+        let sequence: Vec<_> = decode_all(&resent)
+            .iter()
+            .map(|m| (m.msg_type().to_string(), m.seq_num().unwrap(), m.get(tags::NEW_SEQ_NO).map(str::to_string)))
+            .collect();
+        let gap_fill = |seq: u64, to: &str| (msg_type::SEQUENCE_RESET.to_string(), seq, Some(to.to_string()));
+        let resent_report = |seq: u64| (msg_type::EXECUTION_REPORT.to_string(), seq, None);
+        assert_eq!(
+            sequence,
+            vec![gap_fill(2, "3"), resent_report(3), gap_fill(4, "5"), resent_report(5), gap_fill(6, "7"), resent_report(7)]
+        );
+
+        let delivered = initiator.on_bytes(&resent, 2 * SEC).unwrap();
+        let exec_ids: Vec<_> = delivered.iter().map(|m| m.get(tags::EXEC_ID).unwrap()).collect();
+        assert_eq!(exec_ids, vec!["1", "2", "3"]);
+        assert_eq!(initiator.store().next_target_seq(), 8);
+    }
+
+    #[test]
+    fn test_client_with_local_acceptor() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let acceptor = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut session = FixSession::new(SessionConfig::new("BROKER", "CLIENT"), SessionRole::Acceptor, MemoryStore::new());
+            let mut buffer = [0u8; 4096];
+            let mut received = Vec::new();
+            while session.state() != SessionState::Closed {
+                let size = stream.read(&mut buffer).unwrap();
+                if size == 0 {
+                    break;
+                }
+                for message in session.on_bytes(&buffer[..size], 0).unwrap() {
+                    if message.msg_type() == msg_type::NEW_ORDER_SINGLE {
+                        let cl_ord_id = message.get(tags::CL_ORD_ID).unwrap().to_string();
+                        let ack = FixMessage::new(msg_type::EXECUTION_REPORT).with(tags::CL_ORD_ID, cl_ord_id);
+                        session.send(ack, 0).unwrap();
+                    }
+                    received.push(message.msg_type().to_string());
+                }
+                stream.write_all(&session.take_outbox()).unwrap();
+            }
+            received
+        });
+
+        let config = SessionConfig::new("CLIENT", "BROKER").with_reset_on_logon();
+        let mut client = FixClient::connect(address, config, MemoryStore::new()).unwrap();
+        client.logon(0).unwrap();
+        let mut now = 0;
+        while !client.session().is_active() {
+            now += 1;
+            client.poll(now).unwrap();
+        }
+        client.send(FixMessage::new(msg_type::NEW_ORDER_SINGLE).with(tags::CL_ORD_ID, 9), now).unwrap();
+        let mut delivered = Vec::new();
+        while delivered.is_empty() {
+            now += 1;
+            delivered = client.poll(now).unwrap();
+        }
+        assert_eq!(delivered[0].msg_type(), msg_type::EXECUTION_REPORT);
+        assert_eq!(delivered[0].get(tags::CL_ORD_ID), Some("9"));
+
+        client.logout(None, now).unwrap();
+        while client.session().state() != SessionState::Closed {
+            now += 1;
+            client.poll(now).unwrap();
+        }
+        assert_eq!(acceptor.join().unwrap(), vec![msg_type::NEW_ORDER_SINGLE, msg_type::LOGOUT]);
+    }
+}
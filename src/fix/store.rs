@@ -0,0 +1,407 @@
+use crate::fix::error::FixError;
+use crate::order::journal::crc32;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Sequence numbers and sent messages of a FIX session, kept for resend requests
+pub trait MessageStore {
+    fn next_sender_seq(&self) -> u64;
+    fn next_target_seq(&self) -> u64;
+    fn set_next_sender_seq(&mut self, seq: u64) -> Result<(), FixError>;
+    fn set_next_target_seq(&mut self, seq: u64) -> Result<(), FixError>;
+    /// Keeps the encoded message sent with `seq`
+    fn store(&mut self, seq: u64, message: &[u8]) -> Result<(), FixError>;
+    /// The sent messages in `begin..=end`, in order
+    fn get(&self, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>, FixError>;
+    /// Back to sequence number 1 with no message, e.g., on a logon with ResetSeqNumFlag
+    fn reset(&mut self) -> Result<(), FixError>;
+    /// Makes the updates so far durable
+    fn sync(&mut self) -> Result<(), FixError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    next_sender_seq: u64,
+    next_target_seq: u64,
+    messages: BTreeMap<u64, Vec<u8>>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self {
+            next_sender_seq: 1,
+            next_target_seq: 1,
+            messages: BTreeMap::new(),
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MessageStore for MemoryStore {
+    #[inline]
+    fn next_sender_seq(&self) -> u64 {
+        self.next_sender_seq
+    }
+
+    #[inline]
+    fn next_target_seq(&self) -> u64 {
+        self.next_target_seq
+    }
+
+    fn set_next_sender_seq(&mut self, seq: u64) -> Result<(), FixError> {
+        self.next_sender_seq = seq;
+        Ok(())
+    }
+
+    fn set_next_target_seq(&mut self, seq: u64) -> Result<(), FixError> {
+        self.next_target_seq = seq;
+        Ok(())
+    }
+
+    fn store(&mut self, seq: u64, message: &[u8]) -> Result<(), FixError> {
+        self.messages.insert(seq, message.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>, FixError> {
+        if begin > end {
+            return Ok(Vec::new());
+        }
+        Ok(self.messages.range(begin..=end).map(|(seq, message)| (*seq, message.clone())).collect())
+    }
+
+    fn reset(&mut self) -> Result<(), FixError> {
+        *self = Self::default();
+        Ok(())
+    }
+}
+
+/// Keeps the sequence numbers in `<dir>/<session>.seqnums` and the sent messages in `<dir>/<session>.body`,
+/// so that a restarted session resumes its sequence numbers and can still answer resend requests.
+///
+/// A body record is `[seq u64 LE][len u32 LE][crc32 of message u32 LE][crc32 of the 16 bytes before u32 LE][message]`. Each change of the sequence numbers appends
+/// `[next sender u64 LE][next target u64 LE]` to the seqnums file, which is compacted into a single record
+/// (written to a temporary file, synced and renamed) every `SEQNUMS_COMPACT_RECORDS` changes.
+/// Both files are synced by `sync`, so a machine crash loses at most the updates since the last `sync`.
+/// A record cut short by a crash is dropped on open, in either file. A damaged body record followed by
+/// more data fails `open` with `CorruptStore` instead of dropping the messages after it
+#[derive(Debug)]
+pub struct FileStore {
+    seqnums_path: PathBuf,
+    seqnums: File,
+    seqnums_records: usize,
+    body: File,
+    memory: MemoryStore,
+    dirty: bool,
+}
+
+const BODY_HEADER_LEN: usize = 20;
+const SEQNUMS_RECORD_LEN: usize = 16;
+const SEQNUMS_COMPACT_RECORDS: usize = 4096;
+
+impl FileStore {
+    pub fn open(dir: impl AsRef<Path>, session: &str) -> Result<Self, FixError> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let seqnums_path = dir.join(format!("{}.seqnums", session));
+        let body_path = dir.join(format!("{}.body", session));
+
+        let mut memory = MemoryStore::default();
+        let bytes = read_or_empty(&seqnums_path)?;
+        let records = bytes.len() / SEQNUMS_RECORD_LEN;
+        if records > 0 {
+            let last = &bytes[(records - 1) * SEQNUMS_RECORD_LEN..records * SEQNUMS_RECORD_LEN];
+            memory.next_sender_seq = u64::from_le_bytes(last[..8].try_into().expect("8 bytes"));
+            memory.next_target_seq = u64::from_le_bytes(last[8..].try_into().expect("8 bytes"));
+        }
+        if bytes.len() % SEQNUMS_RECORD_LEN != 0 {
+            let logged = seqnums_path.display().to_string();
+            flashlog::flash_warn!("FIX"; "dropping a torn sequence number record of {}", logged);
+        }
+
+        let bytes = read_or_empty(&body_path)?;
+        let offset = scan_body(&bytes, &mut memory)?;
+        if offset < bytes.len() {
+            let logged = body_path.display().to_string();
+            flashlog::flash_warn!("FIX"; "dropping a torn record at {} of {}", offset, logged);
+        }
+        // a stored message was sent, even if the sequence numbers written after it were lost
+        if let Some(last) = memory.messages.keys().next_back() {
+            memory.next_sender_seq = memory.next_sender_seq.max(last + 1);
+        }
+        let body = OpenOptions::new().create(true).write(true).truncate(false).open(&body_path)?;
+        body.set_len(offset as u64)?;
+        let seqnums = OpenOptions::new().create(true).write(true).truncate(false).open(&seqnums_path)?;
+        let mut store = Self {
+            seqnums_path,
+            seqnums,
+            seqnums_records: 0,
+            body,
+            memory,
+            dirty: false,
+        };
+        store.compact_seqnums()?;
+        store.body.sync_all()?;
+        Ok(store)
+    }
+
+    fn append_seqnums(&mut self) -> Result<(), FixError> {
+        if self.seqnums_records >= SEQNUMS_COMPACT_RECORDS {
+            return self.compact_seqnums();
+        }
+        self.seqnums.seek(SeekFrom::End(0))?;
+        self.seqnums.write_all(&self.seqnums_record())?;
+        self.seqnums_records += 1;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Replaces the seqnums file with a single record, through a synced temporary file
+    fn compact_seqnums(&mut self) -> Result<(), FixError> {
+        let tmp = self.seqnums_path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&self.seqnums_record())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.seqnums_path)?;
+        self.seqnums = OpenOptions::new().write(true).open(&self.seqnums_path)?;
+        self.seqnums_records = 1;
+        Ok(())
+    }
+
+    fn seqnums_record(&self) -> [u8; SEQNUMS_RECORD_LEN] {
+        let mut record = [0; SEQNUMS_RECORD_LEN];
+        record[..8].copy_from_slice(&self.memory.next_sender_seq.to_le_bytes());
+        record[8..].copy_from_slice(&self.memory.next_target_seq.to_le_bytes());
+        record
+    }
+}
+
+/// Loads the body records into `memory` and returns the length of the valid prefix.
+/// Only a tail that a crash can leave behind is left out of it
+fn scan_body(bytes: &[u8], memory: &mut MemoryStore) -> Result<usize, FixError> {
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let rest = &bytes[offset..];
+        if rest.len() < BODY_HEADER_LEN {
+            break;
+        }
+        let word = |i: usize| u32::from_le_bytes(rest[i..i + 4].try_into().expect("4 bytes"));
+        if crc32(&rest[..16]) != word(16) {
+            // an appended record the crash left as zeros
+            if rest.iter().all(|b| *b == 0) {
+                break;
+            }
+            return Err(FixError::CorruptStore { offset: offset as u64 });
+        }
+        let seq = u64::from_le_bytes(rest[..8].try_into().expect("8 bytes"));
+        let (len, crc) = (word(8) as usize, word(12));
+        let end = offset + BODY_HEADER_LEN + len;
+        if end > bytes.len() {
+            break;
+        }
+        let message = &bytes[offset + BODY_HEADER_LEN..end];
+        if crc32(message) != crc {
+            // the last record may be cut short after its length was written
+            if end == bytes.len() {
+                break;
+            }
+            return Err(FixError::CorruptStore { offset: offset as u64 });
+        }
+        memory.messages.insert(seq, message.to_vec());
+        offset = end;
+    }
+    Ok(offset)
+}
+
+fn read_or_empty(path: &Path) -> Result<Vec<u8>, FixError> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(bytes),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl MessageStore for FileStore {
+    #[inline]
+    fn next_sender_seq(&self) -> u64 {
+        self.memory.next_sender_seq
+    }
+
+    #[inline]
+    fn next_target_seq(&self) -> u64 {
+        self.memory.next_target_seq
+    }
+
+    fn set_next_sender_seq(&mut self, seq: u64) -> Result<(), FixError> {
+        if seq == self.memory.next_sender_seq {
+            return Ok(());
+        }
+        self.memory.next_sender_seq = seq;
+        self.append_seqnums()
+    }
+
+    fn set_next_target_seq(&mut self, seq: u64) -> Result<(), FixError> {
+        if seq == self.memory.next_target_seq {
+            return Ok(());
+        }
+        self.memory.next_target_seq = seq;
+        self.append_seqnums()
+    }
+
+    fn store(&mut self, seq: u64, message: &[u8]) -> Result<(), FixError> {
+        let len = u32::try_from(message.len()).map_err(|_| FixError::BadBodyLength)?;
+        let mut record = Vec::with_capacity(BODY_HEADER_LEN + message.len());
+        record.extend_from_slice(&seq.to_le_bytes());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&crc32(message).to_le_bytes());
+        let header_crc = crc32(&record);
+        record.extend_from_slice(&header_crc.to_le_bytes());
+        record.extend_from_slice(message);
+        // the file is not opened in append mode, so that `open` and `reset` can truncate it
+        self.body.seek(SeekFrom::End(0))?;
+        self.body.write_all(&record)?;
+        self.dirty = true;
+        self.memory.store(seq, message)
+    }
+
+    fn get(&self, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>, FixError> {
+        self.memory.get(begin, end)
+    }
+
+    fn reset(&mut self) -> Result<(), FixError> {
+        self.memory.reset()?;
+        self.body.set_len(0)?;
+        self.body.sync_all()?;
+        self.compact_seqnums()
+    }
+
+    fn sync(&mut self) -> Result<(), FixError> {
+        if self.dirty {
+            self.body.sync_data()?;
+            self.seqnums.sync_data()?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_store_resumes() {
+        let dir = std::env::temp_dir().join(format!("fix_store_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        {
+            let mut store = FileStore::open(&dir, "CLIENT-BROKER").unwrap();
+            assert_eq!((store.next_sender_seq(), store.next_target_seq()), (1, 1));
+            store.store(1, b"first").unwrap();
+            store.store(2, b"second").unwrap();
+            store.set_next_sender_seq(3).unwrap();
+            store.set_next_target_seq(5).unwrap();
+        }
+        // a torn record
+        let body_path = dir.join("CLIENT-BROKER.body");
+        let mut bytes = std::fs::read(&body_path).unwrap();
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        std::fs::write(&body_path, bytes).unwrap();
+
+        let mut store = FileStore::open(&dir, "CLIENT-BROKER").unwrap();
+        assert_eq!((store.next_sender_seq(), store.next_target_seq()), (3, 5));
+        assert_eq!(store.get(2, 10).unwrap(), vec![(2, b"second".to_vec())]);
+        store.store(3, b"third").unwrap();
+        assert_eq!(store.get(1, 3).unwrap().len(), 3);
+
+        store.reset().unwrap();
+        assert_eq!(store.next_sender_seq(), 1);
+        assert!(store.get(1, 10).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_store_torn_seqnums() {
+        let dir = std::env::temp_dir().join(format!("fix_store_torn_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        {
+            let mut store = FileStore::open(&dir, "CLIENT-BROKER").unwrap();
+            for seq in 2..=SEQNUMS_COMPACT_RECORDS as u64 + 10 {
+                store.set_next_target_seq(seq).unwrap();
+            }
+            store.store(1, b"first").unwrap();
+            store.set_next_sender_seq(2).unwrap();
+            store.sync().unwrap();
+        }
+        // compacted on the way, then a record cut short by a crash
+        let seqnums_path = dir.join("CLIENT-BROKER.seqnums");
+        let mut bytes = std::fs::read(&seqnums_path).unwrap();
+        assert!(bytes.len() < 32 * SEQNUMS_RECORD_LEN);
+        bytes.extend_from_slice(&9u64.to_le_bytes());
+        std::fs::write(&seqnums_path, bytes).unwrap();
+
+        let store = FileStore::open(&dir, "CLIENT-BROKER").unwrap();
+        assert_eq!((store.next_sender_seq(), store.next_target_seq()), (2, SEQNUMS_COMPACT_RECORDS as u64 + 10));
+        assert_eq!(std::fs::read(&seqnums_path).unwrap().len(), SEQNUMS_RECORD_LEN);
+        drop(store);
+
+        // the sequence numbers are lost, the stored messages still move the sender past them
+        std::fs::remove_file(&seqnums_path).unwrap();
+        let store = FileStore::open(&dir, "CLIENT-BROKER").unwrap();
+        assert_eq!((store.next_sender_seq(), store.next_target_seq()), (2, 1));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_store_corrupt_body() {
+        let dir = std::env::temp_dir().join(format!("fix_store_corrupt_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        {
+            let mut store = FileStore::open(&dir, "CLIENT-BROKER").unwrap();
+            store.store(1, b"first").unwrap();
+            store.store(2, b"second").unwrap();
+            store.store(3, b"third").unwrap();
+        }
+        let body_path = dir.join("CLIENT-BROKER.body");
+        let good = std::fs::read(&body_path).unwrap();
+
+        // a zero filled tail left by a crash is dropped
+        let mut bytes = good.clone();
+        bytes.extend_from_slice(&[0; 64]);
+        std::fs::write(&body_path, &bytes).unwrap();
+        let store = FileStore::open(&dir, "CLIENT-BROKER").unwrap();
+        assert_eq!(store.get(1, 10).unwrap().len(), 3);
+        drop(store);
+        assert_eq!(std::fs::read(&body_path).unwrap(), good);
+
+        // so is the last message cut short
+        let mut bytes = good.clone();
+        let last = bytes.len() - 1;
+        bytes[last] = 0;
+        std::fs::write(&body_path, &bytes).unwrap();
+        let store = FileStore::open(&dir, "CLIENT-BROKER").unwrap();
+        assert_eq!(store.get(1, 10).unwrap().len(), 2);
+        drop(store);
+
+        // a damaged length in the middle keeps the file as it is
+        let mut bytes = good.clone();
+        bytes[BODY_HEADER_LEN + 5 + 8] = 0xFF;
+        std::fs::write(&body_path, &bytes).unwrap();
+        assert_eq!(FileStore::open(&dir, "CLIENT-BROKER").unwrap_err(), FixError::CorruptStore { offset: 25 });
+        // so is a damaged message
+        let mut bytes = good.clone();
+        bytes[BODY_HEADER_LEN + 1] ^= 1;
+        std::fs::write(&body_path, &bytes).unwrap();
+        assert_eq!(FileStore::open(&dir, "CLIENT-BROKER").unwrap_err(), FixError::CorruptStore { offset: 0 });
+        assert_eq!(std::fs::read(&body_path).unwrap(), bytes);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{BookPrice, BookQuantity, InstId};
+use crate::price::PriceError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstrumentError {
+    UnknownInstrument(InstId),
+    /// a code and venue received from a counterparty that are not in the registry
+    UnknownCode { code: String, venue: String },
+    OffTick { price: BookPrice, tick_size: BookPrice },
+    InvalidQuantity { quantity: BookQuantity, lot_size: BookQuantity },
+    Price(PriceError),
+    Parse(String),
+    Io(String),
+}
+
+impl std::fmt::Display for InstrumentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InstrumentError::UnknownInstrument(id) => write!(f, "unknown instrument: {}", id),
+            InstrumentError::UnknownCode { code, venue } => write!(f, "unknown instrument: {}@{}", code, venue),
+            InstrumentError::OffTick { price, tick_size } => {
+                write!(f, "price {} is not a multiple of tick size {}", price, tick_size)
+            }
+            InstrumentError::InvalidQuantity { quantity, lot_size } => {
+                write!(f, "quantity {} is not a positive multiple of lot size {}", quantity, lot_size)
+            }
+            InstrumentError::Price(e) => write!(f, "{}", e),
+            InstrumentError::Parse(msg) => write!(f, "failed to parse instrument data: {}", msg),
+            InstrumentError::Io(msg) => write!(f, "failed to read instrument data: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for InstrumentError {}
+
+impl From<std::io::Error> for InstrumentError {
+    fn from(e: std::io::Error) -> Self {
+        InstrumentError::Io(e.to_string())
+    }
+}
+
+impl From<PriceError> for InstrumentError {
+    fn from(e: PriceError) -> Self {
+        InstrumentError::Price(e)
+    }
+}
@@ -0,0 +1,3 @@
+pub mod spec;
+pub mod registry;
+pub mod error;
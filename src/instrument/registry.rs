@@ -0,0 +1,272 @@
+use crate::{InstId, OrderCore};
+use crate::data::session::{SessionSchedule, SessionWindow, NANOS_PER_SECOND};
+use crate::instrument::error::InstrumentError;
+use crate::instrument::spec::InstrumentSpec;
+use crate::order::request::OrderRequest;
+use rustc_hash::FxHashMap;
+use std::path::Path;
+
+/// `InstrumentSpec`s keyed by `InstId`
+#[derive(Debug, Clone, Default)]
+pub struct InstrumentRegistry {
+    specs: FxHashMap<InstId, InstrumentSpec>,
+    /// venue -> code -> id, to look up codes received from a counterparty without interning them
+    ids: FxHashMap<String, FxHashMap<String, InstId>>,
+}
+
+impl InstrumentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the former spec of the same `InstId` if any
+    pub fn insert(&mut self, spec: InstrumentSpec) -> Option<InstrumentSpec> {
+        self.ids
+            .entry(spec.id.venue_str().to_string())
+            .or_default()
+            .insert(spec.id.code_str().to_string(), spec.id);
+        self.specs.insert(spec.id, spec)
+    }
+
+    pub fn remove(&mut self, id: &InstId) -> Option<InstrumentSpec> {
+        if let Some(codes) = self.ids.get_mut(id.venue_str()) {
+            codes.remove(id.code_str());
+            if codes.is_empty() {
+                self.ids.remove(id.venue_str());
+            }
+        }
+        self.specs.remove(id)
+    }
+
+    /// The id of a registered instrument by code and venue. Unlike `InstId::from_str`,
+    /// an unknown code is not interned
+    #[inline]
+    pub fn find(&self, code: &str, venue: &str) -> Option<InstId> {
+        self.ids.get(venue)?.get(code).copied()
+    }
+
+    #[inline]
+    pub fn try_find(&self, code: &str, venue: &str) -> Result<InstId, InstrumentError> {
+        self.find(code, venue).ok_or_else(|| InstrumentError::UnknownCode {
+            code: code.to_string(),
+            venue: venue.to_string(),
+        })
+    }
+
+    #[inline]
+    pub fn get(&self, id: &InstId) -> Option<&InstrumentSpec> {
+        self.specs.get(id)
+    }
+
+    #[inline]
+    pub fn try_get(&self, id: &InstId) -> Result<&InstrumentSpec, InstrumentError> {
+        self.specs.get(id).ok_or(InstrumentError::UnknownInstrument(*id))
+    }
+
+    #[inline]
+    pub fn contains(&self, id: &InstId) -> bool {
+        self.specs.contains_key(id)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.specs.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &InstrumentSpec> {
+        self.specs.values()
+    }
+
+    /// Validates the order against the spec of `id`
+    pub fn validate(&self, id: &InstId, order_core: &OrderCore) -> Result<(), InstrumentError> {
+        self.try_get(id)?.validate_order(order_core)
+    }
+
+    pub fn validate_request(&self, request: &OrderRequest) -> Result<(), InstrumentError> {
+        self.validate(&request.instid, &request.order_core)
+    }
+
+    /// A JSON array of `InstrumentSpec`s. `id` is written as "code@venue"
+    pub fn from_json_str(json: &str) -> Result<Self, InstrumentError> {
+        let specs: Vec<InstrumentSpec> =
+            serde_json::from_str(json).map_err(|e| InstrumentError::Parse(e.to_string()))?;
+        let mut registry = Self::new();
+        specs.into_iter().for_each(|spec| {
+            registry.insert(spec);
+        });
+        Ok(registry)
+    }
+
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self, InstrumentError> {
+        Self::from_json_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn to_json_string(&self) -> Result<String, InstrumentError> {
+        let specs: Vec<&InstrumentSpec> = self.specs.values().collect();
+        serde_json::to_string_pretty(&specs).map_err(|e| InstrumentError::Parse(e.to_string()))
+    }
+
+    /// Comma separated values with a header. Quoted fields are not supported.
+    ///
+    /// Required columns: id (code@venue), tick_size, price_scale
+    /// Optional columns: lot_size, contract_multiplier, currency, venue,
+    /// utc_offset_minutes, trading_hours (e.g., "09:00-15:30;18:00-05:00")
+    pub fn from_csv_str(csv: &str) -> Result<Self, InstrumentError> {
+        let mut lines = csv
+            .lines()
+            .map(str::trim)
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let header: Vec<&str> = match lines.next() {
+            Some((_, line)) => line.split(',').map(str::trim).collect(),
+            None => return Ok(Self::new()),
+        };
+        let column = |name: &str| header.iter().position(|h| *h == name);
+        let required = |name: &str| {
+            column(name).ok_or_else(|| InstrumentError::Parse(format!("missing column: {}", name)))
+        };
+        let id_col = required("id")?;
+        let tick_col = required("tick_size")?;
+        let scale_col = required("price_scale")?;
+
+        let mut registry = Self::new();
+        for (line_no, line) in lines {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let field = |name: &str| column(name).and_then(|i| fields.get(i)).copied().filter(|f| !f.is_empty());
+            let err = |what: &str| InstrumentError::Parse(format!("line {}: invalid {}", line_no + 1, what));
+
+            let id = fields.get(id_col).filter(|f| f.contains('@')).ok_or_else(|| err("id"))?;
+            let tick_size = fields.get(tick_col).and_then(|f| f.parse().ok()).ok_or_else(|| err("tick_size"))?;
+            let price_scale = fields.get(scale_col).and_then(|f| f.parse().ok()).ok_or_else(|| err("price_scale"))?;
+
+            let mut spec = InstrumentSpec::new(InstId::from_combined_str(id), tick_size, price_scale);
+            if let Some(lot_size) = field("lot_size") {
+                spec.lot_size = lot_size.parse().map_err(|_| err("lot_size"))?;
+            }
+            if let Some(multiplier) = field("contract_multiplier") {
+                spec.contract_multiplier = multiplier.parse().map_err(|_| err("contract_multiplier"))?;
+            }
+            if let Some(currency) = field("currency") {
+                spec.currency = currency.to_string();
+            }
+            if let Some(venue) = field("venue") {
+                spec.venue = venue.to_string();
+            }
+            if let Some(offset) = field("utc_offset_minutes") {
+                let minutes: i64 = offset.parse().map_err(|_| err("utc_offset_minutes"))?;
+                spec.trading_hours.utc_offset = minutes * 60 * NANOS_PER_SECOND as i64;
+            }
+            if let Some(hours) = field("trading_hours") {
+                spec.trading_hours.windows = parse_windows(hours).ok_or_else(|| err("trading_hours"))?;
+            }
+            registry.insert(spec);
+        }
+        Ok(registry)
+    }
+
+    pub fn load_csv<P: AsRef<Path>>(path: P) -> Result<Self, InstrumentError> {
+        Self::from_csv_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// Picks the format by the file extension (.json or .csv)
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, InstrumentError> {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::load_json(path),
+            Some("csv") => Self::load_csv(path),
+            _ => Err(InstrumentError::Parse(format!("unknown file type: {}", path.as_ref().display()))),
+        }
+    }
+
+    /// The trading hours of `id`, if the instrument is known
+    #[inline]
+    pub fn trading_hours(&self, id: &InstId) -> Option<&SessionSchedule> {
+        self.specs.get(id).map(|spec| &spec.trading_hours)
+    }
+}
+
+/// "HH:MM[:SS]-HH:MM[:SS]" separated by ';'
+fn parse_windows(hours: &str) -> Option<Vec<SessionWindow>> {
+    let parse_time = |time: &str| -> Option<(u64, u64, u64)> {
+        let mut parts = time.trim().split(':').map(|p| p.parse::<u64>());
+        let h = parts.next()?.ok()?;
+        let m = parts.next()?.ok()?;
+        let s = match parts.next() {
+            Some(s) => s.ok()?,
+            None => 0,
+        };
+        (h < 24 && m < 60 && s < 60).then_some((h, m, s))
+    };
+
+    hours
+        .split(';')
+        .filter(|window| !window.trim().is_empty())
+        .map(|window| {
+            let (start, end) = window.split_once('-')?;
+            Some(SessionWindow::from_hms(parse_time(start)?, parse_time(end)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::core::LimitOrder;
+    use crate::order::enums::OrderSide;
+
+    #[test]
+    fn test_load_csv() {
+        let csv = "\
+id,tick_size,price_scale,lot_size,contract_multiplier,currency,utc_offset_minutes,trading_hours
+# KOSPI200 futures
+KR4101V30001@KRX,5,2,1,250000,KRW,540,08:45-15:45;18:00-05:00
+KR7005930003@KRX,100,0,,,KRW,540,09:00-15:30
+";
+        let registry = InstrumentRegistry::from_csv_str(csv).unwrap();
+        assert_eq!(registry.len(), 2);
+
+        let future = registry.get(&InstId::from_str("KR4101V30001", "KRX")).unwrap();
+        assert_eq!(future.tick_size, 5);
+        assert_eq!(future.contract_multiplier, 250_000.0);
+        assert_eq!(future.venue, "KRX");
+        assert_eq!(future.trading_hours.windows.len(), 2);
+        assert_eq!(future.price_to_f64(35_025), 350.25);
+        assert_eq!(future.notional(35_025, 2), 350.25 * 2.0 * 250_000.0);
+
+        let stock = registry.get(&InstId::from_str("KR7005930003", "KRX")).unwrap();
+        assert_eq!(stock.lot_size, 1);
+
+        let bad = "id,tick_size,price_scale\nKR7005930003@KRX,abc,0";
+        assert!(matches!(InstrumentRegistry::from_csv_str(bad), Err(InstrumentError::Parse(_))));
+    }
+
+    #[test]
+    fn test_json_round_trip_and_validation() {
+        let id = InstId::from_str("KR7005930003", "KRX");
+        let mut registry = InstrumentRegistry::new();
+        registry.insert(InstrumentSpec::new(id, 100, 0).with_lot_size(10).with_currency("KRW"));
+
+        let json = registry.to_json_string().unwrap();
+        let loaded = InstrumentRegistry::from_json_str(&json).unwrap();
+        assert_eq!(loaded.get(&id), registry.get(&id));
+
+        let order = |price, quantity| OrderCore::LimitOrder(LimitOrder::new(price, quantity, OrderSide::Bid, 1));
+        assert!(loaded.validate(&id, &order(70_000, 10)).is_ok());
+        assert_eq!(
+            loaded.validate(&id, &order(70_050, 10)),
+            Err(InstrumentError::OffTick { price: 70_050, tick_size: 100 })
+        );
+        assert!(matches!(loaded.validate(&id, &order(70_000, 15)), Err(InstrumentError::InvalidQuantity { .. })));
+        assert_eq!(loaded.find("KR7005930003", "KRX"), Some(id));
+        assert_eq!(loaded.find("KR7005930003", "NYSE"), None);
+        let mut removed = loaded.clone();
+        removed.remove(&id);
+        assert_eq!(removed.find("KR7005930003", "KRX"), None);
+        let unknown = InstId::from_str("UNKNOWN", "KRX");
+        assert_eq!(loaded.validate(&unknown, &order(1, 1)), Err(InstrumentError::UnknownInstrument(unknown)));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{BookPrice, BookQuantity, InstId, OrderCore};
+use crate::data::session::SessionSchedule;
+use crate::instrument::error::InstrumentError;
+use crate::price::{self, PriceError, ScaledPrice, TickRounding};
+use serde::{Deserialize, Serialize};
+
+/// Reference data of an instrument.
+/// `BookPrice` is an integer: with `price_scale` = 2, the book price 12345 means 123.45.
+/// `tick_size` is expressed in book units as well.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstrumentSpec {
+    pub id: InstId,
+    pub tick_size: BookPrice,
+    pub price_scale: u8,
+    #[serde(default = "default_lot_size")]
+    pub lot_size: BookQuantity,
+    #[serde(default = "default_contract_multiplier")]
+    pub contract_multiplier: f64,
+    #[serde(default)]
+    pub currency: String,
+    #[serde(default)]
+    pub venue: String,
+    #[serde(default)]
+    pub trading_hours: SessionSchedule,
+}
+
+fn default_lot_size() -> BookQuantity {
+    1
+}
+
+fn default_contract_multiplier() -> f64 {
+    1.0
+}
+
+impl InstrumentSpec {
+    pub fn new(id: InstId, tick_size: BookPrice, price_scale: u8) -> Self {
+        Self {
+            id,
+            tick_size,
+            price_scale,
+            lot_size: default_lot_size(),
+            contract_multiplier: default_contract_multiplier(),
+            currency: String::new(),
+            venue: id.venue_str().to_string(),
+            trading_hours: SessionSchedule::default(),
+        }
+    }
+
+    pub fn with_lot_size(mut self, lot_size: BookQuantity) -> Self {
+        self.lot_size = lot_size;
+        self
+    }
+
+    pub fn with_contract_multiplier(mut self, contract_multiplier: f64) -> Self {
+        self.contract_multiplier = contract_multiplier;
+        self
+    }
+
+    pub fn with_currency(mut self, currency: &str) -> Self {
+        self.currency = currency.to_string();
+        self
+    }
+
+    pub fn with_venue(mut self, venue: &str) -> Self {
+        self.venue = venue.to_string();
+        self
+    }
+
+    pub fn with_trading_hours(mut self, trading_hours: SessionSchedule) -> Self {
+        self.trading_hours = trading_hours;
+        self
+    }
+
+    /// 10^price_scale
+    #[inline]
+    pub fn price_unit(&self) -> f64 {
+        10f64.powi(self.price_scale as i32)
+    }
+
+    #[inline]
+    pub fn price_to_f64(&self, price: BookPrice) -> f64 {
+        price::to_f64(price, self.price_scale)
+    }
+
+    /// Rounded to the nearest book unit (not to the tick)
+    #[inline]
+    pub fn price_from_f64(&self, value: f64) -> Result<BookPrice, PriceError> {
+        price::from_f64(value, self.price_scale)
+    }
+
+    /// Converts and rounds to a valid tick
+    #[inline]
+    pub fn tick_price_from_f64(&self, value: f64, rounding: TickRounding) -> Result<BookPrice, PriceError> {
+        self.round_to_tick(self.price_from_f64(value)?, rounding)
+    }
+
+    #[inline]
+    pub fn price_to_string(&self, price: BookPrice) -> String {
+        price::to_decimal_string(price, self.price_scale)
+    }
+
+    #[inline]
+    pub fn price_from_str(&self, s: &str) -> Result<BookPrice, PriceError> {
+        price::from_decimal_str(s, self.price_scale)
+    }
+
+    #[inline]
+    pub fn scaled(&self, price: BookPrice) -> ScaledPrice {
+        ScaledPrice::new(price, self.price_scale)
+    }
+
+    #[inline]
+    pub fn round_to_tick(&self, price: BookPrice, rounding: TickRounding) -> Result<BookPrice, PriceError> {
+        price::round_to_tick(price, self.tick_size, rounding)
+    }
+
+    #[inline]
+    pub fn add_ticks(&self, price: BookPrice, ticks: i64) -> Result<BookPrice, PriceError> {
+        price::add_ticks(price, ticks, self.tick_size)
+    }
+
+    #[inline]
+    pub fn ticks_between(&self, from: BookPrice, to: BookPrice) -> Result<i64, PriceError> {
+        price::ticks_between(from, to, self.tick_size)
+    }
+
+    /// price * quantity * contract multiplier in the instrument currency
+    #[inline]
+    pub fn notional(&self, price: BookPrice, quantity: BookQuantity) -> f64 {
+        self.price_to_f64(price) * quantity as f64 * self.contract_multiplier
+    }
+
+    #[inline]
+    pub fn is_on_tick(&self, price: BookPrice) -> bool {
+        price::is_on_tick(price, self.tick_size)
+    }
+
+    #[inline]
+    pub fn is_valid_quantity(&self, quantity: BookQuantity) -> bool {
+        quantity > 0 && (self.lot_size == 0 || quantity.is_multiple_of(self.lot_size))
+    }
+
+    pub fn validate_price(&self, price: BookPrice) -> Result<(), InstrumentError> {
+        if self.is_on_tick(price) {
+            Ok(())
+        } else {
+            Err(InstrumentError::OffTick { price, tick_size: self.tick_size })
+        }
+    }
+
+    pub fn validate_quantity(&self, quantity: BookQuantity) -> Result<(), InstrumentError> {
+        if self.is_valid_quantity(quantity) {
+            Ok(())
+        } else {
+            Err(InstrumentError::InvalidQuantity { quantity, lot_size: self.lot_size })
+        }
+    }
+
+    /// Checks the prices (including stop prices and trailing amounts) against the tick size and the quantity against the lot size
+    pub fn validate_order(&self, order_core: &OrderCore) -> Result<(), InstrumentError> {
+        match order_core {
+            OrderCore::LimitOrder(order) => {
+                self.validate_price(order.price)?;
+                self.validate_quantity(order.quantity)
+            }
+            OrderCore::ModifyOrder(order) => {
+                self.validate_price(order.price)?;
+                self.validate_quantity(order.quantity)
+            }
+            OrderCore::MarketOrder(order) => self.validate_quantity(order.quantity),
+            OrderCore::StopMarketOrder(order) => {
+                self.validate_price(order.stop_price)?;
+                self.validate_quantity(order.quantity)
+            }
+            OrderCore::StopLimitOrder(order) => {
+                self.validate_price(order.stop_price)?;
+                self.validate_price(order.price)?;
+                self.validate_quantity(order.quantity)
+            }
+            OrderCore::TrailingStopOrder(order) => {
+                self.validate_price(order.trail)?;
+                if let Some(limit_offset) = order.limit_offset {
+                    self.validate_price(limit_offset)?;
+                }
+                self.validate_quantity(order.quantity)
+            }
+            OrderCore::IfTouchedOrder(order) => {
+                self.validate_price(order.trigger_price)?;
+                if let Some(price) = order.price {
+                    self.validate_price(price)?;
+                }
+                self.validate_quantity(order.quantity)
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -3,17 +3,29 @@ pub mod tcp_client;
 pub mod unique_id;
 pub mod order;
 pub mod data;
+pub mod instrument;
+pub mod price;
+pub mod risk;
+pub mod position;
+pub mod fix;
+pub mod protocol;
+pub mod feed;
+pub mod sbe;
 
 use static_id::StaticId;
 
 pub type UnixNano = u64;
 pub type BookQuantity = u64;
+/// An integer number of 10^-price_scale units, see `price` and `InstrumentSpec::price_scale`
 pub type BookPrice = i64;
 pub type OrderId = u64;
 pub type TimeStamp = UnixNano;
 pub type InstId = StaticId;
 pub type OrderCount = u32;
+/// An integer number of 10^-scale units like `BookPrice`, with the yield scale of the venue
 pub type BookYield = i32;
+pub type TradeId = u64;
+pub type ExecId = u64;
 
 pub use order::core::OrderCore;
 
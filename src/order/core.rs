@@ -1,18 +1,112 @@
 use crate::{BookPrice, BookQuantity, OrderId};
-use crate::order::enums::OrderSide;
+use crate::order::enums::{OrderSide, OrderType, TimeInForce};
+use crate::order::error::OrderError;
 //
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The kind of an `OrderCore`, named after its variants as in existing code and serialized data.
+/// `OrderType` is the enum used across the crate; the two convert losslessly
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum CoreType {
     LimitOrder,
     MarketOrder,
     CancelOrder,
     ModifyOrder,
     RemoveOtherOrder,
+    StopMarketOrder,
+    StopLimitOrder,
+    TrailingStopOrder,
+    IfTouchedOrder,
     NullOrder,
 }
 
+impl CoreType {
+    /// Orders that wait for a trigger price before going to the book
+    #[inline]
+    pub fn is_conditional(&self) -> bool {
+        OrderType::from(*self).is_conditional()
+    }
+
+    /// Numeric wire code, the same as `OrderType::code`
+    #[inline]
+    pub fn code(&self) -> u8 {
+        OrderType::from(*self).code()
+    }
+}
+
+impl From<OrderType> for CoreType {
+    #[inline]
+    fn from(order_type: OrderType) -> Self {
+        match order_type {
+            OrderType::Limit => CoreType::LimitOrder,
+            OrderType::Market => CoreType::MarketOrder,
+            OrderType::Cancel => CoreType::CancelOrder,
+            OrderType::Modify => CoreType::ModifyOrder,
+            OrderType::RemoveOther => CoreType::RemoveOtherOrder,
+            OrderType::StopMarket => CoreType::StopMarketOrder,
+            OrderType::StopLimit => CoreType::StopLimitOrder,
+            OrderType::TrailingStop => CoreType::TrailingStopOrder,
+            OrderType::IfTouched => CoreType::IfTouchedOrder,
+            OrderType::Null => CoreType::NullOrder,
+        }
+    }
+}
+
+impl From<CoreType> for OrderType {
+    #[inline]
+    fn from(core_type: CoreType) -> Self {
+        match core_type {
+            CoreType::LimitOrder => OrderType::Limit,
+            CoreType::MarketOrder => OrderType::Market,
+            CoreType::CancelOrder => OrderType::Cancel,
+            CoreType::ModifyOrder => OrderType::Modify,
+            CoreType::RemoveOtherOrder => OrderType::RemoveOther,
+            CoreType::StopMarketOrder => OrderType::StopMarket,
+            CoreType::StopLimitOrder => OrderType::StopLimit,
+            CoreType::TrailingStopOrder => OrderType::TrailingStop,
+            CoreType::IfTouchedOrder => OrderType::IfTouched,
+            CoreType::NullOrder => OrderType::Null,
+        }
+    }
+}
+
+impl From<&OrderCore> for CoreType {
+    #[inline]
+    fn from(order_core: &OrderCore) -> Self {
+        order_core.core_type()
+    }
+}
+
+impl From<&OrderCore> for OrderType {
+    #[inline]
+    fn from(order_core: &OrderCore) -> Self {
+        order_core.order_type()
+    }
+}
+
+/// Accepts both the `CoreType` and the `OrderType` names, e.g., "LimitOrder" and "Limit"
+impl std::str::FromStr for CoreType {
+    type Err = OrderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<OrderType>().map(CoreType::from).map_err(|_| OrderError::UnknownCode {
+            kind: "CoreType",
+            code: s.to_string(),
+        })
+    }
+}
+
+impl TryFrom<u8> for CoreType {
+    type Error = OrderError;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        OrderType::try_from(code).map(CoreType::from).map_err(|_| OrderError::UnknownCode {
+            kind: "CoreType",
+            code: code.to_string(),
+        })
+    }
+}
+
 impl std::fmt::Display for CoreType {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -21,17 +115,110 @@ impl std::fmt::Display for CoreType {
             CoreType::CancelOrder => write!(f, "CancelOrder"),
             CoreType::ModifyOrder => write!(f, "ModifyOrder"),
             CoreType::RemoveOtherOrder => write!(f, "RemoveOtherOrder"),
+            CoreType::StopMarketOrder => write!(f, "StopMarketOrder"),
+            CoreType::StopLimitOrder => write!(f, "StopLimitOrder"),
+            CoreType::TrailingStopOrder => write!(f, "TrailingStopOrder"),
+            CoreType::IfTouchedOrder => write!(f, "IfTouchedOrder"),
             CoreType::NullOrder => write!(f, "NullOrder"),
         }
     }
 }
 
+/// Execution instructions and tags of a new order. The default is a plain day order
+#[derive(Debug, Clone, Serialize, Deserialize, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OrderAttributes {
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    /// rejected instead of taking liquidity
+    #[serde(default)]
+    pub post_only: bool,
+    /// may only decrease the position
+    #[serde(default)]
+    pub reduce_only: bool,
+    /// the quantity shown on the book for an iceberg order, Some(0) for a hidden order
+    #[serde(default)]
+    pub display_quantity: Option<BookQuantity>,
+    /// the minimum quantity of the immediate execution
+    #[serde(default)]
+    pub min_quantity: Option<BookQuantity>,
+    #[serde(default)]
+    pub account: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+impl OrderAttributes {
+    #[inline]
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    #[inline]
+    pub fn with_post_only(mut self) -> Self {
+        self.post_only = true;
+        self
+    }
+
+    #[inline]
+    pub fn with_reduce_only(mut self) -> Self {
+        self.reduce_only = true;
+        self
+    }
+
+    #[inline]
+    pub fn with_display_quantity(mut self, display_quantity: BookQuantity) -> Self {
+        self.display_quantity = Some(display_quantity);
+        self
+    }
+
+    #[inline]
+    pub fn with_min_quantity(mut self, min_quantity: BookQuantity) -> Self {
+        self.min_quantity = Some(min_quantity);
+        self
+    }
+
+    pub fn with_account(mut self, account: &str) -> Self {
+        self.account = Some(account.to_string());
+        self
+    }
+
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    #[inline]
+    pub fn is_hidden(&self) -> bool {
+        self.display_quantity == Some(0)
+    }
+
+    /// Checks that the attributes are consistent with each other and with an order of `quantity`
+    pub fn validate(&self, quantity: BookQuantity, is_market: bool) -> Result<(), OrderError> {
+        if self.post_only && (is_market || self.time_in_force.is_immediate()) {
+            return Err(OrderError::InvalidAttributes("post-only order must rest on the book"));
+        }
+        if is_market && (self.display_quantity.is_some() || self.post_only) {
+            return Err(OrderError::InvalidAttributes("market order can not rest on the book"));
+        }
+        if self.display_quantity.is_some_and(|display| display > quantity) {
+            return Err(OrderError::InvalidAttributes("display quantity exceeds the order quantity"));
+        }
+        if self.min_quantity.is_some_and(|min| min > quantity) {
+            return Err(OrderError::InvalidAttributes("minimum quantity exceeds the order quantity"));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct LimitOrder {
     pub price: BookPrice,
     pub quantity: BookQuantity,
     pub order_side: OrderSide, // should I keep this? book also has its side
     pub order_id: OrderId,
+    #[serde(default)]
+    pub attributes: OrderAttributes,
 }
 
 impl LimitOrder {
@@ -47,8 +234,15 @@ impl LimitOrder {
             quantity,
             order_side,
             order_id,
+            attributes: OrderAttributes::default(),
         }
     }
+
+    #[inline]
+    pub fn with_attributes(mut self, attributes: OrderAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -56,6 +250,8 @@ pub struct MarketOrder {
     pub quantity: BookQuantity,
     pub order_side: OrderSide,
     pub order_id: OrderId,
+    #[serde(default)]
+    pub attributes: OrderAttributes,
 }
 
 impl MarketOrder {
@@ -65,8 +261,15 @@ impl MarketOrder {
             quantity,
             order_side,
             order_id,
+            attributes: OrderAttributes::default(),
         }
     }
+
+    #[inline]
+    pub fn with_attributes(mut self, attributes: OrderAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
 }
 
 /// Normally, it is used for my order
@@ -82,11 +285,15 @@ impl CancelOrder {
     }
 }
 
+/// Cancel/replace of my order `order_id`. `quantity` is the new total quantity (including what is already filled).
+/// `new_order_id` is the client order id of the replacement for venues that assign a new id on replace
 #[derive(Debug, Clone, Serialize, Deserialize, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ModifyOrder {
     pub order_id: OrderId,
     pub price: BookPrice,
     pub quantity: BookQuantity,
+    #[serde(default)]
+    pub new_order_id: Option<OrderId>,
 }
 
 impl ModifyOrder {
@@ -96,8 +303,21 @@ impl ModifyOrder {
             order_id,
             price,
             quantity,
+            new_order_id: None,
         }
     }
+
+    #[inline]
+    pub fn with_new_order_id(mut self, new_order_id: OrderId) -> Self {
+        self.new_order_id = Some(new_order_id);
+        self
+    }
+
+    /// The id the order has once the replace is done
+    #[inline]
+    pub fn replaced_order_id(&self) -> OrderId {
+        self.new_order_id.unwrap_or(self.order_id)
+    }
 }
 
 /// If you want to remove order in a different ratio, you can use ModifyOrder
@@ -119,6 +339,156 @@ impl RemoveOtherOrder {
     }
 }
 
+/// Becomes a market order once the price reaches `stop_price`
+/// (at or above for a bid, at or below for an ask)
+#[derive(Debug, Clone, Serialize, Deserialize, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StopMarketOrder {
+    pub stop_price: BookPrice,
+    pub quantity: BookQuantity,
+    pub order_side: OrderSide,
+    pub order_id: OrderId,
+    #[serde(default)]
+    pub attributes: OrderAttributes,
+}
+
+impl StopMarketOrder {
+    #[inline]
+    pub fn new(stop_price: BookPrice, quantity: BookQuantity, order_side: OrderSide, order_id: OrderId) -> Self {
+        Self {
+            stop_price,
+            quantity,
+            order_side,
+            order_id,
+            attributes: OrderAttributes::default(),
+        }
+    }
+
+    #[inline]
+    pub fn with_attributes(mut self, attributes: OrderAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+}
+
+/// Becomes a limit order at `price` once the price reaches `stop_price`
+#[derive(Debug, Clone, Serialize, Deserialize, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StopLimitOrder {
+    pub stop_price: BookPrice,
+    pub price: BookPrice,
+    pub quantity: BookQuantity,
+    pub order_side: OrderSide,
+    pub order_id: OrderId,
+    #[serde(default)]
+    pub attributes: OrderAttributes,
+}
+
+impl StopLimitOrder {
+    #[inline]
+    pub fn new(
+        stop_price: BookPrice,
+        price: BookPrice,
+        quantity: BookQuantity,
+        order_side: OrderSide,
+        order_id: OrderId,
+    ) -> Self {
+        Self {
+            stop_price,
+            price,
+            quantity,
+            order_side,
+            order_id,
+            attributes: OrderAttributes::default(),
+        }
+    }
+
+    #[inline]
+    pub fn with_attributes(mut self, attributes: OrderAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+}
+
+/// A stop `trail` away from the best price seen since the order was placed
+/// (below the highest price for an ask, above the lowest price for a bid).
+/// Becomes a market order, or a limit order `limit_offset` beyond the stop, when triggered
+#[derive(Debug, Clone, Serialize, Deserialize, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TrailingStopOrder {
+    pub trail: BookPrice,
+    #[serde(default)]
+    pub limit_offset: Option<BookPrice>,
+    pub quantity: BookQuantity,
+    pub order_side: OrderSide,
+    pub order_id: OrderId,
+    #[serde(default)]
+    pub attributes: OrderAttributes,
+}
+
+impl TrailingStopOrder {
+    #[inline]
+    pub fn new(trail: BookPrice, quantity: BookQuantity, order_side: OrderSide, order_id: OrderId) -> Self {
+        Self {
+            trail,
+            limit_offset: None,
+            quantity,
+            order_side,
+            order_id,
+            attributes: OrderAttributes::default(),
+        }
+    }
+
+    #[inline]
+    pub fn with_limit_offset(mut self, limit_offset: BookPrice) -> Self {
+        self.limit_offset = Some(limit_offset);
+        self
+    }
+
+    #[inline]
+    pub fn with_attributes(mut self, attributes: OrderAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+}
+
+/// Market-if-touched (`price` = None) or limit-if-touched order. Triggers once the price reaches
+/// `trigger_price` in the favorable direction (at or below for a bid, at or above for an ask)
+#[derive(Debug, Clone, Serialize, Deserialize, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IfTouchedOrder {
+    pub trigger_price: BookPrice,
+    #[serde(default)]
+    pub price: Option<BookPrice>,
+    pub quantity: BookQuantity,
+    pub order_side: OrderSide,
+    pub order_id: OrderId,
+    #[serde(default)]
+    pub attributes: OrderAttributes,
+}
+
+impl IfTouchedOrder {
+    #[inline]
+    pub fn new(trigger_price: BookPrice, quantity: BookQuantity, order_side: OrderSide, order_id: OrderId) -> Self {
+        Self {
+            trigger_price,
+            price: None,
+            quantity,
+            order_side,
+            order_id,
+            attributes: OrderAttributes::default(),
+        }
+    }
+
+    #[inline]
+    pub fn with_limit_price(mut self, price: BookPrice) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    #[inline]
+    pub fn with_attributes(mut self, attributes: OrderAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct NullOrder {}
 
@@ -130,6 +500,10 @@ pub enum OrderCore {
     MarketOrder(MarketOrder),
     CancelOrder(CancelOrder), 
     ModifyOrder(ModifyOrder),
+    StopMarketOrder(StopMarketOrder),
+    StopLimitOrder(StopLimitOrder),
+    TrailingStopOrder(TrailingStopOrder),
+    IfTouchedOrder(IfTouchedOrder),
 }
 
 impl Default for OrderCore {
@@ -141,31 +515,67 @@ impl Default for OrderCore {
 impl OrderCore {
     #[inline]
     pub fn core_type(&self) -> CoreType {
+        self.order_type().into()
+    }
+
+    #[inline]
+    pub fn order_type(&self) -> OrderType {
         match self {
-            OrderCore::LimitOrder(_) => CoreType::LimitOrder,
-            OrderCore::MarketOrder(_) => CoreType::MarketOrder,
-            OrderCore::CancelOrder(_) => CoreType::CancelOrder,
-            OrderCore::ModifyOrder(_) => CoreType::ModifyOrder,
-            OrderCore::RemoveOtherOrder(_) => CoreType::RemoveOtherOrder,
-            OrderCore::NullOrder(_) => CoreType::NullOrder,
+            OrderCore::LimitOrder(_) => OrderType::Limit,
+            OrderCore::MarketOrder(_) => OrderType::Market,
+            OrderCore::CancelOrder(_) => OrderType::Cancel,
+            OrderCore::ModifyOrder(_) => OrderType::Modify,
+            OrderCore::RemoveOtherOrder(_) => OrderType::RemoveOther,
+            OrderCore::StopMarketOrder(_) => OrderType::StopMarket,
+            OrderCore::StopLimitOrder(_) => OrderType::StopLimit,
+            OrderCore::TrailingStopOrder(_) => OrderType::TrailingStop,
+            OrderCore::IfTouchedOrder(_) => OrderType::IfTouched,
+            OrderCore::NullOrder(_) => OrderType::Null,
         }
     }
 
+    #[inline]
+    pub fn is_conditional(&self) -> bool {
+        self.order_type().is_conditional()
+    }
+
     #[inline]
     pub fn set_quantity(&mut self, quantity: BookQuantity) {
         match self {
             OrderCore::LimitOrder(order) => order.quantity = quantity,
             OrderCore::MarketOrder(order) => order.quantity = quantity,
             OrderCore::ModifyOrder(order) => order.quantity = quantity,
+            OrderCore::StopMarketOrder(order) => order.quantity = quantity,
+            OrderCore::StopLimitOrder(order) => order.quantity = quantity,
+            OrderCore::TrailingStopOrder(order) => order.quantity = quantity,
+            OrderCore::IfTouchedOrder(order) => order.quantity = quantity,
+            _ => {},
+        }
+    }
+
+    #[inline]
+    pub fn set_order_id(&mut self, order_id: OrderId) {
+        match self {
+            OrderCore::LimitOrder(order) => order.order_id = order_id,
+            OrderCore::MarketOrder(order) => order.order_id = order_id,
+            OrderCore::CancelOrder(order) => order.order_id = order_id,
+            OrderCore::ModifyOrder(order) => order.order_id = order_id,
+            OrderCore::StopMarketOrder(order) => order.order_id = order_id,
+            OrderCore::StopLimitOrder(order) => order.order_id = order_id,
+            OrderCore::TrailingStopOrder(order) => order.order_id = order_id,
+            OrderCore::IfTouchedOrder(order) => order.order_id = order_id,
             _ => {},
         }
     }
 
+    /// Sets the limit price (of the order after the trigger for a conditional order)
     #[inline]
     pub fn set_price(&mut self, price: BookPrice) {
         match self {
             OrderCore::LimitOrder(order) => order.price = price,
             OrderCore::ModifyOrder(order) => order.price = price,
+            OrderCore::StopLimitOrder(order) => order.price = price,
+            OrderCore::IfTouchedOrder(order) if order.price.is_some() => order.price = Some(price),
             _ => {},
         }
     }
@@ -176,6 +586,32 @@ impl OrderCore {
             OrderCore::LimitOrder(order) => Some(order.quantity),
             OrderCore::MarketOrder(order) => Some(order.quantity),
             OrderCore::ModifyOrder(order) => Some(order.quantity),
+            OrderCore::StopMarketOrder(order) => Some(order.quantity),
+            OrderCore::StopLimitOrder(order) => Some(order.quantity),
+            OrderCore::TrailingStopOrder(order) => Some(order.quantity),
+            OrderCore::IfTouchedOrder(order) => Some(order.quantity),
+            _ => None,
+        }
+    }
+
+    /// The price the order rests at on the book. None for conditional orders, which are not on the book
+    #[inline]
+    pub fn price(&self) -> Option<BookPrice> {
+        match self {
+            OrderCore::LimitOrder(order) => Some(order.price),
+            OrderCore::ModifyOrder(order) => Some(order.price),
+            OrderCore::RemoveOtherOrder(order) => Some(order.price),
+            _ => None,
+        }
+    }
+
+    /// The price a conditional order waits for. None for a trailing stop, whose stop moves with the market
+    #[inline]
+    pub fn trigger_price(&self) -> Option<BookPrice> {
+        match self {
+            OrderCore::StopMarketOrder(order) => Some(order.stop_price),
+            OrderCore::StopLimitOrder(order) => Some(order.stop_price),
+            OrderCore::IfTouchedOrder(order) => Some(order.trigger_price),
             _ => None,
         }
     }
@@ -185,10 +621,71 @@ impl OrderCore {
         match self {
             OrderCore::LimitOrder(order) => Some(order.order_side),
             OrderCore::MarketOrder(order) => Some(order.order_side),
+            OrderCore::StopMarketOrder(order) => Some(order.order_side),
+            OrderCore::StopLimitOrder(order) => Some(order.order_side),
+            OrderCore::TrailingStopOrder(order) => Some(order.order_side),
+            OrderCore::IfTouchedOrder(order) => Some(order.order_side),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn attributes(&self) -> Option<&OrderAttributes> {
+        match self {
+            OrderCore::LimitOrder(order) => Some(&order.attributes),
+            OrderCore::MarketOrder(order) => Some(&order.attributes),
+            OrderCore::StopMarketOrder(order) => Some(&order.attributes),
+            OrderCore::StopLimitOrder(order) => Some(&order.attributes),
+            OrderCore::TrailingStopOrder(order) => Some(&order.attributes),
+            OrderCore::IfTouchedOrder(order) => Some(&order.attributes),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn attributes_mut(&mut self) -> Option<&mut OrderAttributes> {
+        match self {
+            OrderCore::LimitOrder(order) => Some(&mut order.attributes),
+            OrderCore::MarketOrder(order) => Some(&mut order.attributes),
+            OrderCore::StopMarketOrder(order) => Some(&mut order.attributes),
+            OrderCore::StopLimitOrder(order) => Some(&mut order.attributes),
+            OrderCore::TrailingStopOrder(order) => Some(&mut order.attributes),
+            OrderCore::IfTouchedOrder(order) => Some(&mut order.attributes),
             _ => None,
         }
     }
 
+    /// Market orders are immediate or cancel unless stated otherwise.
+    /// For a conditional order, it is the lifetime of the order waiting for its trigger
+    #[inline]
+    pub fn time_in_force(&self) -> Option<TimeInForce> {
+        match self {
+            OrderCore::MarketOrder(order) => match order.attributes.time_in_force {
+                TimeInForce::Fok => Some(TimeInForce::Fok),
+                _ => Some(TimeInForce::Ioc),
+            },
+            _ => self.attributes().map(|attributes| attributes.time_in_force),
+        }
+    }
+
+    /// Checks the attributes of a new order
+    pub fn validate_attributes(&self) -> Result<(), OrderError> {
+        match self {
+            OrderCore::LimitOrder(order) => order.attributes.validate(order.quantity, false),
+            OrderCore::MarketOrder(order) => order.attributes.validate(order.quantity, true),
+            OrderCore::StopMarketOrder(order) => order.attributes.validate(order.quantity, true),
+            OrderCore::StopLimitOrder(order) => order.attributes.validate(order.quantity, false),
+            OrderCore::TrailingStopOrder(order) => {
+                if order.trail <= 0 {
+                    return Err(OrderError::InvalidAttributes("trailing amount must be positive"));
+                }
+                order.attributes.validate(order.quantity, order.limit_offset.is_none())
+            }
+            OrderCore::IfTouchedOrder(order) => order.attributes.validate(order.quantity, order.price.is_none()),
+            _ => Ok(()),
+        }
+    }
+
     #[inline]
     pub fn order_id(&self) -> Option<OrderId> {
         match self {
@@ -196,6 +693,10 @@ impl OrderCore {
             OrderCore::MarketOrder(order) => Some(order.order_id),
             OrderCore::CancelOrder(order) => Some(order.order_id),
             OrderCore::ModifyOrder(order) => Some(order.order_id),
+            OrderCore::StopMarketOrder(order) => Some(order.order_id),
+            OrderCore::StopLimitOrder(order) => Some(order.order_id),
+            OrderCore::TrailingStopOrder(order) => Some(order.order_id),
+            OrderCore::IfTouchedOrder(order) => Some(order.order_id),
             _ => None,
         }
     }
@@ -222,4 +723,40 @@ mod tests {
         assert_eq!(book_order.order_side, order_side);
         assert_eq!(book_order.order_id, order_id);
     }
+
+    #[test]
+    fn test_order_attributes() {
+        use super::*;
+
+        // attributes are optional in serialized orders
+        let json = r#"{"price":100,"quantity":10,"order_side":"Bid","order_id":1}"#;
+        let order: LimitOrder = serde_json::from_str(json).unwrap();
+        assert_eq!(order.attributes, OrderAttributes::default());
+
+        let attributes = OrderAttributes::default()
+            .with_time_in_force(TimeInForce::Gtd(1_000))
+            .with_display_quantity(2)
+            .with_account("acc-1");
+        let core = OrderCore::LimitOrder(LimitOrder::new(100, 10, OrderSide::Bid, 1).with_attributes(attributes.clone()));
+        let json = serde_json::to_string(&core).unwrap();
+        assert_eq!(serde_json::from_str::<OrderCore>(&json).unwrap(), core);
+        assert_eq!(core.attributes(), Some(&attributes));
+        assert_eq!(core.time_in_force(), Some(TimeInForce::Gtd(1_000)));
+        assert!(core.validate_attributes().is_ok());
+
+        let market = OrderCore::MarketOrder(MarketOrder::new(10, OrderSide::Ask, 2));
+        assert_eq!(market.time_in_force(), Some(TimeInForce::Ioc));
+
+        let invalid = [
+            OrderAttributes::default().with_post_only().with_time_in_force(TimeInForce::Ioc),
+            OrderAttributes::default().with_display_quantity(11),
+            OrderAttributes::default().with_min_quantity(11),
+        ];
+        for attributes in invalid {
+            let core = OrderCore::LimitOrder(LimitOrder::new(100, 10, OrderSide::Bid, 1).with_attributes(attributes));
+            assert!(core.validate_attributes().is_err());
+        }
+        let post_only_market = MarketOrder::new(10, OrderSide::Ask, 2).with_attributes(OrderAttributes::default().with_post_only());
+        assert!(OrderCore::MarketOrder(post_only_market).validate_attributes().is_err());
+    }
 }
\ No newline at end of file
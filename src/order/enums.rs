@@ -1,3 +1,5 @@
+use crate::TimeStamp;
+use crate::order::error::OrderError;
 use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, Hash, Eq, PartialOrd, Ord, Copy)]
@@ -7,7 +9,81 @@ pub enum OrderSide {
     Ask,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Copy)]
+impl OrderSide {
+    /// FIX Side (54)
+    #[inline]
+    pub fn fix_code(&self) -> char {
+        match self {
+            OrderSide::Bid => '1',
+            OrderSide::Ask => '2',
+        }
+    }
+}
+
+impl std::fmt::Display for OrderSide {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OrderSide::Bid => write!(f, "Bid"),
+            OrderSide::Ask => write!(f, "Ask"),
+        }
+    }
+}
+
+impl std::str::FromStr for OrderSide {
+    type Err = OrderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Bid" | "Buy" => Ok(OrderSide::Bid),
+            "Ask" | "Sell" => Ok(OrderSide::Ask),
+            _ => Err(OrderError::UnknownCode { kind: "OrderSide", code: s.to_string() }),
+        }
+    }
+}
+
+impl TryFrom<char> for OrderSide {
+    type Error = OrderError;
+
+    fn try_from(code: char) -> Result<Self, Self::Error> {
+        match code {
+            '1' => Ok(OrderSide::Bid),
+            '2' => Ok(OrderSide::Ask),
+            _ => Err(OrderError::UnknownCode { kind: "OrderSide", code: code.to_string() }),
+        }
+    }
+}
+
+/// How long an order stays on the book
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, Hash, Eq, PartialOrd, Ord, Copy)]
+pub enum TimeInForce {
+    #[default]
+    Day,
+    /// immediate or cancel: the part not executed at once is canceled
+    Ioc,
+    /// fill or kill: executed in full at once, or canceled
+    Fok,
+    Gtc,
+    /// good till the given time
+    Gtd(TimeStamp),
+}
+
+impl TimeInForce {
+    /// The order never rests on the book
+    #[inline]
+    pub fn is_immediate(&self) -> bool {
+        matches!(self, TimeInForce::Ioc | TimeInForce::Fok)
+    }
+
+    #[inline]
+    pub fn is_expired(&self, now: TimeStamp) -> bool {
+        match self {
+            TimeInForce::Gtd(expire_time) => now >= *expire_time,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Copy)]
 pub enum OrderStatus {
     PendingNew,
     Accepted,
@@ -15,14 +91,293 @@ pub enum OrderStatus {
     FullyFilled,
     Canceled,
     Rejected,
+    PendingCancel,
+    PendingReplace,
+}
+
+impl OrderStatus {
+    /// No transition is allowed from a terminal status
+    #[inline]
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, OrderStatus::FullyFilled | OrderStatus::Canceled | OrderStatus::Rejected)
+    }
+
+    /// The order may be resting on the book (including the in-flight states)
+    #[inline]
+    pub fn is_working(&self) -> bool {
+        !self.is_terminal()
+    }
+
+    /// Legal transitions of an order:
+    /// - fills may arrive before the acknowledgement, and while a cancel or replace is in flight
+    /// - a rejected cancel or replace brings the order back to Accepted or PartiallyFilled
+    /// - PendingNew may be rejected or canceled (e.g., unfilled IOC) directly
+    #[inline]
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        match self {
+            PendingNew => matches!(next, Accepted | PartiallyFilled | FullyFilled | Canceled | Rejected | PendingCancel),
+            Accepted => matches!(next, PartiallyFilled | FullyFilled | Canceled | PendingCancel | PendingReplace),
+            PartiallyFilled => matches!(next, FullyFilled | Canceled | PendingCancel | PendingReplace),
+            PendingCancel => matches!(next, Accepted | PartiallyFilled | FullyFilled | Canceled),
+            PendingReplace => matches!(next, Accepted | PartiallyFilled | FullyFilled | Canceled | PendingCancel),
+            FullyFilled | Canceled | Rejected => false,
+        }
+    }
+}
+
+const ORDER_STATUSES: [OrderStatus; 8] = [
+    OrderStatus::PendingNew,
+    OrderStatus::Accepted,
+    OrderStatus::PartiallyFilled,
+    OrderStatus::FullyFilled,
+    OrderStatus::Canceled,
+    OrderStatus::Rejected,
+    OrderStatus::PendingCancel,
+    OrderStatus::PendingReplace,
+];
+
+impl OrderStatus {
+    #[inline]
+    pub fn all() -> &'static [OrderStatus] {
+        &ORDER_STATUSES
+    }
+
+    /// FIX OrdStatus (39)
+    #[inline]
+    pub fn fix_code(&self) -> char {
+        match self {
+            OrderStatus::PendingNew => 'A',
+            OrderStatus::Accepted => '0',
+            OrderStatus::PartiallyFilled => '1',
+            OrderStatus::FullyFilled => '2',
+            OrderStatus::Canceled => '4',
+            OrderStatus::Rejected => '8',
+            OrderStatus::PendingCancel => '6',
+            OrderStatus::PendingReplace => 'E',
+        }
+    }
+}
+
+impl std::fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OrderStatus::PendingNew => write!(f, "PendingNew"),
+            OrderStatus::Accepted => write!(f, "Accepted"),
+            OrderStatus::PartiallyFilled => write!(f, "PartiallyFilled"),
+            OrderStatus::FullyFilled => write!(f, "FullyFilled"),
+            OrderStatus::Canceled => write!(f, "Canceled"),
+            OrderStatus::Rejected => write!(f, "Rejected"),
+            OrderStatus::PendingCancel => write!(f, "PendingCancel"),
+            OrderStatus::PendingReplace => write!(f, "PendingReplace"),
+        }
+    }
+}
+
+impl std::str::FromStr for OrderStatus {
+    type Err = OrderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ORDER_STATUSES
+            .iter()
+            .find(|status| status.to_string() == s)
+            .copied()
+            .ok_or_else(|| OrderError::UnknownCode { kind: "OrderStatus", code: s.to_string() })
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// From FIX OrdStatus (39). Expired (C) and DoneForDay (3) are read as Canceled
+impl TryFrom<char> for OrderStatus {
+    type Error = OrderError;
+
+    fn try_from(code: char) -> Result<Self, Self::Error> {
+        match code {
+            'C' | '3' => Ok(OrderStatus::Canceled),
+            _ => ORDER_STATUSES
+                .iter()
+                .find(|status| status.fix_code() == code)
+                .copied()
+                .ok_or_else(|| OrderError::UnknownCode { kind: "OrderStatus", code: code.to_string() }),
+        }
+    }
+}
+
+/// The kind of an order: `OrderCore::core_type` and the name used by gateways
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum OrderType {
     Limit,
     Market,
     Cancel,
     Modify,
     RemoveOther,
+    StopMarket,
+    StopLimit,
+    TrailingStop,
+    IfTouched,
     Null,
-}
\ No newline at end of file
+}
+
+const ORDER_TYPES: [OrderType; 10] = [
+    OrderType::Limit,
+    OrderType::Market,
+    OrderType::Cancel,
+    OrderType::Modify,
+    OrderType::RemoveOther,
+    OrderType::StopMarket,
+    OrderType::StopLimit,
+    OrderType::TrailingStop,
+    OrderType::IfTouched,
+    OrderType::Null,
+];
+
+impl OrderType {
+    #[inline]
+    pub fn all() -> &'static [OrderType] {
+        &ORDER_TYPES
+    }
+
+    /// Numeric wire code
+    #[inline]
+    pub fn code(&self) -> u8 {
+        match self {
+            OrderType::Null => 0,
+            OrderType::Limit => 1,
+            OrderType::Market => 2,
+            OrderType::Cancel => 3,
+            OrderType::Modify => 4,
+            OrderType::RemoveOther => 5,
+            OrderType::StopMarket => 6,
+            OrderType::StopLimit => 7,
+            OrderType::TrailingStop => 8,
+            OrderType::IfTouched => 9,
+        }
+    }
+
+    /// FIX OrdType (40), for new orders only. A trailing stop goes as a stop with peg instructions
+    #[inline]
+    pub fn fix_code(&self) -> Option<char> {
+        match self {
+            OrderType::Market => Some('1'),
+            OrderType::Limit => Some('2'),
+            OrderType::StopMarket | OrderType::TrailingStop => Some('3'),
+            OrderType::StopLimit => Some('4'),
+            OrderType::IfTouched => Some('J'),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_conditional(&self) -> bool {
+        matches!(
+            self,
+            OrderType::StopMarket | OrderType::StopLimit | OrderType::TrailingStop | OrderType::IfTouched
+        )
+    }
+}
+
+impl std::fmt::Display for OrderType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OrderType::Limit => write!(f, "Limit"),
+            OrderType::Market => write!(f, "Market"),
+            OrderType::Cancel => write!(f, "Cancel"),
+            OrderType::Modify => write!(f, "Modify"),
+            OrderType::RemoveOther => write!(f, "RemoveOther"),
+            OrderType::StopMarket => write!(f, "StopMarket"),
+            OrderType::StopLimit => write!(f, "StopLimit"),
+            OrderType::TrailingStop => write!(f, "TrailingStop"),
+            OrderType::IfTouched => write!(f, "IfTouched"),
+            OrderType::Null => write!(f, "Null"),
+        }
+    }
+}
+
+/// Accepts both the `OrderType` and the `OrderCore` variant names, e.g., "Limit" and "LimitOrder"
+impl std::str::FromStr for OrderType {
+    type Err = OrderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.strip_suffix("Order").unwrap_or(s);
+        ORDER_TYPES
+            .iter()
+            .find(|order_type| order_type.to_string() == name)
+            .copied()
+            .ok_or_else(|| OrderError::UnknownCode { kind: "OrderType", code: s.to_string() })
+    }
+}
+
+impl TryFrom<u8> for OrderType {
+    type Error = OrderError;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        ORDER_TYPES
+            .iter()
+            .find(|order_type| order_type.code() == code)
+            .copied()
+            .ok_or_else(|| OrderError::UnknownCode { kind: "OrderType", code: code.to_string() })
+    }
+}
+
+/// From FIX OrdType (40). '3' is read as a stop market order
+impl TryFrom<char> for OrderType {
+    type Error = OrderError;
+
+    fn try_from(code: char) -> Result<Self, Self::Error> {
+        match code {
+            '1' => Ok(OrderType::Market),
+            '2' => Ok(OrderType::Limit),
+            '3' => Ok(OrderType::StopMarket),
+            '4' => Ok(OrderType::StopLimit),
+            'J' => Ok(OrderType::IfTouched),
+            _ => Err(OrderError::UnknownCode { kind: "OrderType", code: code.to_string() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::core::CoreType;
+
+    #[test]
+    fn test_order_type_round_trips() {
+        for order_type in OrderType::all() {
+            assert_eq!(order_type.to_string().parse::<OrderType>().unwrap(), *order_type);
+            assert_eq!(format!("{}Order", order_type).parse::<OrderType>().unwrap(), *order_type);
+            let core_type = CoreType::from(*order_type);
+            assert_eq!(OrderType::from(core_type), *order_type);
+            assert_eq!((core_type.code(), core_type.is_conditional()), (order_type.code(), order_type.is_conditional()));
+            assert_eq!(core_type.to_string(), format!("{}Order", order_type));
+            assert_eq!(core_type.to_string().parse::<CoreType>().unwrap(), core_type);
+            assert_eq!(CoreType::try_from(core_type.code()).unwrap(), core_type);
+            let json = serde_json::to_string(&core_type).unwrap();
+            assert_eq!(json, format!("\"{}\"", core_type));
+            assert_eq!(serde_json::from_str::<CoreType>(&json).unwrap(), core_type);
+            assert_eq!(OrderType::try_from(order_type.code()).unwrap(), *order_type);
+            if let Some(fix_code) = order_type.fix_code() {
+                let parsed = OrderType::try_from(fix_code).unwrap();
+                assert_eq!(parsed.fix_code(), Some(fix_code));
+            }
+        }
+        assert!(OrderType::try_from(10u8).is_err());
+        assert!("Iceberg".parse::<OrderType>().is_err());
+    }
+
+    #[test]
+    fn test_status_and_side_codes() {
+        let mut seen = rustc_hash::FxHashSet::default();
+        for status in OrderStatus::all() {
+            assert!(seen.insert(*status));
+            assert_eq!(status.to_string().parse::<OrderStatus>().unwrap(), *status);
+            assert_eq!(OrderStatus::try_from(status.fix_code()).unwrap(), *status);
+        }
+        assert_eq!(OrderStatus::try_from('C').unwrap(), OrderStatus::Canceled);
+        assert!(OrderStatus::try_from('Z').is_err());
+
+        for side in [OrderSide::Bid, OrderSide::Ask] {
+            assert_eq!(side.to_string().parse::<OrderSide>().unwrap(), side);
+            assert_eq!(OrderSide::try_from(side.fix_code()).unwrap(), side);
+        }
+        assert_eq!("Sell".parse::<OrderSide>().unwrap(), OrderSide::Ask);
+    }
+}
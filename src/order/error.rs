@@ -0,0 +1,101 @@
+use crate::{BookQuantity, ExecId, OrderId};
+use crate::order::enums::{OrderStatus, OrderType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderError {
+    IllegalTransition {
+        order_id: Option<OrderId>,
+        from: OrderStatus,
+        to: OrderStatus,
+    },
+    MissingOrderId(OrderType),
+    NotAnOrder(OrderType),
+    DuplicateOrderId(OrderId),
+    /// the id belongs to an order that is already in the history
+    RetiredOrderId(OrderId),
+    /// a new order must not be filled, canceled or rejected already
+    AlreadyTerminal {
+        order_id: Option<OrderId>,
+        status: OrderStatus,
+    },
+    UnknownOrder(OrderId),
+    NotFillable(OrderType),
+    ZeroFillQuantity(Option<OrderId>),
+    DuplicateExecId {
+        order_id: Option<OrderId>,
+        exec_id: ExecId,
+    },
+    Overfill {
+        order_id: Option<OrderId>,
+        quantity: BookQuantity,
+        filled: BookQuantity,
+        fill_quantity: BookQuantity,
+    },
+    OrderIdMismatch {
+        expected: Option<OrderId>,
+        received: OrderId,
+    },
+    NotReplaceable(OrderType),
+    NoPendingReplace(Option<OrderId>),
+    CumQuantityMismatch {
+        order_id: Option<OrderId>,
+        expected: BookQuantity,
+        reported: BookQuantity,
+    },
+    IdSpaceExhausted {
+        prefix: u16,
+    },
+    InvalidClOrdId(String),
+    InvalidAttributes(&'static str),
+    NotConditional(OrderType),
+    /// a name or wire code that maps on no variant of `kind`
+    UnknownCode {
+        kind: &'static str,
+        code: String,
+    },
+}
+
+impl std::fmt::Display for OrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OrderError::IllegalTransition { order_id, from, to } => {
+                write!(f, "illegal status transition of order {:?}: {:?} -> {:?}", order_id, from, to)
+            }
+            OrderError::MissingOrderId(core_type) => write!(f, "{} has no order id", core_type),
+            OrderError::NotAnOrder(core_type) => write!(f, "{} is not a new order", core_type),
+            OrderError::DuplicateOrderId(order_id) => write!(f, "duplicate order id: {}", order_id),
+            OrderError::RetiredOrderId(order_id) => write!(f, "order id {} belongs to a retired order", order_id),
+            OrderError::AlreadyTerminal { order_id, status } => {
+                write!(f, "order {:?} is already {:?}", order_id, status)
+            }
+            OrderError::UnknownOrder(order_id) => write!(f, "unknown order id: {}", order_id),
+            OrderError::NotFillable(core_type) => write!(f, "{} can not be filled", core_type),
+            OrderError::ZeroFillQuantity(order_id) => write!(f, "zero quantity fill on order {:?}", order_id),
+            OrderError::DuplicateExecId { order_id, exec_id } => {
+                write!(f, "duplicate execution id {} on order {:?}", exec_id, order_id)
+            }
+            OrderError::Overfill { order_id, quantity, filled, fill_quantity } => write!(
+                f,
+                "overfill on order {:?}: quantity {}, filled {}, fill {}",
+                order_id, quantity, filled, fill_quantity
+            ),
+            OrderError::OrderIdMismatch { expected, received } => {
+                write!(f, "execution report for order {} applied on order {:?}", received, expected)
+            }
+            OrderError::NotReplaceable(core_type) => write!(f, "{} can not be replaced", core_type),
+            OrderError::NoPendingReplace(order_id) => write!(f, "no replace in flight on order {:?}", order_id),
+            OrderError::CumQuantityMismatch { order_id, expected, reported } => write!(
+                f,
+                "cumulative quantity mismatch on order {:?}: expected {}, reported {}",
+                order_id, expected, reported
+            ),
+            OrderError::IdSpaceExhausted { prefix } => write!(f, "no order id left with the prefix {}", prefix),
+            OrderError::InvalidClOrdId(clordid) => write!(f, "invalid ClOrdID: {}", clordid),
+            OrderError::InvalidAttributes(reason) => write!(f, "invalid order attributes: {}", reason),
+            OrderError::NotConditional(core_type) => write!(f, "{} is not a conditional order", core_type),
+            OrderError::UnknownCode { kind, code } => write!(f, "unknown {} code: {}", kind, code),
+        }
+    }
+}
+
+impl std::error::Error for OrderError {}
@@ -0,0 +1,138 @@
+use crate::{BookPrice, BookQuantity, ExecId, InstId, OrderId, TimeStamp};
+use crate::order::enums::OrderStatus;
+use crate::order::fill::Fill;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ExecType {
+    New,
+    Trade,
+    Canceled,
+    Replaced,
+    PendingCancel,
+    PendingReplace,
+    Rejected,
+    /// the cancel or replace request was rejected and the order keeps working
+    CancelRejected,
+    Expired,
+}
+
+/// The request answered by a `CancelRejected` report
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum CancelRejectResponseTo {
+    Cancel,
+    Replace,
+}
+
+/// An execution report from the venue about one of my orders
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExecutionReport {
+    pub exec_id: ExecId,
+    pub order_id: OrderId,
+    /// the id before a cancel/replace, when the venue assigns a new id on replace
+    pub orig_order_id: Option<OrderId>,
+    pub instid: InstId,
+    pub exec_type: ExecType,
+    //
+    pub last_price: BookPrice,
+    pub last_quantity: BookQuantity,
+    pub cum_quantity: BookQuantity,
+    pub leaves_quantity: BookQuantity,
+    pub avg_price: f64,
+    /// the terms of the order after this report, if the venue reports them
+    pub order_price: Option<BookPrice>,
+    pub order_quantity: Option<BookQuantity>,
+    //
+    pub reject_reason: Option<String>,
+    /// set on `CancelRejected` reports, when the venue tells which request was rejected
+    pub response_to: Option<CancelRejectResponseTo>,
+    //
+    pub datatime: TimeStamp, // venue time
+    pub systemtime: TimeStamp,
+}
+
+impl ExecutionReport {
+    pub fn new(exec_id: ExecId, order_id: OrderId, instid: InstId, exec_type: ExecType) -> Self {
+        Self {
+            exec_id,
+            order_id,
+            orig_order_id: None,
+            instid,
+            exec_type,
+            last_price: 0,
+            last_quantity: 0,
+            cum_quantity: 0,
+            leaves_quantity: 0,
+            avg_price: 0.0,
+            order_price: None,
+            order_quantity: None,
+            reject_reason: None,
+            response_to: None,
+            datatime: 0,
+            systemtime: 0,
+        }
+    }
+
+    pub fn with_orig_order_id(mut self, orig_order_id: OrderId) -> Self {
+        self.orig_order_id = Some(orig_order_id);
+        self
+    }
+
+    pub fn with_fill(mut self, last_price: BookPrice, last_quantity: BookQuantity) -> Self {
+        self.last_price = last_price;
+        self.last_quantity = last_quantity;
+        self
+    }
+
+    pub fn with_quantities(mut self, cum_quantity: BookQuantity, leaves_quantity: BookQuantity, avg_price: f64) -> Self {
+        self.cum_quantity = cum_quantity;
+        self.leaves_quantity = leaves_quantity;
+        self.avg_price = avg_price;
+        self
+    }
+
+    pub fn with_order_terms(mut self, order_price: Option<BookPrice>, order_quantity: Option<BookQuantity>) -> Self {
+        self.order_price = order_price;
+        self.order_quantity = order_quantity;
+        self
+    }
+
+    pub fn with_reject_reason(mut self, reject_reason: &str) -> Self {
+        self.reject_reason = Some(reject_reason.to_string());
+        self
+    }
+
+    pub fn with_response_to(mut self, response_to: CancelRejectResponseTo) -> Self {
+        self.response_to = Some(response_to);
+        self
+    }
+
+    pub fn with_times(mut self, datatime: TimeStamp, systemtime: TimeStamp) -> Self {
+        self.datatime = datatime;
+        self.systemtime = systemtime;
+        self
+    }
+
+    /// The fill carried by a trade report
+    #[inline]
+    pub fn fill(&self) -> Option<Fill> {
+        match self.exec_type {
+            ExecType::Trade => Some(Fill::new(self.exec_id, self.last_price, self.last_quantity, self.datatime)),
+            _ => None,
+        }
+    }
+
+    /// The status of the order after this report
+    pub fn ord_status(&self) -> OrderStatus {
+        let working = if self.cum_quantity > 0 { OrderStatus::PartiallyFilled } else { OrderStatus::Accepted };
+        match self.exec_type {
+            ExecType::New | ExecType::Replaced | ExecType::CancelRejected => working,
+            ExecType::Trade if self.leaves_quantity == 0 => OrderStatus::FullyFilled,
+            ExecType::Trade => OrderStatus::PartiallyFilled,
+            ExecType::Canceled | ExecType::Expired => OrderStatus::Canceled,
+            ExecType::PendingCancel => OrderStatus::PendingCancel,
+            ExecType::PendingReplace => OrderStatus::PendingReplace,
+            ExecType::Rejected => OrderStatus::Rejected,
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use crate::{BookPrice, BookQuantity, ExecId, TimeStamp};
+use crate::order::enums::OrderStatus;
+use serde::{Deserialize, Serialize};
+
+/// An execution of my order
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Fill {
+    pub exec_id: ExecId,
+    pub price: BookPrice,
+    pub quantity: BookQuantity,
+    pub time: TimeStamp,
+}
+
+impl Fill {
+    #[inline]
+    pub fn new(exec_id: ExecId, price: BookPrice, quantity: BookQuantity, time: TimeStamp) -> Self {
+        Self {
+            exec_id,
+            price,
+            quantity,
+            time,
+        }
+    }
+}
+
+/// The state of the order right after a fill is applied
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct FillResult {
+    pub status: OrderStatus,
+    pub filled: BookQuantity,
+    pub leaves: BookQuantity,
+    /// average fill price in book units
+    pub avg_price: f64,
+}
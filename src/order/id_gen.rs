@@ -0,0 +1,389 @@
+use crate::{OrderId, UnixNano};
+use crate::order::error::OrderError;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Bits of an `OrderId` taken by the session prefix
+pub const PREFIX_BITS: u32 = 16;
+/// Bits of an `OrderId` taken by the sequence
+pub const SEQUENCE_BITS: u32 = 64 - PREFIX_BITS;
+pub const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+
+#[inline]
+pub fn compose(prefix: u16, sequence: u64) -> OrderId {
+    ((prefix as u64) << SEQUENCE_BITS) | (sequence & MAX_SEQUENCE)
+}
+
+#[inline]
+pub fn prefix_of(order_id: OrderId) -> u16 {
+    (order_id >> SEQUENCE_BITS) as u16
+}
+
+#[inline]
+pub fn sequence_of(order_id: OrderId) -> u64 {
+    order_id & MAX_SEQUENCE
+}
+
+/// Lock-free generator of unique and increasing order ids.
+///
+/// An id is `prefix << 48 | sequence`. The prefix separates sessions (or processes) sharing a venue account,
+/// and the sequence only goes up. To stay unique after a restart, the sequence must start above
+/// every id issued before: either seed it from the clock (`from_time`) or from a persisted high-water mark.
+#[derive(Debug)]
+pub struct OrderIdGenerator {
+    prefix: u16,
+    next: AtomicU64,
+}
+
+impl OrderIdGenerator {
+    /// The first id has the sequence `seed`
+    pub fn new(prefix: u16, seed: u64) -> Result<Self, OrderError> {
+        if seed > MAX_SEQUENCE {
+            return Err(OrderError::IdSpaceExhausted { prefix });
+        }
+        Ok(Self {
+            prefix,
+            next: AtomicU64::new(seed),
+        })
+    }
+
+    /// Seeds with the milliseconds since the unix epoch. Ids stay unique across restarts
+    /// as long as the previous run issued less than one id per millisecond on average.
+    pub fn from_time(prefix: u16, now: UnixNano) -> Self {
+        Self {
+            prefix,
+            next: AtomicU64::new((now / 1_000_000).min(MAX_SEQUENCE)),
+        }
+    }
+
+    /// Resumes after the last issued id (`high_water_mark`) of a previous run
+    pub fn from_high_water_mark(prefix: u16, high_water_mark: OrderId) -> Result<Self, OrderError> {
+        Self::new(prefix, sequence_of(high_water_mark) + 1)
+    }
+
+    /// Resumes from the high-water mark stored in `path`, and from the clock if the file does not exist.
+    /// The larger of the two seeds is taken.
+    pub fn load(prefix: u16, path: impl AsRef<Path>, now: UnixNano) -> std::io::Result<Self> {
+        let by_time = Self::from_time(prefix, now);
+        match load_high_water_mark(path)? {
+            Some(high_water_mark) => {
+                let generator = Self::from_high_water_mark(prefix, high_water_mark)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                Ok(if generator.peek() > by_time.peek() { generator } else { by_time })
+            }
+            None => Ok(by_time),
+        }
+    }
+
+    #[inline]
+    pub fn prefix(&self) -> u16 {
+        self.prefix
+    }
+
+    /// The id the next call to `next_id` would return, without consuming it
+    #[inline]
+    pub fn peek(&self) -> OrderId {
+        compose(self.prefix, self.next.load(Ordering::Acquire))
+    }
+
+    /// The last issued id, to be persisted for the next run. It saturates at `MAX_SEQUENCE` once the space is exhausted
+    #[inline]
+    pub fn high_water_mark(&self) -> OrderId {
+        let next = self.next.load(Ordering::Acquire).min(MAX_SEQUENCE + 1);
+        compose(self.prefix, next.saturating_sub(1))
+    }
+
+    #[inline]
+    pub fn next_id(&self) -> Result<OrderId, OrderError> {
+        let sequence = self.next.fetch_add(1, Ordering::AcqRel);
+        if sequence > MAX_SEQUENCE {
+            return Err(OrderError::IdSpaceExhausted { prefix: self.prefix });
+        }
+        Ok(compose(self.prefix, sequence))
+    }
+
+    /// Reserves `size` consecutive ids at once, to be handed out without touching the shared counter.
+    /// A failed reservation consumes nothing
+    pub fn reserve(&self, size: u64) -> Result<IdBlock, OrderError> {
+        let end_of = |start: u64| start.checked_add(size).filter(|end| *end <= MAX_SEQUENCE + 1);
+        let start = self
+            .next
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, end_of)
+            .map_err(|_| OrderError::IdSpaceExhausted { prefix: self.prefix })?;
+        Ok(IdBlock {
+            prefix: self.prefix,
+            next: start,
+            end: start + size,
+        })
+    }
+
+    /// Writes the high-water mark so that the next run can resume with `load`
+    pub fn store_high_water_mark(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        store_high_water_mark(path, self.high_water_mark())
+    }
+}
+
+pub fn load_high_water_mark(path: impl AsRef<Path>) -> std::io::Result<Option<OrderId>> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => s
+            .trim()
+            .parse::<OrderId>()
+            .map(Some)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Written and synced to a temporary file first and renamed, so a crash never leaves a truncated mark.
+/// The directory is synced after the rename, so the new mark survives a crash once this returns
+pub fn store_high_water_mark(path: impl AsRef<Path>, high_water_mark: OrderId) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let path = path.as_ref();
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(high_water_mark.to_string().as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(tmp, path)?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::File::open(dir)?.sync_all()
+}
+
+/// A range of ids reserved from an `OrderIdGenerator`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdBlock {
+    prefix: u16,
+    next: u64,
+    end: u64,
+}
+
+impl IdBlock {
+    #[inline]
+    pub fn remaining(&self) -> u64 {
+        self.end - self.next
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.next >= self.end
+    }
+}
+
+impl Iterator for IdBlock {
+    type Item = OrderId;
+
+    #[inline]
+    fn next(&mut self) -> Option<OrderId> {
+        if self.next >= self.end {
+            return None;
+        }
+        let sequence = self.next;
+        self.next += 1;
+        Some(compose(self.prefix, sequence))
+    }
+}
+
+/// A per-thread generator refilling its block from a shared `OrderIdGenerator`.
+/// Ids are increasing within a thread, but not across threads.
+#[derive(Debug)]
+pub struct LocalIdGenerator {
+    shared: Arc<OrderIdGenerator>,
+    block: IdBlock,
+    block_size: u64,
+}
+
+impl LocalIdGenerator {
+    pub fn new(shared: Arc<OrderIdGenerator>, block_size: u64) -> Self {
+        let block = IdBlock {
+            prefix: shared.prefix(),
+            next: 0,
+            end: 0,
+        };
+        Self {
+            shared,
+            block,
+            block_size: block_size.max(1),
+        }
+    }
+
+    #[inline]
+    pub fn next_id(&mut self) -> Result<OrderId, OrderError> {
+        if let Some(order_id) = self.block.next() {
+            return Ok(order_id);
+        }
+        self.block = self.shared.reserve(self.block_size)?;
+        self.block.next().ok_or(OrderError::IdSpaceExhausted { prefix: self.shared.prefix() })
+    }
+}
+
+const DIGITS: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// The alphanumeric encoding of an `OrderId` into a ClOrdID string.
+/// Base36 uses upper case letters only, for venues with case insensitive ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClOrdIdEncoding {
+    Decimal,
+    Base36,
+    Base62,
+}
+
+impl ClOrdIdEncoding {
+    #[inline]
+    pub fn radix(&self) -> u64 {
+        match self {
+            ClOrdIdEncoding::Decimal => 10,
+            ClOrdIdEncoding::Base36 => 36,
+            ClOrdIdEncoding::Base62 => 62,
+        }
+    }
+
+    /// The length of `u64::MAX` in this encoding: a zero-padded id of this width sorts like the id
+    pub fn max_width(&self) -> usize {
+        match self {
+            ClOrdIdEncoding::Decimal => 20,
+            ClOrdIdEncoding::Base36 => 13,
+            ClOrdIdEncoding::Base62 => 11,
+        }
+    }
+
+    /// Shortest encoding of `order_id`
+    pub fn encode(&self, order_id: OrderId) -> String {
+        self.encode_padded(order_id, 1)
+    }
+
+    /// Zero-padded to `width` characters at least
+    pub fn encode_padded(&self, order_id: OrderId, width: usize) -> String {
+        let radix = self.radix();
+        let mut buf = [b'0'; 20];
+        let mut pos = buf.len();
+        let mut value = order_id;
+        loop {
+            pos -= 1;
+            buf[pos] = DIGITS[(value % radix) as usize];
+            value /= radix;
+            if value == 0 {
+                break;
+            }
+        }
+        let digits = std::str::from_utf8(&buf[pos..]).expect("ascii digits");
+        format!("{:0>width$}", digits, width = width)
+    }
+
+    pub fn decode(&self, clordid: &str) -> Result<OrderId, OrderError> {
+        let invalid = || OrderError::InvalidClOrdId(clordid.to_string());
+        if clordid.is_empty() {
+            return Err(invalid());
+        }
+        let radix = self.radix();
+        clordid.bytes().try_fold(0u64, |acc, b| {
+            let digit = match b {
+                b'0'..=b'9' => (b - b'0') as u64,
+                b'A'..=b'Z' => (b - b'A') as u64 + 10,
+                b'a'..=b'z' => (b - b'a') as u64 + 36,
+                _ => return Err(invalid()),
+            };
+            if digit >= radix {
+                return Err(invalid());
+            }
+            acc.checked_mul(radix).and_then(|v| v.checked_add(digit)).ok_or_else(invalid)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_ids_are_unique_across_threads() {
+        let generator = Arc::new(OrderIdGenerator::new(7, 1).unwrap());
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let generator = generator.clone();
+                std::thread::spawn(move || {
+                    if i % 2 == 0 {
+                        (0..1000).map(|_| generator.next_id().unwrap()).collect::<Vec<_>>()
+                    } else {
+                        let mut local = LocalIdGenerator::new(generator, 64);
+                        (0..1000).map(|_| local.next_id().unwrap()).collect::<Vec<_>>()
+                    }
+                })
+            })
+            .collect();
+
+        let mut seen = HashSet::new();
+        for handle in handles {
+            let ids = handle.join().unwrap();
+            assert!(ids.windows(2).all(|w| w[0] < w[1]));
+            for id in ids {
+                assert_eq!(prefix_of(id), 7);
+                assert!(seen.insert(id));
+            }
+        }
+        assert_eq!(seen.len(), 4000);
+    }
+
+    #[test]
+    fn test_restart_from_high_water_mark() {
+        let path = std::env::temp_dir().join(format!("id_gen_hwm_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let first = OrderIdGenerator::load(3, &path, 0).unwrap();
+        let last = (0..10).map(|_| first.next_id().unwrap()).last().unwrap();
+        first.store_high_water_mark(&path).unwrap();
+        assert_eq!(load_high_water_mark(&path).unwrap(), Some(last));
+
+        let second = OrderIdGenerator::load(3, &path, 0).unwrap();
+        assert!(second.next_id().unwrap() > last);
+        // the clock wins when it is ahead of the mark
+        let third = OrderIdGenerator::load(3, &path, 5_000_000_000).unwrap();
+        assert_eq!(sequence_of(third.next_id().unwrap()), 5_000);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_id_space_exhausted() {
+        let generator = OrderIdGenerator::new(1, MAX_SEQUENCE).unwrap();
+        assert_eq!(generator.next_id().map(sequence_of), Ok(MAX_SEQUENCE));
+        assert_eq!(generator.next_id(), Err(OrderError::IdSpaceExhausted { prefix: 1 }));
+        assert!(generator.reserve(1).is_err());
+        assert_eq!(sequence_of(generator.high_water_mark()), MAX_SEQUENCE);
+
+        let generator = OrderIdGenerator::new(1, 10).unwrap();
+        assert!(generator.reserve(u64::MAX).is_err());
+        assert!(generator.reserve(MAX_SEQUENCE).is_err());
+        assert_eq!(generator.next_id().map(sequence_of), Ok(10));
+        let block = generator.reserve(MAX_SEQUENCE - 10).unwrap();
+        assert_eq!(block.remaining(), MAX_SEQUENCE - 10);
+        assert!(generator.reserve(1).is_err());
+        assert_eq!(sequence_of(generator.high_water_mark()), MAX_SEQUENCE);
+    }
+
+    #[test]
+    fn test_clordid_encoding() {
+        for encoding in [ClOrdIdEncoding::Decimal, ClOrdIdEncoding::Base36, ClOrdIdEncoding::Base62] {
+            for id in [0, 1, 35, 61, 62, 123_456_789, compose(u16::MAX, MAX_SEQUENCE)] {
+                let s = encoding.encode(id);
+                assert!(s.len() <= encoding.max_width());
+                assert_eq!(encoding.decode(&s), Ok(id));
+                assert_eq!(encoding.decode(&encoding.encode_padded(id, 12)), Ok(id));
+            }
+            // padded ids sort like the ids
+            let a = encoding.encode_padded(99, encoding.max_width());
+            let b = encoding.encode_padded(100, encoding.max_width());
+            assert!(a < b);
+        }
+        assert_eq!(ClOrdIdEncoding::Base36.encode(35), "Z");
+        assert_eq!(ClOrdIdEncoding::Base62.encode(61), "z");
+        assert!(ClOrdIdEncoding::Base36.decode("z").is_err());
+        assert!(ClOrdIdEncoding::Decimal.decode("").is_err());
+        assert!(ClOrdIdEncoding::Base62.decode("zzzzzzzzzzzz").is_err());
+    }
+}
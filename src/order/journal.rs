@@ -0,0 +1,523 @@
+use crate::{InstId, OrderCore, OrderId, TimeStamp};
+use crate::order::core::ModifyOrder;
+use crate::order::enums::OrderStatus;
+use crate::order::error::OrderError;
+use crate::order::execution::ExecutionReport;
+use crate::order::fill::Fill;
+use crate::order::manager::OrderManager;
+use crate::order::request::OrderRequest;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const JOURNAL_MAGIC: [u8; 4] = *b"OJNL";
+pub const JOURNAL_VERSION: u16 = 2;
+/// magic, version (u16 LE), reserved (u16)
+pub const HEADER_LEN: usize = 8;
+/// payload length (u32 LE), crc32 of the payload (u32 LE), crc32 of the 8 bytes before (u32 LE)
+const RECORD_HEADER_LEN: usize = 12;
+/// the longest payload appended
+pub const MAX_PAYLOAD_LEN: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JournalError {
+    Io(String),
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// the last record was cut short, e.g., the process died while writing it
+    Truncated { offset: u64 },
+    ChecksumMismatch { offset: u64 },
+    Decode { offset: u64, msg: String },
+    /// the payload is longer than `MAX_PAYLOAD_LEN`
+    RecordTooLarge(usize),
+    /// the entry was journaled, but the manager refused it
+    Order(OrderError),
+}
+
+impl std::fmt::Display for JournalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JournalError::Io(msg) => write!(f, "journal io error: {}", msg),
+            JournalError::BadMagic => write!(f, "not an order journal"),
+            JournalError::UnsupportedVersion(version) => write!(f, "unsupported journal version: {}", version),
+            JournalError::Truncated { offset } => write!(f, "truncated journal record at {}", offset),
+            JournalError::ChecksumMismatch { offset } => write!(f, "corrupted journal record at {}", offset),
+            JournalError::Decode { offset, msg } => write!(f, "undecodable journal record at {}: {}", offset, msg),
+            JournalError::RecordTooLarge(len) => write!(f, "journal record of {} bytes, the maximum is {}", len, MAX_PAYLOAD_LEN),
+            JournalError::Order(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+impl From<std::io::Error> for JournalError {
+    fn from(e: std::io::Error) -> Self {
+        JournalError::Io(e.to_string())
+    }
+}
+
+impl From<OrderError> for JournalError {
+    fn from(e: OrderError) -> Self {
+        JournalError::Order(e)
+    }
+}
+
+/// One change of my orders, mirroring the `OrderManager` calls
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum JournalEntry {
+    /// a new order sent
+    New { instid: InstId, order_core: OrderCore, time: TimeStamp },
+    Accepted { order_id: OrderId, time: TimeStamp },
+    Rejected { order_id: OrderId, time: TimeStamp },
+    CancelRequested { order_id: OrderId, time: TimeStamp },
+    CancelRejected { order_id: OrderId, time: TimeStamp },
+    Canceled { order_id: OrderId, time: TimeStamp },
+    ReplaceRequested { modify: ModifyOrder, time: TimeStamp },
+    ReplaceRejected { order_id: OrderId, time: TimeStamp },
+    Fill { order_id: OrderId, fill: Fill },
+    ExecutionReport(ExecutionReport),
+}
+
+impl JournalEntry {
+    pub fn apply(&self, manager: &mut OrderManager) -> Result<OrderStatus, OrderError> {
+        match self {
+            JournalEntry::New { instid, order_core, time } => {
+                manager.insert(OrderRequest::new(*instid, order_core.clone(), *time))?;
+                Ok(OrderStatus::PendingNew)
+            }
+            JournalEntry::Accepted { order_id, time } => manager.on_accepted(*order_id, *time),
+            JournalEntry::Rejected { order_id, time } => manager.on_rejected(*order_id, *time),
+            JournalEntry::CancelRequested { order_id, time } => manager.on_cancel_requested(*order_id, *time),
+            JournalEntry::CancelRejected { order_id, time } => manager.on_cancel_rejected(*order_id, *time),
+            JournalEntry::Canceled { order_id, time } => manager.on_canceled(*order_id, *time),
+            JournalEntry::ReplaceRequested { modify, time } => manager.on_replace_requested(modify, *time),
+            JournalEntry::ReplaceRejected { order_id, time } => manager.on_replace_rejected(*order_id, *time),
+            JournalEntry::Fill { order_id, fill } => manager.on_fill(*order_id, fill),
+            JournalEntry::ExecutionReport(report) => manager.on_execution_report(report),
+        }
+    }
+}
+
+/// When the journal is forced to disk. Every record is handed to the OS as soon as it is appended,
+/// so it survives a crash of the process in any case; fsync protects against a crash of the machine
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SyncPolicy {
+    None,
+    /// fsync every `n` records
+    Batch(usize),
+    EveryWrite,
+}
+
+/// Append-only journal of `JournalEntry`s.
+///
+/// The file starts with a header (magic and format version). Each record is
+/// `[payload length: u32][crc32 of payload: u32][crc32 of the length and payload crc: u32][payload: JSON]`,
+/// little-endian. The header checksum tells a torn last record from a corrupted length.
+#[derive(Debug)]
+pub struct OrderJournal {
+    path: PathBuf,
+    file: File,
+    policy: SyncPolicy,
+    unsynced: usize,
+    // the end of the last complete record
+    len: u64,
+    buf: Vec<u8>,
+}
+
+impl OrderJournal {
+    /// Opens (or creates) the journal at `path` for appending.
+    /// A torn record at the end of an existing journal is cut off. A corrupted record is an error
+    /// and the file is left as it is, since the records after it may still be good
+    pub fn open(path: impl AsRef<Path>, policy: SyncPolicy) -> Result<Self, JournalError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        if file.metadata()?.len() == 0 {
+            let mut header = [0u8; HEADER_LEN];
+            header[..4].copy_from_slice(&JOURNAL_MAGIC);
+            header[4..6].copy_from_slice(&JOURNAL_VERSION.to_le_bytes());
+            file.write_all(&header)?;
+            file.sync_all()?;
+        } else {
+            let scan = JournalReader::open(&path)?.scan()?;
+            match scan.tail_error {
+                None => {}
+                Some(error @ JournalError::Truncated { .. }) => {
+                    flashlog::flash_warn!("JOURNAL"; "cutting off the journal at {}: {}", scan.valid_len, error);
+                    file.set_len(scan.valid_len)?;
+                    file.sync_all()?;
+                }
+                Some(error) => return Err(error),
+            }
+        }
+        let len = file.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            path,
+            file,
+            policy,
+            unsynced: 0,
+            len,
+            buf: Vec::with_capacity(256),
+        })
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the record (and syncs per the policy). On failure, the file is cut back to the last complete record
+    pub fn append(&mut self, entry: &JournalEntry) -> Result<(), JournalError> {
+        self.buf.clear();
+        self.buf.extend_from_slice(&[0u8; RECORD_HEADER_LEN]);
+        serde_json::to_writer(&mut self.buf, entry).map_err(|e| JournalError::Io(e.to_string()))?;
+        seal_record(&mut self.buf)?;
+
+        self.unsynced += 1;
+        let written = self.file.write_all(&self.buf).map_err(JournalError::from).and_then(|_| match self.policy {
+            SyncPolicy::None => Ok(()),
+            SyncPolicy::Batch(n) if self.unsynced < n.max(1) => Ok(()),
+            SyncPolicy::Batch(_) | SyncPolicy::EveryWrite => self.sync(),
+        });
+        match written {
+            Ok(()) => {
+                self.len += self.buf.len() as u64;
+                Ok(())
+            }
+            Err(e) => {
+                self.unsynced -= 1;
+                let len = self.len;
+                if let Err(rollback) = self.file.set_len(len).and_then(|_| self.file.seek(SeekFrom::Start(len))) {
+                    flashlog::flash_error!("JOURNAL"; "failed to cut the journal back to {}: {}", len, rollback);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Appends the entry and then applies it on `manager`. Nothing is applied if the entry could not be journaled.
+    /// An entry the manager refuses stays in the journal and is skipped on replay
+    pub fn record(&mut self, entry: JournalEntry, manager: &mut OrderManager) -> Result<OrderStatus, JournalError> {
+        self.append(&entry)?;
+        Ok(entry.apply(manager)?)
+    }
+
+    pub fn sync(&mut self) -> Result<(), JournalError> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+}
+
+impl Drop for OrderJournal {
+    fn drop(&mut self) {
+        if self.unsynced > 0 {
+            let _ = self.file.sync_data();
+        }
+    }
+}
+
+/// Fills in the record header of the payload following it in `record`
+fn seal_record(record: &mut [u8]) -> Result<(), JournalError> {
+    let len = record.len() - RECORD_HEADER_LEN;
+    let payload_len = u32::try_from(len)
+        .ok()
+        .filter(|_| len <= MAX_PAYLOAD_LEN)
+        .ok_or(JournalError::RecordTooLarge(len))?;
+    let crc = crc32(&record[RECORD_HEADER_LEN..]);
+    record[..4].copy_from_slice(&payload_len.to_le_bytes());
+    record[4..8].copy_from_slice(&crc.to_le_bytes());
+    let header_crc = crc32(&record[..8]);
+    record[8..12].copy_from_slice(&header_crc.to_le_bytes());
+    Ok(())
+}
+
+/// The entries read from a journal
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalScan {
+    pub entries: Vec<JournalEntry>,
+    /// the length of the journal up to the last good record
+    pub valid_len: u64,
+    /// why reading stopped before the end of the file
+    pub tail_error: Option<JournalError>,
+}
+
+pub struct JournalReader {
+    data: Vec<u8>,
+}
+
+impl JournalReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, JournalError> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        Ok(Self { data })
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
+    /// Reads up to the first bad record. A bad file header is an error.
+    /// Only a record cut short at the end of the file (or a zero-filled tail, space allocated but never written)
+    /// is `Truncated`; anything else is corruption
+    pub fn scan(&self) -> Result<JournalScan, JournalError> {
+        let data = &self.data;
+        if data.len() < HEADER_LEN || data[..4] != JOURNAL_MAGIC {
+            return Err(JournalError::BadMagic);
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != JOURNAL_VERSION {
+            return Err(JournalError::UnsupportedVersion(version));
+        }
+
+        let mut entries = Vec::new();
+        let mut offset = HEADER_LEN;
+        let mut tail_error = None;
+        while offset < data.len() {
+            let at = offset as u64;
+            let rest = &data[offset..];
+            if rest.len() < RECORD_HEADER_LEN {
+                tail_error = Some(JournalError::Truncated { offset: at });
+                break;
+            }
+            let word = |i: usize| u32::from_le_bytes(rest[i..i + 4].try_into().expect("4 bytes"));
+            let (len, crc) = (word(0) as usize, word(4));
+            if crc32(&rest[..8]) != word(8) {
+                tail_error = Some(match rest.iter().all(|b| *b == 0) {
+                    true => JournalError::Truncated { offset: at },
+                    false => JournalError::ChecksumMismatch { offset: at },
+                });
+                break;
+            }
+            let start = offset + RECORD_HEADER_LEN;
+            if len > MAX_PAYLOAD_LEN {
+                tail_error = Some(JournalError::ChecksumMismatch { offset: at });
+                break;
+            }
+            if data.len() - start < len {
+                tail_error = Some(JournalError::Truncated { offset: at });
+                break;
+            }
+            let payload = &data[start..start + len];
+            if crc32(payload) != crc {
+                tail_error = Some(JournalError::ChecksumMismatch { offset: at });
+                break;
+            }
+            match serde_json::from_slice::<JournalEntry>(payload) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    tail_error = Some(JournalError::Decode { offset: at, msg: e.to_string() });
+                    break;
+                }
+            }
+            offset = start + len;
+        }
+        Ok(JournalScan {
+            entries,
+            valid_len: offset as u64,
+            tail_error,
+        })
+    }
+}
+
+/// The result of a replay
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub applied: usize,
+    /// entries the manager refused, e.g., an order id journaled twice
+    pub skipped: usize,
+    pub tail_error: Option<JournalError>,
+}
+
+/// Rebuilds the state of my orders from the journal at `path` into `manager` (normally empty)
+pub fn replay(path: impl AsRef<Path>, manager: &mut OrderManager) -> Result<Replay, JournalError> {
+    let scan = JournalReader::open(path)?.scan()?;
+    let mut replay = Replay {
+        applied: 0,
+        skipped: 0,
+        tail_error: scan.tail_error,
+    };
+    for entry in &scan.entries {
+        match entry.apply(manager) {
+            Ok(_) => replay.applied += 1,
+            Err(e) => {
+                flashlog::flash_warn!("JOURNAL"; "skipped journal entry on replay: {}", e);
+                replay.skipped += 1;
+            }
+        }
+    }
+    Ok(replay)
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE)
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::core::LimitOrder;
+    use crate::order::enums::OrderSide;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}_{}.journal", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn new_order(order_id: OrderId) -> JournalEntry {
+        JournalEntry::New {
+            instid: InstId::from_str("005930", "KRX"),
+            order_core: OrderCore::LimitOrder(LimitOrder::new(100, 10, OrderSide::Bid, order_id)),
+            time: 1,
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_replay_rebuilds_manager() {
+        let path = temp_path("replay");
+        let mut manager = OrderManager::default();
+        {
+            let mut journal = OrderJournal::open(&path, SyncPolicy::Batch(2)).unwrap();
+            journal.record(new_order(1), &mut manager).unwrap();
+            journal.record(new_order(2), &mut manager).unwrap();
+            journal.record(JournalEntry::Accepted { order_id: 1, time: 2 }, &mut manager).unwrap();
+            journal.record(JournalEntry::Fill { order_id: 1, fill: Fill::new(7, 100, 4, 3) }, &mut manager).unwrap();
+            journal.record(JournalEntry::Rejected { order_id: 2, time: 3 }, &mut manager).unwrap();
+            journal.record(JournalEntry::ReplaceRequested { modify: ModifyOrder::new(1, 99, 10), time: 4 }, &mut manager).unwrap();
+            // journaled, but refused by the manager
+            assert!(matches!(journal.record(new_order(1), &mut manager), Err(JournalError::Order(_))));
+        }
+
+        let mut replayed = OrderManager::default();
+        let replay = replay(&path, &mut replayed).unwrap();
+        assert_eq!(replay, Replay { applied: 6, skipped: 1, tail_error: None });
+        assert_eq!(replayed.get(&1), manager.get(&1));
+        assert_eq!(replayed.find(&2), manager.find(&2));
+        assert_eq!(replayed.len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_corruption_detection() {
+        let path = temp_path("corruption");
+        {
+            let mut journal = OrderJournal::open(&path, SyncPolicy::EveryWrite).unwrap();
+            journal.append(&new_order(1)).unwrap();
+            journal.append(&new_order(2)).unwrap();
+        }
+        let data = std::fs::read(&path).unwrap();
+        let full_len = data.len();
+
+        // a torn write at the end
+        std::fs::write(&path, &data[..full_len - 3]).unwrap();
+        let scan = JournalReader::open(&path).unwrap().scan().unwrap();
+        assert_eq!(scan.entries.len(), 1);
+        assert!(matches!(scan.tail_error, Some(JournalError::Truncated { .. })));
+        // reopening cuts the torn record off and appends after the good ones
+        {
+            let mut journal = OrderJournal::open(&path, SyncPolicy::None).unwrap();
+            journal.append(&new_order(3)).unwrap();
+        }
+        let scan = JournalReader::open(&path).unwrap().scan().unwrap();
+        assert_eq!((scan.entries.len(), scan.tail_error), (2, None));
+
+        // a flipped byte
+        let mut data = std::fs::read(&path).unwrap();
+        let last = data.len() - 2;
+        data[last] ^= 0xFF;
+        let scan = JournalReader::from_bytes(data.clone()).scan().unwrap();
+        assert_eq!(scan.entries.len(), 1);
+        assert!(matches!(scan.tail_error, Some(JournalError::ChecksumMismatch { .. })));
+
+        data[4] = 9;
+        assert_eq!(JournalReader::from_bytes(data).scan().unwrap_err(), JournalError::UnsupportedVersion(9));
+        assert_eq!(JournalReader::from_bytes(b"nope".to_vec()).scan().unwrap_err(), JournalError::BadMagic);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_mid_file_corruption_is_not_cut() {
+        let path = temp_path("mid_file");
+        {
+            let mut journal = OrderJournal::open(&path, SyncPolicy::EveryWrite).unwrap();
+            (1..=3).for_each(|order_id| journal.append(&new_order(order_id)).unwrap());
+        }
+        let mut data = std::fs::read(&path).unwrap();
+        let first_payload = HEADER_LEN + RECORD_HEADER_LEN;
+        data[first_payload + 5] ^= 0xFF;
+        std::fs::write(&path, &data).unwrap();
+
+        let error = OrderJournal::open(&path, SyncPolicy::None).unwrap_err();
+        assert_eq!(error, JournalError::ChecksumMismatch { offset: HEADER_LEN as u64 });
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+
+        // a corrupted length is not taken for a torn record either
+        data[first_payload + 5] ^= 0xFF;
+        data[HEADER_LEN + 3] = 0x7F;
+        std::fs::write(&path, &data).unwrap();
+        assert!(matches!(OrderJournal::open(&path, SyncPolicy::None), Err(JournalError::ChecksumMismatch { .. })));
+        assert_eq!(std::fs::read(&path).unwrap().len(), data.len());
+
+        // neither is a plausible length running past the end of the file
+        let past_the_end = data.len() as u32;
+        data[HEADER_LEN..HEADER_LEN + 4].copy_from_slice(&past_the_end.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+        let error = OrderJournal::open(&path, SyncPolicy::None).unwrap_err();
+        assert_eq!(error, JournalError::ChecksumMismatch { offset: HEADER_LEN as u64 });
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_zero_filled_tail_is_cut() {
+        let path = temp_path("zero_tail");
+        {
+            let mut journal = OrderJournal::open(&path, SyncPolicy::EveryWrite).unwrap();
+            journal.append(&new_order(1)).unwrap();
+        }
+        let good_len = std::fs::metadata(&path).unwrap().len();
+        let mut data = std::fs::read(&path).unwrap();
+        data.resize(data.len() + 40, 0);
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(JournalReader::from_bytes(data).scan().unwrap().tail_error, Some(JournalError::Truncated { offset: good_len }));
+
+        OrderJournal::open(&path, SyncPolicy::None).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), good_len);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_record_too_large() {
+        let mut record = vec![0u8; RECORD_HEADER_LEN + MAX_PAYLOAD_LEN + 1];
+        assert_eq!(seal_record(&mut record), Err(JournalError::RecordTooLarge(MAX_PAYLOAD_LEN + 1)));
+        record.pop();
+        seal_record(&mut record).unwrap();
+        let data = [&JOURNAL_MAGIC[..], &JOURNAL_VERSION.to_le_bytes(), &[0, 0], &record].concat();
+        let scan = JournalReader::from_bytes(data).scan().unwrap();
+        assert!(matches!(scan.tail_error, Some(JournalError::Decode { offset: 8, .. })));
+    }
+}
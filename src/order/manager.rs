@@ -0,0 +1,459 @@
+use crate::{BookPrice, BookQuantity, InstId, OrderId, TimeStamp};
+use crate::order::core::ModifyOrder;
+use crate::order::enums::{OrderSide, OrderStatus};
+use crate::order::error::OrderError;
+use crate::order::execution::ExecutionReport;
+use crate::order::fill::Fill;
+use crate::order::request::OrderRequest;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Keeps all my orders (`OrderRequest`s of `LimitOrder` or `MarketOrder`) by `OrderId`.
+///
+/// Working orders are indexed by `InstId`, and limit orders additionally by side and price.
+/// Once an order becomes terminal (filled, canceled, rejected), it is moved out of the indexes
+/// into a bounded history where the oldest order is evicted first.
+/// The ids of the orders in the history can not be reused until they are evicted.
+///
+/// An order is keyed by its current id. When a cancel/replace assigns a new id,
+/// the former id and the id in flight keep routing to the same order.
+#[derive(Debug, Clone)]
+pub struct OrderManager {
+    orders: FxHashMap<OrderId, OrderRequest>,
+    aliases: FxHashMap<OrderId, OrderId>,
+    by_instrument: FxHashMap<InstId, FxHashSet<OrderId>>,
+    by_price: FxHashMap<(InstId, OrderSide), BTreeMap<BookPrice, Vec<OrderId>>>,
+    history: VecDeque<OrderRequest>,
+    history_capacity: usize,
+    // the ids (current, former and in flight) of the orders in the history
+    retired_ids: FxHashSet<OrderId>,
+}
+
+impl Default for OrderManager {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+impl OrderManager {
+    pub fn new(history_capacity: usize) -> Self {
+        Self {
+            orders: FxHashMap::default(),
+            aliases: FxHashMap::default(),
+            by_instrument: FxHashMap::default(),
+            by_price: FxHashMap::default(),
+            history: VecDeque::with_capacity(history_capacity),
+            history_capacity,
+            retired_ids: FxHashSet::default(),
+        }
+    }
+
+    /// Starts tracking a new order
+    pub fn insert(&mut self, request: OrderRequest) -> Result<(), OrderError> {
+        let order_type = request.order_core.order_type();
+        if request.order_core.order_side().is_none() {
+            return Err(OrderError::NotAnOrder(order_type));
+        }
+        let order_id = request.get_id().ok_or(OrderError::MissingOrderId(order_type))?;
+        if self.resolve(order_id).is_some() {
+            return Err(OrderError::DuplicateOrderId(order_id));
+        }
+        if self.retired_ids.contains(&order_id) {
+            return Err(OrderError::RetiredOrderId(order_id));
+        }
+        if request.status.is_terminal() {
+            return Err(OrderError::AlreadyTerminal { order_id: Some(order_id), status: request.status });
+        }
+        request.order_core.validate_attributes()?;
+
+        self.index(order_id, &request);
+        self.orders.insert(order_id, request);
+        Ok(())
+    }
+
+    /// The current id of the working order known by `order_id` (current, former or in-flight id)
+    #[inline]
+    pub fn resolve(&self, order_id: OrderId) -> Option<OrderId> {
+        if self.orders.contains_key(&order_id) {
+            Some(order_id)
+        } else {
+            self.aliases.get(&order_id).copied()
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, order_id: &OrderId) -> Option<&OrderRequest> {
+        self.resolve(*order_id).and_then(|key| self.orders.get(&key))
+    }
+
+    /// Finds the order among the working orders first, and then in the history
+    pub fn find(&self, order_id: &OrderId) -> Option<&OrderRequest> {
+        self.get(order_id)
+            .or_else(|| self.history.iter().rev().find(|request| request.is_known_id(*order_id)))
+    }
+
+    #[inline]
+    pub fn contains(&self, order_id: &OrderId) -> bool {
+        self.resolve(*order_id).is_some()
+    }
+
+    /// Number of working orders
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &OrderRequest> {
+        self.orders.values()
+    }
+
+    /// Terminal orders, the oldest first
+    #[inline]
+    pub fn history(&self) -> &VecDeque<OrderRequest> {
+        &self.history
+    }
+
+    pub fn on_accepted(&mut self, order_id: OrderId, time: TimeStamp) -> Result<OrderStatus, OrderError> {
+        self.update(order_id, |request| request.accepted(time))
+    }
+
+    pub fn on_rejected(&mut self, order_id: OrderId, time: TimeStamp) -> Result<OrderStatus, OrderError> {
+        self.update(order_id, |request| request.rejected(time))
+    }
+
+    pub fn on_cancel_requested(&mut self, order_id: OrderId, time: TimeStamp) -> Result<OrderStatus, OrderError> {
+        self.update(order_id, |request| request.pending_cancel(time))
+    }
+
+    /// The cancel (or replace) request was rejected by the venue
+    pub fn on_cancel_rejected(&mut self, order_id: OrderId, time: TimeStamp) -> Result<OrderStatus, OrderError> {
+        self.update(order_id, |request| request.restore_working(time))
+    }
+
+    pub fn on_canceled(&mut self, order_id: OrderId, time: TimeStamp) -> Result<OrderStatus, OrderError> {
+        self.update(order_id, |request| request.canceled(time))
+    }
+
+    /// A cancel/replace has been sent for `modify.order_id`
+    pub fn on_replace_requested(&mut self, modify: &ModifyOrder, time: TimeStamp) -> Result<OrderStatus, OrderError> {
+        let key = self.resolve(modify.order_id).ok_or(OrderError::UnknownOrder(modify.order_id))?;
+        let new_order_id = modify.replaced_order_id();
+        if new_order_id != key && self.resolve(new_order_id).is_some() {
+            return Err(OrderError::DuplicateOrderId(new_order_id));
+        }
+        if new_order_id != key && self.retired_ids.contains(&new_order_id) {
+            return Err(OrderError::RetiredOrderId(new_order_id));
+        }
+        let status = self.update(key, |request| request.request_replace(modify, time))?;
+        if new_order_id != key {
+            self.aliases.insert(new_order_id, key);
+        }
+        Ok(status)
+    }
+
+    pub fn on_replace_rejected(&mut self, order_id: OrderId, time: TimeStamp) -> Result<OrderStatus, OrderError> {
+        let pending = self.get(&order_id).and_then(|request| request.pending_replace);
+        let status = self.update(order_id, |request| request.replace_rejected(time).map(|_| ()))?;
+        if let Some(pending) = pending {
+            if !self.orders.contains_key(&pending.new_order_id) {
+                self.aliases.remove(&pending.new_order_id);
+            }
+        }
+        Ok(status)
+    }
+
+    pub fn on_fill(&mut self, order_id: OrderId, fill: &Fill) -> Result<OrderStatus, OrderError> {
+        self.update(order_id, |request| request.trade(fill).map(|_| ()))
+    }
+
+    /// Routes the report to the order of `report.order_id` (or `report.orig_order_id`)
+    pub fn on_execution_report(&mut self, report: &ExecutionReport) -> Result<OrderStatus, OrderError> {
+        let key = self
+            .resolve(report.order_id)
+            .or_else(|| report.orig_order_id.and_then(|orig_order_id| self.resolve(orig_order_id)))
+            .ok_or(OrderError::UnknownOrder(report.order_id))?;
+        self.update(key, |request| request.apply_execution_report(report).map(|_| ()))
+    }
+
+    /// Sum of the open quantity of my working limit orders at `price`
+    pub fn open_quantity_at(&self, instid: &InstId, side: OrderSide, price: BookPrice) -> BookQuantity {
+        self.by_price
+            .get(&(*instid, side))
+            .and_then(|levels| levels.get(&price))
+            .map_or(0, |ids| ids.iter().filter_map(|id| self.orders.get(id)).map(|r| r.open_quantity()).sum())
+    }
+
+    /// Sum of the open quantity of my working orders on `side`
+    pub fn open_quantity(&self, instid: &InstId, side: OrderSide) -> BookQuantity {
+        self.working_orders_on_side(instid, side).map(|request| request.open_quantity()).sum()
+    }
+
+    pub fn working_orders<'a>(&'a self, instid: &InstId) -> impl Iterator<Item = &'a OrderRequest> + 'a {
+        self.by_instrument
+            .get(instid)
+            .into_iter()
+            .flat_map(|ids| ids.iter())
+            .filter_map(|id| self.orders.get(id))
+    }
+
+    pub fn working_orders_on_side<'a>(
+        &'a self,
+        instid: &InstId,
+        side: OrderSide,
+    ) -> impl Iterator<Item = &'a OrderRequest> + 'a {
+        self.working_orders(instid).filter(move |request| request.order_core.order_side() == Some(side))
+    }
+
+    /// My working limit orders on `side` from the best price (highest bid, lowest ask)
+    pub fn price_levels(&self, instid: &InstId, side: OrderSide) -> Vec<(BookPrice, BookQuantity)> {
+        let Some(levels) = self.by_price.get(&(*instid, side)) else {
+            return Vec::new();
+        };
+        let level = |(price, ids): (&BookPrice, &Vec<OrderId>)| {
+            let quantity = ids.iter().filter_map(|id| self.orders.get(id)).map(|r| r.open_quantity()).sum();
+            (*price, quantity)
+        };
+        match side {
+            OrderSide::Bid => levels.iter().rev().map(level).collect(),
+            OrderSide::Ask => levels.iter().map(level).collect(),
+        }
+    }
+
+    #[inline]
+    pub fn working_count(&self, instid: &InstId) -> usize {
+        self.by_instrument.get(instid).map_or(0, |ids| ids.len())
+    }
+
+    /// Applies `f` on the order, and retires it if it has become terminal.
+    /// The indexes follow the order if `f` has changed its price or id (e.g., replaced)
+    fn update<F>(&mut self, order_id: OrderId, f: F) -> Result<OrderStatus, OrderError>
+    where
+        F: FnOnce(&mut OrderRequest) -> Result<(), OrderError>,
+    {
+        let key = self.resolve(order_id).ok_or(OrderError::UnknownOrder(order_id))?;
+        let request = self.orders.get_mut(&key).expect("resolved key is in orders");
+        let price = request.order_core.price();
+        f(request)?;
+        let status = request.status;
+        let new_key = request.get_id().unwrap_or(key);
+
+        if new_key != key || request.order_core.price() != price {
+            let request = self.orders.remove(&key).expect("resolved key is in orders");
+            self.unindex_instrument(key, request.instid);
+            self.unindex_price(key, request.instid, request.order_core.order_side(), price);
+            self.index(new_key, &request);
+            self.orders.insert(new_key, request);
+            if new_key != key {
+                self.aliases.remove(&new_key);
+                self.aliases.values_mut().filter(|v| **v == key).for_each(|v| *v = new_key);
+                self.aliases.insert(key, new_key);
+            }
+        }
+        if status.is_terminal() {
+            self.retire(new_key);
+        }
+        Ok(status)
+    }
+
+    fn index(&mut self, order_id: OrderId, request: &OrderRequest) {
+        self.by_instrument.entry(request.instid).or_default().insert(order_id);
+        self.index_price(order_id, request.instid, request.order_core.order_side(), request.order_core.price());
+    }
+
+    fn unindex(&mut self, order_id: OrderId, request: &OrderRequest) {
+        self.unindex_instrument(order_id, request.instid);
+        self.unindex_price(order_id, request.instid, request.order_core.order_side(), request.order_core.price());
+    }
+
+    fn unindex_instrument(&mut self, order_id: OrderId, instid: InstId) {
+        if let Some(ids) = self.by_instrument.get_mut(&instid) {
+            ids.remove(&order_id);
+            if ids.is_empty() {
+                self.by_instrument.remove(&instid);
+            }
+        }
+    }
+
+    fn index_price(&mut self, order_id: OrderId, instid: InstId, side: Option<OrderSide>, price: Option<BookPrice>) {
+        if let (Some(price), Some(side)) = (price, side) {
+            self.by_price
+                .entry((instid, side))
+                .or_default()
+                .entry(price)
+                .or_default()
+                .push(order_id);
+        }
+    }
+
+    fn unindex_price(&mut self, order_id: OrderId, instid: InstId, side: Option<OrderSide>, price: Option<BookPrice>) {
+        if let (Some(price), Some(side)) = (price, side) {
+            if let Some(levels) = self.by_price.get_mut(&(instid, side)) {
+                if let Some(ids) = levels.get_mut(&price) {
+                    ids.retain(|id| *id != order_id);
+                    if ids.is_empty() {
+                        levels.remove(&price);
+                    }
+                }
+                if levels.is_empty() {
+                    self.by_price.remove(&(instid, side));
+                }
+            }
+        }
+    }
+
+    fn retire(&mut self, order_id: OrderId) {
+        if let Some(request) = self.orders.remove(&order_id) {
+            self.unindex(order_id, &request);
+            self.aliases.retain(|_, key| *key != order_id);
+            if self.history_capacity == 0 {
+                return;
+            }
+            if self.history.len() == self.history_capacity {
+                if let Some(evicted) = self.history.pop_front() {
+                    known_ids(&evicted).for_each(|id| {
+                        self.retired_ids.remove(&id);
+                    });
+                }
+            }
+            self.retired_ids.extend(known_ids(&request));
+            self.history.push_back(request);
+        }
+    }
+}
+
+fn known_ids(request: &OrderRequest) -> impl Iterator<Item = OrderId> + '_ {
+    let pending = request.pending_replace.map(|pending| pending.new_order_id);
+    request.get_id().into_iter().chain(pending).chain(request.orig_order_ids.iter().copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OrderCore;
+    use crate::order::core::{CancelOrder, LimitOrder, MarketOrder};
+
+    fn limit(order_id: OrderId, side: OrderSide, price: BookPrice, quantity: BookQuantity) -> OrderRequest {
+        let id = InstId::from_str("KR7005930003", "KRX");
+        OrderRequest::new(id, OrderCore::LimitOrder(LimitOrder::new(price, quantity, side, order_id)), 0)
+    }
+
+    #[test]
+    fn test_indexes_and_queries() {
+        let mut manager = OrderManager::new(2);
+        manager.insert(limit(1, OrderSide::Bid, 100, 10)).unwrap();
+        manager.insert(limit(2, OrderSide::Bid, 100, 5)).unwrap();
+        manager.insert(limit(3, OrderSide::Bid, 99, 7)).unwrap();
+        manager.insert(limit(4, OrderSide::Ask, 101, 3)).unwrap();
+        let instid = manager.get(&1).unwrap().instid;
+        let market = OrderCore::MarketOrder(MarketOrder::new(1, OrderSide::Ask, 5));
+        manager.insert(OrderRequest::new(instid, market, 0)).unwrap();
+
+        assert_eq!(manager.insert(limit(1, OrderSide::Bid, 100, 1)), Err(OrderError::DuplicateOrderId(1)));
+        let cancel = OrderRequest::new(instid, OrderCore::CancelOrder(CancelOrder::new(9)), 0);
+        assert!(matches!(manager.insert(cancel), Err(OrderError::NotAnOrder(_))));
+
+        manager.on_accepted(1, 1).unwrap();
+        manager.on_fill(1, &Fill::new(1, 100, 4, 2)).unwrap();
+        assert_eq!(manager.open_quantity_at(&instid, OrderSide::Bid, 100), 11);
+        assert_eq!(manager.open_quantity(&instid, OrderSide::Bid), 18);
+        assert_eq!(manager.open_quantity(&instid, OrderSide::Ask), 4);
+        assert_eq!(manager.price_levels(&instid, OrderSide::Bid), vec![(100, 11), (99, 7)]);
+        assert_eq!(manager.working_count(&instid), 5);
+        assert_eq!(manager.working_orders_on_side(&instid, OrderSide::Ask).count(), 2);
+    }
+
+    #[test]
+    fn test_terminal_orders_are_evicted_into_history() {
+        let mut manager = OrderManager::new(2);
+        for order_id in 1..=3 {
+            manager.insert(limit(order_id, OrderSide::Ask, 100 + order_id as BookPrice, 10)).unwrap();
+            manager.on_accepted(order_id, 1).unwrap();
+        }
+        let instid = manager.get(&1).unwrap().instid;
+
+        assert_eq!(manager.on_fill(1, &Fill::new(1, 101, 10, 2)), Ok(OrderStatus::FullyFilled));
+        manager.on_cancel_requested(2, 3).unwrap();
+        assert_eq!(manager.on_canceled(2, 4), Ok(OrderStatus::Canceled));
+        assert_eq!(manager.on_rejected(3, 5).err().map(|e| matches!(e, OrderError::IllegalTransition { .. })), Some(true));
+        manager.on_canceled(3, 5).unwrap();
+
+        assert!(manager.is_empty());
+        assert_eq!(manager.working_count(&instid), 0);
+        assert_eq!(manager.open_quantity_at(&instid, OrderSide::Ask, 101), 0);
+        let retired: Vec<Option<OrderId>> = manager.history().iter().map(|r| r.get_id()).collect();
+        assert_eq!(retired, vec![Some(2), Some(3)]);
+        assert!(manager.find(&3).is_some());
+        assert!(manager.find(&1).is_none());
+        assert_eq!(manager.on_canceled(1, 6), Err(OrderError::UnknownOrder(1)));
+
+        // 1 was evicted from the history, 3 is still there
+        assert_eq!(manager.insert(limit(3, OrderSide::Ask, 103, 10)), Err(OrderError::RetiredOrderId(3)));
+        manager.insert(limit(1, OrderSide::Ask, 101, 10)).unwrap();
+        let mut filled = limit(4, OrderSide::Ask, 104, 10);
+        filled.status = OrderStatus::FullyFilled;
+        assert_eq!(
+            manager.insert(filled),
+            Err(OrderError::AlreadyTerminal { order_id: Some(4), status: OrderStatus::FullyFilled })
+        );
+    }
+
+    #[test]
+    fn test_execution_report_moves_price_index() {
+        use crate::order::execution::{ExecType, ExecutionReport};
+
+        let mut manager = OrderManager::default();
+        manager.insert(limit(1, OrderSide::Bid, 100, 10)).unwrap();
+        let instid = manager.get(&1).unwrap().instid;
+        let report = |exec_id, exec_type| ExecutionReport::new(exec_id, 1, instid, exec_type);
+
+        manager.on_execution_report(&report(1, ExecType::New)).unwrap();
+        manager.on_execution_report(&report(2, ExecType::PendingReplace)).unwrap();
+        let replaced = report(3, ExecType::Replaced).with_order_terms(Some(98), None);
+        manager.on_execution_report(&replaced).unwrap();
+
+        assert_eq!(manager.open_quantity_at(&instid, OrderSide::Bid, 100), 0);
+        assert_eq!(manager.open_quantity_at(&instid, OrderSide::Bid, 98), 10);
+    }
+
+    #[test]
+    fn test_replace_with_new_order_id() {
+        use crate::order::execution::{ExecType, ExecutionReport};
+
+        let mut manager = OrderManager::default();
+        manager.insert(limit(1, OrderSide::Ask, 100, 10)).unwrap();
+        manager.on_accepted(1, 1).unwrap();
+        let instid = manager.get(&1).unwrap().instid;
+
+        manager.on_replace_requested(&ModifyOrder::new(1, 101, 6).with_new_order_id(2), 2).unwrap();
+        assert_eq!(manager.get(&2).and_then(|r| r.get_id()), Some(1));
+        // fill against the old order during the replace
+        manager.on_fill(1, &Fill::new(1, 100, 4, 3)).unwrap();
+
+        let replaced = ExecutionReport::new(2, 2, instid, ExecType::Replaced).with_orig_order_id(1);
+        assert_eq!(manager.on_execution_report(&replaced), Ok(OrderStatus::PartiallyFilled));
+        assert_eq!(manager.get(&1).and_then(|r| r.get_id()), Some(2));
+        assert_eq!(manager.open_quantity_at(&instid, OrderSide::Ask, 100), 0);
+        assert_eq!(manager.open_quantity_at(&instid, OrderSide::Ask, 101), 2);
+
+        manager.on_replace_requested(&ModifyOrder::new(2, 102, 6).with_new_order_id(3), 4).unwrap();
+        assert_eq!(manager.on_replace_rejected(3, 5), Ok(OrderStatus::PartiallyFilled));
+        assert!(!manager.contains(&3));
+
+        manager.on_fill(1, &Fill::new(2, 101, 2, 6)).unwrap();
+        assert!(manager.is_empty());
+        assert!(!manager.contains(&1));
+        assert_eq!(manager.find(&1).map(|r| r.status), Some(OrderStatus::FullyFilled));
+
+        // the ids of a retired order are not reused by a replace either
+        manager.insert(limit(5, OrderSide::Ask, 100, 10)).unwrap();
+        manager.on_accepted(5, 7).unwrap();
+        let modify = ModifyOrder::new(5, 101, 10).with_new_order_id(2);
+        assert_eq!(manager.on_replace_requested(&modify, 8), Err(OrderError::RetiredOrderId(2)));
+        assert_eq!(manager.get(&5).map(|r| r.status), Some(OrderStatus::Accepted));
+    }
+}
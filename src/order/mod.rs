@@ -1,3 +1,12 @@
 pub mod core;
 pub mod request;
-pub mod enums;
\ No newline at end of file
+pub mod enums;
+pub mod error;
+pub mod manager;
+pub mod fill;
+pub mod execution;
+pub mod id_gen;
+pub mod simulation;
+pub mod trigger;
+pub mod journal;
+pub mod recon;
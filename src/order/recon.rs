@@ -0,0 +1,332 @@
+use crate::{BookPrice, BookQuantity, ExecId, OrderId};
+use crate::order::enums::OrderStatus;
+use crate::order::execution::{ExecType, ExecutionReport};
+use crate::order::manager::OrderManager;
+use crate::order::request::OrderRequest;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+
+/// A difference between my order state and the drop copy of the venue
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind")]
+pub enum Discrepancy {
+    /// the venue reports an order I do not know
+    UnknownOrder { order_id: OrderId },
+    /// I have an order the venue never reported
+    UnreportedOrder { order_id: OrderId, status: OrderStatus },
+    /// the venue reports a fill I have not applied
+    MissingFill { order_id: OrderId, exec_id: ExecId, price: BookPrice, quantity: BookQuantity },
+    /// I have a fill the venue does not report
+    UnreportedFill { order_id: OrderId, exec_id: ExecId },
+    FillMismatch {
+        order_id: OrderId,
+        exec_id: ExecId,
+        price: BookPrice,
+        quantity: BookQuantity,
+        reported_price: BookPrice,
+        reported_quantity: BookQuantity,
+    },
+    FilledQuantityMismatch { order_id: OrderId, filled: BookQuantity, reported: BookQuantity },
+    OrderQuantityMismatch { order_id: OrderId, quantity: BookQuantity, reported: BookQuantity },
+    PriceMismatch { order_id: OrderId, price: BookPrice, reported: BookPrice },
+    StatusDivergence { order_id: OrderId, status: OrderStatus, reported: OrderStatus },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReconReport {
+    /// my orders compared
+    pub orders: usize,
+    /// drop copy reports compared
+    pub reports: usize,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl ReconReport {
+    #[inline]
+    pub fn is_clean(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Reconciles my orders (working and history of an `OrderManager`) against the execution reports
+/// of a venue drop-copy feed, matched by `OrderId` (including former ids of replaced orders).
+///
+/// Reports may arrive in any order: the state of an order is taken from its most advanced report
+/// (the largest cumulative quantity, then a terminal one, then the latest venue time).
+/// Orders still `PendingNew` without any report are in flight and not reported as discrepancies.
+#[derive(Debug, Clone, Default)]
+pub struct Reconciler {
+    reports: Vec<ExecutionReport>,
+}
+
+impl Reconciler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_report(&mut self, report: ExecutionReport) {
+        self.reports.push(report);
+    }
+
+    pub fn extend<I: IntoIterator<Item = ExecutionReport>>(&mut self, reports: I) {
+        self.reports.extend(reports);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.reports.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.reports.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.reports.clear();
+    }
+
+    pub fn reconcile(&self, manager: &OrderManager) -> ReconReport {
+        let orders: Vec<&OrderRequest> = manager.iter().chain(manager.history().iter()).collect();
+        self.reconcile_orders(&orders)
+    }
+
+    pub fn reconcile_orders(&self, orders: &[&OrderRequest]) -> ReconReport {
+        let mut report = ReconReport {
+            orders: orders.len(),
+            reports: self.reports.len(),
+            discrepancies: Vec::new(),
+        };
+        // reports grouped by my order (index into `orders`), in arrival order
+        let mut by_order: FxHashMap<usize, Vec<&ExecutionReport>> = FxHashMap::default();
+        let mut unknown: Vec<OrderId> = Vec::new();
+        let mut index_of: FxHashMap<OrderId, usize> = FxHashMap::default();
+        for (index, order) in orders.iter().enumerate() {
+            let pending = order.pending_replace.map(|pending| pending.new_order_id);
+            for order_id in order.get_id().into_iter().chain(pending).chain(order.orig_order_ids.iter().copied()) {
+                index_of.insert(order_id, index);
+            }
+        }
+        for execution in &self.reports {
+            let found = index_of
+                .get(&execution.order_id)
+                .or_else(|| execution.orig_order_id.and_then(|orig_order_id| index_of.get(&orig_order_id)))
+                .copied();
+            match found {
+                Some(index) => by_order.entry(index).or_default().push(execution),
+                None if !unknown.contains(&execution.order_id) => unknown.push(execution.order_id),
+                None => {}
+            }
+        }
+        report
+            .discrepancies
+            .extend(unknown.into_iter().map(|order_id| Discrepancy::UnknownOrder { order_id }));
+
+        for (index, order) in orders.iter().enumerate() {
+            let order_id = order.get_id().unwrap_or_default();
+            match by_order.get(&index) {
+                Some(executions) => compare(order_id, order, executions, &mut report.discrepancies),
+                None if order.status == OrderStatus::PendingNew => {}
+                None => report.discrepancies.push(Discrepancy::UnreportedOrder {
+                    order_id,
+                    status: order.status,
+                }),
+            }
+        }
+        report
+    }
+}
+
+fn compare(order_id: OrderId, order: &OrderRequest, executions: &[&ExecutionReport], out: &mut Vec<Discrepancy>) {
+    // fills
+    let mut reported_exec_ids = FxHashSet::default();
+    for execution in executions.iter().filter(|e| e.exec_type == ExecType::Trade) {
+        reported_exec_ids.insert(execution.exec_id);
+        match order.fills.iter().find(|fill| fill.exec_id == execution.exec_id) {
+            None => out.push(Discrepancy::MissingFill {
+                order_id,
+                exec_id: execution.exec_id,
+                price: execution.last_price,
+                quantity: execution.last_quantity,
+            }),
+            Some(fill) if fill.price != execution.last_price || fill.quantity != execution.last_quantity => {
+                out.push(Discrepancy::FillMismatch {
+                    order_id,
+                    exec_id: execution.exec_id,
+                    price: fill.price,
+                    quantity: fill.quantity,
+                    reported_price: execution.last_price,
+                    reported_quantity: execution.last_quantity,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for fill in order.fills.iter().filter(|fill| !reported_exec_ids.contains(&fill.exec_id)) {
+        out.push(Discrepancy::UnreportedFill {
+            order_id,
+            exec_id: fill.exec_id,
+        });
+    }
+
+    let Some(last) = executions
+        .iter()
+        .max_by_key(|e| (e.cum_quantity, e.ord_status().is_terminal(), e.datatime))
+    else {
+        return;
+    };
+
+    let filled = order.filled.unwrap_or(0);
+    if last.cum_quantity != filled {
+        out.push(Discrepancy::FilledQuantityMismatch {
+            order_id,
+            filled,
+            reported: last.cum_quantity,
+        });
+    }
+    if let (Some(reported), Some(quantity)) = (last.order_quantity, order.order_core.quantity()) {
+        if reported != quantity {
+            out.push(Discrepancy::OrderQuantityMismatch { order_id, quantity, reported });
+        }
+    }
+    if let (Some(reported), Some(price)) = (last.order_price, order.order_core.price()) {
+        if reported != price {
+            out.push(Discrepancy::PriceMismatch { order_id, price, reported });
+        }
+    }
+
+    let reported = last.ord_status();
+    if settled(order.status, filled) != settled(reported, last.cum_quantity) {
+        out.push(Discrepancy::StatusDivergence {
+            order_id,
+            status: order.status,
+            reported,
+        });
+    }
+}
+
+/// In-flight states are compared as the working state they leave the order in
+fn settled(status: OrderStatus, filled: BookQuantity) -> OrderStatus {
+    match status {
+        OrderStatus::PendingNew | OrderStatus::PendingCancel | OrderStatus::PendingReplace | OrderStatus::Accepted => {
+            if filled > 0 {
+                OrderStatus::PartiallyFilled
+            } else {
+                OrderStatus::Accepted
+            }
+        }
+        status => status,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InstId, OrderCore};
+    use crate::order::core::LimitOrder;
+    use crate::order::enums::OrderSide;
+    use crate::order::fill::Fill;
+
+    fn instid() -> InstId {
+        InstId::from_str("005930", "KRX")
+    }
+
+    fn limit(order_id: OrderId) -> OrderRequest {
+        OrderRequest::new(instid(), OrderCore::LimitOrder(LimitOrder::new(100, 10, OrderSide::Bid, order_id)), 0)
+    }
+
+    fn trade(exec_id: ExecId, order_id: OrderId, quantity: BookQuantity, cum: BookQuantity) -> ExecutionReport {
+        ExecutionReport::new(exec_id, order_id, instid(), ExecType::Trade)
+            .with_fill(100, quantity)
+            .with_quantities(cum, 10 - cum, 100.0)
+            .with_order_terms(Some(100), Some(10))
+    }
+
+    #[test]
+    fn test_clean_reconciliation() {
+        let mut manager = OrderManager::default();
+        manager.insert(limit(1)).unwrap();
+        manager.on_accepted(1, 1).unwrap();
+        manager.on_fill(1, &Fill::new(11, 100, 4, 2)).unwrap();
+
+        let mut reconciler = Reconciler::new();
+        reconciler.add_report(ExecutionReport::new(10, 1, instid(), ExecType::New).with_quantities(0, 10, 0.0));
+        reconciler.add_report(trade(11, 1, 4, 4));
+        let report = reconciler.reconcile(&manager);
+        assert!(report.is_clean(), "{:?}", report);
+        assert_eq!((report.orders, report.reports), (1, 2));
+    }
+
+    #[test]
+    fn test_discrepancies() {
+        let mut manager = OrderManager::default();
+        manager.insert(limit(1)).unwrap();
+        manager.on_accepted(1, 1).unwrap();
+        manager.on_fill(1, &Fill::new(11, 100, 3, 2)).unwrap();
+        manager.on_fill(1, &Fill::new(12, 100, 1, 2)).unwrap();
+        manager.insert(limit(2)).unwrap();
+        manager.on_accepted(2, 1).unwrap();
+        // in flight, so not expected in the drop copy yet
+        manager.insert(limit(3)).unwrap();
+
+        let mut reconciler = Reconciler::new();
+        reconciler.extend([
+            trade(11, 1, 4, 4),
+            trade(13, 1, 6, 10),
+            ExecutionReport::new(20, 9, instid(), ExecType::New),
+        ]);
+        let report = reconciler.reconcile(&manager);
+        let expected = vec![
+            Discrepancy::UnknownOrder { order_id: 9 },
+            Discrepancy::FillMismatch {
+                order_id: 1,
+                exec_id: 11,
+                price: 100,
+                quantity: 3,
+                reported_price: 100,
+                reported_quantity: 4,
+            },
+            Discrepancy::MissingFill { order_id: 1, exec_id: 13, price: 100, quantity: 6 },
+            Discrepancy::UnreportedFill { order_id: 1, exec_id: 12 },
+            Discrepancy::FilledQuantityMismatch { order_id: 1, filled: 4, reported: 10 },
+            Discrepancy::StatusDivergence {
+                order_id: 1,
+                status: OrderStatus::PartiallyFilled,
+                reported: OrderStatus::FullyFilled,
+            },
+        ];
+        let mut discrepancies = report.discrepancies.clone();
+        let unreported = discrepancies.iter().position(|d| matches!(d, Discrepancy::UnreportedOrder { order_id: 2, .. }));
+        discrepancies.remove(unreported.expect("order 2 is not reported"));
+        assert_eq!(discrepancies, expected);
+
+        let json = report.to_json().unwrap();
+        assert!(json.contains("\"kind\": \"MissingFill\""));
+        let parsed: ReconReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, report);
+    }
+
+    #[test]
+    fn test_reports_out_of_order() {
+        let mut manager = OrderManager::default();
+        manager.insert(limit(1)).unwrap();
+        manager.on_accepted(1, 1).unwrap();
+        manager.on_fill(1, &Fill::new(11, 100, 4, 2)).unwrap();
+        manager.on_fill(1, &Fill::new(12, 100, 6, 3)).unwrap();
+
+        let mut reconciler = Reconciler::new();
+        let mut new = ExecutionReport::new(10, 1, instid(), ExecType::New).with_quantities(0, 10, 0.0);
+        new.datatime = 1;
+        let mut first = trade(11, 1, 4, 4);
+        first.datatime = 2;
+        let mut second = trade(12, 1, 6, 10);
+        second.datatime = 3;
+        reconciler.extend([second, new, first]);
+        let report = reconciler.reconcile(&manager);
+        assert!(report.is_clean(), "{:?}", report);
+    }
+}
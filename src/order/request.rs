@@ -1,7 +1,27 @@
-use crate::{OrderCore, InstId, TimeStamp, BookQuantity, OrderId};
-use crate::order::enums::OrderStatus;
+use crate::{OrderCore, InstId, TimeStamp, BookPrice, BookQuantity, OrderId};
+use crate::order::core::ModifyOrder;
+use crate::order::enums::{OrderStatus, OrderType};
+use crate::order::error::OrderError;
+use crate::order::execution::{ExecType, ExecutionReport};
+use crate::order::fill::{Fill, FillResult};
 use serde::{Serialize, Deserialize};
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct StatusTransition {
+    pub from: OrderStatus,
+    pub to: OrderStatus,
+    pub time: TimeStamp,
+}
+
+/// A cancel/replace request in flight. The order keeps its old terms until the venue confirms it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PendingReplace {
+    pub new_order_id: OrderId,
+    pub price: BookPrice,
+    pub quantity: BookQuantity,
+    pub time: TimeStamp,
+}
+
 /// The 'Request' means it is my order
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct OrderRequest {
@@ -10,6 +30,15 @@ pub struct OrderRequest {
     pub systemtime: TimeStamp,
     pub status: OrderStatus,
     pub filled: Option<BookQuantity>,
+    #[serde(default)]
+    pub history: Vec<StatusTransition>,
+    #[serde(default)]
+    pub fills: Vec<Fill>,
+    #[serde(default)]
+    pub pending_replace: Option<PendingReplace>,
+    /// the ids this order had before being replaced, the oldest first
+    #[serde(default)]
+    pub orig_order_ids: Vec<OrderId>,
 }
 
 impl OrderRequest {
@@ -30,26 +59,459 @@ impl OrderRequest {
             systemtime,
             filled: None,
             instid,
+            history: Vec::new(),
+            fills: Vec::new(),
+            pending_replace: None,
+            orig_order_ids: Vec::new(),
+        }
+    }
+
+    /// Quantity still working on the venue (leaves quantity). 0 once the order is terminal
+    #[inline]
+    pub fn open_quantity(&self) -> BookQuantity {
+        if self.status.is_terminal() {
+            return 0;
+        }
+        self.order_core
+            .quantity()
+            .map_or(0, |quantity| quantity.saturating_sub(self.filled.unwrap_or(0)))
+    }
+
+    /// The largest quantity that may still be executed: while a replace is in flight,
+    /// either the old or the new terms can be live on the venue
+    #[inline]
+    pub fn worst_case_open_quantity(&self) -> BookQuantity {
+        let open = self.open_quantity();
+        match self.pending_replace {
+            Some(pending) if !self.status.is_terminal() => {
+                open.max(pending.quantity.saturating_sub(self.filled.unwrap_or(0)))
+            }
+            _ => open,
+        }
+    }
+
+    /// Whether `order_id` is the current id, a former id, or the id of the replacement in flight
+    #[inline]
+    pub fn is_known_id(&self, order_id: OrderId) -> bool {
+        self.get_id() == Some(order_id)
+            || self.pending_replace.is_some_and(|pending| pending.new_order_id == order_id)
+            || self.orig_order_ids.contains(&order_id)
+    }
 
+    /// Moves the order to `to` if the transition is legal, and records it in `history`
+    #[inline]
+    pub fn transition(&mut self, to: OrderStatus, time: TimeStamp) -> Result<(), OrderError> {
+        if !self.status.can_transition_to(to) {
+            return Err(OrderError::IllegalTransition {
+                order_id: self.get_id(),
+                from: self.status,
+                to,
+            });
         }
+        self.history.push(StatusTransition { from: self.status, to, time });
+        self.status = to;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn accepted(&mut self, time: TimeStamp) -> Result<(), OrderError> {
+        self.transition(OrderStatus::Accepted, time)
+    }
+
+    #[inline]
+    pub fn rejected(&mut self, time: TimeStamp) -> Result<(), OrderError> {
+        self.transition(OrderStatus::Rejected, time)
     }
 
     #[inline]
-    pub fn accepted(&mut self) {
-        self.status = OrderStatus::Accepted;
+    pub fn pending_cancel(&mut self, time: TimeStamp) -> Result<(), OrderError> {
+        self.transition(OrderStatus::PendingCancel, time)
     }
 
     #[inline]
-    pub fn trade(&mut self, amount: BookQuantity) -> OrderStatus {
-        let quantity = self.order_core.quantity().expect("Order quantity not found in trade");
-        let fill_amount = self.filled.unwrap_or(0) + amount;
-        if fill_amount >= quantity {
-            self.status = OrderStatus::FullyFilled;
+    pub fn pending_replace(&mut self, time: TimeStamp) -> Result<(), OrderError> {
+        self.transition(OrderStatus::PendingReplace, time)
+    }
+
+    #[inline]
+    pub fn canceled(&mut self, time: TimeStamp) -> Result<(), OrderError> {
+        self.transition(OrderStatus::Canceled, time)?;
+        self.pending_replace = None;
+        Ok(())
+    }
+
+    /// Sends a cancel/replace: the order goes to PendingReplace and keeps its old terms until confirmed.
+    /// Fills arriving in the meantime are applied against the old order.
+    pub fn request_replace(&mut self, modify: &ModifyOrder, time: TimeStamp) -> Result<(), OrderError> {
+        let order_id = self.get_id();
+        if order_id != Some(modify.order_id) {
+            return Err(OrderError::OrderIdMismatch { expected: order_id, received: modify.order_id });
+        }
+        if self.order_core.order_type() != OrderType::Limit {
+            return Err(OrderError::NotReplaceable(self.order_core.order_type()));
+        }
+        self.transition(OrderStatus::PendingReplace, time)?;
+        self.pending_replace = Some(PendingReplace {
+            new_order_id: modify.replaced_order_id(),
+            price: modify.price,
+            quantity: modify.quantity,
+            time,
+        });
+        Ok(())
+    }
+
+    /// The venue confirmed the replace. The reported terms, if any, take precedence over the requested ones.
+    /// If the new quantity is not more than the filled quantity, the order is done.
+    pub fn replace_accepted(
+        &mut self,
+        price: Option<BookPrice>,
+        quantity: Option<BookQuantity>,
+        time: TimeStamp,
+    ) -> Result<OrderStatus, OrderError> {
+        let order_id = self.get_id();
+        let pending = self.pending_replace.ok_or(OrderError::NoPendingReplace(order_id))?;
+        let price = price.unwrap_or(pending.price);
+        let quantity = quantity.unwrap_or(pending.quantity);
+        let filled = self.filled.unwrap_or(0);
+
+        let next = if filled >= quantity {
+            OrderStatus::FullyFilled
+        } else if self.status == OrderStatus::PendingCancel {
+            OrderStatus::PendingCancel
+        } else if filled > 0 {
+            OrderStatus::PartiallyFilled
         } else {
-            self.status = OrderStatus::PartiallyFilled;
+            OrderStatus::Accepted
+        };
+        if next != self.status && !self.status.can_transition_to(next) {
+            return Err(OrderError::IllegalTransition { order_id, from: self.status, to: next });
+        }
+
+        self.pending_replace = None;
+        if let Some(order_id) = order_id.filter(|id| *id != pending.new_order_id) {
+            self.orig_order_ids.push(order_id);
+            self.order_core.set_order_id(pending.new_order_id);
+        }
+        self.order_core.set_price(price);
+        self.order_core.set_quantity(quantity);
+        if next != self.status {
+            self.transition(next, time)?;
+        }
+        Ok(self.status)
+    }
+
+    /// The venue rejected the replace: the old terms stay and the order goes back to working
+    pub fn replace_rejected(&mut self, time: TimeStamp) -> Result<OrderStatus, OrderError> {
+        if self.pending_replace.is_none() {
+            return Err(OrderError::NoPendingReplace(self.get_id()));
+        }
+        if self.status == OrderStatus::PendingReplace {
+            self.restore_working(time)?;
+        }
+        self.pending_replace = None;
+        Ok(self.status)
+    }
+
+    /// A rejected cancel or replace request puts the order back to its working status
+    #[inline]
+    pub fn restore_working(&mut self, time: TimeStamp) -> Result<(), OrderError> {
+        let working = if self.filled.unwrap_or(0) > 0 {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::Accepted
+        };
+        self.transition(working, time)
+    }
+
+    /// Volume weighted average of the fill prices in book units
+    #[inline]
+    pub fn avg_price(&self) -> Option<f64> {
+        let (value, quantity) = self.fills.iter().fold((0.0, 0), |(value, quantity), fill| {
+            (value + fill.price as f64 * fill.quantity as f64, quantity + fill.quantity)
+        });
+        if quantity == 0 {
+            None
+        } else {
+            Some(value / quantity as f64)
+        }
+    }
+
+    /// Applies a fill. Nothing is changed when an error is returned:
+    /// - the core has no quantity (cancel, null, etc.)
+    /// - zero quantity or an execution id that has already been applied
+    /// - the fill exceeds the order quantity
+    /// - the order can not be filled in its status (e.g., Rejected, Canceled)
+    ///
+    /// A partial fill while a cancel or replace is in flight keeps the pending status
+    pub fn trade(&mut self, fill: &Fill) -> Result<FillResult, OrderError> {
+        let order_id = self.get_id();
+        let quantity = match self.order_core.quantity() {
+            Some(quantity) if self.order_core.order_side().is_some() => quantity,
+            _ => return Err(OrderError::NotFillable(self.order_core.order_type())),
+        };
+        if fill.quantity == 0 {
+            return Err(OrderError::ZeroFillQuantity(order_id));
+        }
+        if self.fills.iter().any(|f| f.exec_id == fill.exec_id) {
+            return Err(OrderError::DuplicateExecId { order_id, exec_id: fill.exec_id });
+        }
+        let filled = self.filled.unwrap_or(0);
+        let fill_amount = filled
+            .checked_add(fill.quantity)
+            .filter(|fill_amount| *fill_amount <= quantity)
+            .ok_or(OrderError::Overfill { order_id, quantity, filled, fill_quantity: fill.quantity })?;
+        let next = if fill_amount == quantity {
+            OrderStatus::FullyFilled
+        } else if matches!(self.status, OrderStatus::PendingCancel | OrderStatus::PendingReplace) {
+            self.status
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+
+        if next != self.status {
+            self.transition(next, fill.time)?;
+        } else if self.status.is_terminal() {
+            return Err(OrderError::IllegalTransition { order_id, from: self.status, to: next });
         }
 
         self.filled = Some(fill_amount);
-        self.status
+        self.fills.push(*fill);
+        if self.status.is_terminal() {
+            self.pending_replace = None;
+        }
+        Ok(FillResult {
+            status: self.status,
+            filled: fill_amount,
+            leaves: self.open_quantity(),
+            avg_price: self.avg_price().unwrap_or_default(),
+        })
+    }
+
+    /// Drives the order by a venue message.
+    /// For trades, the reported cumulative quantity must match ours after the fill,
+    /// otherwise nothing is applied and `CumQuantityMismatch` is returned.
+    pub fn apply_execution_report(&mut self, report: &ExecutionReport) -> Result<OrderStatus, OrderError> {
+        let order_id = self.get_id();
+        let is_mine = self.is_known_id(report.order_id)
+            || report.orig_order_id.is_some_and(|orig_order_id| self.is_known_id(orig_order_id));
+        if !is_mine {
+            return Err(OrderError::OrderIdMismatch { expected: order_id, received: report.order_id });
+        }
+
+        let time = report.datatime;
+        match report.exec_type {
+            ExecType::New => self.accepted(time)?,
+            ExecType::Trade => {
+                let filled = self.filled.unwrap_or(0);
+                let expected = filled.checked_add(report.last_quantity).ok_or(OrderError::Overfill {
+                    order_id,
+                    quantity: self.order_core.quantity().unwrap_or_default(),
+                    filled,
+                    fill_quantity: report.last_quantity,
+                })?;
+                if expected != report.cum_quantity {
+                    return Err(OrderError::CumQuantityMismatch { order_id, expected, reported: report.cum_quantity });
+                }
+                let fill = report.fill().expect("trade report carries a fill");
+                self.trade(&fill)?;
+            }
+            ExecType::Canceled | ExecType::Expired => self.canceled(time)?,
+            ExecType::PendingCancel => {
+                if self.status != OrderStatus::PendingCancel {
+                    self.pending_cancel(time)?;
+                }
+            }
+            ExecType::PendingReplace => {
+                if self.status != OrderStatus::PendingReplace {
+                    self.pending_replace(time)?;
+                }
+            }
+            ExecType::Replaced => {
+                if self.pending_replace.is_none() {
+                    // a replace we have not tracked (e.g., sent by another session)
+                    self.pending_replace = Some(PendingReplace {
+                        new_order_id: report.order_id,
+                        price: report.order_price.or(self.order_core.price()).unwrap_or_default(),
+                        quantity: report.order_quantity.or(self.order_core.quantity()).unwrap_or_default(),
+                        time,
+                    });
+                }
+                self.replace_accepted(report.order_price, report.order_quantity, time)?;
+            }
+            ExecType::Rejected => self.rejected(time)?,
+            ExecType::CancelRejected => {
+                if self.pending_replace.is_some() {
+                    self.replace_rejected(time)?;
+                } else {
+                    self.restore_working(time)?;
+                }
+            }
+        }
+        Ok(self.status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::core::{CancelOrder, LimitOrder};
+    use crate::order::enums::OrderSide;
+
+    fn request() -> OrderRequest {
+        let core = OrderCore::LimitOrder(LimitOrder::new(100, 10, OrderSide::Bid, 1));
+        OrderRequest::new(InstId::default(), core, 0)
+    }
+
+    #[test]
+    fn test_legal_lifecycle() {
+        let mut order = request();
+        order.accepted(1).unwrap();
+        assert_eq!(order.trade(&Fill::new(1, 100, 3, 2)).unwrap().status, OrderStatus::PartiallyFilled);
+        assert_eq!(order.trade(&Fill::new(2, 100, 3, 3)).unwrap().status, OrderStatus::PartiallyFilled);
+        order.pending_cancel(4).unwrap();
+        assert_eq!(order.trade(&Fill::new(3, 100, 1, 5)).unwrap().status, OrderStatus::PendingCancel);
+        order.restore_working(6).unwrap();
+        order.pending_cancel(7).unwrap();
+        order.canceled(8).unwrap();
+
+        let statuses: Vec<(OrderStatus, TimeStamp)> = order.history.iter().map(|t| (t.to, t.time)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (OrderStatus::Accepted, 1),
+                (OrderStatus::PartiallyFilled, 2),
+                (OrderStatus::PendingCancel, 4),
+                (OrderStatus::PartiallyFilled, 6),
+                (OrderStatus::PendingCancel, 7),
+                (OrderStatus::Canceled, 8),
+            ]
+        );
+        assert_eq!(order.filled, Some(7));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_illegal_transitions() {
+        let mut canceled = request();
+        canceled.canceled(1).unwrap();
+        assert!(canceled.accepted(2).is_err());
+        assert_eq!(canceled.status, OrderStatus::Canceled);
+
+        let mut rejected = request();
+        rejected.rejected(1).unwrap();
+        assert_eq!(
+            rejected.trade(&Fill::new(1, 100, 10, 2)),
+            Err(OrderError::IllegalTransition {
+                order_id: Some(1),
+                from: OrderStatus::Rejected,
+                to: OrderStatus::FullyFilled,
+            })
+        );
+        assert_eq!(rejected.filled, None);
+        assert_eq!(rejected.history.len(), 1);
+
+        let mut accepted = request();
+        accepted.accepted(1).unwrap();
+        assert!(accepted.accepted(2).is_err());
+        assert!(accepted.rejected(2).is_err());
+    }
+
+    #[test]
+    fn test_fill_protection() {
+        let mut order = request();
+        order.accepted(1).unwrap();
+
+        let result = order.trade(&Fill::new(1, 100, 4, 2)).unwrap();
+        assert_eq!((result.filled, result.leaves, result.avg_price), (4, 6, 100.0));
+        let result = order.trade(&Fill::new(2, 95, 4, 3)).unwrap();
+        assert_eq!((result.filled, result.leaves, result.avg_price), (8, 2, 97.5));
+
+        assert_eq!(
+            order.trade(&Fill::new(2, 95, 1, 4)),
+            Err(OrderError::DuplicateExecId { order_id: Some(1), exec_id: 2 })
+        );
+        assert_eq!(
+            order.trade(&Fill::new(3, 95, 3, 4)),
+            Err(OrderError::Overfill { order_id: Some(1), quantity: 10, filled: 8, fill_quantity: 3 })
+        );
+        assert_eq!(order.trade(&Fill::new(3, 95, 0, 4)), Err(OrderError::ZeroFillQuantity(Some(1))));
+        // a fill that would wrap the filled quantity around
+        assert_eq!(
+            order.trade(&Fill::new(3, 95, u64::MAX - 5, 4)),
+            Err(OrderError::Overfill { order_id: Some(1), quantity: 10, filled: 8, fill_quantity: u64::MAX - 5 })
+        );
+        assert_eq!(order.filled, Some(8));
+
+        let result = order.trade(&Fill::new(3, 90, 2, 5)).unwrap();
+        assert_eq!((result.status, result.leaves), (OrderStatus::FullyFilled, 0));
+        assert_eq!(order.fills.len(), 3);
+
+        let mut cancel = OrderRequest::new(InstId::default(), OrderCore::CancelOrder(CancelOrder::new(1)), 0);
+        assert!(matches!(cancel.trade(&Fill::new(1, 100, 1, 1)), Err(OrderError::NotFillable(_))));
+    }
+
+    #[test]
+    fn test_execution_report() {
+        let mut order = request();
+        let report = |exec_id, exec_type| ExecutionReport::new(exec_id, 1, InstId::default(), exec_type);
+
+        assert_eq!(order.apply_execution_report(&report(1, ExecType::New)), Ok(OrderStatus::Accepted));
+        let trade = report(2, ExecType::Trade).with_fill(100, 4).with_quantities(4, 6, 100.0);
+        assert_eq!(order.apply_execution_report(&trade), Ok(OrderStatus::PartiallyFilled));
+
+        let inconsistent = report(3, ExecType::Trade).with_fill(100, 4).with_quantities(9, 1, 100.0);
+        assert_eq!(
+            order.apply_execution_report(&inconsistent),
+            Err(OrderError::CumQuantityMismatch { order_id: Some(1), expected: 8, reported: 9 })
+        );
+        assert_eq!(order.filled, Some(4));
+        let wrapping = report(3, ExecType::Trade).with_fill(100, u64::MAX).with_quantities(3, 7, 100.0);
+        assert!(matches!(order.apply_execution_report(&wrapping), Err(OrderError::Overfill { filled: 4, .. })));
+        assert_eq!(order.filled, Some(4));
+
+        let other = ExecutionReport::new(4, 2, InstId::default(), ExecType::Canceled);
+        assert!(matches!(order.apply_execution_report(&other), Err(OrderError::OrderIdMismatch { .. })));
+
+        order.request_replace(&ModifyOrder::new(1, 99, 20), 5).unwrap();
+        let replaced = report(5, ExecType::Replaced).with_order_terms(Some(99), Some(20));
+        assert_eq!(order.apply_execution_report(&replaced), Ok(OrderStatus::PartiallyFilled));
+        assert_eq!((order.order_core.price(), order.open_quantity()), (Some(99), 16));
+
+        assert_eq!(order.apply_execution_report(&report(6, ExecType::Expired)), Ok(OrderStatus::Canceled));
+    }
+
+    #[test]
+    fn test_cancel_replace_chain() {
+        let mut order = request();
+        order.accepted(1).unwrap();
+        order.trade(&Fill::new(1, 100, 2, 2)).unwrap();
+
+        let modify = ModifyOrder::new(1, 101, 15).with_new_order_id(2);
+        order.request_replace(&modify, 3).unwrap();
+        assert_eq!(order.status, OrderStatus::PendingReplace);
+        assert_eq!((order.open_quantity(), order.worst_case_open_quantity()), (8, 13));
+
+        // a fill against the old order while the replace is in flight
+        let fill = ExecutionReport::new(2, 1, InstId::default(), ExecType::Trade)
+            .with_fill(100, 3)
+            .with_quantities(5, 5, 100.0);
+        assert_eq!(order.apply_execution_report(&fill), Ok(OrderStatus::PendingReplace));
+
+        let replaced = ExecutionReport::new(3, 2, InstId::default(), ExecType::Replaced).with_orig_order_id(1);
+        assert_eq!(order.apply_execution_report(&replaced), Ok(OrderStatus::PartiallyFilled));
+        assert_eq!((order.get_id(), order.order_core.price(), order.open_quantity()), (Some(2), Some(101), 10));
+        assert_eq!(order.orig_order_ids, vec![1]);
+        assert!(order.is_known_id(1));
+
+        // the next replace is rejected and the terms stay
+        order.request_replace(&ModifyOrder::new(2, 102, 4).with_new_order_id(3), 4).unwrap();
+        let rejected = ExecutionReport::new(4, 3, InstId::default(), ExecType::CancelRejected).with_orig_order_id(2);
+        assert_eq!(order.apply_execution_report(&rejected), Ok(OrderStatus::PartiallyFilled));
+        assert_eq!((order.get_id(), order.order_core.price(), order.open_quantity()), (Some(2), Some(101), 10));
+        assert!(order.pending_replace.is_none());
+
+        // replacing down to the filled quantity finishes the order
+        order.request_replace(&ModifyOrder::new(2, 101, 5).with_new_order_id(4), 5).unwrap();
+        assert_eq!(order.replace_accepted(None, None, 6), Ok(OrderStatus::FullyFilled));
+        assert_eq!(order.orig_order_ids, vec![1, 2]);
+    }
+}
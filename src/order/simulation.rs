@@ -0,0 +1,204 @@
+use crate::{BookPrice, BookQuantity, OrderCore, TimeStamp};
+use crate::data::level::LevelSnapshot;
+use crate::data::snapshot::QuoteSnapshot;
+use crate::order::core::OrderAttributes;
+use crate::order::enums::{OrderSide, TimeInForce};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum SimulatedReject {
+    NotAnOrder,
+    InvalidAttributes,
+    Expired,
+    /// a post-only order would take liquidity
+    WouldTakeLiquidity,
+    /// a reduce-only order would not decrease the position
+    WouldIncreasePosition,
+}
+
+/// The outcome of a new order sent against a quote snapshot
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SimulatedExecution {
+    /// (price, quantity) taken from the opposite side, best first
+    pub fills: Vec<(BookPrice, BookQuantity)>,
+    /// left on the book
+    pub resting: BookQuantity,
+    /// the visible part of `resting`
+    pub displayed: BookQuantity,
+    /// canceled right away (immediate orders, reduce-only cap)
+    pub canceled: BookQuantity,
+    pub reject: Option<SimulatedReject>,
+}
+
+impl SimulatedExecution {
+    fn rejected(reject: SimulatedReject) -> Self {
+        Self {
+            reject: Some(reject),
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn filled(&self) -> BookQuantity {
+        self.fills.iter().map(|(_, quantity)| quantity).sum()
+    }
+}
+
+/// Executes a new order against the levels of `quote` the way the venue would,
+/// honoring the time in force and the attributes of the order.
+/// `position` is the signed position (long > 0) used by reduce-only orders.
+/// The quote is not modified, and the order is assumed to be alone at the venue.
+pub fn simulate(order_core: &OrderCore, quote: &QuoteSnapshot, position: i64, now: TimeStamp) -> SimulatedExecution {
+    let (limit, quantity, side, attributes) = match order_core {
+        OrderCore::LimitOrder(order) => (Some(order.price), order.quantity, order.order_side, &order.attributes),
+        OrderCore::MarketOrder(order) => (None, order.quantity, order.order_side, &order.attributes),
+        _ => return SimulatedExecution::rejected(SimulatedReject::NotAnOrder),
+    };
+    if order_core.validate_attributes().is_err() {
+        return SimulatedExecution::rejected(SimulatedReject::InvalidAttributes);
+    }
+    if attributes.time_in_force.is_expired(now) {
+        return SimulatedExecution::rejected(SimulatedReject::Expired);
+    }
+    let time_in_force = order_core.time_in_force().unwrap_or_default();
+
+    let mut canceled = 0;
+    let mut quantity = quantity;
+    if attributes.reduce_only {
+        let reducible = match side {
+            OrderSide::Bid if position < 0 => position.unsigned_abs(),
+            OrderSide::Ask if position > 0 => position.unsigned_abs(),
+            _ => 0,
+        };
+        if reducible == 0 {
+            return SimulatedExecution::rejected(SimulatedReject::WouldIncreasePosition);
+        }
+        canceled = quantity.saturating_sub(reducible);
+        quantity -= canceled;
+    }
+
+    let opposite = match side {
+        OrderSide::Bid => &quote.ask_quote_data,
+        OrderSide::Ask => &quote.bid_quote_data,
+    };
+    let crossing: Vec<&LevelSnapshot> = opposite
+        .iter()
+        .take(quote.quote_level_cut)
+        .filter(|level| level.book_quantity > 0)
+        .take_while(|level| match (limit, side) {
+            (None, _) => true,
+            (Some(price), OrderSide::Bid) => level.book_price <= price,
+            (Some(price), OrderSide::Ask) => level.book_price >= price,
+        })
+        .collect();
+    let available: BookQuantity = crossing.iter().map(|level| level.book_quantity).sum();
+
+    if attributes.post_only && available > 0 {
+        return SimulatedExecution::rejected(SimulatedReject::WouldTakeLiquidity);
+    }
+
+    let executable = available.min(quantity);
+    let all_or_nothing = time_in_force == TimeInForce::Fok && executable < quantity;
+    let below_minimum = attributes.min_quantity.is_some_and(|min| executable < min);
+    let mut fills = Vec::new();
+    if !all_or_nothing && !below_minimum {
+        let mut left = executable;
+        for level in crossing {
+            if left == 0 {
+                break;
+            }
+            let take = level.book_quantity.min(left);
+            fills.push((level.book_price, take));
+            left -= take;
+        }
+    }
+    let remaining = quantity - fills.iter().map(|(_, quantity)| quantity).sum::<BookQuantity>();
+
+    if time_in_force.is_immediate() {
+        canceled += remaining;
+        SimulatedExecution {
+            fills,
+            canceled,
+            ..Default::default()
+        }
+    } else {
+        SimulatedExecution {
+            fills,
+            resting: remaining,
+            displayed: displayed_quantity(attributes, remaining),
+            canceled,
+            reject: None,
+        }
+    }
+}
+
+#[inline]
+fn displayed_quantity(attributes: &OrderAttributes, resting: BookQuantity) -> BookQuantity {
+    attributes.display_quantity.map_or(resting, |display| display.min(resting))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::core::{LimitOrder, MarketOrder};
+
+    fn quote() -> QuoteSnapshot {
+        let level = |book_price, book_quantity| LevelSnapshot {
+            book_price,
+            book_quantity,
+            ..Default::default()
+        };
+        let mut quote = QuoteSnapshot::sample(0);
+        quote.ask_quote_data = vec![level(101, 5), level(102, 5), level(103, 5)];
+        quote.bid_quote_data = vec![level(99, 5), level(98, 5), level(97, 5)];
+        quote.quote_level_cut = 3;
+        quote
+    }
+
+    fn bid(price: BookPrice, quantity: BookQuantity, attributes: OrderAttributes) -> OrderCore {
+        OrderCore::LimitOrder(LimitOrder::new(price, quantity, OrderSide::Bid, 1).with_attributes(attributes))
+    }
+
+    #[test]
+    fn test_time_in_force() {
+        let quote = quote();
+        let day = simulate(&bid(102, 12, OrderAttributes::default()), &quote, 0, 0);
+        assert_eq!(day.fills, vec![(101, 5), (102, 5)]);
+        assert_eq!((day.resting, day.displayed, day.canceled), (2, 2, 0));
+
+        let ioc = simulate(&bid(102, 12, OrderAttributes::default().with_time_in_force(TimeInForce::Ioc)), &quote, 0, 0);
+        assert_eq!((ioc.filled(), ioc.resting, ioc.canceled), (10, 0, 2));
+
+        let fok = simulate(&bid(102, 12, OrderAttributes::default().with_time_in_force(TimeInForce::Fok)), &quote, 0, 0);
+        assert_eq!((fok.filled(), fok.canceled), (0, 12));
+        let fok = simulate(&bid(102, 10, OrderAttributes::default().with_time_in_force(TimeInForce::Fok)), &quote, 0, 0);
+        assert_eq!((fok.filled(), fok.canceled), (10, 0));
+
+        let gtd = bid(100, 1, OrderAttributes::default().with_time_in_force(TimeInForce::Gtd(10)));
+        assert_eq!(simulate(&gtd, &quote, 0, 9).resting, 1);
+        assert_eq!(simulate(&gtd, &quote, 0, 10).reject, Some(SimulatedReject::Expired));
+
+        let market = OrderCore::MarketOrder(MarketOrder::new(20, OrderSide::Ask, 2));
+        let market = simulate(&market, &quote, 0, 0);
+        assert_eq!((market.filled(), market.canceled), (15, 5));
+    }
+
+    #[test]
+    fn test_order_attributes() {
+        let quote = quote();
+        let post_only = simulate(&bid(101, 1, OrderAttributes::default().with_post_only()), &quote, 0, 0);
+        assert_eq!(post_only.reject, Some(SimulatedReject::WouldTakeLiquidity));
+        assert_eq!(simulate(&bid(100, 1, OrderAttributes::default().with_post_only()), &quote, 0, 0).resting, 1);
+
+        let min = simulate(&bid(101, 8, OrderAttributes::default().with_min_quantity(6)), &quote, 0, 0);
+        assert_eq!((min.filled(), min.resting), (0, 8));
+
+        let iceberg = simulate(&bid(100, 8, OrderAttributes::default().with_display_quantity(3)), &quote, 0, 0);
+        assert_eq!((iceberg.resting, iceberg.displayed), (8, 3));
+
+        let reduce_only = bid(101, 8, OrderAttributes::default().with_reduce_only());
+        assert_eq!(simulate(&reduce_only, &quote, 3, 0).reject, Some(SimulatedReject::WouldIncreasePosition));
+        let reduced = simulate(&reduce_only, &quote, -3, 0);
+        assert_eq!((reduced.filled(), reduced.canceled), (3, 5));
+    }
+}
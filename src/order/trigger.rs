@@ -0,0 +1,318 @@
+use crate::{BookPrice, InstId, OrderCore, OrderId, TimeStamp};
+use crate::data::snapshot::QuoteSnapshot;
+use crate::data::trade::TradeTick;
+use crate::order::core::{LimitOrder, MarketOrder, OrderAttributes};
+use crate::order::enums::OrderSide;
+use crate::order::error::OrderError;
+use rustc_hash::FxHashMap;
+
+/// A conditional order held on the client side
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingTrigger {
+    pub instid: InstId,
+    pub order_core: OrderCore,
+    /// the best price seen so far by a trailing stop
+    pub reference: Option<BookPrice>,
+    pub time: TimeStamp,
+}
+
+impl PendingTrigger {
+    /// The stop or trigger price the order is waiting for, None for a trailing stop with no price seen yet
+    pub fn stop_price(&self) -> Option<BookPrice> {
+        match &self.order_core {
+            OrderCore::TrailingStopOrder(order) => self.reference.map(|reference| match order.order_side {
+                OrderSide::Bid => reference + order.trail,
+                OrderSide::Ask => reference - order.trail,
+            }),
+            core => core.trigger_price(),
+        }
+    }
+
+    /// Updates the trailing reference with `price` and tells if the trigger is hit
+    fn observe(&mut self, price: BookPrice) -> bool {
+        let side = self.order_core.order_side().unwrap_or_default();
+        if let OrderCore::TrailingStopOrder(_) = self.order_core {
+            self.reference = Some(match (self.reference, side) {
+                (None, _) => price,
+                (Some(reference), OrderSide::Bid) => reference.min(price),
+                (Some(reference), OrderSide::Ask) => reference.max(price),
+            });
+        }
+        let Some(stop_price) = self.stop_price() else {
+            return false;
+        };
+        match (&self.order_core, side) {
+            (OrderCore::IfTouchedOrder(_), OrderSide::Bid) => price <= stop_price,
+            (OrderCore::IfTouchedOrder(_), OrderSide::Ask) => price >= stop_price,
+            (_, OrderSide::Bid) => price >= stop_price,
+            (_, OrderSide::Ask) => price <= stop_price,
+        }
+    }
+
+    /// The order to send once triggered
+    fn convert(&self) -> OrderCore {
+        let stop_price = self.stop_price().unwrap_or_default();
+        let market = |quantity, order_side, order_id, attributes: &OrderAttributes| {
+            OrderCore::MarketOrder(MarketOrder::new(quantity, order_side, order_id).with_attributes(attributes.clone()))
+        };
+        let limit = |price, quantity, order_side, order_id, attributes: &OrderAttributes| {
+            OrderCore::LimitOrder(LimitOrder::new(price, quantity, order_side, order_id).with_attributes(attributes.clone()))
+        };
+        match &self.order_core {
+            OrderCore::StopMarketOrder(o) => market(o.quantity, o.order_side, o.order_id, &o.attributes),
+            OrderCore::StopLimitOrder(o) => limit(o.price, o.quantity, o.order_side, o.order_id, &o.attributes),
+            OrderCore::TrailingStopOrder(o) => match o.limit_offset {
+                Some(offset) => {
+                    let price = match o.order_side {
+                        OrderSide::Bid => stop_price + offset,
+                        OrderSide::Ask => stop_price - offset,
+                    };
+                    limit(price, o.quantity, o.order_side, o.order_id, &o.attributes)
+                }
+                None => market(o.quantity, o.order_side, o.order_id, &o.attributes),
+            },
+            OrderCore::IfTouchedOrder(o) => match o.price {
+                Some(price) => limit(price, o.quantity, o.order_side, o.order_id, &o.attributes),
+                None => market(o.quantity, o.order_side, o.order_id, &o.attributes),
+            },
+            core => core.clone(),
+        }
+    }
+}
+
+/// A conditional order whose trigger has been hit, converted into a `LimitOrder` or `MarketOrder`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggeredOrder {
+    pub instid: InstId,
+    pub order_core: OrderCore,
+    /// the market price that hit the trigger
+    pub trigger_price: BookPrice,
+    pub time: TimeStamp,
+}
+
+/// Client-side trigger evaluation of stop, trailing-stop and if-touched orders
+/// for venues that do not support them natively.
+///
+/// Trades are compared with the trade price. On quotes, a bid order watches the best ask
+/// and an ask order watches the best bid, i.e., the price it would trade at.
+#[derive(Debug, Clone, Default)]
+pub struct TriggerEngine {
+    pending: FxHashMap<InstId, Vec<PendingTrigger>>,
+    instruments: FxHashMap<OrderId, InstId>,
+}
+
+impl TriggerEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, instid: InstId, order_core: OrderCore, time: TimeStamp) -> Result<(), OrderError> {
+        let order_type = order_core.order_type();
+        if !order_type.is_conditional() {
+            return Err(OrderError::NotConditional(order_type));
+        }
+        let order_id = order_core.order_id().ok_or(OrderError::MissingOrderId(order_type))?;
+        if self.instruments.contains_key(&order_id) {
+            return Err(OrderError::DuplicateOrderId(order_id));
+        }
+        order_core.validate_attributes()?;
+
+        self.instruments.insert(order_id, instid);
+        self.pending.entry(instid).or_default().push(PendingTrigger {
+            instid,
+            order_core,
+            reference: None,
+            time,
+        });
+        Ok(())
+    }
+
+    pub fn cancel(&mut self, order_id: OrderId) -> Option<PendingTrigger> {
+        let instid = self.instruments.remove(&order_id)?;
+        let pending = self.pending.get_mut(&instid)?;
+        let index = pending.iter().position(|p| p.order_core.order_id() == Some(order_id))?;
+        let canceled = pending.remove(index);
+        if pending.is_empty() {
+            self.pending.remove(&instid);
+        }
+        Some(canceled)
+    }
+
+    pub fn get(&self, order_id: OrderId) -> Option<&PendingTrigger> {
+        let instid = self.instruments.get(&order_id)?;
+        self.pending.get(instid)?.iter().find(|p| p.order_core.order_id() == Some(order_id))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.instruments.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.instruments.is_empty()
+    }
+
+    pub fn on_trade(&mut self, trade: &TradeTick) -> Vec<TriggeredOrder> {
+        self.evaluate(trade.id, trade.datatime, |_| Some(trade.price))
+    }
+
+    pub fn on_quote(&mut self, quote: &QuoteSnapshot) -> Vec<TriggeredOrder> {
+        let ask = quote.best_ask().map(|level| level.book_price);
+        let bid = quote.best_bid().map(|level| level.book_price);
+        self.evaluate(quote.id, quote.datatime, |side| match side {
+            OrderSide::Bid => ask,
+            OrderSide::Ask => bid,
+        })
+    }
+
+    /// Removes the good-till-date orders expired at `now`
+    pub fn expire(&mut self, now: TimeStamp) -> Vec<PendingTrigger> {
+        let mut expired = Vec::new();
+        for pending in self.pending.values_mut() {
+            let (gone, kept): (Vec<_>, Vec<_>) = std::mem::take(pending).into_iter().partition(|p| {
+                p.order_core.time_in_force().is_some_and(|time_in_force| time_in_force.is_expired(now))
+            });
+            *pending = kept;
+            expired.extend(gone);
+        }
+        self.forget(&expired);
+        expired
+    }
+
+    fn evaluate<F>(&mut self, instid: InstId, time: TimeStamp, price_for: F) -> Vec<TriggeredOrder>
+    where
+        F: Fn(OrderSide) -> Option<BookPrice>,
+    {
+        let Some(pending) = self.pending.get_mut(&instid) else {
+            return Vec::new();
+        };
+        let mut triggered = Vec::new();
+        pending.retain_mut(|p| {
+            let side = p.order_core.order_side().unwrap_or_default();
+            match price_for(side) {
+                Some(price) if p.observe(price) => {
+                    triggered.push((p.convert(), price));
+                    false
+                }
+                _ => true,
+            }
+        });
+        let triggered: Vec<TriggeredOrder> = triggered
+            .into_iter()
+            .map(|(order_core, trigger_price)| TriggeredOrder {
+                instid,
+                order_core,
+                trigger_price,
+                time,
+            })
+            .collect();
+        for t in &triggered {
+            if let Some(order_id) = t.order_core.order_id() {
+                self.instruments.remove(&order_id);
+            }
+        }
+        if self.pending.get(&instid).is_some_and(|pending| pending.is_empty()) {
+            self.pending.remove(&instid);
+        }
+        triggered
+    }
+
+    fn forget(&mut self, removed: &[PendingTrigger]) {
+        for p in removed {
+            if let Some(order_id) = p.order_core.order_id() {
+                self.instruments.remove(&order_id);
+            }
+        }
+        self.pending.retain(|_, pending| !pending.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::level::LevelSnapshot;
+    use crate::order::core::{IfTouchedOrder, OrderAttributes, StopLimitOrder, StopMarketOrder, TrailingStopOrder};
+    use crate::order::enums::TimeInForce;
+
+    fn instid() -> InstId {
+        InstId::from_str("KOSPI200F", "KRX")
+    }
+
+    fn trade(price: BookPrice) -> TradeTick {
+        TradeTick::new(instid(), 1, price, 1, OrderSide::Bid, 10, 10)
+    }
+
+    fn quote(bid: BookPrice, ask: BookPrice) -> QuoteSnapshot {
+        let level = |book_price| LevelSnapshot {
+            book_price,
+            book_quantity: 1,
+            ..Default::default()
+        };
+        let mut quote = QuoteSnapshot::sample(0);
+        quote.id = instid();
+        quote.ask_quote_data = vec![level(ask)];
+        quote.bid_quote_data = vec![level(bid)];
+        quote.quote_level_cut = 1;
+        quote
+    }
+
+    #[test]
+    fn test_stop_and_if_touched() {
+        let mut engine = TriggerEngine::new();
+        engine.insert(instid(), OrderCore::StopMarketOrder(StopMarketOrder::new(105, 1, OrderSide::Bid, 1)), 0).unwrap();
+        engine.insert(instid(), OrderCore::StopLimitOrder(StopLimitOrder::new(95, 94, 1, OrderSide::Ask, 2)), 0).unwrap();
+        let mit = IfTouchedOrder::new(97, 1, OrderSide::Bid, 3).with_limit_price(97);
+        engine.insert(instid(), OrderCore::IfTouchedOrder(mit), 0).unwrap();
+        assert!(engine.insert(instid(), OrderCore::LimitOrder(LimitOrder::new(1, 1, OrderSide::Bid, 4)), 0).is_err());
+
+        assert!(engine.on_quote(&quote(99, 101)).is_empty());
+        // the bid stop watches the ask
+        let triggered = engine.on_quote(&quote(100, 105));
+        assert_eq!(triggered.len(), 1);
+        assert_eq!(triggered[0].order_core, OrderCore::MarketOrder(MarketOrder::new(1, OrderSide::Bid, 1)));
+        assert_eq!(triggered[0].trigger_price, 105);
+
+        let triggered = engine.on_trade(&trade(95));
+        let cores: Vec<_> = triggered.into_iter().map(|t| t.order_core).collect();
+        assert_eq!(
+            cores,
+            vec![
+                OrderCore::LimitOrder(LimitOrder::new(94, 1, OrderSide::Ask, 2)),
+                OrderCore::LimitOrder(LimitOrder::new(97, 1, OrderSide::Bid, 3)),
+            ]
+        );
+        assert!(engine.is_empty());
+    }
+
+    #[test]
+    fn test_trailing_stop() {
+        let mut engine = TriggerEngine::new();
+        let trailing = TrailingStopOrder::new(3, 1, OrderSide::Ask, 1).with_limit_offset(1);
+        engine.insert(instid(), OrderCore::TrailingStopOrder(trailing), 0).unwrap();
+
+        for price in [100, 104, 102, 106] {
+            assert!(engine.on_trade(&trade(price)).is_empty());
+        }
+        assert_eq!(engine.get(1).and_then(|p| p.stop_price()), Some(103));
+        let triggered = engine.on_trade(&trade(103));
+        assert_eq!(triggered[0].order_core, OrderCore::LimitOrder(LimitOrder::new(102, 1, OrderSide::Ask, 1)));
+        assert!(engine.get(1).is_none());
+    }
+
+    #[test]
+    fn test_cancel_and_expire() {
+        let mut engine = TriggerEngine::new();
+        let gtd = OrderAttributes::default().with_time_in_force(TimeInForce::Gtd(100));
+        let stop = StopMarketOrder::new(105, 1, OrderSide::Bid, 1).with_attributes(gtd);
+        engine.insert(instid(), OrderCore::StopMarketOrder(stop), 0).unwrap();
+        engine.insert(instid(), OrderCore::StopMarketOrder(StopMarketOrder::new(105, 1, OrderSide::Bid, 2)), 0).unwrap();
+
+        assert!(engine.expire(99).is_empty());
+        assert_eq!(engine.expire(100).len(), 1);
+        assert!(engine.cancel(2).is_some());
+        assert!(engine.cancel(2).is_none());
+        assert!(engine.is_empty());
+        assert!(engine.on_trade(&trade(110)).is_empty());
+    }
+}
@@ -0,0 +1,350 @@
+use crate::{BookPrice, BookQuantity, InstId, TimeStamp};
+use crate::data::snapshot::QuoteSnapshot;
+use crate::instrument::spec::InstrumentSpec;
+use crate::order::enums::OrderSide;
+use crate::order::fill::Fill;
+use crate::order::request::OrderRequest;
+use crate::price;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// How the cost of the open position is computed when a part of it is closed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum CostMethod {
+    /// the oldest lots are closed first
+    #[default]
+    Fifo,
+    /// every unit costs the average price of the open position
+    WeightedAverage,
+}
+
+/// An open lot: bought (or sold short) `quantity` at `price`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Lot {
+    pub price: BookPrice,
+    pub quantity: BookQuantity,
+}
+
+/// The position of one instrument. Prices and PnL are in book units (price x quantity),
+/// see `PositionKeeper::snapshot` for values in the instrument currency
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Position {
+    pub instid: InstId,
+    /// long > 0
+    pub net: i64,
+    pub bought: BookQuantity,
+    pub sold: BookQuantity,
+    /// open lots, oldest first, all on the side of `net`
+    pub lots: VecDeque<Lot>,
+    /// cost of the open position under the weighted average method
+    pub weighted_cost: f64,
+    pub realized_fifo: f64,
+    pub realized_weighted: f64,
+    pub last_fill_time: TimeStamp,
+}
+
+impl Position {
+    pub fn new(instid: InstId) -> Self {
+        Self {
+            instid,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn long(&self) -> BookQuantity {
+        self.net.max(0) as BookQuantity
+    }
+
+    #[inline]
+    pub fn short(&self) -> BookQuantity {
+        (-self.net).max(0) as BookQuantity
+    }
+
+    #[inline]
+    pub fn is_flat(&self) -> bool {
+        self.net == 0
+    }
+
+    pub fn apply(&mut self, side: OrderSide, price: BookPrice, quantity: BookQuantity, time: TimeStamp) {
+        self.last_fill_time = time;
+        match side {
+            OrderSide::Bid => self.bought += quantity,
+            OrderSide::Ask => self.sold += quantity,
+        }
+        let direction: i64 = match side {
+            OrderSide::Bid => 1,
+            OrderSide::Ask => -1,
+        };
+        let open = self.net.unsigned_abs();
+        let closing = if self.net * direction < 0 { quantity.min(open) } else { 0 };
+
+        if closing > 0 {
+            // closing a long (sell) gains price - cost, closing a short (buy) gains cost - price
+            let sign = -direction as f64;
+            let mut left = closing;
+            while left > 0 {
+                let Some(lot) = self.lots.front_mut() else {
+                    break;
+                };
+                let take = lot.quantity.min(left);
+                self.realized_fifo += sign * (price - lot.price) as f64 * take as f64;
+                lot.quantity -= take;
+                left -= take;
+                if lot.quantity == 0 {
+                    self.lots.pop_front();
+                }
+            }
+            let average = self.weighted_cost / open as f64;
+            self.realized_weighted += sign * (price as f64 - average) * closing as f64;
+            self.weighted_cost -= average * closing as f64;
+            self.net += direction * closing as i64;
+            if self.net == 0 {
+                self.weighted_cost = 0.0;
+            }
+        }
+
+        let opening = quantity - closing;
+        if opening > 0 {
+            self.lots.push_back(Lot { price, quantity: opening });
+            self.weighted_cost += price as f64 * opening as f64;
+            self.net += direction * opening as i64;
+        }
+    }
+
+    /// Average cost of the open position in book units
+    pub fn avg_cost(&self, method: CostMethod) -> Option<f64> {
+        let open = self.net.unsigned_abs();
+        if open == 0 {
+            return None;
+        }
+        let cost = match method {
+            CostMethod::Fifo => self.lots.iter().map(|lot| lot.price as f64 * lot.quantity as f64).sum(),
+            CostMethod::WeightedAverage => self.weighted_cost,
+        };
+        Some(cost / open as f64)
+    }
+
+    #[inline]
+    pub fn realized(&self, method: CostMethod) -> f64 {
+        match method {
+            CostMethod::Fifo => self.realized_fifo,
+            CostMethod::WeightedAverage => self.realized_weighted,
+        }
+    }
+
+    /// PnL of the open position marked at `mark`, in book units
+    pub fn unrealized(&self, method: CostMethod, mark: BookPrice) -> f64 {
+        self.avg_cost(method)
+            .map_or(0.0, |avg_cost| (mark as f64 - avg_cost) * self.net as f64)
+    }
+}
+
+/// A position valued in the instrument currency, for dashboards
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PositionSnapshot {
+    pub instid: InstId,
+    pub net: i64,
+    pub long: BookQuantity,
+    pub short: BookQuantity,
+    pub bought: BookQuantity,
+    pub sold: BookQuantity,
+    pub avg_cost: Option<f64>,
+    pub mark: Option<BookPrice>,
+    pub realized_pnl: f64,
+    /// None if the position is open and there is no mark
+    pub unrealized_pnl: Option<f64>,
+    pub last_fill_time: TimeStamp,
+}
+
+impl PositionSnapshot {
+    #[inline]
+    pub fn total_pnl(&self) -> Option<f64> {
+        self.unrealized_pnl.map(|unrealized| self.realized_pnl + unrealized)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Valuation {
+    price_scale: u8,
+    contract_multiplier: f64,
+}
+
+impl Default for Valuation {
+    fn default() -> Self {
+        Self {
+            price_scale: 0,
+            contract_multiplier: 1.0,
+        }
+    }
+}
+
+/// Positions and PnL per `InstId` derived from my fills, marked against the quote mid.
+/// Instruments registered with `add_instrument` are valued with their price scale and contract multiplier
+#[derive(Debug, Clone, Default)]
+pub struct PositionKeeper {
+    method: CostMethod,
+    positions: FxHashMap<InstId, Position>,
+    valuations: FxHashMap<InstId, Valuation>,
+    marks: FxHashMap<InstId, BookPrice>,
+}
+
+impl PositionKeeper {
+    pub fn new(method: CostMethod) -> Self {
+        Self {
+            method,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn method(&self) -> CostMethod {
+        self.method
+    }
+
+    pub fn add_instrument(&mut self, spec: &InstrumentSpec) {
+        self.valuations.insert(
+            spec.id,
+            Valuation {
+                price_scale: spec.price_scale,
+                contract_multiplier: spec.contract_multiplier,
+            },
+        );
+    }
+
+    pub fn on_fill(&mut self, instid: InstId, side: OrderSide, fill: &Fill) {
+        self.positions
+            .entry(instid)
+            .or_insert_with(|| Position::new(instid))
+            .apply(side, fill.price, fill.quantity, fill.time);
+    }
+
+    /// Applies a fill of my order `request`
+    pub fn on_order_fill(&mut self, request: &OrderRequest, fill: &Fill) {
+        if let Some(side) = request.order_core.order_side() {
+            self.on_fill(request.instid, side, fill);
+        }
+    }
+
+    pub fn on_quote(&mut self, quote: &QuoteSnapshot) {
+        if let Some(mid) = quote.mid() {
+            self.marks.insert(quote.id, mid);
+        }
+    }
+
+    #[inline]
+    pub fn set_mark(&mut self, instid: InstId, mark: BookPrice) {
+        self.marks.insert(instid, mark);
+    }
+
+    #[inline]
+    pub fn position(&self, instid: &InstId) -> Option<&Position> {
+        self.positions.get(instid)
+    }
+
+    #[inline]
+    pub fn net(&self, instid: &InstId) -> i64 {
+        self.positions.get(instid).map_or(0, |position| position.net)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Position> {
+        self.positions.values()
+    }
+
+    pub fn snapshot(&self, instid: &InstId) -> Option<PositionSnapshot> {
+        let position = self.positions.get(instid)?;
+        let valuation = self.valuations.get(instid).copied().unwrap_or_default();
+        let to_currency = |raw: f64| price::to_f64(1, valuation.price_scale) * raw * valuation.contract_multiplier;
+        let mark = self.marks.get(instid).copied();
+        let unrealized_pnl = match mark {
+            Some(mark) => Some(to_currency(position.unrealized(self.method, mark))),
+            None if position.is_flat() => Some(0.0),
+            None => None,
+        };
+        Some(PositionSnapshot {
+            instid: *instid,
+            net: position.net,
+            long: position.long(),
+            short: position.short(),
+            bought: position.bought,
+            sold: position.sold,
+            avg_cost: position
+                .avg_cost(self.method)
+                .map(|avg_cost| price::to_f64(1, valuation.price_scale) * avg_cost),
+            mark,
+            realized_pnl: to_currency(position.realized(self.method)),
+            unrealized_pnl,
+            last_fill_time: position.last_fill_time,
+        })
+    }
+
+    pub fn snapshots(&self) -> Vec<PositionSnapshot> {
+        self.positions.keys().filter_map(|instid| self.snapshot(instid)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instid() -> InstId {
+        InstId::from_str("KOSPI200F", "KRX")
+    }
+
+    fn fill(exec_id: u64, price: BookPrice, quantity: BookQuantity) -> Fill {
+        Fill::new(exec_id, price, quantity, exec_id)
+    }
+
+    #[test]
+    fn test_fifo_and_weighted_cost() {
+        let mut position = Position::new(instid());
+        position.apply(OrderSide::Bid, 100, 10, 1);
+        position.apply(OrderSide::Bid, 110, 10, 2);
+        assert_eq!(position.avg_cost(CostMethod::Fifo), Some(105.0));
+        assert_eq!(position.avg_cost(CostMethod::WeightedAverage), Some(105.0));
+
+        position.apply(OrderSide::Ask, 120, 15, 3);
+        assert_eq!(position.net, 5);
+        // fifo closes 10 @ 100 and 5 @ 110
+        assert_eq!(position.realized(CostMethod::Fifo), 250.0);
+        assert_eq!(position.realized(CostMethod::WeightedAverage), 225.0);
+        assert_eq!(position.avg_cost(CostMethod::Fifo), Some(110.0));
+        assert_eq!(position.avg_cost(CostMethod::WeightedAverage), Some(105.0));
+
+        // flip to short 5 @ 90
+        position.apply(OrderSide::Ask, 90, 10, 4);
+        assert_eq!((position.net, position.long(), position.short()), (-5, 0, 5));
+        assert_eq!(position.realized(CostMethod::Fifo), 250.0 - 100.0);
+        assert_eq!(position.avg_cost(CostMethod::WeightedAverage), Some(90.0));
+        assert_eq!(position.unrealized(CostMethod::Fifo, 80), 50.0);
+        assert_eq!((position.bought, position.sold), (20, 25));
+    }
+
+    #[test]
+    fn test_keeper_snapshot_in_currency() {
+        let mut keeper = PositionKeeper::new(CostMethod::Fifo);
+        keeper.add_instrument(&InstrumentSpec::new(instid(), 5, 2).with_contract_multiplier(250_000.0));
+        keeper.on_fill(instid(), OrderSide::Bid, &fill(1, 35_000, 2));
+        let snapshot = keeper.snapshot(&instid()).unwrap();
+        assert_eq!(snapshot.unrealized_pnl, None);
+
+        let mut quote = QuoteSnapshot::sample(1);
+        quote.id = instid();
+        quote.ask_quote_data[0].book_price = 35_110;
+        quote.ask_quote_data[0].book_quantity = 1;
+        quote.bid_quote_data[0].book_price = 35_090;
+        quote.bid_quote_data[0].book_quantity = 1;
+        keeper.on_quote(&quote);
+        keeper.on_fill(instid(), OrderSide::Ask, &fill(2, 35_050, 1));
+
+        let snapshot = keeper.snapshot(&instid()).unwrap();
+        assert_eq!(snapshot.net, 1);
+        assert_eq!(snapshot.avg_cost, Some(350.0));
+        // (350.50 - 350.00) * 250,000 and (351.00 - 350.00) * 250,000
+        assert!((snapshot.realized_pnl - 125_000.0).abs() < 1e-6);
+        assert!((snapshot.unrealized_pnl.unwrap() - 250_000.0).abs() < 1e-6);
+        assert!((snapshot.total_pnl().unwrap() - 375_000.0).abs() < 1e-6);
+        assert_eq!(keeper.snapshots().len(), 1);
+    }
+}
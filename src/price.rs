@@ -0,0 +1,333 @@
+//! Fixed-point conversions of `BookPrice` (and `BookYield`).
+//!
+//! A book price is an integer number of `10^-scale` units, e.g., with scale 2 the book price 12345 is 123.45.
+//! The scale is a property of the instrument (`InstrumentSpec::price_scale`), not of the value,
+//! so the helpers here always take the scale explicitly. `BookYield` follows the same convention with its own scale.
+use crate::BookPrice;
+use serde::{Deserialize, Serialize};
+
+/// 10^18 is the largest power of ten in i64
+pub const MAX_PRICE_SCALE: u8 = 18;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriceError {
+    InvalidScale(u8),
+    InvalidTickSize(BookPrice),
+    NotFinite,
+    Overflow,
+    /// the decimal has more fractional digits than the scale allows
+    TooPrecise(String),
+    Parse(String),
+}
+
+impl std::fmt::Display for PriceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PriceError::InvalidScale(scale) => write!(f, "price scale {} exceeds {}", scale, MAX_PRICE_SCALE),
+            PriceError::InvalidTickSize(tick_size) => write!(f, "tick size must be positive: {}", tick_size),
+            PriceError::NotFinite => write!(f, "price is not finite"),
+            PriceError::Overflow => write!(f, "price overflows BookPrice"),
+            PriceError::TooPrecise(s) => write!(f, "price {} has more digits than the price scale", s),
+            PriceError::Parse(s) => write!(f, "invalid decimal price: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for PriceError {}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+pub enum TickRounding {
+    /// toward negative infinity
+    Down,
+    /// toward positive infinity
+    Up,
+    /// to the closest tick, ties toward positive infinity
+    #[default]
+    Nearest,
+}
+
+/// 10^scale
+#[inline]
+pub fn scale_factor(scale: u8) -> Result<i64, PriceError> {
+    if scale > MAX_PRICE_SCALE {
+        return Err(PriceError::InvalidScale(scale));
+    }
+    Ok(10i64.pow(scale as u32))
+}
+
+#[inline]
+pub fn to_f64(price: BookPrice, scale: u8) -> f64 {
+    price as f64 / 10f64.powi(scale as i32)
+}
+
+/// Rounded to the nearest book unit
+#[inline]
+pub fn from_f64(value: f64, scale: u8) -> Result<BookPrice, PriceError> {
+    scale_factor(scale)?;
+    if !value.is_finite() {
+        return Err(PriceError::NotFinite);
+    }
+    let scaled = (value * 10f64.powi(scale as i32)).round();
+    // i64::MAX as f64 rounds up to 2^63, which is already out of range
+    if scaled >= i64::MAX as f64 || scaled < i64::MIN as f64 {
+        return Err(PriceError::Overflow);
+    }
+    Ok(scaled as BookPrice)
+}
+
+/// Parses a decimal string such as "-123.45" exactly. Trailing zeros beyond the scale are allowed.
+pub fn from_decimal_str(s: &str, scale: u8) -> Result<BookPrice, PriceError> {
+    let factor = scale_factor(scale)?;
+    let trimmed = s.trim();
+    let (negative, digits) = match trimmed.as_bytes().first() {
+        Some(b'-') => (true, &trimmed[1..]),
+        Some(b'+') => (false, &trimmed[1..]),
+        _ => (false, trimmed),
+    };
+    let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+    let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if (int_part.is_empty() && frac_part.is_empty()) || !is_digits(int_part) || !is_digits(frac_part) {
+        return Err(PriceError::Parse(s.to_string()));
+    }
+
+    let (kept, dropped) = frac_part.split_at(frac_part.len().min(scale as usize));
+    if dropped.bytes().any(|b| b != b'0') {
+        return Err(PriceError::TooPrecise(s.to_string()));
+    }
+
+    // accumulate as a negative number so that i64::MIN can be parsed
+    let mut value: i64 = 0;
+    for b in int_part.bytes() {
+        value = value
+            .checked_mul(10)
+            .and_then(|v| v.checked_sub((b - b'0') as i64))
+            .ok_or(PriceError::Overflow)?;
+    }
+    value = value.checked_mul(factor).ok_or(PriceError::Overflow)?;
+    let mut frac: i64 = 0;
+    for b in kept.bytes() {
+        frac = frac * 10 + (b - b'0') as i64;
+    }
+    frac *= 10i64.pow((scale as usize - kept.len()) as u32);
+    value = value.checked_sub(frac).ok_or(PriceError::Overflow)?;
+
+    if negative {
+        Ok(value)
+    } else {
+        value.checked_neg().ok_or(PriceError::Overflow)
+    }
+}
+
+/// Writes `price` with exactly `scale` fractional digits, e.g., (12345, 2) => "123.45"
+pub fn to_decimal_string(price: BookPrice, scale: u8) -> String {
+    let abs = price.unsigned_abs();
+    let sign = if price < 0 { "-" } else { "" };
+    if scale == 0 {
+        return format!("{}{}", sign, abs);
+    }
+    let factor = 10u64.pow(scale.min(MAX_PRICE_SCALE) as u32);
+    format!(
+        "{}{}.{:0width$}",
+        sign,
+        abs / factor,
+        abs % factor,
+        width = scale as usize
+    )
+}
+
+/// Rounds `price` to a multiple of `tick_size`. A non-positive tick size leaves the price as it is.
+/// `Overflow` if the tick is beyond the range of `BookPrice`
+#[inline]
+pub fn round_to_tick(price: BookPrice, tick_size: BookPrice, rounding: TickRounding) -> Result<BookPrice, PriceError> {
+    if tick_size <= 0 {
+        return Ok(price);
+    }
+    let remainder = price.rem_euclid(tick_size);
+    if remainder == 0 {
+        return Ok(price);
+    }
+    let down = price.checked_sub(remainder);
+    let up = || price.checked_add(tick_size - remainder);
+    let rounded = match rounding {
+        TickRounding::Down => down,
+        TickRounding::Up => up(),
+        TickRounding::Nearest if remainder >= tick_size - remainder => up(),
+        TickRounding::Nearest => down,
+    };
+    rounded.ok_or(PriceError::Overflow)
+}
+
+#[inline]
+pub fn is_on_tick(price: BookPrice, tick_size: BookPrice) -> bool {
+    tick_size <= 0 || price.rem_euclid(tick_size) == 0
+}
+
+/// `price` moved by `ticks` ticks (negative moves down)
+#[inline]
+pub fn add_ticks(price: BookPrice, ticks: i64, tick_size: BookPrice) -> Result<BookPrice, PriceError> {
+    if tick_size <= 0 {
+        return Err(PriceError::InvalidTickSize(tick_size));
+    }
+    ticks
+        .checked_mul(tick_size)
+        .and_then(|offset| price.checked_add(offset))
+        .ok_or(PriceError::Overflow)
+}
+
+/// Number of whole ticks from `from` to `to`, rounded toward zero
+#[inline]
+pub fn ticks_between(from: BookPrice, to: BookPrice, tick_size: BookPrice) -> Result<i64, PriceError> {
+    if tick_size <= 0 {
+        return Err(PriceError::InvalidTickSize(tick_size));
+    }
+    let diff = to.checked_sub(from).ok_or(PriceError::Overflow)?;
+    Ok(diff / tick_size)
+}
+
+/// Converts a price between scales. Going to a smaller scale must not lose digits.
+pub fn rescale(price: BookPrice, from_scale: u8, to_scale: u8) -> Result<BookPrice, PriceError> {
+    if to_scale >= from_scale {
+        let factor = scale_factor(to_scale - from_scale)?;
+        scale_factor(to_scale)?;
+        price.checked_mul(factor).ok_or(PriceError::Overflow)
+    } else {
+        let factor = scale_factor(from_scale - to_scale)?;
+        if price % factor != 0 {
+            return Err(PriceError::TooPrecise(to_decimal_string(price, from_scale)));
+        }
+        Ok(price / factor)
+    }
+}
+
+/// A `BookPrice` bundled with its scale, for the places where the two travel together
+/// (display, serialization to text based protocols, etc.)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct ScaledPrice {
+    pub raw: BookPrice,
+    pub scale: u8,
+}
+
+impl ScaledPrice {
+    #[inline]
+    pub fn new(raw: BookPrice, scale: u8) -> Self {
+        Self { raw, scale }
+    }
+
+    #[inline]
+    pub fn from_f64(value: f64, scale: u8) -> Result<Self, PriceError> {
+        Ok(Self { raw: from_f64(value, scale)?, scale })
+    }
+
+    #[inline]
+    pub fn from_decimal_str(s: &str, scale: u8) -> Result<Self, PriceError> {
+        Ok(Self { raw: from_decimal_str(s, scale)?, scale })
+    }
+
+    #[inline]
+    pub fn to_f64(&self) -> f64 {
+        to_f64(self.raw, self.scale)
+    }
+
+    #[inline]
+    pub fn rescale(&self, scale: u8) -> Result<Self, PriceError> {
+        Ok(Self { raw: rescale(self.raw, self.scale, scale)?, scale })
+    }
+
+    #[inline]
+    pub fn round_to_tick(&self, tick_size: BookPrice, rounding: TickRounding) -> Result<Self, PriceError> {
+        Ok(Self { raw: round_to_tick(self.raw, tick_size, rounding)?, scale: self.scale })
+    }
+
+    #[inline]
+    pub fn add_ticks(&self, ticks: i64, tick_size: BookPrice) -> Result<Self, PriceError> {
+        Ok(Self { raw: add_ticks(self.raw, ticks, tick_size)?, scale: self.scale })
+    }
+}
+
+impl std::fmt::Display for ScaledPrice {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", to_decimal_string(self.raw, self.scale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_decimal_conversions() {
+        assert_eq!(from_decimal_str("123.45", 2), Ok(12_345));
+        assert_eq!(from_decimal_str("-0.5", 2), Ok(-50));
+        assert_eq!(from_decimal_str("7", 3), Ok(7_000));
+        assert_eq!(from_decimal_str(".25", 2), Ok(25));
+        assert_eq!(from_decimal_str("1.2500", 2), Ok(125));
+        assert!(matches!(from_decimal_str("1.255", 2), Err(PriceError::TooPrecise(_))));
+        assert!(matches!(from_decimal_str("1.2.3", 2), Err(PriceError::Parse(_))));
+        assert!(matches!(from_decimal_str("-", 2), Err(PriceError::Parse(_))));
+        assert_eq!(from_decimal_str("92233720368547758.08", 2), Err(PriceError::Overflow));
+        assert_eq!(from_decimal_str("-92233720368547758.08", 2), Ok(i64::MIN));
+
+        assert_eq!(to_decimal_string(12_345, 2), "123.45");
+        assert_eq!(to_decimal_string(-5, 2), "-0.05");
+        assert_eq!(to_decimal_string(70_000, 0), "70000");
+        assert_eq!(ScaledPrice::new(i64::MIN, 2).to_string(), "-92233720368547758.08");
+
+        assert_eq!(from_f64(0.1 + 0.2, 2), Ok(30));
+        assert_eq!(from_f64(f64::NAN, 2), Err(PriceError::NotFinite));
+        assert_eq!(from_f64(1e30, 2), Err(PriceError::Overflow));
+        assert_eq!(from_f64(1.0, 19), Err(PriceError::InvalidScale(19)));
+    }
+
+    #[test]
+    fn test_tick_rounding() {
+        assert_eq!(round_to_tick(12_347, 5, TickRounding::Down), Ok(12_345));
+        assert_eq!(round_to_tick(12_347, 5, TickRounding::Up), Ok(12_350));
+        assert_eq!(round_to_tick(12_347, 5, TickRounding::Nearest), Ok(12_345));
+        assert_eq!(round_to_tick(-12_347, 5, TickRounding::Down), Ok(-12_350));
+        assert_eq!(round_to_tick(10, 4, TickRounding::Nearest), Ok(12));
+        // the tick above i64::MAX and the one below i64::MIN do not exist
+        assert_eq!(round_to_tick(i64::MAX, 10, TickRounding::Up), Err(PriceError::Overflow));
+        assert_eq!(round_to_tick(i64::MAX, 10, TickRounding::Nearest), Err(PriceError::Overflow));
+        assert_eq!(round_to_tick(i64::MAX, 10, TickRounding::Down), Ok(i64::MAX - 7));
+        assert_eq!(round_to_tick(i64::MIN, 10, TickRounding::Down), Err(PriceError::Overflow));
+        assert_eq!(round_to_tick(i64::MIN, 10, TickRounding::Up), Ok(i64::MIN + 8));
+        assert_eq!(add_ticks(100, -3, 5), Ok(85));
+        assert_eq!(ticks_between(85, 100, 5), Ok(3));
+        assert_eq!(rescale(12_345, 2, 4), Ok(1_234_500));
+        assert!(rescale(12_345, 2, 1).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_decimal_round_trip(raw in any::<i64>(), scale in 0u8..=MAX_PRICE_SCALE) {
+            let s = to_decimal_string(raw, scale);
+            prop_assert_eq!(from_decimal_str(&s, scale), Ok(raw));
+        }
+
+        #[test]
+        fn prop_f64_round_trip(raw in -(1i64 << 50)..(1i64 << 50), scale in 0u8..=8) {
+            prop_assert_eq!(from_f64(to_f64(raw, scale), scale), Ok(raw));
+        }
+
+        #[test]
+        fn prop_round_to_tick(price in -(1i64 << 60)..(1i64 << 60), tick_size in 1i64..1_000_000) {
+            let down = round_to_tick(price, tick_size, TickRounding::Down).unwrap();
+            let up = round_to_tick(price, tick_size, TickRounding::Up).unwrap();
+            let nearest = round_to_tick(price, tick_size, TickRounding::Nearest).unwrap();
+            prop_assert!(is_on_tick(down, tick_size) && is_on_tick(up, tick_size) && is_on_tick(nearest, tick_size));
+            prop_assert!(down <= price && price <= up);
+            prop_assert!(up - down == 0 || up - down == tick_size);
+            prop_assert!(nearest == down || nearest == up);
+            prop_assert!((nearest - price).abs() * 2 <= tick_size);
+        }
+
+        #[test]
+        fn prop_tick_arithmetic(price in -(1i64 << 40)..(1i64 << 40), ticks in -100_000i64..100_000, tick_size in 1i64..10_000) {
+            let start = round_to_tick(price, tick_size, TickRounding::Down).unwrap();
+            let moved = add_ticks(start, ticks, tick_size).unwrap();
+            prop_assert!(is_on_tick(moved, tick_size));
+            prop_assert_eq!(ticks_between(start, moved, tick_size), Ok(ticks));
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::TimeStamp;
+use crate::order::execution::ExecutionReport;
+use crate::order::request::OrderRequest;
+use crate::protocol::error::ProtocolError;
+
+/// A venue message decoded from the front of a buffer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded {
+    /// bytes consumed, including the framing
+    pub len: usize,
+    /// None for messages without an order event (heartbeats, system events, etc.)
+    pub report: Option<ExecutionReport>,
+}
+
+impl Decoded {
+    #[inline]
+    pub fn new(len: usize, report: Option<ExecutionReport>) -> Self {
+        Self { len, report }
+    }
+}
+
+/// The frames decoded by `OrderProtocol::decode_all`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DecodedFrames {
+    /// the reports of the frames decoded before `error`, whose state changes are applied
+    pub reports: Vec<ExecutionReport>,
+    /// bytes of the decoded frames. On an error, the failing frame starts here
+    pub consumed: usize,
+    pub error: Option<ProtocolError>,
+    /// the length of the failing frame, to skip it. None when the framing itself is broken
+    pub failed_len: Option<usize>,
+}
+
+/// A binary order entry protocol: `OrderRequest`s to wire bytes and venue messages to `ExecutionReport`s.
+/// Implementations keep whatever per-order state the protocol needs (tokens, cumulative quantities, etc.),
+/// so one instance serves one session.
+pub trait OrderProtocol {
+    fn name(&self) -> &'static str;
+
+    /// Appends the frame of `request` to `buf`. The order core decides the message:
+    /// a new order, a cancel (`CancelOrder`) or a cancel/replace (`ModifyOrder`)
+    fn encode(&mut self, request: &OrderRequest, buf: &mut Vec<u8>) -> Result<(), ProtocolError>;
+
+    /// Decodes the first frame of `buf`, None until the frame is complete
+    fn decode(&mut self, buf: &[u8], systemtime: TimeStamp) -> Result<Option<Decoded>, ProtocolError>;
+
+    /// The length of the complete frame at the front of `buf` from the framing alone,
+    /// None until it is complete or if the framing is broken
+    fn frame_len(&self, buf: &[u8]) -> Option<usize>;
+
+    /// Decodes every complete frame of `buf`, stopping at the first frame that fails
+    fn decode_all(&mut self, buf: &[u8], systemtime: TimeStamp) -> DecodedFrames {
+        let mut frames = DecodedFrames::default();
+        loop {
+            match self.decode(&buf[frames.consumed..], systemtime) {
+                Ok(Some(decoded)) => {
+                    frames.consumed += decoded.len;
+                    frames.reports.extend(decoded.report);
+                }
+                Ok(None) => return frames,
+                Err(e) => {
+                    frames.error = Some(e);
+                    frames.failed_len = self.frame_len(&buf[frames.consumed..]);
+                    return frames;
+                }
+            }
+        }
+    }
+}
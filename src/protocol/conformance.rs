@@ -0,0 +1,341 @@
+//! A conformance kit for `OrderProtocol` implementations.
+//! An adapter supplies a `ConformanceVenue` speaking the venue side of its protocol,
+//! and `run_conformance` drives both through the order lifecycle.
+use crate::{BookPrice, BookQuantity, InstId, OrderCore, OrderId, TimeStamp};
+use crate::order::core::{CancelOrder, LimitOrder, ModifyOrder, NullOrder};
+use crate::order::enums::{OrderSide, OrderStatus};
+use crate::order::execution::{ExecType, ExecutionReport};
+use crate::order::manager::OrderManager;
+use crate::order::request::OrderRequest;
+use crate::protocol::adapter::OrderProtocol;
+use crate::protocol::error::ProtocolError;
+use std::fmt::Debug;
+
+/// How the venue answers a client message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VenueResponse {
+    /// accepted, canceled or replaced, depending on the request
+    Accept,
+    /// rejected, or cancel/replace rejected
+    Reject,
+}
+
+/// The venue side of a protocol, enough to answer the messages of the kit
+pub trait ConformanceVenue {
+    /// Answers the client frame `request` with the frame of `response`
+    fn respond(&mut self, request: &[u8], response: VenueResponse) -> Result<Vec<u8>, ProtocolError>;
+
+    /// An unsolicited execution of `quantity` of the accepted order `order_id` at its limit price
+    fn execute(&mut self, order_id: OrderId, quantity: BookQuantity) -> Result<Vec<u8>, ProtocolError>;
+}
+
+/// The order the kit sends. `price` and `replace_price` must be valid for the protocol's instrument data
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConformanceOrder {
+    pub instid: InstId,
+    pub price: BookPrice,
+    pub replace_price: BookPrice,
+    /// at least 2, it is filled in two parts
+    pub quantity: BookQuantity,
+}
+
+impl ConformanceOrder {
+    pub fn new(instid: InstId, price: BookPrice, quantity: BookQuantity) -> Self {
+        Self {
+            instid,
+            price,
+            replace_price: price,
+            quantity,
+        }
+    }
+
+    pub fn with_replace_price(mut self, replace_price: BookPrice) -> Self {
+        self.replace_price = replace_price;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConformanceCase {
+    pub name: &'static str,
+    /// None if passed
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConformanceReport {
+    pub protocol: &'static str,
+    pub cases: Vec<ConformanceCase>,
+}
+
+impl ConformanceReport {
+    #[inline]
+    pub fn is_passed(&self) -> bool {
+        self.cases.iter().all(|case| case.error.is_none())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &ConformanceCase> {
+        self.cases.iter().filter(|case| case.error.is_some())
+    }
+
+    /// Panics with the failed cases, for adapter tests
+    pub fn assert_passed(&self) {
+        let failures: Vec<String> = self
+            .failures()
+            .map(|case| format!("{}: {}", case.name, case.error.as_deref().unwrap_or_default()))
+            .collect();
+        assert!(failures.is_empty(), "{} failed conformance:\n{}", self.protocol, failures.join("\n"));
+    }
+}
+
+const ORDER_ID: OrderId = 1;
+const REPLACED_ORDER_ID: OrderId = 2;
+
+type CaseFn<P, V> = fn(&mut Harness<P, V>) -> Result<(), String>;
+
+/// Runs every case on a fresh protocol and venue from `make`
+pub fn run_conformance<P, V, F>(make: F, order: &ConformanceOrder) -> ConformanceReport
+where
+    P: OrderProtocol,
+    V: ConformanceVenue,
+    F: Fn() -> (P, V),
+{
+    let cases: [(&'static str, CaseFn<P, V>); 11] = [
+        ("accept", accept),
+        ("fills", fills),
+        ("cancel", cancel),
+        ("replace", replace),
+        ("reject", reject),
+        ("cancel_reject", cancel_reject),
+        ("replace_reject", replace_reject),
+        ("partial_frames", partial_frames),
+        ("back_to_back_frames", back_to_back_frames),
+        ("unsupported_order", unsupported_order),
+        ("order_manager", order_manager),
+    ];
+    let mut protocol_name = "";
+    let cases = cases
+        .iter()
+        .map(|(name, case)| {
+            let (protocol, venue) = make();
+            protocol_name = protocol.name();
+            let mut harness = Harness { protocol, venue, order, time: 0 };
+            ConformanceCase { name, error: case(&mut harness).err() }
+        })
+        .collect();
+    ConformanceReport { protocol: protocol_name, cases }
+}
+
+struct Harness<'a, P, V> {
+    protocol: P,
+    venue: V,
+    order: &'a ConformanceOrder,
+    time: TimeStamp,
+}
+
+impl<P: OrderProtocol, V: ConformanceVenue> Harness<'_, P, V> {
+    fn request(&mut self, order_core: OrderCore) -> OrderRequest {
+        self.time += 1;
+        OrderRequest::new(self.order.instid, order_core, self.time)
+    }
+
+    fn limit(&mut self) -> OrderRequest {
+        let core = LimitOrder::new(self.order.price, self.order.quantity, OrderSide::Bid, ORDER_ID);
+        self.request(OrderCore::LimitOrder(core))
+    }
+
+    fn cancel(&mut self) -> OrderRequest {
+        self.request(OrderCore::CancelOrder(CancelOrder::new(ORDER_ID)))
+    }
+
+    fn modify(&self) -> ModifyOrder {
+        ModifyOrder::new(ORDER_ID, self.order.replace_price, self.order.quantity * 2).with_new_order_id(REPLACED_ORDER_ID)
+    }
+
+    /// The venue frame answering `request`
+    fn venue_bytes(&mut self, request: &OrderRequest, response: VenueResponse) -> Result<Vec<u8>, String> {
+        let mut buf = Vec::new();
+        self.protocol.encode(request, &mut buf).map_err(|e| format!("encode: {}", e))?;
+        if buf.is_empty() {
+            return Err("encode wrote nothing".to_string());
+        }
+        self.venue.respond(&buf, response).map_err(|e| format!("venue: {}", e))
+    }
+
+    fn send(&mut self, request: &OrderRequest, response: VenueResponse) -> Result<ExecutionReport, String> {
+        let bytes = self.venue_bytes(request, response)?;
+        self.receive(&bytes)
+    }
+
+    fn execute(&mut self, quantity: BookQuantity) -> Result<ExecutionReport, String> {
+        let bytes = self.venue.execute(ORDER_ID, quantity).map_err(|e| format!("venue: {}", e))?;
+        self.receive(&bytes)
+    }
+
+    /// Decodes one frame which must be exactly `bytes` and carry a report
+    fn receive(&mut self, bytes: &[u8]) -> Result<ExecutionReport, String> {
+        self.time += 1;
+        let decoded = self
+            .protocol
+            .decode(bytes, self.time)
+            .map_err(|e| format!("decode: {}", e))?
+            .ok_or("decode: a complete frame decoded as incomplete")?;
+        expect("decoded length", decoded.len, bytes.len())?;
+        decoded.report.ok_or_else(|| "decode: no report".to_string())
+    }
+
+    fn accepted(&mut self) -> Result<ExecutionReport, String> {
+        let request = self.limit();
+        self.send(&request, VenueResponse::Accept)
+    }
+}
+
+fn expect<T: PartialEq + Debug>(what: &str, actual: T, expected: T) -> Result<(), String> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("{}: expected {:?}, got {:?}", what, expected, actual))
+    }
+}
+
+fn accept<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    let report = h.accepted()?;
+    expect("exec type", report.exec_type, ExecType::New)?;
+    expect("order id", report.order_id, ORDER_ID)?;
+    expect("instrument", report.instid, h.order.instid)?;
+    expect("cum quantity", report.cum_quantity, 0)?;
+    expect("leaves quantity", report.leaves_quantity, h.order.quantity)?;
+    expect("order price", report.order_price, Some(h.order.price))
+}
+
+fn fills<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    h.accepted()?;
+    let first = h.order.quantity / 2;
+    let report = h.execute(first)?;
+    expect("exec type", report.exec_type, ExecType::Trade)?;
+    expect("last quantity", report.last_quantity, first)?;
+    expect("last price", report.last_price, h.order.price)?;
+    expect("cum quantity", report.cum_quantity, first)?;
+    expect("leaves quantity", report.leaves_quantity, h.order.quantity - first)?;
+    expect("status", report.ord_status(), OrderStatus::PartiallyFilled)?;
+
+    let report = h.execute(h.order.quantity - first)?;
+    expect("cum quantity", report.cum_quantity, h.order.quantity)?;
+    expect("leaves quantity", report.leaves_quantity, 0)?;
+    expect("status", report.ord_status(), OrderStatus::FullyFilled)?;
+    expect("avg price", report.avg_price, h.order.price as f64)?;
+    expect("distinct exec ids", report.exec_id != 0, true)
+}
+
+fn cancel<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    h.accepted()?;
+    let request = h.cancel();
+    let report = h.send(&request, VenueResponse::Accept)?;
+    expect("exec type", report.exec_type, ExecType::Canceled)?;
+    expect("order id", report.order_id, ORDER_ID)?;
+    expect("leaves quantity", report.leaves_quantity, 0)
+}
+
+fn replace<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    h.accepted()?;
+    let filled = h.order.quantity / 2;
+    h.execute(filled)?;
+    let modify = h.modify();
+    let request = h.request(OrderCore::ModifyOrder(modify.clone()));
+    let report = h.send(&request, VenueResponse::Accept)?;
+    expect("exec type", report.exec_type, ExecType::Replaced)?;
+    expect("order id", report.order_id, REPLACED_ORDER_ID)?;
+    expect("orig order id", report.orig_order_id, Some(ORDER_ID))?;
+    expect("order price", report.order_price, Some(modify.price))?;
+    expect("order quantity", report.order_quantity, Some(modify.quantity))?;
+    expect("cum quantity", report.cum_quantity, filled)?;
+    expect("leaves quantity", report.leaves_quantity, modify.quantity - filled)
+}
+
+fn reject<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    let request = h.limit();
+    let report = h.send(&request, VenueResponse::Reject)?;
+    expect("exec type", report.exec_type, ExecType::Rejected)?;
+    expect("order id", report.order_id, ORDER_ID)
+}
+
+fn cancel_reject<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    h.accepted()?;
+    let request = h.cancel();
+    let report = h.send(&request, VenueResponse::Reject)?;
+    expect("exec type", report.exec_type, ExecType::CancelRejected)?;
+    expect("order id", report.order_id, ORDER_ID)
+}
+
+fn replace_reject<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    h.accepted()?;
+    let request = h.request(OrderCore::ModifyOrder(h.modify()));
+    let report = h.send(&request, VenueResponse::Reject)?;
+    expect("exec type", report.exec_type, ExecType::CancelRejected)?;
+    expect("order id", report.order_id, ORDER_ID)?;
+    // the original order still works
+    let report = h.execute(1)?;
+    expect("fill after replace reject", report.order_id, ORDER_ID)
+}
+
+fn partial_frames<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    let request = h.limit();
+    let bytes = h.venue_bytes(&request, VenueResponse::Accept)?;
+    for len in 0..bytes.len() {
+        let decoded = h.protocol.decode(&bytes[..len], 0).map_err(|e| format!("decode of {} bytes: {}", len, e))?;
+        if decoded.is_some() {
+            return Err(format!("decoded a frame from {} of {} bytes", len, bytes.len()));
+        }
+        expect("frame length of a partial frame", h.protocol.frame_len(&bytes[..len]), None)?;
+    }
+    expect("frame length", h.protocol.frame_len(&bytes), Some(bytes.len()))?;
+    expect("exec type", h.receive(&bytes)?.exec_type, ExecType::New)
+}
+
+fn back_to_back_frames<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    let request = h.limit();
+    let mut bytes = h.venue_bytes(&request, VenueResponse::Accept)?;
+    bytes.extend(h.venue.execute(ORDER_ID, 1).map_err(|e| format!("venue: {}", e))?);
+    // the first byte of a third frame stays in the buffer
+    bytes.push(bytes[0]);
+
+    let frames = h.protocol.decode_all(&bytes, 0);
+    if let Some(e) = frames.error {
+        return Err(format!("decode: {}", e));
+    }
+    let exec_types: Vec<ExecType> = frames.reports.iter().map(|report| report.exec_type).collect();
+    expect("exec types", exec_types, vec![ExecType::New, ExecType::Trade])?;
+    expect("consumed", frames.consumed, bytes.len() - 1)
+}
+
+fn unsupported_order<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    let request = h.request(OrderCore::NullOrder(NullOrder {}));
+    let mut buf = Vec::new();
+    match h.protocol.encode(&request, &mut buf) {
+        Ok(()) => Err("a NullOrder was encoded".to_string()),
+        Err(_) => expect("bytes written on error", buf.len(), 0),
+    }
+}
+
+fn order_manager<P: OrderProtocol, V: ConformanceVenue>(h: &mut Harness<P, V>) -> Result<(), String> {
+    let mut manager = OrderManager::new(8);
+    let request = h.limit();
+    manager.insert(request.clone()).map_err(|e| e.to_string())?;
+    let mut apply = |report: ExecutionReport| manager.on_execution_report(&report).map_err(|e| e.to_string());
+
+    apply(h.send(&request, VenueResponse::Accept)?)?;
+    apply(h.execute(1)?)?;
+    let modify = h.modify();
+    let replace = h.request(OrderCore::ModifyOrder(modify.clone()));
+    let report = h.send(&replace, VenueResponse::Accept)?;
+    manager.on_replace_requested(&modify, h.time).map_err(|e| e.to_string())?;
+    manager.on_execution_report(&report).map_err(|e| e.to_string())?;
+
+    let cancel = h.request(OrderCore::CancelOrder(CancelOrder::new(REPLACED_ORDER_ID)));
+    let report = h.send(&cancel, VenueResponse::Accept)?;
+    let status = manager.on_execution_report(&report).map_err(|e| e.to_string())?;
+    expect("final status", status, OrderStatus::Canceled)?;
+    let order = manager.find(&REPLACED_ORDER_ID).ok_or("replaced order not found")?;
+    expect("filled", order.filled, Some(1))?;
+    expect("order quantity", order.order_core.quantity(), Some(modify.quantity))
+}
@@ -0,0 +1,55 @@
+use crate::instrument::error::InstrumentError;
+use crate::order::enums::OrderType;
+use crate::order::error::OrderError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolError {
+    /// an order or an attribute the protocol can not express
+    Unsupported(String),
+    UnknownMessageType(u8),
+    /// the frame is shorter than its message type requires
+    BadLength {
+        msg_type: u8,
+        len: usize,
+    },
+    InvalidField {
+        field: &'static str,
+        value: String,
+    },
+    /// a venue message about an order this session did not send
+    UnknownToken(String),
+    MissingOrderId(OrderType),
+    Instrument(InstrumentError),
+    Order(OrderError),
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProtocolError::Unsupported(what) => write!(f, "unsupported by the protocol: {}", what),
+            ProtocolError::UnknownMessageType(msg_type) => write!(f, "unknown message type: 0x{:02x}", msg_type),
+            ProtocolError::BadLength { msg_type, len } => {
+                write!(f, "bad length {} of message type 0x{:02x}", len, msg_type)
+            }
+            ProtocolError::InvalidField { field, value } => write!(f, "invalid {}: {}", field, value),
+            ProtocolError::UnknownToken(token) => write!(f, "unknown order token: {}", token),
+            ProtocolError::MissingOrderId(core_type) => write!(f, "{} has no order id for the protocol", core_type),
+            ProtocolError::Instrument(e) => write!(f, "{}", e),
+            ProtocolError::Order(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<InstrumentError> for ProtocolError {
+    fn from(e: InstrumentError) -> Self {
+        ProtocolError::Instrument(e)
+    }
+}
+
+impl From<OrderError> for ProtocolError {
+    fn from(e: OrderError) -> Self {
+        ProtocolError::Order(e)
+    }
+}
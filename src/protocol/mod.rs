@@ -0,0 +1,4 @@
+pub mod error;
+pub mod adapter;
+pub mod ouch;
+pub mod conformance;
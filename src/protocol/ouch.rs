@@ -0,0 +1,833 @@
+//! An OUCH 4.2 style order entry protocol over SoupBinTCP framing.
+//! A frame is a 2 byte big endian length, a packet type and a message. Integers are big endian,
+//! alphas are left aligned and padded with spaces, and prices are u32 with 4 decimals.
+//! Order tokens are the `OrderId`s in base36.
+use crate::{BookPrice, BookQuantity, InstId, OrderCore, OrderId, TimeStamp};
+use crate::instrument::registry::InstrumentRegistry;
+use crate::order::core::{LimitOrder, ModifyOrder};
+use crate::order::enums::{OrderSide, OrderType, TimeInForce};
+use crate::order::execution::{CancelRejectResponseTo, ExecType, ExecutionReport};
+use crate::order::id_gen::ClOrdIdEncoding;
+use crate::order::request::OrderRequest;
+use crate::price;
+use crate::protocol::adapter::{Decoded, OrderProtocol};
+use crate::protocol::conformance::{ConformanceVenue, VenueResponse};
+use crate::protocol::error::ProtocolError;
+use crate::instrument::error::InstrumentError;
+use rustc_hash::FxHashMap;
+
+pub const PRICE_SCALE: u8 = 4;
+pub const TOKEN_LEN: usize = 14;
+pub const STOCK_LEN: usize = 8;
+pub const FIRM_LEN: usize = 4;
+
+/// SoupBinTCP packet types
+pub mod packet {
+    /// client to venue
+    pub const UNSEQUENCED_DATA: u8 = b'U';
+    /// venue to client
+    pub const SEQUENCED_DATA: u8 = b'S';
+    pub const SERVER_HEARTBEAT: u8 = b'H';
+    pub const LOGIN_ACCEPTED: u8 = b'A';
+    pub const LOGIN_REJECTED: u8 = b'J';
+    pub const END_OF_SESSION: u8 = b'Z';
+    /// either way
+    pub const DEBUG: u8 = b'+';
+}
+
+pub mod msg_type {
+    pub const ENTER_ORDER: u8 = b'O';
+    pub const REPLACE_ORDER: u8 = b'U';
+    pub const CANCEL_ORDER: u8 = b'X';
+    //
+    pub const SYSTEM_EVENT: u8 = b'S';
+    pub const ACCEPTED: u8 = b'A';
+    pub const REPLACED: u8 = b'U';
+    pub const CANCELED: u8 = b'C';
+    pub const EXECUTED: u8 = b'E';
+    pub const REJECTED: u8 = b'J';
+    pub const CANCEL_REJECT: u8 = b'I';
+
+    /// The length of a client message of the type
+    pub fn client_len(msg_type: u8) -> Option<usize> {
+        match msg_type {
+            ENTER_ORDER => Some(49),
+            REPLACE_ORDER => Some(47),
+            CANCEL_ORDER => Some(19),
+            _ => None,
+        }
+    }
+
+    /// The length of a venue message of the type
+    pub fn venue_len(msg_type: u8) -> Option<usize> {
+        match msg_type {
+            SYSTEM_EVENT => Some(10),
+            ACCEPTED => Some(66),
+            REPLACED => Some(80),
+            CANCELED => Some(28),
+            EXECUTED => Some(40),
+            REJECTED => Some(24),
+            CANCEL_REJECT => Some(23),
+            _ => None,
+        }
+    }
+}
+
+/// Time in force values in seconds, with the two special values for the session
+pub mod tif {
+    pub const IMMEDIATE: u32 = 0;
+    pub const MARKET_HOURS: u32 = 99_998;
+    pub const SYSTEM_HOURS: u32 = 99_999;
+}
+
+const ENCODING: ClOrdIdEncoding = ClOrdIdEncoding::Base36;
+
+#[inline]
+pub fn token(order_id: OrderId) -> String {
+    ENCODING.encode(order_id)
+}
+
+pub fn order_id_of(token: &str) -> Result<OrderId, ProtocolError> {
+    ENCODING
+        .decode(token)
+        .map_err(|_| ProtocolError::InvalidField { field: "order token", value: token.to_string() })
+}
+
+pub fn time_in_force_code(time_in_force: TimeInForce) -> Result<u32, ProtocolError> {
+    match time_in_force {
+        TimeInForce::Ioc => Ok(tif::IMMEDIATE),
+        TimeInForce::Day => Ok(tif::MARKET_HOURS),
+        TimeInForce::Gtc => Ok(tif::SYSTEM_HOURS),
+        other => Err(ProtocolError::Unsupported(format!("time in force {:?}", other))),
+    }
+}
+
+pub fn reject_reason(code: u8) -> &'static str {
+    match code {
+        b'T' => "test mode",
+        b'H' => "halted",
+        b'Z' => "shares exceed safety threshold",
+        b'S' => "invalid stock",
+        b'D' => "invalid display type",
+        b'C' => "exchange closed",
+        b'X' => "invalid price",
+        b'O' => "other",
+        _ => "unknown reason",
+    }
+}
+
+fn side_code(side: OrderSide) -> u8 {
+    match side {
+        OrderSide::Bid => b'B',
+        OrderSide::Ask => b'S',
+    }
+}
+
+fn side_of(code: u8) -> Result<OrderSide, ProtocolError> {
+    match code {
+        b'B' => Ok(OrderSide::Bid),
+        // sell, sell short and sell short exempt
+        b'S' | b'T' | b'E' => Ok(OrderSide::Ask),
+        _ => Err(ProtocolError::InvalidField { field: "side", value: (code as char).to_string() }),
+    }
+}
+
+fn wire_quantity(field: &'static str, quantity: BookQuantity) -> Result<u32, ProtocolError> {
+    u32::try_from(quantity).map_err(|_| ProtocolError::InvalidField { field, value: quantity.to_string() })
+}
+
+fn wire_price(price: BookPrice, price_scale: u8) -> Result<u32, ProtocolError> {
+    let wire = price::rescale(price, price_scale, PRICE_SCALE).map_err(InstrumentError::from)?;
+    u32::try_from(wire).map_err(|_| ProtocolError::InvalidField { field: "price", value: price.to_string() })
+}
+
+fn book_price(wire: u32, price_scale: u8) -> Result<BookPrice, ProtocolError> {
+    Ok(price::rescale(wire as BookPrice, PRICE_SCALE, price_scale).map_err(InstrumentError::from)?)
+}
+
+fn put_alpha(buf: &mut Vec<u8>, value: &[u8], width: usize) {
+    buf.extend_from_slice(value);
+    buf.resize(buf.len() + width - value.len(), b' ');
+}
+
+fn check_alpha(field: &'static str, value: &str, width: usize) -> Result<(), ProtocolError> {
+    if value.len() > width || !value.is_ascii() {
+        return Err(ProtocolError::InvalidField { field, value: value.to_string() });
+    }
+    Ok(())
+}
+
+fn alpha_at(message: &[u8], offset: usize, width: usize) -> Result<&str, ProtocolError> {
+    let raw = &message[offset..offset + width];
+    std::str::from_utf8(raw)
+        .map(|s| s.trim_end_matches(' '))
+        .map_err(|_| ProtocolError::InvalidField { field: "alpha", value: String::from_utf8_lossy(raw).into_owned() })
+}
+
+#[inline]
+fn u32_at(message: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(message[offset..offset + 4].try_into().expect("4 bytes"))
+}
+
+#[inline]
+fn u64_at(message: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(message[offset..offset + 8].try_into().expect("8 bytes"))
+}
+
+/// Appends a frame of `packet_type` with the message written by `write`
+fn put_frame(buf: &mut Vec<u8>, packet_type: u8, write: impl FnOnce(&mut Vec<u8>)) {
+    let start = buf.len();
+    buf.extend_from_slice(&[0, 0, packet_type]);
+    write(buf);
+    let len = (buf.len() - start - 2) as u16;
+    buf[start..start + 2].copy_from_slice(&len.to_be_bytes());
+}
+
+struct Frame<'a> {
+    packet_type: u8,
+    message: &'a [u8],
+    len: usize,
+}
+
+/// The first frame of `buf`, None until it is complete
+fn split_frame(buf: &[u8]) -> Result<Option<Frame<'_>>, ProtocolError> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let len = u16::from_be_bytes([buf[0], buf[1]]) as usize;
+    if len == 0 {
+        return Err(ProtocolError::BadLength { msg_type: 0, len });
+    }
+    if buf.len() < 2 + len {
+        return Ok(None);
+    }
+    Ok(Some(Frame { packet_type: buf[2], message: &buf[3..2 + len], len: 2 + len }))
+}
+
+/// The message type after checking the length of the message
+fn checked_type(message: &[u8], expected_len: fn(u8) -> Option<usize>) -> Result<u8, ProtocolError> {
+    let msg_type = *message.first().ok_or(ProtocolError::BadLength { msg_type: 0, len: 0 })?;
+    let len = expected_len(msg_type).ok_or(ProtocolError::UnknownMessageType(msg_type))?;
+    if message.len() != len {
+        return Err(ProtocolError::BadLength { msg_type, len: message.len() });
+    }
+    Ok(msg_type)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct OuchOrder {
+    instid: InstId,
+    side: OrderSide,
+    /// including the executed quantity
+    quantity: BookQuantity,
+    price: BookPrice,
+    time_in_force: u32,
+    display: u8,
+    min_quantity: u32,
+    cum_quantity: BookQuantity,
+    /// sum of price * quantity of the executions in book units
+    notional: f64,
+}
+
+impl OuchOrder {
+    #[inline]
+    fn leaves_quantity(&self) -> BookQuantity {
+        self.quantity.saturating_sub(self.cum_quantity)
+    }
+
+    #[inline]
+    fn avg_price(&self) -> f64 {
+        if self.cum_quantity == 0 {
+            0.0
+        } else {
+            self.notional / self.cum_quantity as f64
+        }
+    }
+
+    fn report(&self, exec_id: u64, order_id: OrderId, exec_type: ExecType) -> ExecutionReport {
+        ExecutionReport::new(exec_id, order_id, self.instid, exec_type).with_quantities(
+            self.cum_quantity,
+            self.leaves_quantity(),
+            self.avg_price(),
+        )
+    }
+}
+
+/// The client side of the protocol. Only limit orders with day, IOC or GTC time in force are supported.
+/// Stocks are the instrument codes, and venue messages are mapped to `InstId`s of `venue`.
+/// Reports other than executions have `exec_id` 0, executions have the match number.
+#[derive(Debug, Clone)]
+pub struct OuchProtocol {
+    registry: InstrumentRegistry,
+    venue: String,
+    firm: String,
+    orders: FxHashMap<OrderId, OuchOrder>,
+}
+
+impl OuchProtocol {
+    pub fn new(registry: InstrumentRegistry, venue: &str) -> Self {
+        Self {
+            registry,
+            venue: venue.to_string(),
+            firm: String::new(),
+            orders: FxHashMap::default(),
+        }
+    }
+
+    pub fn with_firm(mut self, firm: &str) -> Self {
+        self.firm = firm.to_string();
+        self
+    }
+
+    #[inline]
+    pub fn registry(&self) -> &InstrumentRegistry {
+        &self.registry
+    }
+
+    /// Orders sent or accepted and not yet done
+    #[inline]
+    pub fn open_orders(&self) -> usize {
+        self.orders.len()
+    }
+
+    fn price_scale(&self, instid: &InstId) -> Result<u8, ProtocolError> {
+        Ok(self.registry.try_get(instid)?.price_scale)
+    }
+
+    fn order(&self, order_id: OrderId) -> Result<&OuchOrder, ProtocolError> {
+        self.orders.get(&order_id).ok_or_else(|| ProtocolError::UnknownToken(token(order_id)))
+    }
+
+    fn order_mut(&mut self, order_id: OrderId) -> Result<&mut OuchOrder, ProtocolError> {
+        self.orders.get_mut(&order_id).ok_or_else(|| ProtocolError::UnknownToken(token(order_id)))
+    }
+
+    fn enter_order(&mut self, instid: InstId, order: &LimitOrder, buf: &mut Vec<u8>) -> Result<(), ProtocolError> {
+        let attributes = &order.attributes;
+        let stock = instid.code_str();
+        check_alpha("stock", stock, STOCK_LEN)?;
+        check_alpha("firm", &self.firm, FIRM_LEN)?;
+        let price = wire_price(order.price, self.price_scale(&instid)?)?;
+        let shares = wire_quantity("shares", order.quantity)?;
+        let time_in_force = time_in_force_code(attributes.time_in_force)?;
+        let min_quantity = wire_quantity("minimum quantity", attributes.min_quantity.unwrap_or(0))?;
+        let display = if attributes.post_only {
+            b'P'
+        } else if attributes.is_hidden() {
+            b'N'
+        } else {
+            b'Y'
+        };
+
+        put_frame(buf, packet::UNSEQUENCED_DATA, |buf| {
+            buf.push(msg_type::ENTER_ORDER);
+            put_alpha(buf, token(order.order_id).as_bytes(), TOKEN_LEN);
+            buf.push(side_code(order.order_side));
+            buf.extend_from_slice(&shares.to_be_bytes());
+            put_alpha(buf, stock.as_bytes(), STOCK_LEN);
+            buf.extend_from_slice(&price.to_be_bytes());
+            buf.extend_from_slice(&time_in_force.to_be_bytes());
+            put_alpha(buf, self.firm.as_bytes(), FIRM_LEN);
+            buf.push(display);
+            buf.push(b'A'); // capacity: agency
+            buf.push(b'N'); // intermarket sweep
+            buf.extend_from_slice(&min_quantity.to_be_bytes());
+            buf.push(b'N'); // cross type: no cross
+            buf.push(b'R'); // customer type: retail
+        });
+        self.orders.insert(
+            order.order_id,
+            OuchOrder {
+                instid,
+                side: order.order_side,
+                quantity: order.quantity,
+                price: order.price,
+                time_in_force,
+                display,
+                min_quantity,
+                cum_quantity: 0,
+                notional: 0.0,
+            },
+        );
+        Ok(())
+    }
+
+    /// The replacement keeps the time in force and the display of the order.
+    /// Shares are the new open quantity: the new total quantity less what is executed
+    fn replace_order(&mut self, modify: &ModifyOrder, buf: &mut Vec<u8>) -> Result<(), ProtocolError> {
+        let new_order_id = modify.new_order_id.ok_or(ProtocolError::MissingOrderId(OrderType::Modify))?;
+        let order = self.order(modify.order_id)?;
+        let price = wire_price(modify.price, self.price_scale(&order.instid)?)?;
+        let shares = wire_quantity("shares", modify.quantity.saturating_sub(order.cum_quantity))?;
+
+        put_frame(buf, packet::UNSEQUENCED_DATA, |buf| {
+            buf.push(msg_type::REPLACE_ORDER);
+            put_alpha(buf, token(modify.order_id).as_bytes(), TOKEN_LEN);
+            put_alpha(buf, token(new_order_id).as_bytes(), TOKEN_LEN);
+            buf.extend_from_slice(&shares.to_be_bytes());
+            buf.extend_from_slice(&price.to_be_bytes());
+            buf.extend_from_slice(&order.time_in_force.to_be_bytes());
+            buf.push(order.display);
+            buf.push(b'N');
+            buf.extend_from_slice(&order.min_quantity.to_be_bytes());
+        });
+        Ok(())
+    }
+
+    /// Accepted and Replaced share the layout up to the order state
+    fn accepted_order(&self, message: &[u8]) -> Result<(OrderId, OuchOrder), ProtocolError> {
+        let order_id = order_id_of(alpha_at(message, 9, TOKEN_LEN)?)?;
+        let instid = self.registry.try_find(alpha_at(message, 28, STOCK_LEN)?, &self.venue)?;
+        let price = book_price(u32_at(message, 36), self.price_scale(&instid)?)?;
+        let order = OuchOrder {
+            instid,
+            side: side_of(message[23])?,
+            quantity: u32_at(message, 24) as BookQuantity,
+            price,
+            time_in_force: u32_at(message, 40),
+            display: message[48],
+            min_quantity: u32_at(message, 59),
+            cum_quantity: 0,
+            notional: 0.0,
+        };
+        Ok((order_id, order))
+    }
+
+    fn on_message(&mut self, message: &[u8], systemtime: TimeStamp) -> Result<Option<ExecutionReport>, ProtocolError> {
+        let msg_type = checked_type(message, msg_type::venue_len)?;
+        let timestamp = u64_at(message, 1);
+        let report = match msg_type {
+            msg_type::SYSTEM_EVENT => return Ok(None),
+            msg_type::ACCEPTED => {
+                let (order_id, order) = self.accepted_order(message)?;
+                let report = order
+                    .report(0, order_id, ExecType::New)
+                    .with_order_terms(Some(order.price), Some(order.quantity));
+                self.orders.insert(order_id, order);
+                report
+            }
+            msg_type::REPLACED => {
+                let (order_id, mut order) = self.accepted_order(message)?;
+                let orig_order_id = order_id_of(alpha_at(message, 65, TOKEN_LEN)?)?;
+                let previous = self.orders.remove(&orig_order_id).ok_or_else(|| ProtocolError::UnknownToken(token(orig_order_id)))?;
+                // the replaced shares are the open quantity
+                order.quantity += previous.cum_quantity;
+                order.cum_quantity = previous.cum_quantity;
+                order.notional = previous.notional;
+                let report = order
+                    .report(0, order_id, ExecType::Replaced)
+                    .with_orig_order_id(orig_order_id)
+                    .with_order_terms(Some(order.price), Some(order.quantity));
+                self.orders.insert(order_id, order);
+                report
+            }
+            msg_type::CANCELED => {
+                let order_id = order_id_of(alpha_at(message, 9, TOKEN_LEN)?)?;
+                let order = self.order_mut(order_id)?;
+                let decrement = (u32_at(message, 23) as BookQuantity).min(order.leaves_quantity());
+                order.quantity -= decrement;
+                if order.leaves_quantity() == 0 {
+                    let report = order.report(0, order_id, ExecType::Canceled);
+                    self.orders.remove(&order_id);
+                    report
+                } else {
+                    // a partial cancel reduces the order in place
+                    order
+                        .report(0, order_id, ExecType::Replaced)
+                        .with_order_terms(Some(order.price), Some(order.quantity))
+                }
+            }
+            msg_type::EXECUTED => {
+                let order_id = order_id_of(alpha_at(message, 9, TOKEN_LEN)?)?;
+                let quantity = u32_at(message, 23) as BookQuantity;
+                let scale = self.price_scale(&self.order(order_id)?.instid)?;
+                let price = book_price(u32_at(message, 27), scale)?;
+                let order = self.order_mut(order_id)?;
+                order.cum_quantity += quantity;
+                order.notional += price as f64 * quantity as f64;
+                let report = order
+                    .report(u64_at(message, 32), order_id, ExecType::Trade)
+                    .with_fill(price, quantity)
+                    .with_order_terms(Some(order.price), Some(order.quantity));
+                if order.leaves_quantity() == 0 {
+                    self.orders.remove(&order_id);
+                }
+                report
+            }
+            msg_type::REJECTED => {
+                let order_id = order_id_of(alpha_at(message, 9, TOKEN_LEN)?)?;
+                let order = self.orders.remove(&order_id).ok_or_else(|| ProtocolError::UnknownToken(token(order_id)))?;
+                order
+                    .report(0, order_id, ExecType::Rejected)
+                    .with_reject_reason(reject_reason(message[23]))
+            }
+            msg_type::CANCEL_REJECT => {
+                let order_id = order_id_of(alpha_at(message, 9, TOKEN_LEN)?)?;
+                self.order(order_id)?
+                    .report(0, order_id, ExecType::CancelRejected)
+                    .with_response_to(CancelRejectResponseTo::Cancel)
+            }
+            other => return Err(ProtocolError::UnknownMessageType(other)),
+        };
+        Ok(Some(report.with_times(timestamp, systemtime)))
+    }
+}
+
+impl OrderProtocol for OuchProtocol {
+    fn name(&self) -> &'static str {
+        "OUCH 4.2"
+    }
+
+    fn encode(&mut self, request: &OrderRequest, buf: &mut Vec<u8>) -> Result<(), ProtocolError> {
+        match &request.order_core {
+            OrderCore::LimitOrder(order) => self.enter_order(request.instid, order, buf),
+            OrderCore::ModifyOrder(modify) => self.replace_order(modify, buf),
+            OrderCore::CancelOrder(cancel) => {
+                put_frame(buf, packet::UNSEQUENCED_DATA, |buf| {
+                    buf.push(msg_type::CANCEL_ORDER);
+                    put_alpha(buf, token(cancel.order_id).as_bytes(), TOKEN_LEN);
+                    buf.extend_from_slice(&0u32.to_be_bytes()); // cancel all the open shares
+                });
+                Ok(())
+            }
+            other => Err(ProtocolError::Unsupported(other.order_type().to_string())),
+        }
+    }
+
+    fn decode(&mut self, buf: &[u8], systemtime: TimeStamp) -> Result<Option<Decoded>, ProtocolError> {
+        let Some(frame) = split_frame(buf)? else {
+            return Ok(None);
+        };
+        match frame.packet_type {
+            packet::SEQUENCED_DATA => Ok(Some(Decoded::new(frame.len, self.on_message(frame.message, systemtime)?))),
+            // session packets, left to the session layer
+            packet::SERVER_HEARTBEAT | packet::LOGIN_ACCEPTED | packet::DEBUG => Ok(Some(Decoded::new(frame.len, None))),
+            packet::LOGIN_REJECTED => {
+                let reason = String::from_utf8_lossy(frame.message).into_owned();
+                flashlog::flash_warn!("OUCH"; "login rejected: {}", reason);
+                Ok(Some(Decoded::new(frame.len, None)))
+            }
+            packet::END_OF_SESSION => {
+                flashlog::flash_info!("OUCH"; "end of session");
+                Ok(Some(Decoded::new(frame.len, None)))
+            }
+            other => Err(ProtocolError::UnknownMessageType(other)),
+        }
+    }
+
+    fn frame_len(&self, buf: &[u8]) -> Option<usize> {
+        split_frame(buf).ok().flatten().map(|frame| frame.len)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct VenueOrder {
+    side: u8,
+    /// open shares
+    shares: u32,
+    stock: Vec<u8>,
+    price: u32,
+    time_in_force: u32,
+    firm: Vec<u8>,
+    display: u8,
+    min_quantity: u32,
+    reference: u64,
+}
+
+/// The venue side of the protocol for tests and the conformance kit.
+/// Orders are accepted and executed as told, with an increasing timestamp, reference and match number.
+#[derive(Debug, Clone, Default)]
+pub struct OuchVenue {
+    orders: FxHashMap<String, VenueOrder>,
+    timestamp: u64,
+    reference: u64,
+    match_number: u64,
+}
+
+impl OuchVenue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn next_timestamp(&mut self) -> u64 {
+        self.timestamp += 1;
+        self.timestamp
+    }
+
+    fn frame(&mut self, msg_type: u8, write: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+        let timestamp = self.next_timestamp();
+        let mut buf = Vec::new();
+        put_frame(&mut buf, packet::SEQUENCED_DATA, |buf| {
+            buf.push(msg_type);
+            buf.extend_from_slice(&timestamp.to_be_bytes());
+            write(buf);
+        });
+        buf
+    }
+
+    /// The body of Accepted and Replaced after the timestamp, up to the order state
+    fn put_order(buf: &mut Vec<u8>, token: &str, order: &VenueOrder) {
+        put_alpha(buf, token.as_bytes(), TOKEN_LEN);
+        buf.push(order.side);
+        buf.extend_from_slice(&order.shares.to_be_bytes());
+        put_alpha(buf, &order.stock, STOCK_LEN);
+        buf.extend_from_slice(&order.price.to_be_bytes());
+        buf.extend_from_slice(&order.time_in_force.to_be_bytes());
+        put_alpha(buf, &order.firm, FIRM_LEN);
+        buf.push(order.display);
+        buf.extend_from_slice(&order.reference.to_be_bytes());
+        buf.push(b'A');
+        buf.push(b'N');
+        buf.extend_from_slice(&order.min_quantity.to_be_bytes());
+        buf.push(b'N');
+        buf.push(b'L'); // order state: live
+    }
+
+    fn cancel_reject(&mut self, token: &str) -> Vec<u8> {
+        self.frame(msg_type::CANCEL_REJECT, |buf| put_alpha(buf, token.as_bytes(), TOKEN_LEN))
+    }
+
+    fn enter(&mut self, message: &[u8], response: VenueResponse) -> Result<Vec<u8>, ProtocolError> {
+        let token = alpha_at(message, 1, TOKEN_LEN)?.to_string();
+        if response == VenueResponse::Reject {
+            return Ok(self.frame(msg_type::REJECTED, |buf| {
+                put_alpha(buf, token.as_bytes(), TOKEN_LEN);
+                buf.push(b'H');
+            }));
+        }
+        self.reference += 1;
+        let order = VenueOrder {
+            side: message[15],
+            shares: u32_at(message, 16),
+            stock: message[20..28].to_vec(),
+            price: u32_at(message, 28),
+            time_in_force: u32_at(message, 32),
+            firm: message[36..40].to_vec(),
+            display: message[40],
+            min_quantity: u32_at(message, 43),
+            reference: self.reference,
+        };
+        let bytes = self.frame(msg_type::ACCEPTED, |buf| {
+            Self::put_order(buf, &token, &order);
+            buf.push(b' '); // BBO weight indicator
+        });
+        self.orders.insert(token, order);
+        Ok(bytes)
+    }
+
+    fn replace(&mut self, message: &[u8], response: VenueResponse) -> Result<Vec<u8>, ProtocolError> {
+        let existing = alpha_at(message, 1, TOKEN_LEN)?.to_string();
+        let replacement = alpha_at(message, 15, TOKEN_LEN)?.to_string();
+        if response == VenueResponse::Reject || !self.orders.contains_key(&existing) {
+            return Ok(self.cancel_reject(&existing));
+        }
+        let mut order = self.orders.remove(&existing).expect("checked above");
+        self.reference += 1;
+        order.shares = u32_at(message, 29);
+        order.price = u32_at(message, 33);
+        order.time_in_force = u32_at(message, 37);
+        order.display = message[41];
+        order.min_quantity = u32_at(message, 43);
+        order.reference = self.reference;
+        let bytes = self.frame(msg_type::REPLACED, |buf| {
+            Self::put_order(buf, &replacement, &order);
+            put_alpha(buf, existing.as_bytes(), TOKEN_LEN);
+            buf.push(b' ');
+        });
+        self.orders.insert(replacement, order);
+        Ok(bytes)
+    }
+
+    fn cancel(&mut self, message: &[u8], response: VenueResponse) -> Result<Vec<u8>, ProtocolError> {
+        let token = alpha_at(message, 1, TOKEN_LEN)?.to_string();
+        let intended = u32_at(message, 15);
+        let Some(order) = self.orders.get_mut(&token).filter(|_| response == VenueResponse::Accept) else {
+            return Ok(self.cancel_reject(&token));
+        };
+        let decrement = order.shares.saturating_sub(intended);
+        order.shares -= decrement;
+        if order.shares == 0 {
+            self.orders.remove(&token);
+        }
+        Ok(self.frame(msg_type::CANCELED, |buf| {
+            put_alpha(buf, token.as_bytes(), TOKEN_LEN);
+            buf.extend_from_slice(&decrement.to_be_bytes());
+            buf.push(b'U'); // user requested
+        }))
+    }
+}
+
+impl ConformanceVenue for OuchVenue {
+    fn respond(&mut self, request: &[u8], response: VenueResponse) -> Result<Vec<u8>, ProtocolError> {
+        let frame = split_frame(request)?.ok_or(ProtocolError::BadLength { msg_type: 0, len: request.len() })?;
+        if frame.packet_type != packet::UNSEQUENCED_DATA {
+            return Err(ProtocolError::UnknownMessageType(frame.packet_type));
+        }
+        let message = frame.message;
+        match checked_type(message, msg_type::client_len)? {
+            msg_type::ENTER_ORDER => self.enter(message, response),
+            msg_type::REPLACE_ORDER => self.replace(message, response),
+            _ => self.cancel(message, response),
+        }
+    }
+
+    fn execute(&mut self, order_id: OrderId, quantity: BookQuantity) -> Result<Vec<u8>, ProtocolError> {
+        let token = token(order_id);
+        let order = self.orders.get_mut(&token).ok_or_else(|| ProtocolError::UnknownToken(token.clone()))?;
+        let quantity = wire_quantity("executed shares", quantity)?.min(order.shares);
+        let price = order.price;
+        order.shares -= quantity;
+        if order.shares == 0 {
+            self.orders.remove(&token);
+        }
+        self.match_number += 1;
+        let match_number = self.match_number;
+        Ok(self.frame(msg_type::EXECUTED, |buf| {
+            put_alpha(buf, token.as_bytes(), TOKEN_LEN);
+            buf.extend_from_slice(&quantity.to_be_bytes());
+            buf.extend_from_slice(&price.to_be_bytes());
+            buf.push(b'A'); // liquidity: added
+            buf.extend_from_slice(&match_number.to_be_bytes());
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instrument::spec::InstrumentSpec;
+    use crate::order::core::{CancelOrder, MarketOrder, OrderAttributes};
+    use crate::protocol::adapter::DecodedFrames;
+    use crate::protocol::conformance::{run_conformance, ConformanceOrder};
+
+    fn instid() -> InstId {
+        InstId::from_str("AAPL", "NASDAQ")
+    }
+
+    fn protocol() -> OuchProtocol {
+        let mut registry = InstrumentRegistry::new();
+        registry.insert(InstrumentSpec::new(instid(), 1, 2));
+        registry.insert(InstrumentSpec::new(InstId::from_str("FINE", "NASDAQ"), 1, 6));
+        OuchProtocol::new(registry, "NASDAQ").with_firm("ABCD")
+    }
+
+    fn limit(order_id: OrderId, attributes: OrderAttributes) -> OrderRequest {
+        let core = LimitOrder::new(15_025, 100, OrderSide::Bid, order_id).with_attributes(attributes);
+        OrderRequest::new(instid(), OrderCore::LimitOrder(core), 0)
+    }
+
+    #[test]
+    fn test_conformance() {
+        let order = ConformanceOrder::new(instid(), 15_025, 100).with_replace_price(15_030);
+        let report = run_conformance(|| (protocol(), OuchVenue::new()), &order);
+        report.assert_passed();
+        assert_eq!(report.protocol, "OUCH 4.2");
+        assert_eq!(report.cases.len(), 11);
+    }
+
+    #[test]
+    fn test_message_layout() {
+        let mut protocol = protocol();
+        let mut buf = Vec::new();
+        protocol.encode(&limit(36, OrderAttributes::default().with_post_only()), &mut buf).unwrap();
+        assert_eq!(buf.len(), 2 + 1 + 49);
+        assert_eq!(&buf[..4], &[0, 50, b'U', b'O']);
+        assert_eq!(&buf[4..18], b"10            ");
+        assert_eq!(buf[18], b'B');
+        assert_eq!(u32_at(&buf, 19), 100);
+        assert_eq!(&buf[23..31], b"AAPL    ");
+        assert_eq!(u32_at(&buf, 31), 1_502_500);
+        assert_eq!(u32_at(&buf, 35), tif::MARKET_HOURS);
+        assert_eq!(&buf[39..43], b"ABCD");
+        assert_eq!(buf[43], b'P');
+
+        buf.clear();
+        let modify = ModifyOrder::new(36, 15_030, 200).with_new_order_id(37);
+        protocol.encode(&OrderRequest::new(instid(), OrderCore::ModifyOrder(modify), 0), &mut buf).unwrap();
+        assert_eq!(buf.len(), 2 + 1 + 47);
+        assert_eq!(&buf[18..32], b"11            ");
+        assert_eq!(u32_at(&buf, 32), 200);
+
+        buf.clear();
+        let cancel = OrderCore::CancelOrder(CancelOrder::new(36));
+        protocol.encode(&OrderRequest::new(instid(), cancel, 0), &mut buf).unwrap();
+        assert_eq!(buf.len(), 2 + 1 + 19);
+        assert_eq!(protocol.open_orders(), 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut protocol = protocol();
+        let mut buf = Vec::new();
+        let market = OrderCore::MarketOrder(MarketOrder::new(100, OrderSide::Bid, 1));
+        assert!(matches!(
+            protocol.encode(&OrderRequest::new(instid(), market, 0), &mut buf),
+            Err(ProtocolError::Unsupported(_))
+        ));
+        let fok = limit(1, OrderAttributes::default().with_time_in_force(TimeInForce::Fok));
+        assert!(matches!(protocol.encode(&fok, &mut buf), Err(ProtocolError::Unsupported(_))));
+        let core = LimitOrder::new(1_000_001, 100, OrderSide::Ask, 2);
+        let too_precise = OrderRequest::new(InstId::from_str("FINE", "NASDAQ"), OrderCore::LimitOrder(core), 0);
+        assert!(matches!(protocol.encode(&too_precise, &mut buf), Err(ProtocolError::Instrument(_))));
+        let modify = OrderCore::ModifyOrder(ModifyOrder::new(1, 15_030, 100));
+        assert_eq!(
+            protocol.encode(&OrderRequest::new(instid(), modify, 0), &mut buf),
+            Err(ProtocolError::MissingOrderId(OrderType::Modify))
+        );
+        assert!(buf.is_empty());
+
+        let mut venue = OuchVenue::new();
+        let unknown = venue.frame(msg_type::CANCEL_REJECT, |buf| put_alpha(buf, b"ZZ", TOKEN_LEN));
+        assert_eq!(protocol.decode(&unknown, 0), Err(ProtocolError::UnknownToken("ZZ".to_string())));
+        assert_eq!(protocol.decode(&[0, 2, b'S', b'E'], 0), Err(ProtocolError::BadLength { msg_type: b'E', len: 1 }));
+        assert_eq!(protocol.decode(&[0, 1, b'Q'], 0), Err(ProtocolError::UnknownMessageType(b'Q')));
+        assert_eq!(protocol.decode(&[0, 1, b'H'], 0), Ok(Some(Decoded::new(3, None))));
+    }
+
+    #[test]
+    fn test_soup_session_packets() {
+        let mut protocol = protocol();
+        let mut bytes = Vec::new();
+        // Login Accepted with a session and a sequence number, Debug, Login Rejected and End of Session
+        put_frame(&mut bytes, packet::LOGIN_ACCEPTED, |buf| buf.extend_from_slice(b"  SESSION1           1"));
+        put_frame(&mut bytes, packet::DEBUG, |buf| buf.extend_from_slice(b"hello"));
+        put_frame(&mut bytes, packet::LOGIN_REJECTED, |buf| buf.push(b'A'));
+        put_frame(&mut bytes, packet::END_OF_SESSION, |_| {});
+        let frames = protocol.decode_all(&bytes, 0);
+        assert_eq!(frames, DecodedFrames { reports: Vec::new(), consumed: bytes.len(), error: None, failed_len: None });
+    }
+
+    #[test]
+    fn test_decode_all_keeps_frames_before_an_error() {
+        let mut protocol = protocol();
+        let mut venue = OuchVenue::new();
+        let mut buf = Vec::new();
+        protocol.encode(&limit(1, OrderAttributes::default()), &mut buf).unwrap();
+        let accepted = venue.respond(&buf, VenueResponse::Accept).unwrap();
+        let unknown = venue.frame(msg_type::CANCEL_REJECT, |buf| put_alpha(buf, b"ZZ", TOKEN_LEN));
+        let executed = venue.execute(1, 40).unwrap();
+
+        let mut bytes = [accepted.clone(), unknown.clone(), executed.clone()].concat();
+        let frames = protocol.decode_all(&bytes, 0);
+        assert_eq!(frames.reports.len(), 1);
+        assert_eq!(frames.reports[0].exec_type, ExecType::New);
+        assert_eq!(frames.consumed, accepted.len());
+        assert_eq!(frames.error, Some(ProtocolError::UnknownToken("ZZ".to_string())));
+        assert_eq!(frames.failed_len, Some(unknown.len()));
+
+        // the caller skips the failing frame and resumes
+        bytes.drain(..frames.consumed + frames.failed_len.unwrap());
+        let frames = protocol.decode_all(&bytes, 0);
+        assert_eq!((frames.reports.len(), frames.consumed, frames.error, frames.failed_len), (1, executed.len(), None, None));
+        assert_eq!(frames.reports[0].cum_quantity, 40);
+
+        // an Accepted of a stock missing from the registry
+        let mut accepted = venue.respond(&buf, VenueResponse::Accept).unwrap();
+        let stock = 2 + 1 + 28;
+        accepted[stock..stock + STOCK_LEN].copy_from_slice(b"ZZZZ    ");
+        assert_eq!(
+            protocol.decode(&accepted, 0),
+            Err(ProtocolError::Instrument(InstrumentError::UnknownCode { code: "ZZZZ".to_string(), venue: "NASDAQ".to_string() }))
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{BookPrice, BookQuantity, InstId, OrderId, TimeStamp};
+use crate::instrument::error::InstrumentError;
+use crate::risk::kill_switch::KillScope;
+
+/// The reason an outgoing order is blocked
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskError {
+    Instrument(InstrumentError),
+    UnknownOrder(OrderId),
+    MaxOrderQuantity {
+        quantity: BookQuantity,
+        limit: BookQuantity,
+    },
+    MaxOrderNotional {
+        notional: f64,
+        limit: f64,
+    },
+    /// a market order or a price band check needs the mid of the instrument
+    NoReferencePrice(InstId),
+    PriceBand {
+        price: BookPrice,
+        mid: BookPrice,
+        band_bps: u32,
+    },
+    MaxOpenOrders {
+        instid: InstId,
+        limit: usize,
+    },
+    MaxPosition {
+        instid: InstId,
+        worst_case: i64,
+        limit: BookQuantity,
+    },
+    /// the order would cross my resting order `resting_order_id`
+    SelfTrade {
+        resting_order_id: OrderId,
+    },
+    DuplicateOrderId(OrderId),
+    /// same instrument, side, price and quantity as `previous_order_id` sent shortly before
+    DuplicateOrder {
+        previous_order_id: OrderId,
+    },
+    KillSwitch(KillScope),
+    /// over the message rate budget, may be sent again at `retry_at`
+    Throttled {
+        retry_at: TimeStamp,
+    },
+}
+
+impl std::fmt::Display for RiskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RiskError::Instrument(e) => write!(f, "{}", e),
+            RiskError::UnknownOrder(order_id) => write!(f, "unknown order id: {}", order_id),
+            RiskError::MaxOrderQuantity { quantity, limit } => {
+                write!(f, "order quantity {} exceeds the limit {}", quantity, limit)
+            }
+            RiskError::MaxOrderNotional { notional, limit } => {
+                write!(f, "order notional {} exceeds the limit {}", notional, limit)
+            }
+            RiskError::NoReferencePrice(instid) => write!(f, "no reference price for {}", instid),
+            RiskError::PriceBand { price, mid, band_bps } => {
+                write!(f, "price {} is more than {}bp away from the mid {}", price, band_bps, mid)
+            }
+            RiskError::MaxOpenOrders { instid, limit } => {
+                write!(f, "{} already has {} open orders", instid, limit)
+            }
+            RiskError::MaxPosition { instid, worst_case, limit } => {
+                write!(f, "worst case position {} of {} exceeds the limit {}", worst_case, instid, limit)
+            }
+            RiskError::SelfTrade { resting_order_id } => {
+                write!(f, "order would trade against my order {}", resting_order_id)
+            }
+            RiskError::DuplicateOrderId(order_id) => write!(f, "duplicate order id: {}", order_id),
+            RiskError::DuplicateOrder { previous_order_id } => {
+                write!(f, "duplicate of order {}", previous_order_id)
+            }
+            RiskError::KillSwitch(scope) => write!(f, "blocked by the kill switch on {:?}", scope),
+            RiskError::Throttled { retry_at } => write!(f, "over the message rate, retry at {}", retry_at),
+        }
+    }
+}
+
+impl std::error::Error for RiskError {}
+
+impl From<InstrumentError> for RiskError {
+    fn from(e: InstrumentError) -> Self {
+        RiskError::Instrument(e)
+    }
+}
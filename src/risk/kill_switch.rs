@@ -0,0 +1,272 @@
+use crate::{InstId, OrderCore, TimeStamp};
+use crate::order::core::CancelOrder;
+use crate::order::enums::{OrderSide, OrderStatus};
+use crate::order::manager::OrderManager;
+use crate::order::request::OrderRequest;
+use crate::risk::error::RiskError;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// What the kill switch stops
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum KillScope {
+    All,
+    Instrument(InstId),
+    Side(InstId, OrderSide),
+}
+
+impl KillScope {
+    /// Whether an order on `instid` and `side` (None if unknown, e.g., modify) falls in the scope
+    #[inline]
+    pub fn covers(&self, instid: &InstId, side: Option<OrderSide>) -> bool {
+        match self {
+            KillScope::All => true,
+            KillScope::Instrument(id) => id == instid,
+            KillScope::Side(id, s) => id == instid && side.is_none_or(|side| side == *s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum KillReason {
+    Api(String),
+    RiskBreach(String),
+    Signal,
+    Sentinel(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum KillAction {
+    Engaged(KillReason),
+    Released,
+}
+
+/// A change of the kill switch state
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KillEvent {
+    pub scope: KillScope,
+    pub action: KillAction,
+    pub time: TimeStamp,
+}
+
+#[derive(Debug, Default)]
+struct KillState {
+    scopes: Vec<KillScope>,
+    events: Vec<KillEvent>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    engaged: AtomicBool,
+    state: Mutex<KillState>,
+}
+
+/// Blocks every new order (and modify) in the engaged scopes. Cancels always go through.
+///
+/// Clones share the same state, so the switch can be engaged from any thread
+/// (API, risk checks, a signal or a sentinel file watcher) and checked on the sending path,
+/// which only reads an atomic flag while nothing is engaged.
+/// Every change is logged and kept in `events`.
+#[derive(Debug, Clone, Default)]
+pub struct KillSwitch {
+    inner: Arc<Inner>,
+}
+
+impl KillSwitch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn is_engaged(&self) -> bool {
+        self.inner.engaged.load(Ordering::Acquire)
+    }
+
+    pub fn engage(&self, scope: KillScope, reason: KillReason, time: TimeStamp) {
+        let mut state = self.inner.state.lock().expect("Failed to lock kill switch");
+        let logged = reason.clone();
+        flashlog::flash_error!("KILL"; "kill switch engaged on {:?} at {}: {:?}", scope, time, logged);
+        if !state.scopes.contains(&scope) {
+            state.scopes.push(scope);
+        }
+        state.events.push(KillEvent {
+            scope,
+            action: KillAction::Engaged(reason),
+            time,
+        });
+        self.inner.engaged.store(true, Ordering::Release);
+    }
+
+    /// Engages the switch on every instrument, e.g., after a risk breach
+    pub fn trip(&self, error: &RiskError, time: TimeStamp) {
+        self.engage(KillScope::All, KillReason::RiskBreach(error.to_string()), time);
+    }
+
+    /// Releases `scope` only. A wider scope still engaged keeps blocking
+    pub fn release(&self, scope: KillScope, time: TimeStamp) {
+        let mut state = self.inner.state.lock().expect("Failed to lock kill switch");
+        flashlog::flash_warn!("KILL"; "kill switch released on {:?} at {}", scope, time);
+        state.scopes.retain(|s| *s != scope);
+        state.events.push(KillEvent {
+            scope,
+            action: KillAction::Released,
+            time,
+        });
+        self.inner.engaged.store(!state.scopes.is_empty(), Ordering::Release);
+    }
+
+    pub fn release_all(&self, time: TimeStamp) {
+        let scopes = self.scopes();
+        for scope in scopes {
+            self.release(scope, time);
+        }
+    }
+
+    pub fn scopes(&self) -> Vec<KillScope> {
+        self.inner.state.lock().expect("Failed to lock kill switch").scopes.clone()
+    }
+
+    pub fn events(&self) -> Vec<KillEvent> {
+        self.inner.state.lock().expect("Failed to lock kill switch").events.clone()
+    }
+
+    #[inline]
+    pub fn blocks(&self, instid: &InstId, side: Option<OrderSide>) -> Option<KillScope> {
+        if !self.is_engaged() {
+            return None;
+        }
+        let state = self.inner.state.lock().expect("Failed to lock kill switch");
+        state.scopes.iter().find(|scope| scope.covers(instid, side)).copied()
+    }
+
+    /// Gate for outgoing requests
+    #[inline]
+    pub fn check(&self, request: &OrderRequest) -> Result<(), RiskError> {
+        if let OrderCore::CancelOrder(_) = request.order_core {
+            return Ok(());
+        }
+        match self.blocks(&request.instid, request.order_core.order_side()) {
+            Some(scope) => Err(RiskError::KillSwitch(scope)),
+            None => Ok(()),
+        }
+    }
+
+    /// Cancels of my working orders in `scope`, skipping those already being canceled
+    pub fn cancel_orders(&self, orders: &OrderManager, scope: KillScope, time: TimeStamp) -> Vec<OrderRequest> {
+        orders
+            .iter()
+            .filter(|request| request.status != OrderStatus::PendingCancel)
+            .filter(|request| scope.covers(&request.instid, request.order_core.order_side()))
+            .filter_map(|request| {
+                let order_id = request.get_id()?;
+                Some(OrderRequest::new(request.instid, OrderCore::CancelOrder(CancelOrder::new(order_id)), time))
+            })
+            .collect()
+    }
+
+    /// Engages the switch and returns the cancels to send
+    pub fn kill(&self, orders: &OrderManager, scope: KillScope, reason: KillReason, time: TimeStamp) -> Vec<OrderRequest> {
+        self.engage(scope, reason, time);
+        self.cancel_orders(orders, scope, time)
+    }
+
+    /// Engages the switch on everything if the sentinel file exists
+    pub fn poll_sentinel(&self, path: impl AsRef<Path>, time: TimeStamp) -> bool {
+        let path = path.as_ref();
+        if !path.exists() {
+            return false;
+        }
+        if self.blocks(&InstId::default(), None) != Some(KillScope::All) {
+            self.engage(KillScope::All, KillReason::Sentinel(path.display().to_string()), time);
+        }
+        true
+    }
+
+    /// Polls the sentinel file every `interval` until it appears
+    pub async fn watch_sentinel(self, path: impl AsRef<Path>, interval: std::time::Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if self.poll_sentinel(path.as_ref(), flashlog::get_unix_nano()) {
+                return;
+            }
+        }
+    }
+
+    /// Engages the switch on everything when the process receives `kind` (e.g., SIGUSR1)
+    #[cfg(unix)]
+    pub async fn watch_signal(self, kind: tokio::signal::unix::SignalKind) -> std::io::Result<()> {
+        let mut signal = tokio::signal::unix::signal(kind)?;
+        signal.recv().await;
+        self.engage(KillScope::All, KillReason::Signal, flashlog::get_unix_nano());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::core::{LimitOrder, ModifyOrder};
+
+    fn instid(code: &str) -> InstId {
+        InstId::from_str(code, "KRX")
+    }
+
+    fn limit(code: &str, order_id: u64, side: OrderSide) -> OrderRequest {
+        OrderRequest::new(instid(code), OrderCore::LimitOrder(LimitOrder::new(100, 1, side, order_id)), 0)
+    }
+
+    #[test]
+    fn test_scoped_kill_and_cancel_all() {
+        let mut orders = OrderManager::default();
+        orders.insert(limit("A", 1, OrderSide::Bid)).unwrap();
+        orders.insert(limit("A", 2, OrderSide::Ask)).unwrap();
+        orders.insert(limit("B", 3, OrderSide::Bid)).unwrap();
+        orders.on_cancel_requested(2, 0).unwrap();
+
+        let switch = KillSwitch::new();
+        assert!(switch.check(&limit("A", 4, OrderSide::Bid)).is_ok());
+
+        let scope = KillScope::Side(instid("A"), OrderSide::Bid);
+        let cancels = switch.kill(&orders, scope, KillReason::Api("test".to_string()), 1);
+        assert_eq!(cancels.len(), 1);
+        assert_eq!(cancels[0].order_core, OrderCore::CancelOrder(CancelOrder::new(1)));
+        assert_eq!(switch.check(&limit("A", 4, OrderSide::Bid)), Err(RiskError::KillSwitch(scope)));
+        assert!(switch.check(&limit("A", 4, OrderSide::Ask)).is_ok());
+        let modify = OrderRequest::new(instid("A"), OrderCore::ModifyOrder(ModifyOrder::new(1, 99, 1)), 0);
+        assert!(switch.check(&modify).is_err());
+        assert!(switch.check(&cancels[0]).is_ok());
+
+        // a clone shares the state
+        let other = switch.clone();
+        other.trip(&RiskError::DuplicateOrderId(9), 2);
+        assert!(switch.check(&limit("B", 5, OrderSide::Ask)).is_err());
+        // order 2 is already being canceled
+        let cancels = switch.cancel_orders(&orders, KillScope::All, 2);
+        let mut ids: Vec<_> = cancels.iter().filter_map(|cancel| cancel.get_id()).collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 3]);
+
+        switch.release_all(3);
+        assert!(!switch.is_engaged());
+        assert!(switch.check(&limit("B", 5, OrderSide::Ask)).is_ok());
+        assert_eq!(switch.events().len(), 4);
+    }
+
+    #[test]
+    fn test_sentinel_file() {
+        let path = std::env::temp_dir().join(format!("kill_switch_sentinel_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let switch = KillSwitch::new();
+        assert!(!switch.poll_sentinel(&path, 0));
+
+        std::fs::write(&path, "").unwrap();
+        assert!(switch.poll_sentinel(&path, 1));
+        assert!(switch.poll_sentinel(&path, 2));
+        assert_eq!(switch.scopes(), vec![KillScope::All]);
+        assert_eq!(switch.events().len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
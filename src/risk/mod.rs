@@ -0,0 +1,4 @@
+pub mod error;
+pub mod pre_trade;
+pub mod kill_switch;
+pub mod throttle;
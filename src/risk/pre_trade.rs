@@ -0,0 +1,393 @@
+use crate::{BookPrice, BookQuantity, InstId, OrderCore, OrderId, TimeStamp};
+use crate::data::snapshot::QuoteSnapshot;
+use crate::instrument::registry::InstrumentRegistry;
+use crate::order::enums::OrderSide;
+use crate::order::manager::OrderManager;
+use crate::order::request::OrderRequest;
+use crate::risk::error::RiskError;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Limits of the pre-trade checks. A limit left as None is not checked
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RiskLimits {
+    #[serde(default)]
+    pub max_order_quantity: Option<BookQuantity>,
+    /// in the instrument currency, see `InstrumentSpec::notional`
+    #[serde(default)]
+    pub max_order_notional: Option<f64>,
+    /// the largest distance of the order price from the mid, in basis points of the mid
+    #[serde(default)]
+    pub price_band_bps: Option<u32>,
+    #[serde(default)]
+    pub max_open_orders: Option<usize>,
+    /// the largest absolute position, assuming all my working orders on the same side get filled
+    #[serde(default)]
+    pub max_position: Option<BookQuantity>,
+    #[serde(default)]
+    pub self_trade_prevention: bool,
+    /// an order with the same instrument, side, price and quantity as one sent within the window is a duplicate
+    #[serde(default)]
+    pub duplicate_window: Option<TimeStamp>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SentOrder {
+    time: TimeStamp,
+    order_id: OrderId,
+    instid: InstId,
+    side: OrderSide,
+    price: Option<BookPrice>,
+    quantity: BookQuantity,
+}
+
+/// Checks each outgoing `OrderRequest` against `RiskLimits` before it is sent.
+///
+/// Limits are set per `InstId`, falling back to the default limits. The engine is fed with
+/// quotes (for the mid) and positions, and looks up my working orders in the `OrderManager`.
+#[derive(Debug, Clone, Default)]
+pub struct PreTradeRisk {
+    default_limits: RiskLimits,
+    limits: FxHashMap<InstId, RiskLimits>,
+    mids: FxHashMap<InstId, BookPrice>,
+    positions: FxHashMap<InstId, i64>,
+    sent: VecDeque<SentOrder>,
+}
+
+impl PreTradeRisk {
+    pub fn new(default_limits: RiskLimits) -> Self {
+        Self {
+            default_limits,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_limits(mut self, instid: InstId, limits: RiskLimits) -> Self {
+        self.set_limits(instid, limits);
+        self
+    }
+
+    pub fn set_limits(&mut self, instid: InstId, limits: RiskLimits) {
+        self.limits.insert(instid, limits);
+    }
+
+    #[inline]
+    pub fn limits(&self, instid: &InstId) -> &RiskLimits {
+        self.limits.get(instid).unwrap_or(&self.default_limits)
+    }
+
+    pub fn on_quote(&mut self, quote: &QuoteSnapshot) {
+        if let Some(mid) = quote.mid() {
+            self.mids.insert(quote.id, mid);
+        }
+    }
+
+    #[inline]
+    pub fn set_mid(&mut self, instid: InstId, mid: BookPrice) {
+        self.mids.insert(instid, mid);
+    }
+
+    #[inline]
+    pub fn mid(&self, instid: &InstId) -> Option<BookPrice> {
+        self.mids.get(instid).copied()
+    }
+
+    /// Signed position (long > 0)
+    #[inline]
+    pub fn set_position(&mut self, instid: InstId, position: i64) {
+        self.positions.insert(instid, position);
+    }
+
+    #[inline]
+    pub fn position(&self, instid: &InstId) -> i64 {
+        self.positions.get(instid).copied().unwrap_or(0)
+    }
+
+    pub fn on_fill(&mut self, instid: InstId, side: OrderSide, quantity: BookQuantity) {
+        let position = self.positions.entry(instid).or_insert(0);
+        match side {
+            OrderSide::Bid => *position += quantity as i64,
+            OrderSide::Ask => *position -= quantity as i64,
+        }
+    }
+
+    /// Checks `request` (a new order or a modify of a working order in `orders`).
+    /// Nothing is remembered here: call `on_sent` once the order passed every other gate (kill switch, throttle)
+    /// and went out, so that a blocked order does not make its retry a duplicate.
+    pub fn check(
+        &self,
+        request: &OrderRequest,
+        orders: &OrderManager,
+        registry: &InstrumentRegistry,
+    ) -> Result<(), RiskError> {
+        let instid = request.instid;
+        let limits = self.limits(&instid);
+
+        // a modify is checked as the order it would become
+        let (order_id, side, quantity, price, original) = match &request.order_core {
+            OrderCore::CancelOrder(_) | OrderCore::RemoveOtherOrder(_) | OrderCore::NullOrder(_) => return Ok(()),
+            OrderCore::ModifyOrder(modify) => {
+                let original = orders.get(&modify.order_id).ok_or(RiskError::UnknownOrder(modify.order_id))?;
+                let side = original.order_core.order_side().unwrap_or_default();
+                (modify.order_id, side, modify.quantity, Some(modify.price), Some(original))
+            }
+            core => {
+                let order_id = core.order_id().unwrap_or_default();
+                let side = core.order_side().unwrap_or_default();
+                (order_id, side, core.quantity().unwrap_or(0), order_price(core), None)
+            }
+        };
+
+        if let Some(spec) = registry.get(&instid) {
+            spec.validate_order(&request.order_core)?;
+        }
+
+        if let Some(limit) = limits.max_order_quantity {
+            if quantity > limit {
+                return Err(RiskError::MaxOrderQuantity { quantity, limit });
+            }
+        }
+
+        let mid = self.mid(&instid);
+        if let Some(limit) = limits.max_order_notional {
+            let spec = registry.try_get(&instid)?;
+            let reference = price.or(mid).ok_or(RiskError::NoReferencePrice(instid))?;
+            let notional = spec.notional(reference, quantity).abs();
+            if notional > limit {
+                return Err(RiskError::MaxOrderNotional { notional, limit });
+            }
+        }
+
+        if let (Some(band_bps), Some(price)) = (limits.price_band_bps, price) {
+            let mid = mid.ok_or(RiskError::NoReferencePrice(instid))?;
+            let distance = (price as i128 - mid as i128).unsigned_abs();
+            if distance * 10_000 > mid.unsigned_abs() as u128 * band_bps as u128 {
+                return Err(RiskError::PriceBand { price, mid, band_bps });
+            }
+        }
+
+        if original.is_none() {
+            if orders.contains(&order_id) {
+                return Err(RiskError::DuplicateOrderId(order_id));
+            }
+            if let Some(limit) = limits.max_open_orders {
+                if orders.working_count(&instid) >= limit {
+                    return Err(RiskError::MaxOpenOrders { instid, limit });
+                }
+            }
+        }
+
+        if let Some(limit) = limits.max_position {
+            // the order a modify targets may be named by an id it had before an earlier replace
+            let checked_id = original.map_or(Some(order_id), |original| original.get_id());
+            let working: BookQuantity = orders
+                .working_orders_on_side(&instid, side)
+                .filter(|working| working.get_id() != checked_id)
+                .map(|working| working.worst_case_open_quantity())
+                .sum();
+            let filled = original.and_then(|original| original.filled).unwrap_or(0);
+            let exposure = (working + quantity.saturating_sub(filled)) as i64;
+            let worst_case = match side {
+                OrderSide::Bid => self.position(&instid) + exposure,
+                OrderSide::Ask => self.position(&instid) - exposure,
+            };
+            if worst_case.unsigned_abs() > limit {
+                return Err(RiskError::MaxPosition { instid, worst_case, limit });
+            }
+        }
+
+        if limits.self_trade_prevention {
+            let opposite = match side {
+                OrderSide::Bid => OrderSide::Ask,
+                OrderSide::Ask => OrderSide::Bid,
+            };
+            let crossing = orders.working_orders_on_side(&instid, opposite).find(|resting| {
+                match (resting.order_core.price(), price, request.order_core.is_conditional()) {
+                    (_, _, true) | (None, _, _) => false,
+                    (Some(_), None, false) => true,
+                    (Some(resting_price), Some(price), false) => match side {
+                        OrderSide::Bid => price >= resting_price,
+                        OrderSide::Ask => price <= resting_price,
+                    },
+                }
+            });
+            if let Some(resting) = crossing {
+                return Err(RiskError::SelfTrade {
+                    resting_order_id: resting.get_id().unwrap_or_default(),
+                });
+            }
+        }
+
+        if original.is_none() {
+            if let Some(window) = limits.duplicate_window {
+                let time = request.systemtime;
+                let duplicate = self.sent.iter().rev().find(|sent| {
+                    sent.time.saturating_add(window) >= time
+                        && sent.instid == instid
+                        && sent.side == side
+                        && sent.price == price
+                        && sent.quantity == quantity
+                });
+                if let Some(duplicate) = duplicate {
+                    return Err(RiskError::DuplicateOrder {
+                        previous_order_id: duplicate.order_id,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remembers a new order that was sent, for the duplicate check
+    pub fn on_sent(&mut self, request: &OrderRequest) {
+        let core = &request.order_core;
+        let (Some(window), Some(side)) = (self.limits(&request.instid).duplicate_window, core.order_side()) else {
+            return;
+        };
+        let time = request.systemtime;
+        while self.sent.front().is_some_and(|sent| sent.time.saturating_add(window) < time) {
+            self.sent.pop_front();
+        }
+        self.sent.push_back(SentOrder {
+            time,
+            order_id: core.order_id().unwrap_or_default(),
+            instid: request.instid,
+            side,
+            price: order_price(core),
+            quantity: core.quantity().unwrap_or(0),
+        });
+    }
+}
+
+/// The price checked against the band: the limit price, or the stop price of a stop-market order
+fn order_price(order_core: &OrderCore) -> Option<BookPrice> {
+    match order_core {
+        OrderCore::LimitOrder(order) => Some(order.price),
+        OrderCore::StopLimitOrder(order) => Some(order.price),
+        OrderCore::StopMarketOrder(order) => Some(order.stop_price),
+        OrderCore::IfTouchedOrder(order) => order.price.or(Some(order.trigger_price)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instrument::spec::InstrumentSpec;
+    use crate::order::core::{CancelOrder, LimitOrder, MarketOrder, ModifyOrder};
+    use crate::order::execution::{ExecType, ExecutionReport};
+
+    fn instid() -> InstId {
+        InstId::from_str("005930", "KRX")
+    }
+
+    fn limit(order_id: OrderId, side: OrderSide, price: BookPrice, quantity: BookQuantity, time: TimeStamp) -> OrderRequest {
+        OrderRequest::new(instid(), OrderCore::LimitOrder(LimitOrder::new(price, quantity, side, order_id)), time)
+    }
+
+    fn setup() -> (PreTradeRisk, OrderManager, InstrumentRegistry) {
+        let limits = RiskLimits {
+            max_order_quantity: Some(100),
+            max_order_notional: Some(50_000.0),
+            price_band_bps: Some(500),
+            max_open_orders: Some(2),
+            max_position: Some(150),
+            self_trade_prevention: true,
+            duplicate_window: Some(1_000),
+        };
+        let mut risk = PreTradeRisk::new(limits);
+        risk.set_mid(instid(), 1_000);
+        let mut registry = InstrumentRegistry::new();
+        registry.insert(InstrumentSpec::new(instid(), 1, 0).with_lot_size(1));
+        (risk, OrderManager::default(), registry)
+    }
+
+    #[test]
+    fn test_order_limits() {
+        let (risk, orders, registry) = setup();
+        assert_eq!(risk.check(&limit(1, OrderSide::Bid, 1_000, 10, 0), &orders, &registry), Ok(()));
+        assert_eq!(
+            risk.check(&limit(2, OrderSide::Bid, 1_000, 101, 0), &orders, &registry),
+            Err(RiskError::MaxOrderQuantity { quantity: 101, limit: 100 })
+        );
+        assert!(matches!(
+            risk.check(&limit(3, OrderSide::Bid, 1_000, 60, 0), &orders, &registry),
+            Err(RiskError::MaxOrderNotional { .. })
+        ));
+        assert!(matches!(
+            risk.check(&limit(4, OrderSide::Ask, 1_051, 1, 0), &orders, &registry),
+            Err(RiskError::PriceBand { .. })
+        ));
+        assert_eq!(risk.check(&limit(5, OrderSide::Ask, 1_050, 1, 0), &orders, &registry), Ok(()));
+
+        // a market order is valued at the mid
+        let market = OrderRequest::new(instid(), OrderCore::MarketOrder(MarketOrder::new(10, OrderSide::Bid, 6)), 0);
+        assert_eq!(risk.check(&market, &orders, &registry), Ok(()));
+        let cancel = OrderRequest::new(instid(), OrderCore::CancelOrder(CancelOrder::new(1)), 0);
+        assert_eq!(risk.check(&cancel, &orders, &registry), Ok(()));
+    }
+
+    #[test]
+    fn test_book_dependent_checks() {
+        let (mut risk, mut orders, registry) = setup();
+        orders.insert(limit(1, OrderSide::Ask, 1_010, 40, 0)).unwrap();
+        orders.insert(limit(2, OrderSide::Bid, 990, 40, 0)).unwrap();
+        risk.set_position(instid(), 100);
+
+        assert_eq!(
+            risk.check(&limit(3, OrderSide::Bid, 1_020, 5, 0), &orders, &registry),
+            Err(RiskError::MaxOpenOrders { instid: instid(), limit: 2 })
+        );
+        // 100 + 40 (working) + 20 > 150
+        risk.set_limits(instid(), RiskLimits { max_open_orders: None, ..risk.limits(&instid()).clone() });
+        assert!(matches!(
+            risk.check(&limit(3, OrderSide::Bid, 990, 20, 0), &orders, &registry),
+            Err(RiskError::MaxPosition { worst_case: 160, .. })
+        ));
+        orders.on_rejected(2, 1).unwrap();
+        assert_eq!(
+            risk.check(&limit(4, OrderSide::Bid, 1_010, 5, 0), &orders, &registry),
+            Err(RiskError::SelfTrade { resting_order_id: 1 })
+        );
+        assert_eq!(
+            risk.check(&limit(1, OrderSide::Bid, 990, 5, 0), &orders, &registry),
+            Err(RiskError::DuplicateOrderId(1))
+        );
+
+        assert_eq!(risk.check(&limit(5, OrderSide::Bid, 990, 5, 0), &orders, &registry), Ok(()));
+        // 5 is blocked after the check (e.g., by the kill switch), so its retry is not a duplicate
+        assert_eq!(risk.check(&limit(6, OrderSide::Bid, 990, 5, 500), &orders, &registry), Ok(()));
+        risk.on_sent(&limit(5, OrderSide::Bid, 990, 5, 0));
+        assert_eq!(
+            risk.check(&limit(6, OrderSide::Bid, 990, 5, 500), &orders, &registry),
+            Err(RiskError::DuplicateOrder { previous_order_id: 5 })
+        );
+        assert_eq!(risk.check(&limit(7, OrderSide::Bid, 990, 5, 1_001), &orders, &registry), Ok(()));
+
+        // a modify is checked with the side of the working order
+        let modify = OrderRequest::new(instid(), OrderCore::ModifyOrder(ModifyOrder::new(1, 960, 40)), 0);
+        assert_eq!(risk.check(&modify, &orders, &registry), Ok(()));
+        let modify = OrderRequest::new(instid(), OrderCore::ModifyOrder(ModifyOrder::new(9, 960, 40)), 0);
+        assert_eq!(risk.check(&modify, &orders, &registry), Err(RiskError::UnknownOrder(9)));
+    }
+
+    #[test]
+    fn test_modify_by_an_older_id() {
+        let (mut risk, mut orders, registry) = setup();
+        risk.set_position(instid(), 100);
+        orders.insert(limit(1, OrderSide::Bid, 990, 40, 0)).unwrap();
+        orders.on_accepted(1, 1).unwrap();
+        orders.on_replace_requested(&ModifyOrder::new(1, 990, 40).with_new_order_id(3), 2).unwrap();
+        let replaced = ExecutionReport::new(1, 3, instid(), ExecType::Replaced)
+            .with_orig_order_id(1)
+            .with_order_terms(Some(990), Some(40));
+        orders.on_execution_report(&replaced).unwrap();
+
+        // the working order is not counted next to itself: 100 + 45 <= 150
+        let modify = OrderRequest::new(instid(), OrderCore::ModifyOrder(ModifyOrder::new(1, 990, 45)), 3);
+        assert_eq!(risk.check(&modify, &orders, &registry), Ok(()));
+        let modify = OrderRequest::new(instid(), OrderCore::ModifyOrder(ModifyOrder::new(3, 960, 51)), 3);
+        assert!(matches!(risk.check(&modify, &orders, &registry), Err(RiskError::MaxPosition { worst_case: 151, .. })));
+    }
+}
@@ -0,0 +1,370 @@
+use crate::{InstId, OrderCore, TimeStamp, UnixNano};
+use crate::order::enums::OrderType;
+use crate::order::request::OrderRequest;
+use crate::risk::error::RiskError;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// How a message budget is counted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ThrottleMode {
+    /// bursts of up to `capacity` messages, refilled by one message every `refill_interval`
+    TokenBucket { capacity: u32, refill_interval: UnixNano },
+    /// at most `max_messages` in any `window`
+    SlidingWindow { max_messages: u32, window: UnixNano },
+}
+
+/// The budget a message is counted against
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    New,
+    Modify,
+    Cancel,
+}
+
+impl MessageKind {
+    pub fn of(core_type: &OrderType) -> Option<Self> {
+        match core_type {
+            OrderType::Modify => Some(MessageKind::Modify),
+            OrderType::Cancel => Some(MessageKind::Cancel),
+            OrderType::Null | OrderType::RemoveOther => None,
+            _ => Some(MessageKind::New),
+        }
+    }
+}
+
+/// What happens to a message over the budget
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ThrottlePolicy {
+    /// held back and released by `poll` when the budget allows, in order per instrument
+    #[default]
+    Queue,
+    Reject,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ThrottleConfig {
+    /// all messages of the session
+    #[serde(default)]
+    pub session: Option<ThrottleMode>,
+    /// the messages of each instrument
+    #[serde(default)]
+    pub per_instrument: Option<ThrottleMode>,
+    #[serde(default)]
+    pub new_order: Option<ThrottleMode>,
+    #[serde(default)]
+    pub modify: Option<ThrottleMode>,
+    #[serde(default)]
+    pub cancel: Option<ThrottleMode>,
+    #[serde(default)]
+    pub policy: ThrottlePolicy,
+    /// cancels are never held back (but still counted)
+    #[serde(default)]
+    pub cancel_bypass: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Limiter {
+    mode: ThrottleMode,
+    /// negative after cancels bypassed an empty bucket
+    tokens: i64,
+    last_refill: TimeStamp,
+    sent: VecDeque<TimeStamp>,
+}
+
+impl Limiter {
+    fn new(mode: ThrottleMode, now: TimeStamp) -> Self {
+        let tokens = match mode {
+            ThrottleMode::TokenBucket { capacity, .. } => capacity as i64,
+            ThrottleMode::SlidingWindow { .. } => 0,
+        };
+        Self {
+            mode,
+            tokens,
+            last_refill: now,
+            sent: VecDeque::new(),
+        }
+    }
+
+    fn refresh(&mut self, now: TimeStamp) {
+        match self.mode {
+            ThrottleMode::TokenBucket { capacity, refill_interval } => {
+                let refill_interval = refill_interval.max(1);
+                let refilled = now.saturating_sub(self.last_refill) / refill_interval;
+                if refilled > 0 {
+                    self.tokens = (self.tokens + refilled as i64).min(capacity as i64);
+                    self.last_refill = if self.tokens == capacity as i64 {
+                        now
+                    } else {
+                        self.last_refill + refilled * refill_interval
+                    };
+                }
+            }
+            ThrottleMode::SlidingWindow { window, .. } => {
+                while self.sent.front().is_some_and(|sent| sent.saturating_add(window) <= now) {
+                    self.sent.pop_front();
+                }
+            }
+        }
+    }
+
+    /// The earliest time a message can go, `now` if it can go at once.
+    /// A zero budget blocks everything, so nothing goes before `TimeStamp::MAX`.
+    fn available_at(&mut self, now: TimeStamp) -> TimeStamp {
+        self.refresh(now);
+        match self.mode {
+            ThrottleMode::TokenBucket { capacity: 0, .. } | ThrottleMode::SlidingWindow { max_messages: 0, .. } => TimeStamp::MAX,
+            ThrottleMode::TokenBucket { refill_interval, .. } => {
+                if self.tokens > 0 {
+                    now
+                } else {
+                    self.last_refill + refill_interval.max(1) * (1 - self.tokens) as u64
+                }
+            }
+            ThrottleMode::SlidingWindow { max_messages, window } => {
+                if (self.sent.len() as u32) < max_messages {
+                    now
+                } else {
+                    let over = self.sent.len() + 1 - max_messages as usize;
+                    self.sent.get(over - 1).map_or(now, |sent| sent + window)
+                }
+            }
+        }
+    }
+
+    fn consume(&mut self, now: TimeStamp) {
+        self.refresh(now);
+        match self.mode {
+            ThrottleMode::TokenBucket { .. } => self.tokens -= 1,
+            ThrottleMode::SlidingWindow { .. } => self.sent.push_back(now),
+        }
+    }
+}
+
+/// The outcome of `Throttle::submit`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Throttled {
+    Send(OrderRequest),
+    Queued,
+    Rejected(OrderRequest, RiskError),
+}
+
+/// Message rate throttle in front of order sending.
+///
+/// A message is counted against the session budget, the budget of its instrument and
+/// the budget of its kind (new, modify, cancel). It goes only if every budget allows it.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    config: ThrottleConfig,
+    session: Option<Limiter>,
+    instruments: FxHashMap<InstId, Limiter>,
+    kinds: FxHashMap<MessageKind, Limiter>,
+    queue: VecDeque<OrderRequest>,
+}
+
+impl Throttle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        Self {
+            session: config.session.map(|mode| Limiter::new(mode, 0)),
+            config,
+            instruments: FxHashMap::default(),
+            kinds: FxHashMap::default(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &ThrottleConfig {
+        &self.config
+    }
+
+    #[inline]
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn submit(&mut self, request: OrderRequest, now: TimeStamp) -> Throttled {
+        let kind = MessageKind::of(&request.order_core.order_type());
+        let bypass = self.config.cancel_bypass && kind == Some(MessageKind::Cancel);
+        // keep the order of the messages of an instrument
+        let behind = !bypass && self.queue.iter().any(|queued| queued.instid == request.instid);
+
+        let available_at = self.available_at(&request, now);
+        if bypass || (!behind && available_at <= now) {
+            self.consume(&request, now);
+            return Throttled::Send(request);
+        }
+        match self.config.policy {
+            ThrottlePolicy::Queue => {
+                self.queue.push_back(request);
+                Throttled::Queued
+            }
+            ThrottlePolicy::Reject => Throttled::Rejected(request, RiskError::Throttled { retry_at: available_at.max(now) }),
+        }
+    }
+
+    /// Releases the queued messages the budgets allow at `now`
+    pub fn poll(&mut self, now: TimeStamp) -> Vec<OrderRequest> {
+        let mut released = Vec::new();
+        let mut blocked = FxHashSet::default();
+        let mut kept = VecDeque::with_capacity(self.queue.len());
+        while let Some(request) = self.queue.pop_front() {
+            if !blocked.contains(&request.instid) && self.available_at(&request, now) <= now {
+                self.consume(&request, now);
+                released.push(request);
+            } else {
+                blocked.insert(request.instid);
+                kept.push_back(request);
+            }
+        }
+        self.queue = kept;
+        released
+    }
+
+    /// The earliest time `poll` may release a queued message
+    pub fn next_release(&mut self, now: TimeStamp) -> Option<TimeStamp> {
+        let queue = std::mem::take(&mut self.queue);
+        let next = queue.iter().map(|request| self.available_at(request, now)).min();
+        self.queue = queue;
+        next
+    }
+
+    /// Drops the queued messages, e.g., when the kill switch is engaged
+    pub fn clear_queue(&mut self) -> Vec<OrderRequest> {
+        self.queue.drain(..).collect()
+    }
+
+    fn kind_mode(&self, kind: MessageKind) -> Option<ThrottleMode> {
+        match kind {
+            MessageKind::New => self.config.new_order,
+            MessageKind::Modify => self.config.modify,
+            MessageKind::Cancel => self.config.cancel,
+        }
+    }
+
+    fn available_at(&mut self, request: &OrderRequest, now: TimeStamp) -> TimeStamp {
+        if let OrderCore::NullOrder(_) = request.order_core {
+            return now;
+        }
+        let mut available_at = now;
+        if let Some(session) = self.session.as_mut() {
+            available_at = available_at.max(session.available_at(now));
+        }
+        if let Some(mode) = self.config.per_instrument {
+            let limiter = self.instruments.entry(request.instid).or_insert_with(|| Limiter::new(mode, now));
+            available_at = available_at.max(limiter.available_at(now));
+        }
+        if let Some(kind) = MessageKind::of(&request.order_core.order_type()) {
+            if let Some(mode) = self.kind_mode(kind) {
+                let limiter = self.kinds.entry(kind).or_insert_with(|| Limiter::new(mode, now));
+                available_at = available_at.max(limiter.available_at(now));
+            }
+        }
+        available_at
+    }
+
+    fn consume(&mut self, request: &OrderRequest, now: TimeStamp) {
+        if let OrderCore::NullOrder(_) = request.order_core {
+            return;
+        }
+        if let Some(session) = self.session.as_mut() {
+            session.consume(now);
+        }
+        if let Some(limiter) = self.instruments.get_mut(&request.instid) {
+            limiter.consume(now);
+        }
+        if let Some(kind) = MessageKind::of(&request.order_core.order_type()) {
+            if let Some(limiter) = self.kinds.get_mut(&kind) {
+                limiter.consume(now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::core::{CancelOrder, LimitOrder, ModifyOrder};
+    use crate::order::enums::OrderSide;
+
+    const MS: UnixNano = 1_000_000;
+
+    fn new_order(code: &str, order_id: u64) -> OrderRequest {
+        let instid = InstId::from_str(code, "KRX");
+        OrderRequest::new(instid, OrderCore::LimitOrder(LimitOrder::new(100, 1, OrderSide::Bid, order_id)), 0)
+    }
+
+    fn cancel(code: &str, order_id: u64) -> OrderRequest {
+        OrderRequest::new(InstId::from_str(code, "KRX"), OrderCore::CancelOrder(CancelOrder::new(order_id)), 0)
+    }
+
+    #[test]
+    fn test_token_bucket_with_queue() {
+        let mut throttle = Throttle::new(ThrottleConfig {
+            session: Some(ThrottleMode::TokenBucket { capacity: 2, refill_interval: 10 * MS }),
+            cancel_bypass: true,
+            ..Default::default()
+        });
+        assert!(matches!(throttle.submit(new_order("A", 1), 0), Throttled::Send(_)));
+        assert!(matches!(throttle.submit(new_order("A", 2), 0), Throttled::Send(_)));
+        assert_eq!(throttle.submit(new_order("A", 3), 0), Throttled::Queued);
+        assert_eq!(throttle.submit(new_order("B", 4), 0), Throttled::Queued);
+        // cancels go at once
+        assert!(matches!(throttle.submit(cancel("A", 1), 0), Throttled::Send(_)));
+
+        // the bypassing cancel has used the next token
+        assert_eq!(throttle.next_release(MS), Some(20 * MS));
+        assert!(throttle.poll(10 * MS).is_empty());
+        let released = throttle.poll(20 * MS);
+        assert_eq!(released.iter().map(|r| r.get_id()).collect::<Vec<_>>(), vec![Some(3)]);
+        assert_eq!(throttle.poll(30 * MS).len(), 1);
+        assert_eq!(throttle.queued(), 0);
+    }
+
+    #[test]
+    fn test_sliding_window_per_instrument_and_kind() {
+        let mut throttle = Throttle::new(ThrottleConfig {
+            per_instrument: Some(ThrottleMode::SlidingWindow { max_messages: 2, window: 100 * MS }),
+            cancel: Some(ThrottleMode::SlidingWindow { max_messages: 1, window: 100 * MS }),
+            policy: ThrottlePolicy::Reject,
+            ..Default::default()
+        });
+        assert!(matches!(throttle.submit(new_order("A", 1), 0), Throttled::Send(_)));
+        assert!(matches!(throttle.submit(new_order("A", 2), 50 * MS), Throttled::Send(_)));
+        match throttle.submit(new_order("A", 3), 60 * MS) {
+            Throttled::Rejected(_, error) => assert_eq!(error, RiskError::Throttled { retry_at: 100 * MS }),
+            other => panic!("unexpected {:?}", other),
+        }
+        // another instrument has its own budget
+        assert!(matches!(throttle.submit(new_order("B", 4), 60 * MS), Throttled::Send(_)));
+        assert!(matches!(throttle.submit(new_order("A", 5), 100 * MS), Throttled::Send(_)));
+
+        assert!(matches!(throttle.submit(cancel("B", 4), 100 * MS), Throttled::Send(_)));
+        assert!(matches!(throttle.submit(cancel("C", 9), 150 * MS), Throttled::Rejected(..)));
+        assert!(matches!(throttle.submit(cancel("C", 9), 200 * MS), Throttled::Send(_)));
+    }
+
+    #[test]
+    fn test_zero_budget_blocks_everything() {
+        let mut throttle = Throttle::new(ThrottleConfig {
+            new_order: Some(ThrottleMode::SlidingWindow { max_messages: 0, window: 100 * MS }),
+            modify: Some(ThrottleMode::TokenBucket { capacity: 0, refill_interval: MS }),
+            ..Default::default()
+        });
+        assert!(matches!(throttle.submit(new_order("A", 1), 0), Throttled::Queued));
+        assert!(throttle.poll(10_000 * MS).is_empty());
+        assert_eq!(throttle.next_release(10_000 * MS), Some(TimeStamp::MAX));
+        assert_eq!(throttle.clear_queue().len(), 1);
+
+        throttle.config.policy = ThrottlePolicy::Reject;
+        match throttle.submit(new_order("B", 2), 0) {
+            Throttled::Rejected(_, error) => assert_eq!(error, RiskError::Throttled { retry_at: TimeStamp::MAX }),
+            other => panic!("unexpected {:?}", other),
+        }
+        let modify = OrderRequest::new(InstId::from_str("B", "KRX"), OrderCore::ModifyOrder(ModifyOrder::new(1, 100, 1)), 0);
+        assert!(matches!(throttle.submit(modify, 10_000 * MS), Throttled::Rejected(..)));
+        // cancels have no budget here
+        assert!(matches!(throttle.submit(cancel("B", 2), 0), Throttled::Send(_)));
+    }
+}
@@ -0,0 +1,839 @@
+//! Zero copy SBE decoding over borrowed buffers (e.g., the buffer `UdpClient::recv` fills) and encoding into a `Vec<u8>`
+use crate::sbe::error::SbeError;
+use crate::sbe::schema::{BlockDef, ByteOrder, EncodedType, FieldDef, GroupDef, MessageDef, Presence, Primitive, Schema, TypeDef};
+use std::ops::Deref;
+
+/// A field value. `Str` and `Bytes` borrow the buffer, `Enum` borrows the schema
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value<'a> {
+    Null,
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    /// a char array up to the first NUL
+    Str(&'a str),
+    Decimal { mantissa: i64, exponent: i8 },
+    /// the name of the valid value
+    Enum(&'a str),
+    /// the bits of a set
+    Set(u64),
+    /// any other composite
+    Bytes(&'a [u8]),
+}
+
+fn read_raw(buf: &[u8], offset: usize, size: usize, order: ByteOrder) -> u64 {
+    let bytes = &buf[offset..offset + size];
+    match order {
+        ByteOrder::LittleEndian => bytes.iter().rev().fold(0, |acc, b| acc << 8 | *b as u64),
+        ByteOrder::BigEndian => bytes.iter().fold(0, |acc, b| acc << 8 | *b as u64),
+    }
+}
+
+fn write_raw(buf: &mut [u8], offset: usize, size: usize, order: ByteOrder, value: u64) {
+    for i in 0..size {
+        let byte = (value >> (8 * i)) as u8;
+        match order {
+            ByteOrder::LittleEndian => buf[offset + i] = byte,
+            ByteOrder::BigEndian => buf[offset + size - 1 - i] = byte,
+        }
+    }
+}
+
+/// The integer of a primitive, sign extended for signed types
+fn read_int(buf: &[u8], offset: usize, primitive: Primitive, order: ByteOrder) -> i128 {
+    let size = primitive.size();
+    let raw = read_raw(buf, offset, size, order);
+    if primitive.is_signed() {
+        let shift = 64 - 8 * size as u32;
+        (((raw << shift) as i64) >> shift) as i128
+    } else {
+        raw as i128
+    }
+}
+
+fn write_int(buf: &mut [u8], offset: usize, primitive: Primitive, order: ByteOrder, value: i128) {
+    write_raw(buf, offset, primitive.size(), order, value as u64);
+}
+
+fn constant_int(encoded: &EncodedType) -> Result<i128, SbeError> {
+    let text = encoded.constant.as_deref().unwrap_or_default();
+    text.trim()
+        .parse()
+        .map_err(|_| SbeError::Schema(format!("invalid constant of {}: {}", encoded.name, text)))
+}
+
+fn read_encoded<'a>(buf: &'a [u8], offset: usize, encoded: &EncodedType, order: ByteOrder) -> Result<Value<'a>, SbeError> {
+    if encoded.presence == Presence::Constant {
+        return match encoded.primitive {
+            Primitive::Char => Ok(Value::Null),
+            _ => Ok(Value::Int(constant_int(encoded)? as i64)),
+        };
+    }
+    if encoded.primitive == Primitive::Char && encoded.length > 1 {
+        let bytes = &buf[offset..offset + encoded.length];
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        return std::str::from_utf8(&bytes[..end])
+            .map(Value::Str)
+            .map_err(|_| SbeError::InvalidValue { field: encoded.name.clone(), value: format!("{:?}", bytes) });
+    }
+    if encoded.primitive.is_float() {
+        let raw = read_raw(buf, offset, encoded.primitive.size(), order);
+        let value = match encoded.primitive {
+            Primitive::Float => f32::from_bits(raw as u32) as f64,
+            _ => f64::from_bits(raw),
+        };
+        return Ok(if value.is_nan() && encoded.presence == Presence::Optional { Value::Null } else { Value::Float(value) });
+    }
+    let value = read_int(buf, offset, encoded.primitive, order);
+    if encoded.presence == Presence::Optional && value == encoded.null() {
+        return Ok(Value::Null);
+    }
+    Ok(if encoded.primitive.is_signed() { Value::Int(value as i64) } else { Value::UInt(value as u64) })
+}
+
+fn read_field<'a>(buf: &'a [u8], offset: usize, field: &'a FieldDef, order: ByteOrder) -> Result<Value<'a>, SbeError> {
+    if let Some(constant) = &field.constant {
+        return Ok(Value::Enum(constant));
+    }
+    match &field.type_def {
+        TypeDef::Encoded(encoded) => read_encoded(buf, offset, encoded, order),
+        TypeDef::Enum { encoding, values, .. } => {
+            let raw = read_raw(buf, offset, encoding.size(), order);
+            values
+                .iter()
+                .find(|(_, value)| *value == raw)
+                .map(|(name, _)| Value::Enum(name))
+                .ok_or_else(|| SbeError::InvalidValue { field: field.name.clone(), value: raw.to_string() })
+        }
+        TypeDef::Set { encoding, .. } => Ok(Value::Set(read_raw(buf, offset, encoding.size(), order))),
+        composite @ TypeDef::Composite { size, .. } => {
+            let (Some(mantissa), Some(exponent)) = (composite.member("mantissa"), composite.member("exponent")) else {
+                return Ok(Value::Bytes(&buf[offset..offset + size]));
+            };
+            let mantissa = match read_encoded(buf, offset + mantissa.offset, mantissa, order)? {
+                Value::Int(mantissa) => mantissa,
+                Value::UInt(mantissa) => mantissa as i64,
+                _ => return Ok(Value::Null),
+            };
+            let exponent = match read_encoded(buf, offset + exponent.offset, exponent, order)? {
+                Value::Int(exponent) => exponent as i8,
+                Value::UInt(exponent) => exponent as i8,
+                _ => return Ok(Value::Null),
+            };
+            Ok(Value::Decimal { mantissa, exponent })
+        }
+    }
+}
+
+/// Writes the null of the optional fields of `block` into `buf`, the other bytes are left zero
+fn write_nulls(buf: &mut [u8], block: &BlockDef, order: ByteOrder) {
+    for field in block.fields.iter() {
+        let members: Vec<&EncodedType> = match &field.type_def {
+            TypeDef::Encoded(encoded) => vec![encoded],
+            TypeDef::Composite { members, .. } => members.iter().collect(),
+            _ => continue,
+        };
+        for member in members.into_iter().filter(|member| member.presence == Presence::Optional) {
+            let offset = field.offset + member.offset;
+            if offset + member.size() <= buf.len() {
+                write_null(buf, offset, member, order);
+            }
+        }
+    }
+}
+
+fn write_encoded(buf: &mut [u8], offset: usize, encoded: &EncodedType, order: ByteOrder, value: Value, field: &str) -> Result<(), SbeError> {
+    let invalid = || SbeError::InvalidValue { field: field.to_string(), value: format!("{:?}", value) };
+    if encoded.presence == Presence::Constant {
+        // a constant is not written, but the value must agree
+        return match value {
+            Value::Int(v) if v as i128 == constant_int(encoded)? => Ok(()),
+            Value::UInt(v) if v as i128 == constant_int(encoded)? => Ok(()),
+            _ => Err(invalid()),
+        };
+    }
+    match value {
+        Value::Null if encoded.presence == Presence::Optional => {
+            write_null(buf, offset, encoded, order);
+            Ok(())
+        }
+        Value::Str(s) if encoded.primitive == Primitive::Char => {
+            if s.len() > encoded.length {
+                return Err(invalid());
+            }
+            buf[offset..offset + encoded.length].fill(0);
+            buf[offset..offset + s.len()].copy_from_slice(s.as_bytes());
+            Ok(())
+        }
+        Value::Float(v) if encoded.primitive.is_float() => {
+            let raw = match encoded.primitive {
+                Primitive::Float => (v as f32).to_bits() as u64,
+                _ => v.to_bits(),
+            };
+            write_raw(buf, offset, encoded.primitive.size(), order, raw);
+            Ok(())
+        }
+        Value::Int(_) | Value::UInt(_) if !encoded.primitive.is_float() => {
+            let v = match value {
+                Value::Int(v) => v as i128,
+                Value::UInt(v) => v as i128,
+                _ => unreachable!(),
+            };
+            let bits = 8 * encoded.primitive.size() as u32;
+            let (min, max) = if encoded.primitive.is_signed() {
+                (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+            } else {
+                (0, (1i128 << bits) - 1)
+            };
+            if v < min || v > max {
+                return Err(invalid());
+            }
+            write_int(buf, offset, encoded.primitive, order, v);
+            Ok(())
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn write_null(buf: &mut [u8], offset: usize, encoded: &EncodedType, order: ByteOrder) {
+    let null = match encoded.primitive {
+        Primitive::Float => f32::NAN.to_bits() as i128,
+        Primitive::Double => f64::NAN.to_bits() as i128,
+        _ => encoded.null(),
+    };
+    (0..encoded.length).for_each(|i| write_int(buf, offset + i * encoded.primitive.size(), encoded.primitive, order, null));
+}
+
+fn write_field(buf: &mut [u8], offset: usize, field: &FieldDef, order: ByteOrder, value: Value) -> Result<(), SbeError> {
+    let invalid = || SbeError::InvalidValue { field: field.name.clone(), value: format!("{:?}", value) };
+    match (&field.type_def, value) {
+        (TypeDef::Encoded(encoded), _) => write_encoded(buf, offset, encoded, order, value, &field.name),
+        // a constant is not written, but the value must agree
+        (TypeDef::Enum { .. }, Value::Enum(name)) if field.constant.is_some() => {
+            if field.constant.as_deref() != Some(name) {
+                return Err(invalid());
+            }
+            Ok(())
+        }
+        (TypeDef::Enum { encoding, values, .. }, Value::Enum(name)) => {
+            let (_, raw) = values.iter().find(|(valid, _)| valid == name).ok_or_else(invalid)?;
+            write_raw(buf, offset, encoding.size(), order, *raw);
+            Ok(())
+        }
+        (TypeDef::Set { encoding, .. }, Value::Set(bits)) => {
+            write_raw(buf, offset, encoding.size(), order, bits);
+            Ok(())
+        }
+        (composite @ TypeDef::Composite { .. }, Value::Decimal { mantissa, exponent }) if composite.is_decimal() => {
+            let mantissa_type = composite.member("mantissa").expect("decimal");
+            let exponent_type = composite.member("exponent").expect("decimal");
+            write_encoded(buf, offset + exponent_type.offset, exponent_type, order, Value::Int(exponent as i64), &field.name)?;
+            write_encoded(buf, offset + mantissa_type.offset, mantissa_type, order, Value::Int(mantissa), &field.name)
+        }
+        (composite @ TypeDef::Composite { .. }, Value::Null) if composite.is_decimal() => {
+            let mantissa_type = composite.member("mantissa").expect("decimal");
+            write_encoded(buf, offset + mantissa_type.offset, mantissa_type, order, Value::Null, &field.name)
+        }
+        (TypeDef::Composite { size, .. }, Value::Bytes(bytes)) if bytes.len() == *size => {
+            buf[offset..offset + size].copy_from_slice(bytes);
+            Ok(())
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn truncated(needed: usize, len: usize) -> SbeError {
+    SbeError::Truncated { needed, len }
+}
+
+/// The end of the block at `start` with its groups and var data
+fn block_end(schema: &Schema, block: &BlockDef, buf: &[u8], start: usize, block_length: usize) -> Result<usize, SbeError> {
+    let mut pos = start.checked_add(block_length).ok_or(truncated(usize::MAX, buf.len()))?;
+    pos = groups_end(schema, &block.groups, buf, pos)?;
+    for data in block.data.iter() {
+        pos = data_end(schema, &data.length, buf, pos)?;
+    }
+    if pos > buf.len() {
+        return Err(truncated(pos, buf.len()));
+    }
+    Ok(pos)
+}
+
+/// The end of the groups at `pos`
+fn groups_end(schema: &Schema, groups: &[GroupDef], buf: &[u8], mut pos: usize) -> Result<usize, SbeError> {
+    for group in groups.iter() {
+        pos = group_end(schema, group, buf, pos)?;
+    }
+    Ok(pos)
+}
+
+/// The end of the group at `pos`. The count is checked against the buffer before walking the entries,
+/// so a hostile numInGroup fails fast
+fn group_end(schema: &Schema, group: &GroupDef, buf: &[u8], pos: usize) -> Result<usize, SbeError> {
+    let (entry_length, count) = dimension(schema, &group.dimension, buf, pos)?;
+    let mut pos = pos + group.dimension.size();
+    let min_entry = group.block.groups.iter().map(|nested| nested.dimension.size())
+        .chain(group.block.data.iter().map(|data| data.length.size()))
+        .fold(entry_length, usize::saturating_add);
+    if min_entry == 0 {
+        return Ok(pos);
+    }
+    let needed = min_entry.checked_mul(count).and_then(|len| len.checked_add(pos)).unwrap_or(usize::MAX);
+    if needed > buf.len() {
+        return Err(truncated(needed, buf.len()));
+    }
+    for _ in 0..count {
+        pos = block_end(schema, &group.block, buf, pos, entry_length)?;
+    }
+    Ok(pos)
+}
+
+/// (block length, count) of the group dimension at `pos`
+fn dimension(schema: &Schema, dimension: &TypeDef, buf: &[u8], pos: usize) -> Result<(usize, usize), SbeError> {
+    if pos + dimension.size() > buf.len() {
+        return Err(truncated(pos + dimension.size(), buf.len()));
+    }
+    let member = |name: &str| {
+        let member = dimension.member(name).expect("checked by the schema");
+        read_int(buf, pos + member.offset, member.primitive, schema.byte_order) as usize
+    };
+    Ok((member("blockLength"), member("numInGroup")))
+}
+
+fn data_end(schema: &Schema, length: &EncodedType, buf: &[u8], pos: usize) -> Result<usize, SbeError> {
+    if pos + length.size() > buf.len() {
+        return Err(truncated(pos + length.size(), buf.len()));
+    }
+    let len = read_int(buf, pos, length.primitive, schema.byte_order) as usize;
+    (pos + length.size()).checked_add(len).ok_or(truncated(usize::MAX, buf.len()))
+}
+
+/// The fixed block of a message or a group entry, with its groups and var data following it
+#[derive(Debug, Clone, Copy)]
+pub struct BlockView<'a> {
+    schema: &'a Schema,
+    block: &'a BlockDef,
+    buf: &'a [u8],
+    start: usize,
+    block_length: usize,
+}
+
+impl<'a> BlockView<'a> {
+    /// Null for a field beyond the block length, i.e., a field the sender's version does not have
+    pub fn value(&self, name: &str) -> Result<Value<'a>, SbeError> {
+        let field = self.block.field(name)?;
+        if field.offset + field.size() > self.block_length {
+            return Ok(Value::Null);
+        }
+        read_field(self.buf, self.start + field.offset, field, self.schema.byte_order)
+    }
+
+    fn mismatch(&self, name: &str, value: Value, expected: &'static str) -> SbeError {
+        match value {
+            Value::Null => SbeError::NullValue(name.to_string()),
+            _ => SbeError::TypeMismatch { field: name.to_string(), expected },
+        }
+    }
+
+    pub fn u64(&self, name: &str) -> Result<u64, SbeError> {
+        match self.value(name)? {
+            Value::UInt(v) => Ok(v),
+            Value::Int(v) if v >= 0 => Ok(v as u64),
+            other => Err(self.mismatch(name, other, "an unsigned integer")),
+        }
+    }
+
+    pub fn i64(&self, name: &str) -> Result<i64, SbeError> {
+        match self.value(name)? {
+            Value::Int(v) => Ok(v),
+            Value::UInt(v) if v <= i64::MAX as u64 => Ok(v as i64),
+            other => Err(self.mismatch(name, other, "an integer")),
+        }
+    }
+
+    pub fn str(&self, name: &str) -> Result<&'a str, SbeError> {
+        match self.value(name)? {
+            Value::Str(s) => Ok(s),
+            other => Err(self.mismatch(name, other, "a string")),
+        }
+    }
+
+    pub fn enum_name(&self, name: &str) -> Result<&'a str, SbeError> {
+        match self.value(name)? {
+            Value::Enum(valid) => Ok(valid),
+            other => Err(self.mismatch(name, other, "an enum")),
+        }
+    }
+
+    /// (mantissa, exponent)
+    pub fn decimal(&self, name: &str) -> Result<(i64, i8), SbeError> {
+        match self.value(name)? {
+            Value::Decimal { mantissa, exponent } => Ok((mantissa, exponent)),
+            other => Err(self.mismatch(name, other, "a decimal")),
+        }
+    }
+
+    /// Where the groups start
+    #[inline]
+    fn groups_start(&self) -> usize {
+        self.start + self.block_length
+    }
+
+    /// The entries of the group
+    pub fn group(&self, name: &str) -> Result<GroupIter<'a>, SbeError> {
+        let index = self.block.group_index(name)?;
+        let pos = groups_end(self.schema, &self.block.groups[..index], self.buf, self.groups_start())?;
+        let group = &self.block.groups[index];
+        let (entry_length, count) = dimension(self.schema, &group.dimension, self.buf, pos)?;
+        Ok(GroupIter {
+            schema: self.schema,
+            block: &group.block,
+            buf: self.buf,
+            pos: pos + group.dimension.size(),
+            entry_length,
+            remaining: count,
+        })
+    }
+
+    /// The bytes of the var data field
+    pub fn data(&self, name: &str) -> Result<&'a [u8], SbeError> {
+        let index = self.block.data_index(name)?;
+        let mut pos = groups_end(self.schema, &self.block.groups, self.buf, self.groups_start())?;
+        for data in self.block.data[..index].iter() {
+            pos = data_end(self.schema, &data.length, self.buf, pos)?;
+        }
+        let length = &self.block.data[index].length;
+        let end = data_end(self.schema, length, self.buf, pos)?;
+        self.buf.get(pos + length.size()..end).ok_or(truncated(end, self.buf.len()))
+    }
+}
+
+/// The entries of a repeating group
+#[derive(Debug, Clone)]
+pub struct GroupIter<'a> {
+    schema: &'a Schema,
+    block: &'a BlockDef,
+    buf: &'a [u8],
+    pos: usize,
+    entry_length: usize,
+    remaining: usize,
+}
+
+impl<'a> GroupIter<'a> {
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl<'a> Iterator for GroupIter<'a> {
+    type Item = Result<BlockView<'a>, SbeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let entry = BlockView {
+            schema: self.schema,
+            block: self.block,
+            buf: self.buf,
+            start: self.pos,
+            block_length: self.entry_length,
+        };
+        match block_end(self.schema, self.block, self.buf, self.pos, self.entry_length) {
+            Ok(end) => {
+                self.pos = end;
+                Some(Ok(entry))
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// A message decoded in place. Derefs to the root block
+#[derive(Debug, Clone, Copy)]
+pub struct MessageView<'a> {
+    message: &'a MessageDef,
+    version: u16,
+    root: BlockView<'a>,
+}
+
+impl<'a> MessageView<'a> {
+    #[inline]
+    pub fn name(&self) -> &'a str {
+        &self.message.name
+    }
+
+    #[inline]
+    pub fn template_id(&self) -> u16 {
+        self.message.id
+    }
+
+    /// The schema version of the sender
+    #[inline]
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// The encoded length including the header, e.g., to step to the next message of a packet
+    pub fn encoded_len(&self) -> Result<usize, SbeError> {
+        let root = &self.root;
+        block_end(root.schema, root.block, root.buf, root.start, root.block_length)
+    }
+}
+
+impl<'a> Deref for MessageView<'a> {
+    type Target = BlockView<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.root
+    }
+}
+
+impl Schema {
+    fn header_value(&self, buf: &[u8], member: &str) -> usize {
+        let member = self.header.member(member).expect("checked on load");
+        read_int(buf, member.offset, member.primitive, self.byte_order) as usize
+    }
+
+    /// Decodes the message at the front of `buf` without copying
+    pub fn decode<'a>(&'a self, buf: &'a [u8]) -> Result<MessageView<'a>, SbeError> {
+        let header_size = self.header_size();
+        if buf.len() < header_size {
+            return Err(truncated(header_size, buf.len()));
+        }
+        let schema_id = self.header_value(buf, "schemaId");
+        if schema_id != self.id as usize {
+            return Err(SbeError::Schema(format!("message of schema {}, expected {}", schema_id, self.id)));
+        }
+        let message = self.message_by_id(self.header_value(buf, "templateId") as u16)?;
+        let block_length = self.header_value(buf, "blockLength");
+        if buf.len() < header_size + block_length {
+            return Err(truncated(header_size + block_length, buf.len()));
+        }
+        let root = BlockView { schema: self, block: &message.block, buf, start: header_size, block_length };
+        Ok(MessageView { message, version: self.header_value(buf, "version") as u16, root })
+    }
+
+    /// Starts a message at the end of `buf`: the header and the block with nulls in the optional fields
+    pub fn encoder<'a>(&'a self, name: &str, buf: &'a mut Vec<u8>) -> Result<MessageEncoder<'a>, SbeError> {
+        let message = self.message(name)?;
+        let start = buf.len();
+        buf.resize(start + self.header_size() + message.block.block_length, 0);
+        let header = &mut buf[start..];
+        for (member, value) in [
+            ("blockLength", message.block.block_length),
+            ("templateId", message.id as usize),
+            ("schemaId", self.id as usize),
+            ("version", self.version as usize),
+        ] {
+            let member = self.header.member(member).expect("checked on load");
+            write_int(header, member.offset, member.primitive, self.byte_order, value as i128);
+        }
+        let block_start = start + self.header_size();
+        write_nulls(&mut buf[block_start..], &message.block, self.byte_order);
+        Ok(MessageEncoder { schema: self, message, buf, start, block_start, next_section: 0, group: None })
+    }
+
+    /// Appends the message `name` written by `write` to `buf` and returns the encoded length.
+    /// On an error, `buf` is left as it was
+    pub fn encode<F>(&self, name: &str, buf: &mut Vec<u8>, write: F) -> Result<usize, SbeError>
+    where
+        F: FnOnce(&mut MessageEncoder) -> Result<(), SbeError>,
+    {
+        let start = buf.len();
+        let encoded = self.encoder(name, buf).and_then(|mut encoder| {
+            write(&mut encoder)?;
+            encoder.finish()
+        });
+        if encoded.is_err() {
+            buf.truncate(start);
+        }
+        encoded
+    }
+}
+
+#[derive(Debug)]
+struct GroupState {
+    index: usize,
+    count: usize,
+    written: usize,
+    entry_start: Option<usize>,
+}
+
+/// Writes a message in schema order: the root fields, then the groups and the var data.
+/// Groups and data skipped are written empty. Groups nested in a group entry are written empty.
+#[derive(Debug)]
+pub struct MessageEncoder<'a> {
+    schema: &'a Schema,
+    message: &'a MessageDef,
+    buf: &'a mut Vec<u8>,
+    start: usize,
+    block_start: usize,
+    /// index into the groups followed by the data of the root block
+    next_section: usize,
+    group: Option<GroupState>,
+}
+
+impl MessageEncoder<'_> {
+    /// Sets a field of the current group entry, or of the root block before any group
+    pub fn set(&mut self, name: &str, value: Value) -> Result<&mut Self, SbeError> {
+        let order = self.schema.byte_order;
+        match &self.group {
+            Some(GroupState { index, entry_start: Some(entry_start), .. }) => {
+                let field = self.message.block.groups[*index].block.field(name)?;
+                write_field(&mut self.buf[*entry_start..], field.offset, field, order, value)?;
+            }
+            Some(_) => return Err(SbeError::OutOfOrder(name.to_string())),
+            None if self.next_section == 0 => {
+                let field = self.message.block.field(name)?;
+                write_field(&mut self.buf[self.block_start..], field.offset, field, order, value)?;
+            }
+            None => return Err(SbeError::OutOfOrder(name.to_string())),
+        }
+        Ok(self)
+    }
+
+    /// Appends empty groups and data of `block`
+    fn write_empty_sections(buf: &mut Vec<u8>, schema: &Schema, block: &BlockDef) {
+        for group in block.groups.iter() {
+            let start = buf.len();
+            buf.resize(start + group.dimension.size(), 0);
+            let member = group.dimension.member("blockLength").expect("checked on load");
+            write_int(&mut buf[start..], member.offset, member.primitive, schema.byte_order, group.block.block_length as i128);
+        }
+        for data in block.data.iter() {
+            buf.resize(buf.len() + data.length.size(), 0);
+        }
+    }
+
+    fn close_entry(&mut self) {
+        if let Some(GroupState { index, entry_start: entry_start @ Some(_), .. }) = &mut self.group {
+            *entry_start = None;
+            let block = &self.message.block.groups[*index].block;
+            Self::write_empty_sections(self.buf, self.schema, block);
+        }
+    }
+
+    fn close_group(&mut self) -> Result<(), SbeError> {
+        self.close_entry();
+        if let Some(group) = self.group.take() {
+            if group.written != group.count {
+                let name = &self.message.block.groups[group.index].name;
+                return Err(SbeError::InvalidValue {
+                    field: name.clone(),
+                    value: format!("{} entries written of {}", group.written, group.count),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes empty sections up to `section` of the root block
+    fn skip_to(&mut self, section: usize, name: &str) -> Result<(), SbeError> {
+        self.close_group()?;
+        if section < self.next_section {
+            return Err(SbeError::OutOfOrder(name.to_string()));
+        }
+        let block = &self.message.block;
+        for skipped in self.next_section..section {
+            let mut empty = BlockDef::default();
+            match block.groups.get(skipped) {
+                Some(group) => empty.groups.push(group.clone()),
+                None => empty.data.push(block.data[skipped - block.groups.len()].clone()),
+            }
+            Self::write_empty_sections(self.buf, self.schema, &empty);
+        }
+        self.next_section = section + 1;
+        Ok(())
+    }
+
+    /// Starts the group of `count` entries, call `next_entry` before setting the fields of each entry
+    pub fn begin_group(&mut self, name: &str, count: usize) -> Result<&mut Self, SbeError> {
+        let index = self.message.block.group_index(name)?;
+        self.skip_to(index, name)?;
+        let group = &self.message.block.groups[index];
+        let start = self.buf.len();
+        self.buf.resize(start + group.dimension.size(), 0);
+        for (member, value) in [("blockLength", group.block.block_length), ("numInGroup", count)] {
+            let member = group.dimension.member(member).expect("checked on load");
+            if value as i128 > (1i128 << (8 * member.primitive.size())) - 1 {
+                return Err(SbeError::InvalidValue { field: name.to_string(), value: value.to_string() });
+            }
+            write_int(&mut self.buf[start..], member.offset, member.primitive, self.schema.byte_order, value as i128);
+        }
+        self.group = Some(GroupState { index, count, written: 0, entry_start: None });
+        Ok(self)
+    }
+
+    pub fn next_entry(&mut self) -> Result<&mut Self, SbeError> {
+        self.close_entry();
+        let Some(state) = &mut self.group else {
+            return Err(SbeError::OutOfOrder("group entry".to_string()));
+        };
+        let group = &self.message.block.groups[state.index];
+        if state.written == state.count {
+            return Err(SbeError::InvalidValue { field: group.name.clone(), value: format!("more than {} entries", state.count) });
+        }
+        state.written += 1;
+        let start = self.buf.len();
+        self.buf.resize(start + group.block.block_length, 0);
+        write_nulls(&mut self.buf[start..], &group.block, self.schema.byte_order);
+        state.entry_start = Some(start);
+        Ok(self)
+    }
+
+    pub fn put_data(&mut self, name: &str, bytes: &[u8]) -> Result<&mut Self, SbeError> {
+        let index = self.message.block.data_index(name)?;
+        self.skip_to(self.message.block.groups.len() + index, name)?;
+        let length = &self.message.block.data[index].length;
+        if bytes.len() as i128 > (1i128 << (8 * length.primitive.size())) - 1 {
+            return Err(SbeError::InvalidValue { field: name.to_string(), value: format!("{} bytes", bytes.len()) });
+        }
+        let start = self.buf.len();
+        self.buf.resize(start + length.size(), 0);
+        write_int(&mut self.buf[start..], 0, length.primitive, self.schema.byte_order, bytes.len() as i128);
+        self.buf.extend_from_slice(bytes);
+        Ok(self)
+    }
+
+    /// Writes the sections left empty and returns the encoded length
+    pub fn finish(mut self) -> Result<usize, SbeError> {
+        let sections = self.message.block.groups.len() + self.message.block.data.len();
+        if self.next_section < sections {
+            self.skip_to(sections, "")?;
+        } else {
+            self.close_group()?;
+        }
+        Ok(self.buf.len() - self.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sbe::schema::tests::SCHEMA;
+
+    #[test]
+    fn test_round_trip() {
+        let schema = Schema::from_xml(SCHEMA).unwrap();
+        let mut buf = vec![0xAA]; // messages are appended
+        let mut encoder = schema.encoder("BookSnapshot", &mut buf).unwrap();
+        encoder.set("symbol", Value::Str("AAPL")).unwrap().set("transactTime", Value::UInt(1_700_000_000_000_000_000)).unwrap();
+        encoder.set("flags", Value::Set(1 << 7)).unwrap();
+        encoder.begin_group("levels", 2).unwrap();
+        encoder.next_entry().unwrap().set("side", Value::Enum("Bid")).unwrap();
+        encoder.set("price", Value::Decimal { mantissa: 150_250_000_000, exponent: -9 }).unwrap();
+        encoder.set("quantity", Value::UInt(100)).unwrap().set("orderCount", Value::UInt(3)).unwrap();
+        encoder.next_entry().unwrap().set("side", Value::Enum("Ask")).unwrap();
+        encoder.set("price", Value::Decimal { mantissa: 150_260_000_000, exponent: -9 }).unwrap();
+        encoder.set("quantity", Value::UInt(200)).unwrap();
+        assert_eq!(encoder.set("side", Value::Enum("Mid")).map(|_| ()), Err(SbeError::InvalidValue { field: "side".to_string(), value: "Enum(\"Mid\")".to_string() }));
+        encoder.put_data("text", b"snapshot").unwrap();
+        let len = encoder.finish().unwrap();
+        assert_eq!(len, 8 + 17 + 4 + 2 * 21 + 2 + 8);
+        assert_eq!(buf.len(), 1 + len);
+
+        let view = schema.decode(&buf[1..]).unwrap();
+        assert_eq!((view.name(), view.template_id(), view.version(), view.encoded_len()), ("BookSnapshot", 1, 1, Ok(len)));
+        assert_eq!(view.str("symbol"), Ok("AAPL"));
+        assert_eq!(view.u64("transactTime"), Ok(1_700_000_000_000_000_000));
+        assert_eq!(view.value("flags"), Ok(Value::Set(128)));
+        let levels: Vec<BlockView> = view.group("levels").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(levels.len(), 2);
+        assert_eq!(levels[0].enum_name("side"), Ok("Bid"));
+        assert_eq!(levels[0].decimal("price"), Ok((150_250_000_000, -9)));
+        assert_eq!(levels[0].u64("orderCount"), Ok(3));
+        assert_eq!(levels[1].u64("quantity"), Ok(200));
+        assert_eq!(levels[1].u64("orderCount"), Err(SbeError::NullValue("orderCount".to_string())));
+        assert_eq!(view.data("text"), Ok(&b"snapshot"[..]));
+        assert_eq!(view.i64("symbol"), Err(SbeError::TypeMismatch { field: "symbol".to_string(), expected: "an integer" }));
+    }
+
+    #[test]
+    fn test_empty_sections_and_errors() {
+        let schema = Schema::from_xml(SCHEMA).unwrap();
+        let mut buf = Vec::new();
+        let mut encoder = schema.encoder("BookSnapshot", &mut buf).unwrap();
+        encoder.set("symbol", Value::Str("MSFT")).unwrap();
+        encoder.put_data("text", b"x").unwrap();
+        assert_eq!(encoder.begin_group("levels", 1).map(|_| ()), Err(SbeError::OutOfOrder("levels".to_string())));
+        let len = encoder.finish().unwrap();
+
+        let view = schema.decode(&buf).unwrap();
+        assert_eq!(view.group("levels").unwrap().count(), 0);
+        assert_eq!(view.data("text"), Ok(&b"x"[..]));
+        assert_eq!(schema.decode(&buf[..len - 1]).unwrap().data("text"), Err(SbeError::Truncated { needed: len, len: len - 1 }));
+        assert_eq!(schema.decode(&buf[..4]).map(|_| ()), Err(SbeError::Truncated { needed: 8, len: 4 }));
+
+        let mut buf = Vec::new();
+        let mut encoder = schema.encoder("BookSnapshot", &mut buf).unwrap();
+        encoder.begin_group("levels", 2).unwrap().next_entry().unwrap();
+        assert!(matches!(encoder.finish(), Err(SbeError::InvalidValue { .. })));
+        let mut buf = vec![0xAA];
+        let encoded = schema.encode("BookSnapshot", &mut buf, |encoder| {
+            encoder.begin_group("levels", 2)?.next_entry()?;
+            Ok(())
+        });
+        assert!(matches!(encoded, Err(SbeError::InvalidValue { .. })));
+        assert_eq!(buf, vec![0xAA]);
+
+        // a constant exponent must agree
+        let mut buf = Vec::new();
+        let mut encoder = schema.encoder("NewOrder", &mut buf).unwrap();
+        assert!(encoder.set("price", Value::Decimal { mantissa: 1, exponent: -2 }).is_err());
+        assert!(encoder.set("quantity", Value::UInt(u32::MAX as u64 + 1)).is_err());
+        encoder.finish().unwrap();
+        let view = schema.decode(&buf).unwrap();
+        assert_eq!(view.value("price"), Ok(Value::Null));
+        assert_eq!(buf.len(), 8 + 40);
+    }
+
+    #[test]
+    fn test_lengths_past_the_buffer() {
+        let schema = Schema::from_xml(SCHEMA).unwrap();
+        let mut buf = Vec::new();
+        schema.encoder("BookSnapshot", &mut buf).unwrap().finish().unwrap();
+        // the header, the block, the levels dimension and the text length
+        assert_eq!(buf.len(), 8 + 17 + 4 + 2);
+
+        let mut hostile = buf.clone();
+        hostile[27..29].copy_from_slice(&u16::MAX.to_le_bytes());
+        let view = schema.decode(&hostile).unwrap();
+        let needed = 29 + 21 * u16::MAX as usize;
+        assert_eq!(view.encoded_len(), Err(SbeError::Truncated { needed, len: buf.len() }));
+        assert_eq!(view.data("text"), Err(SbeError::Truncated { needed, len: buf.len() }));
+        let mut levels = view.group("levels").unwrap();
+        assert_eq!(levels.remaining(), u16::MAX as usize);
+        assert!(matches!(levels.next(), Some(Err(SbeError::Truncated { .. }))));
+        assert!(levels.next().is_none());
+
+        let mut hostile = buf.clone();
+        hostile[29..31].copy_from_slice(&u16::MAX.to_le_bytes());
+        let view = schema.decode(&hostile).unwrap();
+        let needed = 31 + u16::MAX as usize;
+        assert_eq!(view.data("text"), Err(SbeError::Truncated { needed, len: buf.len() }));
+        assert_eq!(view.encoded_len(), Err(SbeError::Truncated { needed, len: buf.len() }));
+    }
+
+    #[test]
+    fn test_constant_fields() {
+        let schema = crate::sbe::schema::tests::constant_schema();
+        let mut buf = Vec::new();
+        let mut encoder = schema.encoder("NewOrder", &mut buf).unwrap();
+        encoder.set("side", Value::Enum("Bid")).unwrap().set("ordType", Value::Enum("Limit")).unwrap();
+        encoder.set("venueSide", Value::Enum("Ask")).unwrap().set("sideCode", Value::UInt(1)).unwrap();
+        assert!(encoder.set("venueSide", Value::Enum("Bid")).is_err());
+        assert!(encoder.set("sideCode", Value::UInt(0)).is_err());
+        encoder.finish().unwrap();
+
+        let view = schema.decode(&buf).unwrap();
+        assert_eq!(view.enum_name("venueSide"), Ok("Ask"));
+        assert_eq!(view.u64("sideCode"), Ok(1));
+        assert_eq!((view.enum_name("side"), view.enum_name("ordType")), (Ok("Bid"), Ok("Limit")));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::instrument::error::InstrumentError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SbeError {
+    /// malformed XML or a schema this codec can not express
+    Schema(String),
+    UnknownTemplate(u16),
+    UnknownMessage(String),
+    UnknownField(String),
+    /// the buffer is shorter than the message
+    Truncated { needed: usize, len: usize },
+    /// the field exists but has another type than requested
+    TypeMismatch { field: String, expected: &'static str },
+    NullValue(String),
+    InvalidValue { field: String, value: String },
+    /// groups and var data must be written in schema order
+    OutOfOrder(String),
+    Instrument(InstrumentError),
+}
+
+impl std::fmt::Display for SbeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SbeError::Schema(msg) => write!(f, "sbe schema error: {}", msg),
+            SbeError::UnknownTemplate(id) => write!(f, "unknown sbe template id: {}", id),
+            SbeError::UnknownMessage(name) => write!(f, "unknown sbe message: {}", name),
+            SbeError::UnknownField(name) => write!(f, "unknown sbe field: {}", name),
+            SbeError::Truncated { needed, len } => write!(f, "sbe buffer of {} bytes, {} needed", len, needed),
+            SbeError::TypeMismatch { field, expected } => write!(f, "sbe field {} is not {}", field, expected),
+            SbeError::NullValue(field) => write!(f, "sbe field {} is null", field),
+            SbeError::InvalidValue { field, value } => write!(f, "invalid value of sbe field {}: {}", field, value),
+            SbeError::OutOfOrder(name) => write!(f, "sbe group or data {} written out of order", name),
+            SbeError::Instrument(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SbeError {}
+
+impl From<InstrumentError> for SbeError {
+    fn from(e: InstrumentError) -> Self {
+        SbeError::Instrument(e)
+    }
+}
@@ -0,0 +1,331 @@
+//! Maps SBE messages to and from `QuoteSnapshot` and `OrderCore` by field names.
+//! A decimal price is rescaled to the `price_scale` of the instrument, an integer price is taken as a book price.
+use crate::{BookPrice, InstId, TimeStamp};
+use crate::data::level::LevelSnapshot;
+use crate::data::snapshot::QuoteSnapshot;
+use crate::instrument::error::InstrumentError;
+use crate::instrument::registry::InstrumentRegistry;
+use crate::instrument::spec::InstrumentSpec;
+use crate::order::core::{LimitOrder, MarketOrder, OrderCore};
+use crate::order::enums::OrderSide;
+use crate::price::rescale;
+use crate::sbe::codec::{BlockView, MessageView, Value};
+use crate::sbe::error::SbeError;
+use crate::sbe::schema::{Presence, Schema, TypeDef};
+
+fn check_message(view: &MessageView, message: &str) -> Result<(), SbeError> {
+    match view.name() == message {
+        true => Ok(()),
+        false => Err(SbeError::UnknownMessage(view.name().to_string())),
+    }
+}
+
+fn book_price(value: Value, field: &str, spec: &InstrumentSpec) -> Result<BookPrice, SbeError> {
+    let invalid = || SbeError::InvalidValue { field: field.to_string(), value: format!("{:?}", value) };
+    match value {
+        Value::Decimal { mantissa, exponent } if exponent <= 0 => {
+            Ok(rescale(mantissa, exponent.unsigned_abs(), spec.price_scale).map_err(InstrumentError::from)?)
+        }
+        Value::Decimal { mantissa, exponent } => {
+            let mantissa = mantissa.checked_mul(10i64.checked_pow(exponent as u32).ok_or_else(invalid)?).ok_or_else(invalid)?;
+            Ok(rescale(mantissa, 0, spec.price_scale).map_err(InstrumentError::from)?)
+        }
+        Value::Int(price) => Ok(price),
+        Value::UInt(price) => i64::try_from(price).map_err(|_| invalid()),
+        Value::Null => Err(SbeError::NullValue(field.to_string())),
+        _ => Err(SbeError::TypeMismatch { field: field.to_string(), expected: "a price" }),
+    }
+}
+
+/// The value of `price` for a field of `type_def`: a decimal with a constant exponent is rescaled to it
+fn price_value(type_def: &TypeDef, price: BookPrice, spec: &InstrumentSpec) -> Result<Value<'static>, SbeError> {
+    if !type_def.is_decimal() {
+        return Ok(Value::Int(price));
+    }
+    let exponent = type_def.member("exponent").expect("a decimal");
+    let scale = match (exponent.presence, exponent.constant.as_deref()) {
+        (Presence::Constant, Some(constant)) => constant
+            .trim()
+            .parse::<i8>()
+            .ok()
+            .filter(|exponent| *exponent <= 0)
+            .ok_or_else(|| SbeError::Schema(format!("unsupported exponent of {}: {}", type_def.name(), constant)))?
+            .unsigned_abs(),
+        _ => spec.price_scale,
+    };
+    let mantissa = rescale(price, spec.price_scale, scale).map_err(InstrumentError::from)?;
+    Ok(Value::Decimal { mantissa, exponent: -(scale as i8) })
+}
+
+fn side_of(name: &str, bid: &str, ask: &str, field: &str) -> Result<OrderSide, SbeError> {
+    match name {
+        name if name == bid => Ok(OrderSide::Bid),
+        name if name == ask => Ok(OrderSide::Ask),
+        name => Err(SbeError::InvalidValue { field: field.to_string(), value: name.to_string() }),
+    }
+}
+
+/// Field names of a book message with one group of levels, both sides in the group
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteMapping {
+    pub message: String,
+    pub symbol: String,
+    pub time: String,
+    pub levels: String,
+    pub side: String,
+    pub price: String,
+    pub quantity: String,
+    /// None if the message has no order count
+    pub order_count: Option<String>,
+    /// the valid values of `side`
+    pub bid: String,
+    pub ask: String,
+}
+
+impl Default for QuoteMapping {
+    fn default() -> Self {
+        Self {
+            message: "BookSnapshot".to_string(),
+            symbol: "symbol".to_string(),
+            time: "transactTime".to_string(),
+            levels: "levels".to_string(),
+            side: "side".to_string(),
+            price: "price".to_string(),
+            quantity: "quantity".to_string(),
+            order_count: Some("orderCount".to_string()),
+            bid: "Bid".to_string(),
+            ask: "Ask".to_string(),
+        }
+    }
+}
+
+impl QuoteMapping {
+    fn level(&self, entry: &BlockView, spec: &InstrumentSpec) -> Result<(OrderSide, LevelSnapshot), SbeError> {
+        let side = side_of(entry.enum_name(&self.side)?, &self.bid, &self.ask, &self.side)?;
+        let order_count = match self.order_count.as_deref().map(|name| (name, entry.value(name))) {
+            None | Some((_, Ok(Value::Null))) => None,
+            Some((name, Ok(Value::UInt(count)))) => Some(u32::try_from(count).map_err(|_| SbeError::InvalidValue {
+                field: name.to_string(),
+                value: count.to_string(),
+            })?),
+            Some((name, Ok(_))) => return Err(SbeError::TypeMismatch { field: name.to_string(), expected: "an unsigned integer" }),
+            Some((_, Err(e))) => return Err(e),
+        };
+        let level = LevelSnapshot {
+            order_count,
+            book_price: book_price(entry.value(&self.price)?, &self.price, spec)?,
+            book_quantity: entry.u64(&self.quantity)?,
+            ..LevelSnapshot::default()
+        };
+        Ok((side, level))
+    }
+
+    /// The snapshot of the instrument `symbol` at `venue`, with the asks ascending and the bids descending
+    pub fn decode(&self, view: &MessageView, registry: &InstrumentRegistry, venue: &str, systemtime: TimeStamp) -> Result<QuoteSnapshot, SbeError> {
+        check_message(view, &self.message)?;
+        let id = registry.try_find(view.str(&self.symbol)?, venue)?;
+        let spec = registry.try_get(&id)?;
+        let mut ask_quote_data = Vec::new();
+        let mut bid_quote_data = Vec::new();
+        for entry in view.group(&self.levels)? {
+            match self.level(&entry?, spec)? {
+                (OrderSide::Bid, level) => bid_quote_data.push(level),
+                (OrderSide::Ask, level) => ask_quote_data.push(level),
+            }
+        }
+        ask_quote_data.sort_by_key(|level| level.book_price);
+        bid_quote_data.sort_by_key(|level| std::cmp::Reverse(level.book_price));
+        Ok(QuoteSnapshot {
+            id,
+            datatime: view.u64(&self.time)?,
+            systemtime,
+            quote_level_cut: ask_quote_data.len().max(bid_quote_data.len()),
+            ask_quote_data,
+            bid_quote_data,
+            all_lp_holdings: None,
+        })
+    }
+
+    /// Appends the first `quote_level_cut` levels of each side of `quote` to `buf` and returns the encoded length.
+    /// Empty levels are left out, so that they do not decode as a level at price 0. Nothing is appended on an error
+    pub fn encode(&self, quote: &QuoteSnapshot, schema: &Schema, registry: &InstrumentRegistry, buf: &mut Vec<u8>) -> Result<usize, SbeError> {
+        let spec = registry.try_get(&quote.id)?;
+        let block = &schema.message(&self.message)?.block;
+        let price_type = &block.groups[block.group_index(&self.levels)?].block.field(&self.price)?.type_def;
+        let bids = quote.bid_quote_data.iter().take(quote.quote_level_cut).map(|level| (&self.bid, level));
+        let asks = quote.ask_quote_data.iter().take(quote.quote_level_cut).map(|level| (&self.ask, level));
+        let levels: Vec<(&String, &LevelSnapshot)> = bids.chain(asks).filter(|(_, level)| level.book_quantity > 0).collect();
+
+        schema.encode(&self.message, buf, |encoder| {
+            encoder.set(&self.symbol, Value::Str(quote.id.code_str()))?;
+            encoder.set(&self.time, Value::UInt(quote.datatime))?;
+            encoder.begin_group(&self.levels, levels.len())?;
+            for (side, level) in levels {
+                encoder.next_entry()?;
+                encoder.set(&self.side, Value::Enum(side))?;
+                encoder.set(&self.price, price_value(price_type, level.book_price, spec)?)?;
+                encoder.set(&self.quantity, Value::UInt(level.book_quantity))?;
+                if let (Some(name), Some(order_count)) = (&self.order_count, level.order_count) {
+                    encoder.set(name, Value::UInt(order_count as u64))?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Field names of a new order message, for `LimitOrder` and `MarketOrder`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderMapping {
+    pub message: String,
+    pub order_id: String,
+    pub symbol: String,
+    pub side: String,
+    pub ord_type: String,
+    pub price: String,
+    pub quantity: String,
+    /// the valid values of `side`
+    pub bid: String,
+    pub ask: String,
+    /// the valid values of `ord_type`
+    pub limit: String,
+    pub market: String,
+}
+
+impl Default for OrderMapping {
+    fn default() -> Self {
+        Self {
+            message: "NewOrder".to_string(),
+            order_id: "orderId".to_string(),
+            symbol: "symbol".to_string(),
+            side: "side".to_string(),
+            ord_type: "ordType".to_string(),
+            price: "price".to_string(),
+            quantity: "quantity".to_string(),
+            bid: "Bid".to_string(),
+            ask: "Ask".to_string(),
+            limit: "Limit".to_string(),
+            market: "Market".to_string(),
+        }
+    }
+}
+
+impl OrderMapping {
+    pub fn decode(&self, view: &MessageView, registry: &InstrumentRegistry, venue: &str) -> Result<(InstId, OrderCore), SbeError> {
+        check_message(view, &self.message)?;
+        let id = registry.try_find(view.str(&self.symbol)?, venue)?;
+        let spec = registry.try_get(&id)?;
+        let side = side_of(view.enum_name(&self.side)?, &self.bid, &self.ask, &self.side)?;
+        let quantity = view.u64(&self.quantity)?;
+        let order_id = view.u64(&self.order_id)?;
+        let order_core = match view.enum_name(&self.ord_type)? {
+            ord_type if ord_type == self.limit => {
+                let price = book_price(view.value(&self.price)?, &self.price, spec)?;
+                OrderCore::LimitOrder(LimitOrder::new(price, quantity, side, order_id))
+            }
+            ord_type if ord_type == self.market => OrderCore::MarketOrder(MarketOrder::new(quantity, side, order_id)),
+            ord_type => return Err(SbeError::InvalidValue { field: self.ord_type.clone(), value: ord_type.to_string() }),
+        };
+        Ok((id, order_core))
+    }
+
+    /// Appends the order to `buf` and returns the encoded length. The price of a market order is left null.
+    /// Nothing is appended on an error
+    pub fn encode(&self, id: &InstId, order_core: &OrderCore, schema: &Schema, registry: &InstrumentRegistry, buf: &mut Vec<u8>) -> Result<usize, SbeError> {
+        let spec = registry.try_get(id)?;
+        let (ord_type, price, quantity, side, order_id) = match order_core {
+            OrderCore::LimitOrder(order) => (&self.limit, Some(order.price), order.quantity, order.order_side, order.order_id),
+            OrderCore::MarketOrder(order) => (&self.market, None, order.quantity, order.order_side, order.order_id),
+            other => {
+                return Err(SbeError::InvalidValue { field: self.ord_type.clone(), value: format!("{:?}", other.order_type()) })
+            }
+        };
+        let price_type = &schema.message(&self.message)?.block.field(&self.price)?.type_def;
+        let price = match price {
+            Some(price) => price_value(price_type, price, spec)?,
+            None => Value::Null,
+        };
+
+        schema.encode(&self.message, buf, |encoder| {
+            encoder.set(&self.order_id, Value::UInt(order_id))?;
+            encoder.set(&self.symbol, Value::Str(id.code_str()))?;
+            encoder.set(&self.side, Value::Enum(if side == OrderSide::Bid { &self.bid } else { &self.ask }))?;
+            encoder.set(&self.ord_type, Value::Enum(ord_type))?;
+            encoder.set(&self.price, price)?;
+            encoder.set(&self.quantity, Value::UInt(quantity))?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sbe::schema::tests::SCHEMA;
+
+    fn registry() -> InstrumentRegistry {
+        let mut registry = InstrumentRegistry::new();
+        registry.insert(InstrumentSpec::new(InstId::from_str("AAPL", "XNAS"), 1, 2));
+        registry
+    }
+
+    #[test]
+    fn test_quote_round_trip() {
+        let schema = Schema::from_xml(SCHEMA).unwrap();
+        let registry = registry();
+        let mapping = QuoteMapping::default();
+        let mut quote = QuoteSnapshot::sample(2);
+        quote.id = InstId::from_str("AAPL", "XNAS");
+        quote.datatime = 1_700_000_000_000_000_000;
+        quote.bid_quote_data[0] = LevelSnapshot { order_count: Some(3), book_price: 15_025, book_quantity: 100, ..Default::default() };
+        quote.bid_quote_data[1] = LevelSnapshot { book_price: 15_024, book_quantity: 50, ..Default::default() };
+        quote.ask_quote_data[0] = LevelSnapshot { order_count: Some(1), book_price: 15_030, book_quantity: 70, ..Default::default() };
+        quote.quote_level_cut = 2;
+
+        let mut buf = Vec::new();
+        let len = mapping.encode(&quote, &schema, &registry, &mut buf).unwrap();
+        let view = schema.decode(&buf).unwrap();
+        assert_eq!(view.encoded_len(), Ok(len));
+        let mut levels = view.group("levels").unwrap();
+        assert_eq!(levels.next().unwrap().unwrap().decimal("price"), Ok((150_250_000_000, -9)));
+
+        let decoded = mapping.decode(&view, &registry, "XNAS", 7).unwrap();
+        // the empty ask level is left out
+        assert_eq!(decoded.ask_quote_data, vec![quote.ask_quote_data[0]]);
+        assert_eq!(decoded.quote_level_cut, 2);
+        assert_eq!((decoded.bid_quote_data, decoded.datatime, decoded.systemtime), (quote.bid_quote_data, quote.datatime, 7));
+        assert_eq!(
+            mapping.decode(&view, &registry, "XNYS", 7),
+            Err(SbeError::Instrument(InstrumentError::UnknownCode { code: "AAPL".to_string(), venue: "XNYS".to_string() }))
+        );
+    }
+
+    #[test]
+    fn test_order_round_trip() {
+        let schema = Schema::from_xml(SCHEMA).unwrap();
+        let registry = registry();
+        let mapping = OrderMapping::default();
+        let id = InstId::from_str("AAPL", "XNAS");
+        let limit = OrderCore::LimitOrder(LimitOrder::new(15_025, 100, OrderSide::Ask, 42));
+        let market = OrderCore::MarketOrder(MarketOrder::new(10, OrderSide::Bid, 43));
+
+        let mut buf = Vec::new();
+        let first = mapping.encode(&id, &limit, &schema, &registry, &mut buf).unwrap();
+        mapping.encode(&id, &market, &schema, &registry, &mut buf).unwrap();
+        let view = schema.decode(&buf).unwrap();
+        assert_eq!(mapping.decode(&view, &registry, "XNAS"), Ok((id, limit)));
+        let view = schema.decode(&buf[first..]).unwrap();
+        assert_eq!(view.value("price"), Ok(Value::Null));
+        assert_eq!(mapping.decode(&view, &registry, "XNAS"), Ok((id, market)));
+
+        let cancel = OrderCore::CancelOrder(crate::order::core::CancelOrder::new(42));
+        assert!(matches!(mapping.encode(&id, &cancel, &schema, &registry, &mut Vec::new()), Err(SbeError::InvalidValue { .. })));
+        assert_eq!(QuoteMapping::default().decode(&view, &registry, "XNAS", 0), Err(SbeError::UnknownMessage("NewOrder".to_string())));
+
+        // nothing is left of a message that failed half way
+        let len = buf.len();
+        let too_large = OrderCore::LimitOrder(LimitOrder::new(15_025, u32::MAX as u64 + 1, OrderSide::Ask, 44));
+        assert!(matches!(mapping.encode(&id, &too_large, &schema, &registry, &mut buf), Err(SbeError::InvalidValue { .. })));
+        assert_eq!(buf.len(), len);
+    }
+}
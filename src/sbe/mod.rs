@@ -0,0 +1,5 @@
+pub mod error;
+pub mod xml;
+pub mod schema;
+pub mod codec;
+pub mod mapping;
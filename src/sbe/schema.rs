@@ -0,0 +1,690 @@
+//! An SBE message schema loaded at runtime from its XML.
+//! Supported: encoded types (with arrays, optional and constant presence), composites of encoded types,
+//! enums, sets, messages with fields, repeating groups (nested too) and var data. Types may refer to types
+//! declared later. Constant fields (by `valueRef` or text) of encoded types and enums take no block space;
+//! constant sets and composites are rejected. `sinceVersion` is not
+//! tracked: a field beyond the block length of an older message reads as null.
+use crate::sbe::error::SbeError;
+use crate::sbe::xml::{self, Element};
+use rustc_hash::FxHashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteOrder {
+    #[default]
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    Char,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Float,
+    Double,
+}
+
+impl Primitive {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" => Some(Primitive::Char),
+            "int8" => Some(Primitive::Int8),
+            "int16" => Some(Primitive::Int16),
+            "int32" => Some(Primitive::Int32),
+            "int64" => Some(Primitive::Int64),
+            "uint8" => Some(Primitive::UInt8),
+            "uint16" => Some(Primitive::UInt16),
+            "uint32" => Some(Primitive::UInt32),
+            "uint64" => Some(Primitive::UInt64),
+            "float" => Some(Primitive::Float),
+            "double" => Some(Primitive::Double),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        match self {
+            Primitive::Char | Primitive::Int8 | Primitive::UInt8 => 1,
+            Primitive::Int16 | Primitive::UInt16 => 2,
+            Primitive::Int32 | Primitive::UInt32 | Primitive::Float => 4,
+            Primitive::Int64 | Primitive::UInt64 | Primitive::Double => 8,
+        }
+    }
+
+    #[inline]
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Primitive::Int8 | Primitive::Int16 | Primitive::Int32 | Primitive::Int64)
+    }
+
+    #[inline]
+    pub fn is_float(&self) -> bool {
+        matches!(self, Primitive::Float | Primitive::Double)
+    }
+
+    /// The default null of optional integers: the minimum of signed and the maximum of unsigned types
+    pub fn default_null(&self) -> i128 {
+        match self {
+            Primitive::Char => 0,
+            Primitive::Int8 => i8::MIN as i128,
+            Primitive::Int16 => i16::MIN as i128,
+            Primitive::Int32 => i32::MIN as i128,
+            Primitive::Int64 => i64::MIN as i128,
+            Primitive::UInt8 => u8::MAX as i128,
+            Primitive::UInt16 => u16::MAX as i128,
+            Primitive::UInt32 => u32::MAX as i128,
+            Primitive::UInt64 | Primitive::Float | Primitive::Double => u64::MAX as i128,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Presence {
+    #[default]
+    Required,
+    Optional,
+    /// takes no space, the value is in the schema
+    Constant,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodedType {
+    pub name: String,
+    pub primitive: Primitive,
+    /// the number of elements, more than 1 for arrays (e.g., char strings)
+    pub length: usize,
+    pub presence: Presence,
+    /// the null of an optional integer
+    pub null_value: Option<i128>,
+    /// the value of a constant
+    pub constant: Option<String>,
+    /// the offset in the enclosing composite
+    pub offset: usize,
+}
+
+impl EncodedType {
+    #[inline]
+    pub fn size(&self) -> usize {
+        match self.presence {
+            Presence::Constant => 0,
+            _ => self.primitive.size() * self.length,
+        }
+    }
+
+    #[inline]
+    pub fn null(&self) -> i128 {
+        self.null_value.unwrap_or(self.primitive.default_null())
+    }
+
+    fn from_element(element: &Element) -> Result<Self, SbeError> {
+        let primitive_name = element.require("primitiveType")?;
+        let primitive = Primitive::from_name(primitive_name)
+            .ok_or_else(|| SbeError::Schema(format!("unknown primitive type: {}", primitive_name)))?;
+        let presence = match element.attribute("presence") {
+            None | Some("required") => Presence::Required,
+            Some("optional") => Presence::Optional,
+            Some("constant") => Presence::Constant,
+            Some(other) => return Err(SbeError::Schema(format!("unknown presence: {}", other))),
+        };
+        Ok(Self {
+            name: element.require("name")?.to_string(),
+            primitive,
+            length: element.parse("length")?.unwrap_or(1),
+            presence,
+            null_value: element.parse("nullValue")?,
+            constant: (presence == Presence::Constant).then(|| element.text.clone()),
+            offset: element.parse("offset")?.unwrap_or(0),
+        })
+    }
+
+    fn primitive(name: &str, primitive: Primitive) -> Self {
+        Self {
+            name: name.to_string(),
+            primitive,
+            length: 1,
+            presence: Presence::Required,
+            null_value: None,
+            constant: None,
+            offset: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeDef {
+    Encoded(EncodedType),
+    Composite {
+        name: String,
+        members: Vec<EncodedType>,
+        size: usize,
+    },
+    Enum {
+        name: String,
+        encoding: Primitive,
+        values: Vec<(String, u64)>,
+    },
+    Set {
+        name: String,
+        encoding: Primitive,
+        choices: Vec<(String, u32)>,
+    },
+}
+
+impl TypeDef {
+    pub fn name(&self) -> &str {
+        match self {
+            TypeDef::Encoded(encoded) => &encoded.name,
+            TypeDef::Composite { name, .. } | TypeDef::Enum { name, .. } | TypeDef::Set { name, .. } => name,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            TypeDef::Encoded(encoded) => encoded.size(),
+            TypeDef::Composite { size, .. } => *size,
+            TypeDef::Enum { encoding, .. } | TypeDef::Set { encoding, .. } => encoding.size(),
+        }
+    }
+
+    pub fn member(&self, name: &str) -> Option<&EncodedType> {
+        match self {
+            TypeDef::Composite { members, .. } => members.iter().find(|member| member.name == name),
+            _ => None,
+        }
+    }
+
+    /// A composite of `mantissa` and `exponent`
+    #[inline]
+    pub fn is_decimal(&self) -> bool {
+        self.member("mantissa").is_some() && self.member("exponent").is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDef {
+    pub name: String,
+    pub id: u16,
+    pub offset: usize,
+    pub type_def: TypeDef,
+    /// the valid value of an enum field with constant presence
+    pub constant: Option<String>,
+}
+
+impl FieldDef {
+    /// The space in the block, none for a constant
+    #[inline]
+    pub fn size(&self) -> usize {
+        match self.constant {
+            Some(_) => 0,
+            None => self.type_def.size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupDef {
+    pub name: String,
+    pub id: u16,
+    /// the composite of `blockLength` and `numInGroup`
+    pub dimension: TypeDef,
+    pub block: BlockDef,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataDef {
+    pub name: String,
+    pub id: u16,
+    /// the `length` member of the var data encoding
+    pub length: EncodedType,
+}
+
+/// The fixed block of a message or a group entry, followed by its groups and var data
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BlockDef {
+    pub block_length: usize,
+    pub fields: Vec<FieldDef>,
+    pub groups: Vec<GroupDef>,
+    pub data: Vec<DataDef>,
+}
+
+impl BlockDef {
+    pub fn field(&self, name: &str) -> Result<&FieldDef, SbeError> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name)
+            .ok_or_else(|| SbeError::UnknownField(name.to_string()))
+    }
+
+    pub fn group_index(&self, name: &str) -> Result<usize, SbeError> {
+        self.groups
+            .iter()
+            .position(|group| group.name == name)
+            .ok_or_else(|| SbeError::UnknownField(name.to_string()))
+    }
+
+    pub fn data_index(&self, name: &str) -> Result<usize, SbeError> {
+        self.data
+            .iter()
+            .position(|data| data.name == name)
+            .ok_or_else(|| SbeError::UnknownField(name.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageDef {
+    pub name: String,
+    pub id: u16,
+    pub block: BlockDef,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub id: u16,
+    pub version: u16,
+    pub byte_order: ByteOrder,
+    /// the message header composite, with `blockLength`, `templateId`, `schemaId` and `version`
+    pub header: TypeDef,
+    types: FxHashMap<String, TypeDef>,
+    messages: Vec<MessageDef>,
+}
+
+impl Schema {
+    pub fn from_xml(xml: &str) -> Result<Self, SbeError> {
+        let root = xml::parse(xml)?;
+        if root.name != "messageSchema" {
+            return Err(SbeError::Schema(format!("the root is <{}>, not <messageSchema>", root.name)));
+        }
+        let byte_order = match root.attribute("byteOrder") {
+            None | Some("littleEndian") => ByteOrder::LittleEndian,
+            Some("bigEndian") => ByteOrder::BigEndian,
+            Some(other) => return Err(SbeError::Schema(format!("unknown byte order: {}", other))),
+        };
+
+        // a type may refer to one declared after it: retry the failed ones while any resolves
+        let mut types = FxHashMap::default();
+        let mut pending: Vec<&Element> = root.children_named("types").flat_map(|types| types.children.iter()).collect();
+        while !pending.is_empty() {
+            let mut failed = Vec::new();
+            let mut error = None;
+            for element in pending.iter().copied() {
+                match parse_type(element, &types) {
+                    Ok(type_def) => {
+                        types.insert(type_def.name().to_string(), type_def);
+                    }
+                    Err(e) => {
+                        error.get_or_insert(e);
+                        failed.push(element);
+                    }
+                }
+            }
+            if let Some(error) = error.filter(|_| failed.len() == pending.len()) {
+                return Err(error);
+            }
+            pending = failed;
+        }
+        let header_name = root.attribute("headerType").unwrap_or("messageHeader");
+        let header = types
+            .get(header_name)
+            .cloned()
+            .ok_or_else(|| SbeError::Schema(format!("missing header type {}", header_name)))?;
+        for member in ["blockLength", "templateId", "schemaId", "version"] {
+            if header.member(member).is_none() {
+                return Err(SbeError::Schema(format!("the header has no {}", member)));
+            }
+        }
+
+        let messages = root
+            .children_named("message")
+            .map(|element| {
+                Ok(MessageDef {
+                    name: element.require("name")?.to_string(),
+                    id: element.parse("id")?.ok_or_else(|| SbeError::Schema("message without id".to_string()))?,
+                    block: parse_block(element, &types)?,
+                })
+            })
+            .collect::<Result<Vec<_>, SbeError>>()?;
+
+        Ok(Self {
+            id: root.parse("id")?.unwrap_or(0),
+            version: root.parse("version")?.unwrap_or(0),
+            byte_order,
+            header,
+            types,
+            messages,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SbeError> {
+        let xml = std::fs::read_to_string(path).map_err(|e| SbeError::Schema(e.to_string()))?;
+        Self::from_xml(&xml)
+    }
+
+    #[inline]
+    pub fn header_size(&self) -> usize {
+        self.header.size()
+    }
+
+    pub fn get_type(&self, name: &str) -> Option<&TypeDef> {
+        self.types.get(name)
+    }
+
+    pub fn messages(&self) -> &[MessageDef] {
+        &self.messages
+    }
+
+    pub fn message(&self, name: &str) -> Result<&MessageDef, SbeError> {
+        self.messages
+            .iter()
+            .find(|message| message.name == name)
+            .ok_or_else(|| SbeError::UnknownMessage(name.to_string()))
+    }
+
+    pub fn message_by_id(&self, id: u16) -> Result<&MessageDef, SbeError> {
+        self.messages.iter().find(|message| message.id == id).ok_or(SbeError::UnknownTemplate(id))
+    }
+}
+
+/// A type by name: a primitive or a type of the schema
+fn resolve(name: &str, types: &FxHashMap<String, TypeDef>) -> Result<TypeDef, SbeError> {
+    if let Some(type_def) = types.get(name) {
+        return Ok(type_def.clone());
+    }
+    Primitive::from_name(name)
+        .map(|primitive| TypeDef::Encoded(EncodedType::primitive(name, primitive)))
+        .ok_or_else(|| SbeError::Schema(format!("unknown type: {}", name)))
+}
+
+fn encoding_of(element: &Element, types: &FxHashMap<String, TypeDef>) -> Result<Primitive, SbeError> {
+    match resolve(element.require("encodingType")?, types)? {
+        TypeDef::Encoded(encoded) => Ok(encoded.primitive),
+        other => Err(SbeError::Schema(format!("{} can not encode <{}>", other.name(), element.name))),
+    }
+}
+
+/// A char value is its character, e.g., '0' is 48
+fn parse_value(text: &str, encoding: Primitive) -> Result<u64, SbeError> {
+    match encoding {
+        Primitive::Char if text.len() == 1 => Ok(text.as_bytes()[0] as u64),
+        _ => text.parse().map_err(|_| SbeError::Schema(format!("invalid value: {}", text))),
+    }
+}
+
+fn parse_type(element: &Element, types: &FxHashMap<String, TypeDef>) -> Result<TypeDef, SbeError> {
+    match element.name.as_str() {
+        "type" => Ok(TypeDef::Encoded(EncodedType::from_element(element)?)),
+        "composite" => {
+            let mut members = Vec::new();
+            let mut offset = 0;
+            for child in element.children.iter() {
+                let mut member = match child.name.as_str() {
+                    "type" => EncodedType::from_element(child)?,
+                    "ref" => match resolve(child.require("type")?, types)? {
+                        TypeDef::Encoded(mut encoded) => {
+                            encoded.name = child.require("name")?.to_string();
+                            encoded
+                        }
+                        other => return Err(SbeError::Schema(format!("composite member {} is not encoded", other.name()))),
+                    },
+                    other => return Err(SbeError::Schema(format!("unsupported composite member <{}>", other))),
+                };
+                member.offset = child.parse("offset")?.unwrap_or(offset);
+                offset = member.offset + member.size();
+                members.push(member);
+            }
+            Ok(TypeDef::Composite { name: element.require("name")?.to_string(), members, size: offset })
+        }
+        "enum" => {
+            let encoding = encoding_of(element, types)?;
+            let values = element
+                .children_named("validValue")
+                .map(|value| Ok((value.require("name")?.to_string(), parse_value(&value.text, encoding)?)))
+                .collect::<Result<Vec<_>, SbeError>>()?;
+            Ok(TypeDef::Enum { name: element.require("name")?.to_string(), encoding, values })
+        }
+        "set" => {
+            let encoding = encoding_of(element, types)?;
+            let choices = element
+                .children_named("choice")
+                .map(|choice| {
+                    let bit = choice.text.parse().map_err(|_| SbeError::Schema(format!("invalid choice: {}", choice.text)))?;
+                    Ok((choice.require("name")?.to_string(), bit))
+                })
+                .collect::<Result<Vec<_>, SbeError>>()?;
+            Ok(TypeDef::Set { name: element.require("name")?.to_string(), encoding, choices })
+        }
+        other => Err(SbeError::Schema(format!("unsupported type element <{}>", other))),
+    }
+}
+
+/// Makes the field of `type_def` a constant of its `valueRef` ("Enum.Value") or its text.
+/// An encoded type takes the value of the enum; an enum keeps the name of the valid value, which is returned.
+/// Constant sets and composites are rejected
+fn constant_field(element: &Element, type_def: &mut TypeDef, types: &FxHashMap<String, TypeDef>) -> Result<Option<String>, SbeError> {
+    let type_name = type_def.name().to_string();
+    let invalid = |value: &str| SbeError::Schema(format!("invalid constant of {}: {}", type_name, value));
+    let (value, raw) = match element.attribute("valueRef") {
+        Some(value_ref) => {
+            let (enum_name, name) = value_ref.split_once('.').ok_or_else(|| invalid(value_ref))?;
+            let Some(TypeDef::Enum { values, .. }) = types.get(enum_name) else {
+                return Err(invalid(value_ref));
+            };
+            let (_, raw) = values.iter().find(|(valid, _)| valid == name).ok_or_else(|| invalid(value_ref))?;
+            (name.to_string(), raw.to_string())
+        }
+        None => (element.text.trim().to_string(), element.text.trim().to_string()),
+    };
+    match type_def {
+        TypeDef::Encoded(encoded) => {
+            encoded.presence = Presence::Constant;
+            encoded.constant = Some(raw);
+            Ok(None)
+        }
+        TypeDef::Enum { values, .. } if values.iter().any(|(valid, _)| *valid == value) => Ok(Some(value)),
+        TypeDef::Enum { .. } => Err(invalid(&value)),
+        _ => Err(SbeError::Schema(format!("constant field {} of {} is not supported", element.require("name")?, type_name))),
+    }
+}
+
+fn parse_block(element: &Element, types: &FxHashMap<String, TypeDef>) -> Result<BlockDef, SbeError> {
+    let mut block = BlockDef::default();
+    let mut offset = 0;
+    for child in element.children.iter() {
+        let name = child.require("name")?.to_string();
+        let id = child.parse("id")?.unwrap_or(0);
+        match child.name.as_str() {
+            "field" => {
+                let mut type_def = resolve(child.require("type")?, types)?;
+                let mut constant = None;
+                match child.attribute("presence") {
+                    Some("optional") => {
+                        if let TypeDef::Encoded(encoded) = &mut type_def {
+                            encoded.presence = Presence::Optional;
+                        }
+                    }
+                    Some("constant") => constant = constant_field(child, &mut type_def, types)?,
+                    _ => {}
+                }
+                let field = FieldDef { name, id, offset: child.parse("offset")?.unwrap_or(offset), type_def, constant };
+                offset = field.offset + field.size();
+                block.fields.push(field);
+            }
+            "group" => {
+                let dimension = resolve(child.attribute("dimensionType").unwrap_or("groupSizeEncoding"), types)?;
+                if dimension.member("blockLength").is_none() || dimension.member("numInGroup").is_none() {
+                    return Err(SbeError::Schema(format!("the dimension of {} lacks blockLength or numInGroup", name)));
+                }
+                let mut group_block = parse_block(child, types)?;
+                if let Some(block_length) = child.parse("blockLength")? {
+                    group_block.block_length = block_length;
+                }
+                block.groups.push(GroupDef { name, id, dimension, block: group_block });
+            }
+            "data" => {
+                let encoding = resolve(child.require("type")?, types)?;
+                let length = encoding
+                    .member("length")
+                    .cloned()
+                    .ok_or_else(|| SbeError::Schema(format!("the encoding of {} has no length", name)))?;
+                block.data.push(DataDef { name, id, length });
+            }
+            other => return Err(SbeError::Schema(format!("unsupported element <{}> in {}", other, element.name))),
+        }
+    }
+    let fields_end = block.fields.iter().map(|field| field.offset + field.size()).max().unwrap_or(0);
+    block.block_length = element.parse("blockLength")?.unwrap_or(fields_end).max(fields_end);
+    Ok(block)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const SCHEMA: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sbe:messageSchema xmlns:sbe="http://fixprotocol.io/2016/sbe" package="test" id="7" version="1" byteOrder="littleEndian">
+    <types>
+        <composite name="messageHeader">
+            <type name="blockLength" primitiveType="uint16"/>
+            <type name="templateId" primitiveType="uint16"/>
+            <type name="schemaId" primitiveType="uint16"/>
+            <type name="version" primitiveType="uint16"/>
+        </composite>
+        <composite name="groupSizeEncoding">
+            <type name="blockLength" primitiveType="uint16"/>
+            <type name="numInGroup" primitiveType="uint16"/>
+        </composite>
+        <composite name="varStringEncoding">
+            <type name="length" primitiveType="uint16"/>
+            <type name="varData" primitiveType="uint8" length="0"/>
+        </composite>
+        <!-- prices with 9 decimals -->
+        <composite name="PRICE9">
+            <type name="mantissa" primitiveType="int64"/>
+            <type name="exponent" primitiveType="int8" presence="constant">-9</type>
+        </composite>
+        <composite name="PRICENULL9">
+            <type name="mantissa" primitiveType="int64" presence="optional"/>
+            <type name="exponent" primitiveType="int8" presence="constant">-9</type>
+        </composite>
+        <type name="Symbol" primitiveType="char" length="8"/>
+        <enum name="Side" encodingType="uint8">
+            <validValue name="Bid">0</validValue>
+            <validValue name="Ask">1</validValue>
+        </enum>
+        <enum name="OrdType" encodingType="char">
+            <validValue name="Market">1</validValue>
+            <validValue name="Limit">2</validValue>
+        </enum>
+        <set name="Flags" encodingType="uint8">
+            <choice name="LastInGroup">0</choice>
+            <choice name="EndOfEvent">7</choice>
+        </set>
+    </types>
+    <sbe:message name="BookSnapshot" id="1" description="levels &amp; sides">
+        <field name="symbol" id="1" type="Symbol"/>
+        <field name="transactTime" id="2" type="uint64"/>
+        <field name="flags" id="3" type="Flags"/>
+        <group name="levels" id="10" dimensionType="groupSizeEncoding">
+            <field name="side" id="11" type="Side"/>
+            <field name="price" id="12" type="PRICE9"/>
+            <field name="quantity" id="13" type="uint64"/>
+            <field name="orderCount" id="14" type="uint32" presence="optional"/>
+        </group>
+        <data name="text" id="20" type="varStringEncoding"/>
+    </sbe:message>
+    <sbe:message name="NewOrder" id="2" blockLength="40">
+        <field name="orderId" id="1" type="uint64"/>
+        <field name="symbol" id="2" type="Symbol"/>
+        <field name="side" id="3" type="Side"/>
+        <field name="ordType" id="4" type="OrdType"/>
+        <field name="price" id="5" type="PRICENULL9"/>
+        <field name="quantity" id="6" type="uint32"/>
+    </sbe:message>
+</sbe:messageSchema>
+"#;
+
+    #[test]
+    fn test_parse_schema() {
+        let schema = Schema::from_xml(SCHEMA).unwrap();
+        assert_eq!((schema.id, schema.version, schema.byte_order), (7, 1, ByteOrder::LittleEndian));
+        assert_eq!(schema.header_size(), 8);
+
+        let snapshot = schema.message("BookSnapshot").unwrap();
+        let offsets: Vec<(&str, usize)> = snapshot.block.fields.iter().map(|f| (f.name.as_str(), f.offset)).collect();
+        assert_eq!(offsets, vec![("symbol", 0), ("transactTime", 8), ("flags", 16)]);
+        assert_eq!(snapshot.block.block_length, 17);
+        let levels = &snapshot.block.groups[0];
+        assert_eq!(levels.block.block_length, 1 + 8 + 8 + 4);
+        assert!(levels.block.field("price").unwrap().type_def.is_decimal());
+        assert_eq!(snapshot.block.data[0].length.primitive, Primitive::UInt16);
+
+        let new_order = schema.message_by_id(2).unwrap();
+        assert_eq!(new_order.block.block_length, 40);
+        match &new_order.block.field("ordType").unwrap().type_def {
+            TypeDef::Enum { encoding, values, .. } => {
+                assert_eq!(*encoding, Primitive::Char);
+                assert_eq!(values[1], ("Limit".to_string(), b'2' as u64));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(schema.message_by_id(3), Err(SbeError::UnknownTemplate(3)));
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        assert!(matches!(Schema::from_xml("<messageSchema><types>"), Err(SbeError::Schema(_))));
+        assert!(matches!(Schema::from_xml("<messageSchema></messageSchema>"), Err(SbeError::Schema(_))));
+        let unknown_type = SCHEMA.replace(r#"type="Symbol"/>
+        <field name="transactTime""#, r#"type="Sym"/>
+        <field name="transactTime""#);
+        assert_eq!(Schema::from_xml(&unknown_type), Err(SbeError::Schema("unknown type: Sym".to_string())));
+        let constant_set = SCHEMA.replace(r#"<field name="ordType""#, r#"<field name="flags" id="7" type="Flags" presence="constant"/>
+        <field name="ordType""#);
+        assert_eq!(
+            Schema::from_xml(&constant_set),
+            Err(SbeError::Schema("constant field flags of Flags is not supported".to_string()))
+        );
+        let bad_ref = SCHEMA.replace(r#"<field name="ordType""#, r#"<field name="venueSide" id="7" type="Side" presence="constant" valueRef="Side.Mid"/>
+        <field name="ordType""#);
+        assert_eq!(Schema::from_xml(&bad_ref), Err(SbeError::Schema("invalid constant of Side: Side.Mid".to_string())));
+    }
+
+    /// Constant fields take no block space, and a composite may refer to a type declared after it
+    pub(crate) fn constant_schema() -> Schema {
+        let xml = SCHEMA
+            .replace(r#"<composite name="messageHeader">"#, r#"<composite name="Instrument">
+            <ref name="symbol" type="Symbol"/>
+            <type name="venue" primitiveType="uint16"/>
+        </composite>
+        <composite name="messageHeader">"#)
+            .replace(r#"<field name="ordType""#, r#"<field name="venueSide" id="7" type="Side" presence="constant" valueRef="Side.Ask"/>
+        <field name="sideCode" id="8" type="uint8" presence="constant" valueRef="Side.Ask"/>
+        <field name="ordType""#);
+        Schema::from_xml(&xml).unwrap()
+    }
+
+    #[test]
+    fn test_constant_fields_and_forward_refs() {
+        let schema = constant_schema();
+        assert_eq!(schema.get_type("Instrument").map(TypeDef::size), Some(10));
+        let new_order = schema.message("NewOrder").unwrap();
+        let offsets: Vec<(&str, usize, usize)> =
+            new_order.block.fields.iter().map(|f| (f.name.as_str(), f.offset, f.size())).collect();
+        assert_eq!(
+            offsets[2..5],
+            [("side", 16, 1), ("venueSide", 17, 0), ("sideCode", 17, 0)]
+        );
+        assert_eq!(new_order.block.field("ordType").unwrap().offset, 17);
+        assert_eq!(new_order.block.field("venueSide").unwrap().constant.as_deref(), Some("Ask"));
+        match &new_order.block.field("sideCode").unwrap().type_def {
+            TypeDef::Encoded(encoded) => assert_eq!((encoded.presence, encoded.constant.as_deref()), (Presence::Constant, Some("1"))),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! A small XML reader, just enough for SBE schemas: elements, attributes and text.
+//! The prolog, comments, DOCTYPE and processing instructions are skipped, and namespace prefixes are dropped.
+use crate::sbe::error::SbeError;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    #[inline]
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn require(&self, name: &str) -> Result<&str, SbeError> {
+        self.attribute(name)
+            .ok_or_else(|| SbeError::Schema(format!("<{}> without {}", self.name, name)))
+    }
+
+    /// The attribute parsed, None if absent
+    pub fn parse<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, SbeError> {
+        self.attribute(name)
+            .map(|value| {
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| SbeError::Schema(format!("invalid {} of <{}>: {}", name, self.name, value)))
+            })
+            .transpose()
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &str) -> SbeError {
+        SbeError::Schema(format!("{} at byte {}", msg, self.pos))
+    }
+
+    #[inline]
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Moves past `end`
+    fn skip_past(&mut self, end: &str) -> Result<(), SbeError> {
+        let at = self.rest().find(end).ok_or_else(|| self.error(&format!("missing {}", end)))?;
+        self.pos += at + end.len();
+        Ok(())
+    }
+
+    /// Skips comments, processing instructions and declarations
+    fn skip_misc(&mut self) -> Result<(), SbeError> {
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!") {
+                self.skip_past(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, SbeError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '=')
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn element(&mut self) -> Result<Element, SbeError> {
+        if !self.rest().starts_with('<') {
+            return Err(self.error("expected an element"));
+        }
+        self.pos += 1;
+        let name = self.name()?;
+        let mut element = Element { name: local_name(name).to_string(), ..Element::default() };
+
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                return Ok(element);
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let key = local_name(self.name()?).to_string();
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(self.error("expected ="));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let quote = self.rest().chars().next().filter(|c| *c == '"' || *c == '\'');
+            let quote = quote.ok_or_else(|| self.error("expected a quoted value"))?;
+            self.pos += 1;
+            let end = self.rest().find(quote).ok_or_else(|| self.error("unterminated value"))?;
+            element.attributes.push((key, unescape(&self.rest()[..end])));
+            self.pos += end + 1;
+        }
+
+        loop {
+            let rest = self.rest();
+            let text_len = rest.find('<').ok_or_else(|| self.error(&format!("unclosed <{}>", name)))?;
+            element.text.push_str(&unescape(&rest[..text_len]));
+            self.pos += text_len;
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                self.pos += 2;
+                let closing = self.name()?;
+                if closing != name {
+                    return Err(self.error(&format!("</{}> closes <{}>", closing, name)));
+                }
+                self.skip_past(">")?;
+                element.text = element.text.trim().to_string();
+                return Ok(element);
+            }
+            if rest.starts_with("<!--") || rest.starts_with("<?") {
+                self.skip_misc()?;
+                continue;
+            }
+            element.children.push(self.element()?);
+        }
+    }
+}
+
+/// Parses the root element of `xml`
+pub fn parse(xml: &str) -> Result<Element, SbeError> {
+    let mut parser = Parser { s: xml, pos: 0 };
+    parser.skip_misc()?;
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if !parser.rest().is_empty() {
+        return Err(parser.error("content after the root element"));
+    }
+    Ok(root)
+}
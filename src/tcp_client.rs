@@ -10,9 +10,13 @@ use flashlog::get_unix_nano;
 pub struct TcpClient {
     stream: TcpStream,
     idle_timeout: Option<UnixNano>,
+    send_timeout: UnixNano,
 }
 
 impl TcpClient {
+    /// `send_all` gives up after this long without the peer reading
+    pub const DEFAULT_SEND_TIMEOUT: UnixNano = 5_000_000_000;
+
     pub fn new<A: ToSocketAddrs>(
         address: A,
         idle_timeout: Option<UnixNano>,
@@ -22,13 +26,39 @@ impl TcpClient {
         Ok(Self {
             stream,
             idle_timeout,
+            send_timeout: Self::DEFAULT_SEND_TIMEOUT,
         })
     }
 
+    pub fn with_send_timeout(mut self, send_timeout: UnixNano) -> Self {
+        self.send_timeout = send_timeout;
+        self
+    }
+
     pub fn send(&mut self, buf: &[u8]) -> Result<usize, Error> {
         self.stream.write(buf)
     }
 
+    /// Writes the whole buffer, spinning while the socket buffer is full.
+    /// Fails with `TimedOut` if the buffer is not written within the send timeout,
+    /// in which case part of it may have been sent
+    pub fn send_all(&mut self, mut buf: &[u8]) -> Result<(), Error> {
+        let start_nano = get_unix_nano();
+        while !buf.is_empty() {
+            match self.stream.write(buf) {
+                Ok(0) => return Err(Error::new(ErrorKind::WriteZero, "Connection closed by peer")),
+                Ok(size) => buf = &buf[size..],
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => {
+                    if get_unix_nano().saturating_sub(start_nano) >= self.send_timeout {
+                        return Err(Error::new(ErrorKind::TimedOut, "Peer is not reading"));
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
     /// Set the non-blocking mode of the stream.
     /// Handle the last data received
     /// returning Ok(None) means that the stream is idle
@@ -84,4 +114,25 @@ impl From<&str> for TcpClient {
     fn from(address: &str) -> Self {
         Self::new(address, None).expect("Failed to create TCP client")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_send_all_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpClient::new(listener.local_addr().unwrap(), None).unwrap().with_send_timeout(50_000_000);
+        // accepted but never read
+        let _peer = listener.accept().unwrap();
+        let buf = vec![0u8; 1 << 20];
+        let error = loop {
+            if let Err(e) = client.send_all(&buf) {
+                break e;
+            }
+        };
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::UnixNano;
-use std::net::UdpSocket;
+use std::net::{SocketAddr, UdpSocket};
 use std::io::{Error, ErrorKind};
 use flashlog::get_unix_nano;
 
@@ -25,6 +25,10 @@ impl UdpClient {
          })
     }
 
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.socket.local_addr()
+    }
+
     pub fn send(&self, buf: &[u8]) -> Result<usize, Error> {
         self.socket.send(buf)
     }
@@ -72,6 +76,16 @@ impl UdpClient {
             }
         }
     }
+
+    /// Receives the next packet without skipping any, for feeds where every packet matters.
+    /// Ok(None) if there is no packet to read
+    pub fn recv_one(&self, buf: &mut [u8]) -> Result<Option<usize>, Error> {
+        match self.socket.recv(buf) {
+            Ok(size) => Ok(Some(size)),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 impl From<&str> for UdpClient {
@@ -58,6 +58,7 @@ impl Default for UniqueId {
 }
 
 impl UniqueId {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(id: &str) -> Self {
         let string = id.to_string();
         let mut cache = UNIQUE_ID_CACHE.lock().expect("Failed to lock id cache");